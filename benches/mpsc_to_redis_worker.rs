@@ -0,0 +1,118 @@
+//! Compares per-payment and batched hand-off from the MPSC buffer to the
+//! payments queue. The queue simulates a Redis round trip per command, which is
+//! the cost batching amortises.
+
+use std::hint::black_box;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use criterion::{
+	BenchmarkId, Criterion, Throughput, criterion_group, criterion_main,
+};
+use rinha_de_backend::domain::payment::Payment;
+use rinha_de_backend::domain::queue::{Message, Queue};
+use rinha_de_backend::infrastructure::metrics::ingress_metrics::IngressMetrics;
+use rinha_de_backend::infrastructure::queue::buffered_payment::BufferedPayment;
+use rinha_de_backend::infrastructure::workers::mpsc_to_redis_worker::{
+	BatchOptions, mpsc_to_redis_worker,
+};
+use rinha_de_backend::use_cases::create_payment::CreatePaymentUseCase;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+const PAYMENTS: usize = 1_000;
+/// Tokio timers have millisecond resolution.
+const ROUND_TRIP: Duration = Duration::from_millis(1);
+
+#[derive(Clone)]
+struct RoundTripQueue;
+
+#[async_trait]
+impl Queue<Payment> for RoundTripQueue {
+	async fn pop(
+		&self,
+	) -> Result<Option<Message<Payment>>, Box<dyn std::error::Error + Send>> {
+		Ok(None)
+	}
+
+	async fn pop_batch(
+		&self,
+		_count: usize,
+	) -> Result<Vec<Message<Payment>>, Box<dyn std::error::Error + Send>> {
+		Ok(Vec::new())
+	}
+
+	async fn push(
+		&self,
+		message: Message<Payment>,
+	) -> Result<(), Box<dyn std::error::Error + Send>> {
+		tokio::time::sleep(ROUND_TRIP).await;
+		black_box(message);
+		Ok(())
+	}
+
+	async fn push_batch(
+		&self,
+		messages: Vec<Message<Payment>>,
+	) -> Result<(), Box<dyn std::error::Error + Send>> {
+		tokio::time::sleep(ROUND_TRIP).await;
+		black_box(messages);
+		Ok(())
+	}
+
+	async fn length(&self) -> Result<usize, Box<dyn std::error::Error + Send>> {
+		Ok(0)
+	}
+}
+
+async fn drain_buffer(max_size: usize) {
+	let (sender, receiver) = mpsc::channel::<BufferedPayment>(PAYMENTS);
+	for _ in 0..PAYMENTS {
+		let payment = Payment {
+			correlation_id: Uuid::new_v4(),
+			amount:         19.9,
+			submitted_at:   None,
+			processed_at:   None,
+			processed_by:   None,
+			received_at:    None,
+		};
+		sender.send(payment.into()).await.unwrap();
+	}
+	drop(sender);
+
+	mpsc_to_redis_worker(
+		receiver,
+		CreatePaymentUseCase::new(RoundTripQueue),
+		None,
+		BatchOptions {
+			max_size,
+			max_linger: Duration::ZERO,
+		},
+		IngressMetrics::new(),
+	)
+	.await;
+}
+
+fn bench_mpsc_to_redis_worker(c: &mut Criterion) {
+	let runtime = tokio::runtime::Builder::new_current_thread()
+		.enable_time()
+		.build()
+		.unwrap();
+
+	let mut group = c.benchmark_group("mpsc_to_redis_worker");
+	group.throughput(Throughput::Elements(PAYMENTS as u64));
+	group.sample_size(10);
+
+	for max_size in [1, 16, 128] {
+		group.bench_with_input(
+			BenchmarkId::new("batch_size", max_size),
+			&max_size,
+			|b, &max_size| b.to_async(&runtime).iter(|| drain_buffer(max_size)),
+		);
+	}
+
+	group.finish();
+}
+
+criterion_group!(benches, bench_mpsc_to_redis_worker);
+criterion_main!(benches);
@@ -0,0 +1,126 @@
+use std::error::Error;
+use std::io::Write;
+
+use crate::adapters::web::schema::parse_timestamp;
+use crate::domain::repository::PaymentRepository;
+use crate::use_cases::dto::{ExportFormat, ExportPaymentsQuery};
+use crate::use_cases::export_payments::ExportPaymentsUseCase;
+
+pub const EXPORT_USAGE: &str = "Usage: rinha-de-backend export [--from \
+                                <timestamp>] [--to <timestamp>] [--format \
+                                csv|ndjson]";
+
+/// Reads the arguments following `export`. Timestamps take the same RFC 3339
+/// or epoch milliseconds forms as the HTTP API.
+pub fn parse_export_args<I>(args: I) -> Result<ExportPaymentsQuery, String>
+where
+	I: IntoIterator<Item = String>,
+{
+	let mut query = ExportPaymentsQuery::default();
+	let mut args = args.into_iter();
+
+	while let Some(flag) = args.next() {
+		let value = args
+			.next()
+			.ok_or_else(|| format!("`{flag}` is missing its value"))?;
+		let timestamp = || {
+			parse_timestamp(&value).ok_or_else(|| {
+				format!(
+					"`{value}` is neither an RFC 3339 timestamp nor epoch \
+					 milliseconds"
+				)
+			})
+		};
+
+		match flag.as_str() {
+			"--from" => query.from = Some(timestamp()?),
+			"--to" => query.to = Some(timestamp()?),
+			"--format" => {
+				query.format = match value.as_str() {
+					"csv" => ExportFormat::Csv,
+					"ndjson" => ExportFormat::Ndjson,
+					_ => return Err(format!("Unknown export format `{value}`")),
+				}
+			}
+			_ => return Err(format!("Unknown argument `{flag}`")),
+		}
+	}
+
+	Ok(query)
+}
+
+/// Writes the export to `out` a page at a time, returning how many bytes were
+/// written.
+pub async fn export_payments<R, W>(
+	use_case: &ExportPaymentsUseCase<R>,
+	query: ExportPaymentsQuery,
+	out: &mut W,
+) -> Result<usize, Box<dyn Error + Send>>
+where
+	R: PaymentRepository + Clone,
+	W: Write,
+{
+	let mut export = use_case.execute(query)?;
+	let mut written = 0;
+
+	while let Some(chunk) = export.next_chunk().await? {
+		out.write_all(&chunk)
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+		written += chunk.len();
+	}
+	out.flush()
+		.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+
+	Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+	use time::OffsetDateTime;
+
+	use super::*;
+
+	fn args(args: &[&str]) -> Vec<String> {
+		args.iter().map(|arg| arg.to_string()).collect()
+	}
+
+	#[test]
+	fn test_parse_export_args_defaults_to_an_open_csv_export() {
+		let query = parse_export_args(args(&[])).unwrap();
+
+		assert_eq!(query.from, None);
+		assert_eq!(query.to, None);
+		assert_eq!(query.format, ExportFormat::Csv);
+	}
+
+	#[test]
+	fn test_parse_export_args_reads_every_flag() {
+		let query = parse_export_args(args(&[
+			"--from",
+			"2025-07-01T00:00:00Z",
+			"--to",
+			"1751414400000",
+			"--format",
+			"ndjson",
+		]))
+		.unwrap();
+
+		assert_eq!(
+			query.from,
+			Some(OffsetDateTime::from_unix_timestamp(1_751_328_000).unwrap())
+		);
+		assert_eq!(
+			query.to,
+			Some(OffsetDateTime::from_unix_timestamp(1_751_414_400).unwrap())
+		);
+		assert_eq!(query.format, ExportFormat::Ndjson);
+	}
+
+	#[test]
+	fn test_parse_export_args_rejects_bad_input() {
+		assert!(parse_export_args(args(&["--from"])).is_err());
+		assert!(parse_export_args(args(&["--from", "yesterday"])).is_err());
+		assert!(parse_export_args(args(&["--format", "xml"])).is_err());
+		assert!(parse_export_args(args(&["--group", "default"])).is_err());
+	}
+}
@@ -0,0 +1,218 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use crate::domain::repository::PaymentRepository;
+use crate::domain::snapshot::SnapshotStore;
+use crate::use_cases::dto::{MigrationCheckpoint, MigrationReport};
+use crate::use_cases::migrate_payments::MigratePaymentsUseCase;
+
+pub const MIGRATE_USAGE: &str =
+	"Usage: rinha-de-backend migrate --target-url <url> [--target-prefix <prefix>] \
+	 [--target-hash-tag] [--checkpoint <file>] [--batch-size <count>] [--restart]";
+
+const DEFAULT_CHECKPOINT_PATH: &str = "migration-checkpoint.json";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrateArgs {
+	/// The target Redis, in the same mode as the configured one.
+	pub target_url:      String,
+	/// Keeps the configured key layout when `None`.
+	pub target_prefix:   Option<String>,
+	pub target_hash_tag: bool,
+	pub checkpoint:      PathBuf,
+	pub batch_size:      Option<usize>,
+	/// Ignores the checkpoint and copies everything again.
+	pub restart:         bool,
+}
+
+/// Reads the arguments following `migrate`.
+pub fn parse_migrate_args<I>(args: I) -> Result<MigrateArgs, String>
+where
+	I: IntoIterator<Item = String>,
+{
+	let mut target_url = None;
+	let mut parsed = MigrateArgs {
+		target_url:      String::new(),
+		target_prefix:   None,
+		target_hash_tag: false,
+		checkpoint:      PathBuf::from(DEFAULT_CHECKPOINT_PATH),
+		batch_size:      None,
+		restart:         false,
+	};
+	let mut args = args.into_iter();
+
+	while let Some(flag) = args.next() {
+		match flag.as_str() {
+			"--target-hash-tag" => parsed.target_hash_tag = true,
+			"--restart" => parsed.restart = true,
+			"--target-url" | "--target-prefix" | "--checkpoint" | "--batch-size" => {
+				let value = args
+					.next()
+					.ok_or_else(|| format!("`{flag}` is missing its value"))?;
+				match flag.as_str() {
+					"--target-url" => target_url = Some(value),
+					"--target-prefix" => parsed.target_prefix = Some(value),
+					"--checkpoint" => parsed.checkpoint = PathBuf::from(value),
+					_ => {
+						parsed.batch_size = Some(
+							value.parse().ok().filter(|size| *size > 0).ok_or_else(
+								|| format!("`{value}` is not a positive batch size"),
+							)?,
+						)
+					}
+				}
+			}
+			_ => return Err(format!("Unknown argument `{flag}`")),
+		}
+	}
+
+	parsed.target_url = target_url.ok_or("`--target-url` is required")?;
+	Ok(parsed)
+}
+
+/// Runs the migration to its end, saving the checkpoint after every step,
+/// then compares both stores.
+pub async fn migrate_payments<S, T>(
+	use_case: &MigratePaymentsUseCase<S, T>,
+	checkpoint_path: &Path,
+	restart: bool,
+) -> Result<(MigrationCheckpoint, MigrationReport), Box<dyn Error + Send>>
+where
+	S: PaymentRepository + SnapshotStore + Clone,
+	T: PaymentRepository + SnapshotStore + Clone,
+{
+	let checkpoint = if restart {
+		MigrationCheckpoint::default()
+	} else {
+		load_checkpoint(checkpoint_path)?
+	};
+
+	let mut migration = use_case.resume(checkpoint);
+	while migration.step().await? {
+		save_checkpoint(checkpoint_path, migration.checkpoint())?;
+	}
+	save_checkpoint(checkpoint_path, migration.checkpoint())?;
+
+	let report = migration.verify().await?;
+	Ok((migration.checkpoint().clone(), report))
+}
+
+/// A missing file is a migration yet to start.
+fn load_checkpoint(
+	path: &Path,
+) -> Result<MigrationCheckpoint, Box<dyn Error + Send>> {
+	match std::fs::read(path) {
+		Ok(bytes) => serde_json::from_slice(&bytes)
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>),
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+			Ok(MigrationCheckpoint::default())
+		}
+		Err(e) => Err(Box::new(e)),
+	}
+}
+
+/// Written aside and renamed over the previous one, so a crash never leaves
+/// half a checkpoint.
+fn save_checkpoint(
+	path: &Path,
+	checkpoint: &MigrationCheckpoint,
+) -> Result<(), Box<dyn Error + Send>> {
+	let bytes = serde_json::to_vec(checkpoint)
+		.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+	let mut temporary = path.as_os_str().to_owned();
+	temporary.push(".tmp");
+
+	std::fs::write(&temporary, bytes)
+		.and_then(|_| std::fs::rename(&temporary, path))
+		.map_err(|e| Box::new(e) as Box<dyn Error + Send>)
+}
+
+#[cfg(test)]
+mod tests {
+	use uuid::Uuid;
+
+	use super::*;
+	use crate::domain::payment::Payment;
+	use crate::domain::queue::Message;
+
+	fn args(args: &[&str]) -> Vec<String> {
+		args.iter().map(|arg| arg.to_string()).collect()
+	}
+
+	#[test]
+	fn test_parse_migrate_args_reads_every_flag() {
+		assert_eq!(
+			parse_migrate_args(args(&[
+				"--target-url",
+				"redis://new-host:6379",
+				"--target-prefix",
+				"payments",
+				"--target-hash-tag",
+				"--checkpoint",
+				"/tmp/migration.json",
+				"--batch-size",
+				"100",
+				"--restart",
+			])),
+			Ok(MigrateArgs {
+				target_url:      "redis://new-host:6379".to_string(),
+				target_prefix:   Some("payments".to_string()),
+				target_hash_tag: true,
+				checkpoint:      PathBuf::from("/tmp/migration.json"),
+				batch_size:      Some(100),
+				restart:         true,
+			})
+		);
+	}
+
+	#[test]
+	fn test_parse_migrate_args_rejects_bad_input() {
+		assert!(parse_migrate_args(args(&[])).is_err());
+		assert!(parse_migrate_args(args(&["--target-url"])).is_err());
+		assert!(
+			parse_migrate_args(args(&[
+				"--target-url",
+				"redis://a",
+				"--batch-size",
+				"0"
+			]))
+			.is_err()
+		);
+		assert!(
+			parse_migrate_args(args(&["--target-url", "redis://a", "--dry-run"]))
+				.is_err()
+		);
+	}
+
+	#[test]
+	fn test_checkpoint_round_trips_through_its_file() {
+		let path = std::env::temp_dir()
+			.join(format!("migration-checkpoint-{}.json", Uuid::new_v4()));
+		assert_eq!(load_checkpoint(&path).unwrap().position, None);
+
+		let message = Message::with(Uuid::new_v4(), Payment {
+			correlation_id: Uuid::new_v4(),
+			amount:         19.9,
+			submitted_at:   None,
+			processed_at:   None,
+			processed_by:   None,
+			received_at:    None,
+		});
+		let checkpoint = MigrationCheckpoint {
+			position: Some(1_751_328_000_000_000_000.0),
+			skip: 2,
+			payments: 500,
+			in_transit: vec![message.clone()],
+			..Default::default()
+		};
+		save_checkpoint(&path, &checkpoint).unwrap();
+		let loaded = load_checkpoint(&path).unwrap();
+		std::fs::remove_file(&path).unwrap();
+
+		assert_eq!(loaded.position, checkpoint.position);
+		assert_eq!(loaded.skip, 2);
+		assert_eq!(loaded.payments, 500);
+		assert_eq!(loaded.in_transit.len(), 1);
+		assert_eq!(loaded.in_transit[0].id, message.id);
+	}
+}
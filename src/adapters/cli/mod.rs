@@ -0,0 +1,3 @@
+pub mod export_command;
+pub mod migrate_command;
+pub mod snapshot_command;
@@ -0,0 +1,105 @@
+use std::error::Error;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use crate::domain::repository::PaymentRepository;
+use crate::domain::snapshot::SnapshotStore;
+use crate::use_cases::dto::RestoreSnapshotResult;
+use crate::use_cases::snapshot::SnapshotUseCase;
+
+pub const SNAPSHOT_USAGE: &str =
+	"Usage: rinha-de-backend snapshot [--output <file>]";
+pub const RESTORE_USAGE: &str = "Usage: rinha-de-backend restore [--input <file>]";
+
+/// Bytes read from the snapshot per call to `SnapshotRestore::feed`.
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Reads the arguments following `snapshot` or `restore`: at most a file
+/// given with `flag`, `None` meaning stdout or stdin.
+pub fn parse_file_arg<I>(args: I, flag: &str) -> Result<Option<PathBuf>, String>
+where
+	I: IntoIterator<Item = String>,
+{
+	let mut path = None;
+	let mut args = args.into_iter();
+
+	while let Some(arg) = args.next() {
+		if arg != flag {
+			return Err(format!("Unknown argument `{arg}`"));
+		}
+		let value = args
+			.next()
+			.ok_or_else(|| format!("`{arg}` is missing its value"))?;
+		path = Some(PathBuf::from(value));
+	}
+
+	Ok(path)
+}
+
+/// Writes the snapshot to `out`, returning how many bytes were written.
+pub async fn write_snapshot<S, W>(
+	use_case: &SnapshotUseCase<S>,
+	out: &mut W,
+) -> Result<usize, Box<dyn Error + Send>>
+where
+	S: SnapshotStore + PaymentRepository + Clone,
+	W: Write,
+{
+	let mut snapshot = use_case.snapshot();
+	let mut written = 0;
+
+	while let Some(chunk) = snapshot.next_chunk().await? {
+		out.write_all(&chunk)
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+		written += chunk.len();
+	}
+	out.flush()
+		.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+
+	Ok(written)
+}
+
+/// Restores the snapshot read from `input`.
+pub async fn restore_snapshot<S, R>(
+	use_case: &SnapshotUseCase<S>,
+	input: &mut R,
+) -> Result<RestoreSnapshotResult, Box<dyn Error + Send>>
+where
+	S: SnapshotStore + PaymentRepository + Clone,
+	R: Read,
+{
+	let mut restore = use_case.restore();
+	let mut buffer = vec![0; READ_BUFFER_SIZE];
+
+	loop {
+		let read = input
+			.read(&mut buffer)
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+		if read == 0 {
+			break;
+		}
+		restore.feed(&buffer[..read]).await?;
+	}
+
+	restore.finish().await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn args(args: &[&str]) -> Vec<String> {
+		args.iter().map(|arg| arg.to_string()).collect()
+	}
+
+	#[test]
+	fn test_parse_file_arg() {
+		assert_eq!(parse_file_arg(args(&[]), "--output"), Ok(None));
+		assert_eq!(
+			parse_file_arg(args(&["--output", "payments.ndjson"]), "--output"),
+			Ok(Some(PathBuf::from("payments.ndjson")))
+		);
+		assert!(parse_file_arg(args(&["--output"]), "--output").is_err());
+		assert!(parse_file_arg(args(&["--input", "a"]), "--output").is_err());
+	}
+}
@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::middleware::{Next, from_fn};
+use actix_web::{Error, ResponseError, Scope, web};
+use tracing::{info, warn};
+
+use crate::adapters::web::errors::ApiError;
+use crate::adapters::web::handlers::{
+	payments_export, payments_purge, payments_restore, payments_snapshot,
+	reload_config, worker_pool_status,
+};
+
+/// Bearer token guarding the `/admin` scope. Without one the admin API is
+/// disabled.
+#[derive(Clone, Default)]
+pub struct AdminToken(Option<Arc<str>>);
+
+impl AdminToken {
+	pub fn new(token: Option<&str>) -> Self {
+		Self(token.map(Arc::from))
+	}
+
+	fn accepts(&self, presented: &str) -> bool {
+		self.0.as_deref().is_some_and(|token| {
+			constant_time_eq(token.as_bytes(), presented.as_bytes())
+		})
+	}
+}
+
+/// Compares without short-circuiting on the first differing byte, so response
+/// times do not leak how much of a guessed token was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+	a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Every operational endpoint, behind `require_admin_token`. The payment
+/// search keeps its `GET /payments` path and wraps the same middleware itself.
+pub fn admin_scope() -> Scope<
+	impl ServiceFactory<
+		ServiceRequest,
+		Config = (),
+		Response = ServiceResponse<impl MessageBody>,
+		Error = Error,
+		InitError = (),
+	>,
+> {
+	web::scope("/admin")
+		.wrap(from_fn(require_admin_token))
+		.service(payments_purge)
+		.service(payments_export)
+		.service(payments_snapshot)
+		.service(payments_restore)
+		.service(worker_pool_status)
+		.service(reload_config)
+}
+
+/// Rejects requests without the configured bearer token and writes an audit
+/// log line, on the `audit` target, for every admin request. `peer` is the
+/// socket address the request came from, while `forwarded_for` is whatever
+/// client the `Forwarded` or `X-Forwarded-For` headers name, which anyone can
+/// set.
+pub async fn require_admin_token<B: MessageBody>(
+	req: ServiceRequest,
+	next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+	let method = req.method().clone();
+	let path = req.path().to_string();
+	let peer = req
+		.peer_addr()
+		.map_or_else(|| "unknown".to_string(), |addr| addr.to_string());
+	let forwarded_for = req
+		.connection_info()
+		.realip_remote_addr()
+		.unwrap_or("none")
+		.to_string();
+
+	let admin_token = req
+		.app_data::<web::Data<AdminToken>>()
+		.map(|token| token.get_ref().clone())
+		.unwrap_or_default();
+	if admin_token.0.is_none() {
+		warn!(target: "audit", %method, %path, %peer, %forwarded_for, "Admin API disabled");
+		return Ok(reject(req, ApiError::Forbidden));
+	}
+
+	let presented = req
+		.headers()
+		.get(AUTHORIZATION)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.strip_prefix("Bearer "));
+	if !presented.is_some_and(|presented| admin_token.accepts(presented)) {
+		warn!(target: "audit", %method, %path, %peer, %forwarded_for, "Rejected admin request");
+		return Ok(reject(req, ApiError::Unauthorized));
+	}
+
+	let response = next.call(req).await?;
+	info!(
+		target: "audit",
+		%method,
+		%path,
+		%peer,
+		%forwarded_for,
+		status = response.status().as_u16(),
+		"Admin request"
+	);
+	Ok(response.map_into_left_body())
+}
+
+fn reject<B>(
+	req: ServiceRequest,
+	error: ApiError,
+) -> ServiceResponse<EitherBody<B>> {
+	req.into_response(error.error_response())
+		.map_into_right_body()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_admin_token_accepts_only_the_configured_token() {
+		let admin_token = AdminToken::new(Some("secret"));
+
+		assert!(admin_token.accepts("secret"));
+		assert!(!admin_token.accepts("secreT"));
+		assert!(!admin_token.accepts("secret2"));
+		assert!(!AdminToken::default().accepts(""));
+	}
+}
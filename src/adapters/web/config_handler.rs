@@ -0,0 +1,19 @@
+use actix_web::{HttpResponse, Responder, post, web};
+use serde_json::json;
+use tracing::error;
+
+use crate::infrastructure::config::routing_reloader::RoutingConfigReloader;
+
+#[post("/config/reload")]
+pub async fn reload_config(
+	reloader: web::Data<RoutingConfigReloader>,
+) -> impl Responder {
+	match reloader.reload() {
+		Ok(report) => HttpResponse::Ok().json(report),
+		Err(e) => {
+			error!("Rejected configuration reload, keeping the running one: {e}");
+			HttpResponse::UnprocessableEntity()
+				.json(json!({ "error": e.to_string() }))
+		}
+	}
+}
@@ -1,5 +1,5 @@
 use actix_web::http::StatusCode;
-use actix_web::http::header::ContentType;
+use actix_web::http::header::{ContentType, RETRY_AFTER, WWW_AUTHENTICATE};
 use actix_web::{HttpResponse, error};
 use derive_more::derive::{Display, Error};
 use serde::Serialize;
@@ -20,8 +20,18 @@ pub enum ApiError {
 	TransactionError,
 	#[display("Request data is invalid.")]
 	BadClientDataError,
+	#[display("{reason}")]
+	InvalidQuery { reason: String },
 	#[display("Internal server error.")]
 	InternalServerError,
+	#[display("Service is temporarily overloaded.")]
+	ServiceUnavailable { retry_after_secs: u64 },
+	#[display("Missing or invalid credentials.")]
+	Unauthorized,
+	#[display("This operation is disabled.")]
+	Forbidden,
+	#[display("{reason}")]
+	Conflict { reason: String },
 }
 
 impl ApiError {
@@ -29,15 +39,33 @@ impl ApiError {
 		match self {
 			ApiError::DatabaseConnectionError => "Insufficient Storage".to_string(),
 			ApiError::TransactionError => "Unprocessable Entity".to_string(),
-			ApiError::BadClientDataError => "Bad request".to_string(),
+			ApiError::BadClientDataError | ApiError::InvalidQuery { .. } => {
+				"Bad request".to_string()
+			}
 			ApiError::InternalServerError => "Internal Server Error".to_string(),
+			ApiError::ServiceUnavailable { .. } => "Service Unavailable".to_string(),
+			ApiError::Unauthorized => "Unauthorized".to_string(),
+			ApiError::Forbidden => "Forbidden".to_string(),
+			ApiError::Conflict { .. } => "Conflict".to_string(),
 		}
 	}
 }
 
 impl error::ResponseError for ApiError {
 	fn error_response(&self) -> HttpResponse {
-		HttpResponse::build(self.status_code())
+		let mut response = HttpResponse::build(self.status_code());
+
+		match self {
+			ApiError::ServiceUnavailable { retry_after_secs } => {
+				response.insert_header((RETRY_AFTER, retry_after_secs.to_string()));
+			}
+			ApiError::Unauthorized => {
+				response.insert_header((WWW_AUTHENTICATE, "Bearer"));
+			}
+			_ => {}
+		}
+
+		response
 			.content_type(ContentType::json())
 			.json(ErrorResponse {
 				status_code: self.status_code().as_u16(),
@@ -50,8 +78,14 @@ impl error::ResponseError for ApiError {
 		match self {
 			ApiError::DatabaseConnectionError => StatusCode::INSUFFICIENT_STORAGE,
 			ApiError::TransactionError => StatusCode::UNPROCESSABLE_ENTITY,
-			ApiError::BadClientDataError => StatusCode::BAD_REQUEST,
+			ApiError::BadClientDataError | ApiError::InvalidQuery { .. } => {
+				StatusCode::BAD_REQUEST
+			}
 			ApiError::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
+			ApiError::ServiceUnavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
+			ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+			ApiError::Forbidden => StatusCode::FORBIDDEN,
+			ApiError::Conflict { .. } => StatusCode::CONFLICT,
 		}
 	}
 }
@@ -97,4 +131,59 @@ mod tests {
 		let resp = error.error_response();
 		assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
 	}
+
+	#[test]
+	fn test_invalid_query_error() {
+		let error = ApiError::InvalidQuery {
+			reason: "`from` is after `to`.".to_string(),
+		};
+		assert_eq!(error.name(), "Bad request");
+		assert_eq!(error.to_string(), "`from` is after `to`.");
+
+		let resp = error.error_response();
+		assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+	}
+
+	#[test]
+	fn test_service_unavailable_error() {
+		let error = ApiError::ServiceUnavailable {
+			retry_after_secs: 3,
+		};
+		assert_eq!(error.name(), "Service Unavailable");
+		assert_eq!(error.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+
+		let resp = error.error_response();
+		assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+		assert_eq!(resp.headers().get(RETRY_AFTER).unwrap(), "3");
+	}
+
+	#[test]
+	fn test_unauthorized_error() {
+		let error = ApiError::Unauthorized;
+		assert_eq!(error.name(), "Unauthorized");
+		assert_eq!(error.status_code(), StatusCode::UNAUTHORIZED);
+
+		let resp = error.error_response();
+		assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+		assert_eq!(resp.headers().get(WWW_AUTHENTICATE).unwrap(), "Bearer");
+	}
+
+	#[test]
+	fn test_forbidden_error() {
+		let error = ApiError::Forbidden;
+		assert_eq!(error.name(), "Forbidden");
+		assert_eq!(error.status_code(), StatusCode::FORBIDDEN);
+	}
+
+	#[test]
+	fn test_conflict_error() {
+		let error = ApiError::Conflict {
+			reason: "Workers are running.".to_string(),
+		};
+		assert_eq!(error.name(), "Conflict");
+		assert_eq!(error.to_string(), "Workers are running.");
+
+		let resp = error.error_response();
+		assert_eq!(resp.status(), StatusCode::CONFLICT);
+	}
 }
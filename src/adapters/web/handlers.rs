@@ -1,3 +1,10 @@
+pub use crate::adapters::web::config_handler::*;
+pub use crate::adapters::web::health_handler::*;
+pub use crate::adapters::web::metrics_handler::*;
+pub use crate::adapters::web::payments_export_handler::*;
 pub use crate::adapters::web::payments_handler::*;
 pub use crate::adapters::web::payments_purge_handler::*;
+pub use crate::adapters::web::payments_search_handler::*;
 pub use crate::adapters::web::payments_summary_handler::*;
+pub use crate::adapters::web::snapshot_handler::*;
+pub use crate::adapters::web::worker_pool_handler::*;
@@ -0,0 +1,20 @@
+use actix_web::{HttpResponse, Responder, get, web};
+use serde_json::json;
+
+use crate::infrastructure::health::readiness_probe::ReadinessProbe;
+
+#[get("/health/live")]
+pub async fn health_live() -> impl Responder {
+	HttpResponse::Ok().json(json!({ "status": "up" }))
+}
+
+#[get("/health/ready")]
+pub async fn health_ready(probe: web::Data<ReadinessProbe>) -> impl Responder {
+	let report = probe.check().await;
+
+	if report.is_ready() {
+		HttpResponse::Ok().json(report)
+	} else {
+		HttpResponse::ServiceUnavailable().json(report)
+	}
+}
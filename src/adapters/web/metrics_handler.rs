@@ -0,0 +1,10 @@
+use actix_web::{HttpResponse, Responder, get, web};
+
+use crate::infrastructure::metrics::registry::MetricsRegistry;
+
+#[get("/metrics")]
+pub async fn metrics(registry: web::Data<MetricsRegistry>) -> impl Responder {
+	HttpResponse::Ok()
+		.content_type("text/plain; version=0.0.4")
+		.body(registry.render())
+}
@@ -1,6 +1,14 @@
+pub mod admin_auth;
+pub mod config_handler;
 pub mod errors;
 pub mod handlers;
+pub mod health_handler;
+pub mod metrics_handler;
+pub mod payments_export_handler;
 pub mod payments_handler;
 pub mod payments_purge_handler;
+pub mod payments_search_handler;
 pub mod payments_summary_handler;
 pub mod schema;
+pub mod snapshot_handler;
+pub mod worker_pool_handler;
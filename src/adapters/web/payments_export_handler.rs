@@ -0,0 +1,80 @@
+use actix_web::web::Bytes;
+use actix_web::{HttpResponse, Responder, ResponseError, get, web};
+use futures::stream;
+use tracing::{error, info};
+
+use crate::adapters::web::errors::ApiError;
+use crate::adapters::web::schema::PaymentsExportFilter;
+use crate::infrastructure::persistence::redis_payment_repository::RedisPaymentRepository;
+use crate::use_cases::dto::{ExportFormat, ExportPaymentsQuery};
+use crate::use_cases::export_payments::ExportPaymentsUseCase;
+use crate::use_cases::get_payment_summary::SummaryWindowError;
+
+/// Mounted under the `/admin` scope, see `admin_scope`. Streams the processed
+/// payments of the window, oldest first, a page at a time.
+#[get("/payments/export")]
+pub async fn payments_export(
+	filter: Result<web::Query<PaymentsExportFilter>, actix_web::Error>,
+	export_use_case: web::Data<ExportPaymentsUseCase<RedisPaymentRepository>>,
+) -> impl Responder {
+	let filter = match filter {
+		Ok(filter) => filter.into_inner(),
+		Err(e) => {
+			return ApiError::InvalidQuery {
+				reason: e.to_string(),
+			}
+			.error_response();
+		}
+	};
+	info!("Received request to export payments: {filter:?}");
+	let query = ExportPaymentsQuery {
+		from:   filter.from,
+		to:     filter.to,
+		format: filter.format,
+	};
+
+	let export = match export_use_case.execute(query) {
+		Ok(export) => export,
+		Err(e) => {
+			return match e.downcast_ref::<SummaryWindowError>() {
+				Some(window_error) => ApiError::InvalidQuery {
+					reason: window_error.to_string(),
+				}
+				.error_response(),
+				None => {
+					error!("Failed to export payments: {e}");
+					ApiError::InternalServerError.error_response()
+				}
+			};
+		}
+	};
+
+	// The status is sent with the first chunk, so a failure midway can only
+	// cut the body short.
+	let body = stream::unfold(export, |mut export| async move {
+		match export.next_chunk().await {
+			Ok(Some(chunk)) => Some((Ok(Bytes::from(chunk)), export)),
+			Ok(None) => None,
+			Err(e) => {
+				error!("Failed to export payments: {e}");
+				Some((
+					Err(actix_web::error::ErrorInternalServerError(
+						"Failed to export payments",
+					)),
+					export,
+				))
+			}
+		}
+	});
+
+	HttpResponse::Ok()
+		.content_type(content_type(filter.format))
+		.streaming(body)
+}
+
+fn content_type(format: ExportFormat) -> &'static str {
+	match format {
+		ExportFormat::Csv => "text/csv; charset=utf-8",
+		ExportFormat::Ndjson => "application/x-ndjson",
+	}
+}
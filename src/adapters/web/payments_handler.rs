@@ -1,22 +1,44 @@
-use actix_web::{HttpResponse, Responder, ResponseError, post, web};
-use log::warn;
+use actix_web::{HttpRequest, HttpResponse, Responder, ResponseError, post, web};
+use time::OffsetDateTime;
+use tracing::{Instrument, info_span, warn};
 
 use crate::adapters::web::errors::ApiError;
 use crate::adapters::web::schema::{PaymentRequest, PaymentResponse};
 use crate::domain::payment::Payment;
-use crate::domain::payment_producer::PaymentProducer;
+use crate::domain::payment_producer::{PaymentProducer, PaymentProducerError};
+use crate::domain::queue::TraceContext;
+use crate::infrastructure::telemetry::propagation::{
+	TRACE_CONTEXT_HEADERS, follow_trace_context,
+};
 
 #[post("/payments")]
 pub async fn payments(
+	request: HttpRequest,
 	payload: web::Json<PaymentRequest>,
 	payment_producer: web::Data<Box<dyn PaymentProducer>>,
 ) -> impl Responder {
+	let span = info_span!(
+		"create_payment",
+		correlation_id = %payload.correlation_id
+	);
+	follow_trace_context(&span, &trace_context_from_headers(&request));
+
+	create_payment(payload, payment_producer)
+		.instrument(span)
+		.await
+}
+
+async fn create_payment(
+	payload: web::Json<PaymentRequest>,
+	payment_producer: web::Data<Box<dyn PaymentProducer>>,
+) -> HttpResponse {
 	let payment = Payment {
 		correlation_id: payload.correlation_id,
 		amount:         payload.amount,
-		requested_at:   None,
+		submitted_at:   None,
 		processed_at:   None,
 		processed_by:   None,
+		received_at:    Some(OffsetDateTime::now_utc()),
 	};
 
 	match payment_producer.send(payment).await {
@@ -24,9 +46,29 @@ pub async fn payments(
 			payment: payload.0,
 			status:  "queued".to_string(),
 		}),
-		Err(e) => {
-			warn!("Error processing payment: {e:?}");
-			ApiError::InternalServerError.error_response()
-		}
+		Err(e) => match e.downcast_ref::<PaymentProducerError>() {
+			Some(PaymentProducerError::Overloaded { retry_after }) => {
+				ApiError::ServiceUnavailable {
+					retry_after_secs: retry_after.as_secs().max(1),
+				}
+				.error_response()
+			}
+			None => {
+				warn!("Error processing payment: {e:?}");
+				ApiError::InternalServerError.error_response()
+			}
+		},
 	}
 }
+
+fn trace_context_from_headers(request: &HttpRequest) -> TraceContext {
+	TraceContext(
+		TRACE_CONTEXT_HEADERS
+			.iter()
+			.filter_map(|name| {
+				let value = request.headers().get(*name)?.to_str().ok()?;
+				Some((name.to_string(), value.to_string()))
+			})
+			.collect(),
+	)
+}
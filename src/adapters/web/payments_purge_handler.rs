@@ -1,21 +1,36 @@
 use actix_web::{HttpResponse, Responder, post, web};
-use log::info;
+use tracing::{error, info};
 
+use crate::adapters::web::schema::PurgePaymentsFilter;
 use crate::infrastructure::persistence::redis_payment_repository::RedisPaymentRepository;
+use crate::use_cases::dto::PurgePaymentsCommand;
 use crate::use_cases::purge_payments::PurgePaymentsUseCase;
 
+/// Mounted under the `/admin` scope, see `admin_scope`. Without filters every
+/// payment is purged, queued and in-flight ones included unless
+/// `preserveQueue=true`; with any filter those are kept unless
+/// `preserveQueue=false`.
 #[post("/purge-payments")]
 pub async fn payments_purge(
+	filter: web::Query<PurgePaymentsFilter>,
 	purge_use_case: web::Data<PurgePaymentsUseCase<RedisPaymentRepository>>,
 ) -> impl Responder {
-	info!("Received request to purge payments");
-	match purge_use_case.execute().await {
-		Ok(_) => {
-			info!("Payments purged successfully");
-			HttpResponse::Ok().body("Payments purged successfully")
+	let filter = filter.into_inner();
+	info!("Received request to purge payments: {filter:?}");
+	let command = PurgePaymentsCommand {
+		group:          filter.group,
+		from:           filter.from,
+		to:             filter.to,
+		preserve_queue: filter.preserve_queue,
+	};
+
+	match purge_use_case.execute(command).await {
+		Ok(result) => {
+			info!("Purged {} payments", result.deleted_payments);
+			HttpResponse::Ok().json(result)
 		}
 		Err(e) => {
-			log::error!("Failed to purge payments: {e}");
+			error!("Failed to purge payments: {e}");
 			HttpResponse::InternalServerError()
 				.body(format!("Failed to purge payments: {e}"))
 		}
@@ -0,0 +1,62 @@
+use actix_web::middleware::from_fn;
+use actix_web::{HttpResponse, Responder, ResponseError, get, web};
+use tracing::error;
+
+use crate::adapters::web::admin_auth::require_admin_token;
+use crate::adapters::web::errors::ApiError;
+use crate::adapters::web::schema::PaymentsSearchFilter;
+use crate::infrastructure::persistence::redis_payment_repository::RedisPaymentRepository;
+use crate::use_cases::dto::SearchPaymentsQuery;
+use crate::use_cases::get_payment_summary::SummaryWindowError;
+use crate::use_cases::search_payments::{
+	SearchPaymentsError, SearchPaymentsUseCase,
+};
+
+/// Served at `/payments`, outside the `/admin` scope, behind the same
+/// `require_admin_token` middleware.
+#[get("/payments", wrap = "from_fn(require_admin_token)")]
+pub async fn payments_search(
+	filter: Result<web::Query<PaymentsSearchFilter>, actix_web::Error>,
+	search_use_case: web::Data<SearchPaymentsUseCase<RedisPaymentRepository>>,
+) -> impl Responder {
+	let filter = match filter {
+		Ok(filter) => filter.into_inner(),
+		Err(e) => {
+			return ApiError::InvalidQuery {
+				reason: e.to_string(),
+			}
+			.error_response();
+		}
+	};
+	let query = SearchPaymentsQuery {
+		processor:  filter.processor,
+		status:     filter.status,
+		from:       filter.from,
+		to:         filter.to,
+		min_amount: filter.min_amount,
+		max_amount: filter.max_amount,
+		order:      filter.order,
+		cursor:     filter.cursor,
+		limit:      filter.limit,
+	};
+
+	match search_use_case.execute(query).await {
+		Ok(page) => HttpResponse::Ok().json(page),
+		Err(e) => {
+			if let Some(search_error) = e.downcast_ref::<SearchPaymentsError>() {
+				return ApiError::InvalidQuery {
+					reason: search_error.to_string(),
+				}
+				.error_response();
+			}
+			if let Some(window_error) = e.downcast_ref::<SummaryWindowError>() {
+				return ApiError::InvalidQuery {
+					reason: window_error.to_string(),
+				}
+				.error_response();
+			}
+			error!("Error searching payments: {e:?}");
+			ApiError::InternalServerError.error_response()
+		}
+	}
+}
@@ -1,27 +1,75 @@
 use actix_web::{HttpResponse, Responder, ResponseError, get, web};
+use tracing::error;
 
 use crate::adapters::web::errors::ApiError;
 use crate::adapters::web::schema::PaymentsSummaryFilter;
 use crate::infrastructure::persistence::redis_payment_repository::RedisPaymentRepository;
-use crate::use_cases::dto::GetPaymentSummaryQuery;
-use crate::use_cases::get_payment_summary::GetPaymentSummaryUseCase;
+use crate::use_cases::dto::{
+	GetPaymentSummaryBreakdownQuery, GetPaymentSummaryQuery,
+};
+use crate::use_cases::get_payment_summary::{
+	GetPaymentSummaryUseCase, SummaryWindowError,
+};
 
 #[get("/payments-summary")]
 pub async fn payments_summary(
-	filter: web::Query<PaymentsSummaryFilter>,
+	filter: Result<web::Query<PaymentsSummaryFilter>, actix_web::Error>,
 	get_payment_summary_use_case: web::Data<
 		GetPaymentSummaryUseCase<RedisPaymentRepository>,
 	>,
 ) -> impl Responder {
+	let filter = match filter {
+		Ok(filter) => filter,
+		Err(e) => {
+			return ApiError::InvalidQuery {
+				reason: e.to_string(),
+			}
+			.error_response();
+		}
+	};
+
+	if let Some(granularity) = filter.granularity {
+		if filter.fees.is_some() {
+			return ApiError::InvalidQuery {
+				reason: "`fees` cannot be combined with `granularity`.".to_string(),
+			}
+			.error_response();
+		}
+
+		let query = GetPaymentSummaryBreakdownQuery {
+			from: filter.from,
+			to: filter.to,
+			granularity,
+			consistent: filter.consistent,
+		};
+
+		return match get_payment_summary_use_case.execute_breakdown(query).await {
+			Ok(breakdown) => HttpResponse::Ok().json(breakdown),
+			Err(e) => summary_error_response(e),
+		};
+	}
+
 	let query = GetPaymentSummaryQuery {
-		from: filter.from,
-		to:   filter.to,
+		from:       filter.from,
+		to:         filter.to,
+		fees:       filter.fees,
+		consistent: filter.consistent,
 	};
 
 	match get_payment_summary_use_case.execute(query).await {
 		Ok(summary) => HttpResponse::Ok().json(summary),
-		Err(e) => {
-			eprintln!("Error getting payment summary: {e:?}");
+		Err(e) => summary_error_response(e),
+	}
+}
+
+fn summary_error_response(e: Box<dyn std::error::Error + Send>) -> HttpResponse {
+	match e.downcast_ref::<SummaryWindowError>() {
+		Some(window_error) => ApiError::InvalidQuery {
+			reason: window_error.to_string(),
+		}
+		.error_response(),
+		None => {
+			error!("Error getting payment summary: {e:?}");
 			ApiError::InternalServerError.error_response()
 		}
 	}
@@ -1,7 +1,12 @@
 use actix_web::cookie::time::OffsetDateTime;
 use serde::{Deserialize, Serialize};
+use time::format_description::well_known::Rfc3339;
 use uuid::Uuid;
 
+use crate::domain::payment::PaymentStatus;
+use crate::domain::repository::SortOrder;
+use crate::use_cases::dto::{ExportFormat, FeeBasis, Granularity};
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct PaymentRequest {
 	#[serde(rename = "correlationId")]
@@ -15,10 +20,180 @@ pub struct PaymentResponse {
 	pub status:  String,
 }
 
+/// `from` and `to` take RFC 3339 timestamps or milliseconds since the epoch.
+/// Leaving one out opens the window on that side.
 #[derive(Debug, Deserialize, Serialize)]
 pub struct PaymentsSummaryFilter {
+	#[serde(
+		serialize_with = "time::serde::rfc3339::option::serialize",
+		deserialize_with = "timestamp::deserialize",
+		default
+	)]
+	pub from:        Option<OffsetDateTime>,
+	#[serde(
+		serialize_with = "time::serde::rfc3339::option::serialize",
+		deserialize_with = "timestamp::deserialize",
+		default
+	)]
+	pub to:          Option<OffsetDateTime>,
+	/// Switches the response to a per-bucket breakdown.
+	pub granularity: Option<Granularity>,
+	/// Adds fees computed at the `current` rates or at the rates in effect
+	/// when each payment was `processing`. Breakdowns do not report fees, so
+	/// it cannot be combined with `granularity`.
+	pub fees:        Option<FeeBasis>,
+	/// Waits, for a bounded time, until the payments requested within the
+	/// window are no longer in flight, and reports those that still are.
+	#[serde(default)]
+	pub consistent:  bool,
+}
+
+/// Takes the same timestamps as `PaymentsSummaryFilter`. Pages come newest
+/// first unless `order=asc`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PaymentsSearchFilter {
+	pub processor:  Option<String>,
+	#[serde(default)]
+	pub status:     PaymentStatus,
+	#[serde(
+		serialize_with = "time::serde::rfc3339::option::serialize",
+		deserialize_with = "timestamp::deserialize",
+		default
+	)]
+	pub from:       Option<OffsetDateTime>,
+	#[serde(
+		serialize_with = "time::serde::rfc3339::option::serialize",
+		deserialize_with = "timestamp::deserialize",
+		default
+	)]
+	pub to:         Option<OffsetDateTime>,
+	#[serde(rename = "minAmount")]
+	pub min_amount: Option<f64>,
+	#[serde(rename = "maxAmount")]
+	pub max_amount: Option<f64>,
+	#[serde(default)]
+	pub order:      SortOrder,
+	pub cursor:     Option<String>,
+	pub limit:      Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PaymentsExportFilter {
+	#[serde(
+		serialize_with = "time::serde::rfc3339::option::serialize",
+		deserialize_with = "timestamp::deserialize",
+		default
+	)]
+	pub from:   Option<OffsetDateTime>,
+	#[serde(
+		serialize_with = "time::serde::rfc3339::option::serialize",
+		deserialize_with = "timestamp::deserialize",
+		default
+	)]
+	pub to:     Option<OffsetDateTime>,
+	#[serde(default)]
+	pub format: ExportFormat,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PurgePaymentsFilter {
+	pub group:          Option<String>,
 	#[serde(with = "time::serde::rfc3339::option", default)]
-	pub from: Option<OffsetDateTime>,
+	pub from:           Option<OffsetDateTime>,
 	#[serde(with = "time::serde::rfc3339::option", default)]
-	pub to:   Option<OffsetDateTime>,
+	pub to:             Option<OffsetDateTime>,
+	#[serde(rename = "preserveQueue", default)]
+	pub preserve_queue: Option<bool>,
+}
+
+/// Reads an RFC 3339 timestamp or a number of milliseconds since the epoch.
+pub fn parse_timestamp(value: &str) -> Option<OffsetDateTime> {
+	match value.parse::<i64>() {
+		Ok(millis) => {
+			OffsetDateTime::from_unix_timestamp_nanos(i128::from(millis) * 1_000_000)
+				.ok()
+		}
+		Err(_) => OffsetDateTime::parse(value, &Rfc3339).ok(),
+	}
+}
+
+mod timestamp {
+	use serde::Deserialize;
+	use serde::de::{Deserializer, Error};
+	use time::OffsetDateTime;
+
+	pub fn deserialize<'de, D>(
+		deserializer: D,
+	) -> Result<Option<OffsetDateTime>, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		let Some(value) = Option::<String>::deserialize(deserializer)? else {
+			return Ok(None);
+		};
+
+		super::parse_timestamp(&value).map(Some).ok_or_else(|| {
+			D::Error::custom(format!(
+				"`{value}` is neither an RFC 3339 timestamp nor epoch milliseconds"
+			))
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_payments_search_filter_reads_camel_case_parameters() {
+		let filter = actix_web::web::Query::<PaymentsSearchFilter>::from_query(
+			"processor=fallback&status=in_flight&minAmount=10.5&maxAmount=20&\
+			 order=asc&limit=10&from=1751364000000",
+		)
+		.unwrap()
+		.into_inner();
+
+		assert_eq!(filter.processor.as_deref(), Some("fallback"));
+		assert_eq!(filter.status, PaymentStatus::InFlight);
+		assert_eq!(filter.min_amount, Some(10.5));
+		assert_eq!(filter.max_amount, Some(20.0));
+		assert_eq!(filter.order, SortOrder::Asc);
+		assert_eq!(filter.limit, Some(10));
+		assert_eq!(
+			filter.from,
+			Some(OffsetDateTime::from_unix_timestamp(1_751_364_000).unwrap())
+		);
+	}
+
+	fn summary_filter(query: &str) -> Result<PaymentsSummaryFilter, String> {
+		actix_web::web::Query::<PaymentsSummaryFilter>::from_query(query)
+			.map(|filter| filter.into_inner())
+			.map_err(|e| e.to_string())
+	}
+
+	#[test]
+	fn test_summary_filter_accepts_rfc3339_and_epoch_millis() {
+		let filter =
+			summary_filter("from=2025-07-01T10:00:00Z&to=1751364000500").unwrap();
+
+		assert_eq!(
+			filter.from,
+			Some(OffsetDateTime::from_unix_timestamp(1_751_364_000).unwrap())
+		);
+		assert_eq!(
+			filter.to,
+			Some(
+				OffsetDateTime::from_unix_timestamp_nanos(1_751_364_000_500_000_000)
+					.unwrap()
+			)
+		);
+		assert!(summary_filter("").unwrap().from.is_none());
+	}
+
+	#[test]
+	fn test_summary_filter_rejects_malformed_timestamps() {
+		assert!(summary_filter("from=yesterday").unwrap_err().ends_with(
+			"`yesterday` is neither an RFC 3339 timestamp nor epoch milliseconds"
+		));
+	}
 }
@@ -0,0 +1,114 @@
+use actix_web::web::Bytes;
+use actix_web::{HttpResponse, Responder, ResponseError, get, post, web};
+use futures::{StreamExt, stream};
+use time::OffsetDateTime;
+use tracing::{error, info};
+
+use crate::adapters::web::errors::ApiError;
+use crate::infrastructure::persistence::redis_payment_repository::RedisPaymentRepository;
+use crate::infrastructure::workers::worker_pool::WorkerPool;
+use crate::use_cases::snapshot::{SnapshotError, SnapshotUseCase};
+
+/// Mounted under the `/admin` scope, see `admin_scope`. Streams every
+/// processed payment, queued message and bucket as NDJSON records.
+#[get("/snapshot")]
+pub async fn payments_snapshot(
+	snapshot_use_case: web::Data<SnapshotUseCase<RedisPaymentRepository>>,
+) -> impl Responder {
+	info!("Received request to snapshot payments");
+
+	// The status is sent with the first chunk, so a failure midway can only
+	// cut the body short.
+	let body =
+		stream::unfold(snapshot_use_case.snapshot(), |mut snapshot| async move {
+			match snapshot.next_chunk().await {
+				Ok(Some(chunk)) => Some((Ok(Bytes::from(chunk)), snapshot)),
+				Ok(None) => None,
+				Err(e) => {
+					error!("Failed to snapshot payments: {e}");
+					Some((
+						Err(actix_web::error::ErrorInternalServerError(
+							"Failed to snapshot payments",
+						)),
+						snapshot,
+					))
+				}
+			}
+		});
+
+	HttpResponse::Ok()
+		.content_type("application/x-ndjson")
+		.insert_header((
+			"Content-Disposition",
+			format!(
+				"attachment; filename=\"payments-{}.ndjson\"",
+				OffsetDateTime::now_utc().unix_timestamp()
+			),
+		))
+		.streaming(body)
+}
+
+/// Mounted under the `/admin` scope, see `admin_scope`. Replaces everything
+/// the store holds with the snapshot in the body.
+///
+/// Refused unless this instance's worker pool was scaled down to zero, see
+/// `reload_config`; the restore itself also refuses while another instance
+/// still has payments in flight.
+#[post("/restore")]
+pub async fn payments_restore(
+	mut payload: web::Payload,
+	snapshot_use_case: web::Data<SnapshotUseCase<RedisPaymentRepository>>,
+	worker_pool: web::Data<WorkerPool>,
+) -> impl Responder {
+	info!("Received request to restore payments");
+
+	if !worker_pool.is_stopped() {
+		return ApiError::Conflict {
+			reason: "Payment processing workers are running; scale them down to \
+			         zero before restoring."
+				.to_string(),
+		}
+		.error_response();
+	}
+
+	let mut restore = snapshot_use_case.restore();
+	while let Some(chunk) = payload.next().await {
+		let chunk = match chunk {
+			Ok(chunk) => chunk,
+			Err(e) => {
+				return ApiError::InvalidQuery {
+					reason: e.to_string(),
+				}
+				.error_response();
+			}
+		};
+		if let Err(e) = restore.feed(&chunk).await {
+			return restore_error(e);
+		}
+	}
+
+	match restore.finish().await {
+		Ok(result) => {
+			info!("Restored payments: {result:?}");
+			HttpResponse::Ok().json(result)
+		}
+		Err(e) => restore_error(e),
+	}
+}
+
+fn restore_error(e: Box<dyn std::error::Error + Send>) -> HttpResponse {
+	match e.downcast_ref::<SnapshotError>() {
+		Some(SnapshotError::PaymentsInFlight { .. }) => ApiError::Conflict {
+			reason: e.to_string(),
+		}
+		.error_response(),
+		Some(snapshot_error) => ApiError::InvalidQuery {
+			reason: snapshot_error.to_string(),
+		}
+		.error_response(),
+		None => {
+			error!("Failed to restore payments: {e}");
+			ApiError::InternalServerError.error_response()
+		}
+	}
+}
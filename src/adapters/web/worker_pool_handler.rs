@@ -0,0 +1,8 @@
+use actix_web::{HttpResponse, Responder, get, web};
+
+use crate::infrastructure::workers::worker_pool::WorkerPool;
+
+#[get("/workers")]
+pub async fn worker_pool_status(pool: web::Data<WorkerPool>) -> impl Responder {
+	HttpResponse::Ok().json(pool.status())
+}
@@ -0,0 +1,47 @@
+use std::sync::{Arc, RwLock};
+
+/// Fee per transaction of each processor, as a fraction of the amount.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FeeRates {
+	pub default:  f64,
+	pub fallback: f64,
+}
+
+impl FeeRates {
+	/// Rate of the processor group a payment was `processed_by`.
+	pub fn for_group(&self, group: &str) -> Option<f64> {
+		match group {
+			"default" => Some(self.default),
+			"fallback" => Some(self.fallback),
+			_ => None,
+		}
+	}
+}
+
+/// The fee rates currently in effect, shared by the writers that stamp them
+/// on processed payments and the readers that report fees.
+#[derive(Debug, Clone, Default)]
+pub struct FeeSchedule {
+	rates: Arc<RwLock<FeeRates>>,
+}
+
+impl FeeSchedule {
+	pub fn new(rates: FeeRates) -> Self {
+		Self {
+			rates: Arc::new(RwLock::new(rates)),
+		}
+	}
+
+	pub fn rates(&self) -> FeeRates {
+		*self.rates.read().unwrap()
+	}
+
+	pub fn set_rate(&self, group: &str, rate: f64) {
+		let mut rates = self.rates.write().unwrap();
+		match group {
+			"default" => rates.default = rate,
+			"fallback" => rates.fallback = rate,
+			_ => {}
+		}
+	}
+}
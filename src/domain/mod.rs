@@ -1,3 +1,4 @@
+pub mod fee_schedule;
 pub mod health_status;
 pub mod payment;
 pub mod payment_processor;
@@ -5,3 +6,4 @@ pub mod payment_producer;
 pub mod payment_router;
 pub mod queue;
 pub mod repository;
+pub mod snapshot;
@@ -2,18 +2,23 @@ use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 use uuid::Uuid;
 
+/// New fields go last: payments queued as msgpack arrays by older versions
+/// are still read by position.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Payment {
 	#[serde(rename = "correlationId")]
 	pub correlation_id: Uuid,
 	pub amount:         f64,
+	/// When the latest attempt was sent to a processor, which knows it as
+	/// `requestedAt`.
 	#[serde(
-		rename = "requestedAt",
+		rename = "submittedAt",
+		alias = "requestedAt",
 		with = "time::serde::rfc3339::option",
 		skip_serializing_if = "Option::is_none",
 		default
 	)]
-	pub requested_at:   Option<OffsetDateTime>,
+	pub submitted_at:   Option<OffsetDateTime>,
 	#[serde(
 		rename = "processedAt",
 		with = "time::serde::rfc3339::option",
@@ -23,11 +28,52 @@ pub struct Payment {
 	pub processed_at:   Option<OffsetDateTime>,
 	#[serde(skip_serializing_if = "Option::is_none", default)]
 	pub processed_by:   Option<String>,
+	/// When the client's request reached this service.
+	#[serde(
+		rename = "receivedAt",
+		with = "time::serde::rfc3339::option",
+		skip_serializing_if = "Option::is_none",
+		default
+	)]
+	pub received_at:    Option<OffsetDateTime>,
+}
+
+impl Payment {
+	/// Falls back to `submitted_at` for payments received before the
+	/// other timestamps were recorded, or not processed yet.
+	pub fn timestamp(&self, timestamp: PaymentTimestamp) -> Option<OffsetDateTime> {
+		match timestamp {
+			PaymentTimestamp::Received => self.received_at.or(self.submitted_at),
+			PaymentTimestamp::Submitted => self.submitted_at,
+			PaymentTimestamp::Processed => self.processed_at.or(self.submitted_at),
+		}
+	}
+}
+
+/// The moments of a payment's life the summaries can filter on.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PaymentTimestamp {
+	Received,
+	/// Matches the processors' own summaries, which filter on `requestedAt`.
+	#[default]
+	Submitted,
+	Processed,
+}
+
+/// Where a payment is in its life once a worker took it.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentStatus {
+	#[default]
+	Processed,
+	/// Sent to a processor, and neither saved nor re-queued yet.
+	InFlight,
 }
 
 #[cfg(test)]
 mod tests {
-	use rinha_de_backend::domain::payment::Payment;
+	use rinha_de_backend::domain::payment::{Payment, PaymentTimestamp};
 	use serde_json;
 	use time::OffsetDateTime;
 	use uuid::Uuid;
@@ -45,19 +91,37 @@ mod tests {
 		let payment = Payment {
 			correlation_id,
 			amount: 1.0,
-			requested_at: Some(requested_at),
+			submitted_at: Some(requested_at),
 			processed_at: None,
 			processed_by: None,
+			received_at: None,
 		};
 
 		let expected_json = serde_json::json!({
 			"correlationId": "7b3739e4-5be8-4f98-84a7-a13fd5984059",
 			"amount": 1.0,
-			"requestedAt": "2017-07-21T17:32:28Z"
+			"submittedAt": "2017-07-21T17:32:28Z"
 		});
 
 		let serialized_payment = serde_json::to_value(&payment).unwrap();
 
 		assert_eq!(serialized_payment, expected_json);
 	}
+
+	#[test]
+	fn test_payment_reads_requested_at_as_submitted_at() {
+		let payment: Payment = serde_json::from_value(serde_json::json!({
+			"correlationId": "7b3739e4-5be8-4f98-84a7-a13fd5984059",
+			"amount": 1.0,
+			"requestedAt": "2017-07-21T17:32:28Z"
+		}))
+		.unwrap();
+
+		assert!(payment.submitted_at.is_some());
+		assert_eq!(payment.received_at, None);
+		assert_eq!(
+			payment.timestamp(PaymentTimestamp::Received),
+			payment.submitted_at
+		);
+	}
 }
@@ -1,7 +1,16 @@
+use std::time::Duration;
+
 use async_trait::async_trait;
+use derive_more::derive::{Display, Error};
 
 use crate::domain::payment::Payment;
 
+#[derive(Debug, Display, Error)]
+pub enum PaymentProducerError {
+	#[display("Payment buffer is saturated.")]
+	Overloaded { retry_after: Duration },
+}
+
 #[async_trait]
 pub trait PaymentProducer: Send + Sync + 'static {
 	async fn send(
@@ -1,16 +1,35 @@
+use std::collections::HashMap;
+
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+/// Propagation fields (W3C `traceparent`/`tracestate`) of the span a message
+/// was produced in, so its consumer can continue the same trace.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct TraceContext(pub HashMap<String, String>);
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Message<B> {
-	pub id:   Uuid,
-	pub body: B,
+	pub id:            Uuid,
+	pub body:          B,
+	/// Defaulted so messages queued before it existed can still be read.
+	#[serde(default)]
+	pub trace_context: TraceContext,
 }
 
 impl<B> Message<B> {
 	pub fn with(id: Uuid, body: B) -> Message<B> {
-		Message { id, body }
+		Message {
+			id,
+			body,
+			trace_context: TraceContext::default(),
+		}
+	}
+
+	pub fn with_trace_context(mut self, trace_context: TraceContext) -> Message<B> {
+		self.trace_context = trace_context;
+		self
 	}
 }
 
@@ -19,8 +38,45 @@ pub trait Queue<B>: Send + Sync + 'static {
 	async fn pop(
 		&self,
 	) -> Result<Option<Message<B>>, Box<dyn std::error::Error + Send>>;
+	async fn pop_batch(
+		&self,
+		count: usize,
+	) -> Result<Vec<Message<B>>, Box<dyn std::error::Error + Send>>;
 	async fn push(
 		&self,
 		message: Message<B>,
 	) -> Result<(), Box<dyn std::error::Error + Send>>;
+	async fn push_batch(
+		&self,
+		messages: Vec<Message<B>>,
+	) -> Result<(), Box<dyn std::error::Error + Send>>;
+	async fn length(&self) -> Result<usize, Box<dyn std::error::Error + Send>>;
+	/// Puts back a message whose processing failed, releasing anything held
+	/// for it.
+	async fn requeue(
+		&self,
+		message: Message<B>,
+	) -> Result<(), Box<dyn std::error::Error + Send>>
+	where
+		B: Send + 'static,
+	{
+		self.push(message).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_message_without_trace_context_can_be_read() {
+		let id = Uuid::new_v4();
+		let encoded = rmp_serde::to_vec(&(id, 42_u32)).unwrap();
+
+		let message: Message<u32> = rmp_serde::from_slice(&encoded).unwrap();
+
+		assert_eq!(message.id, id);
+		assert_eq!(message.body, 42);
+		assert_eq!(message.trace_context, TraceContext::default());
+	}
 }
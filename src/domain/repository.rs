@@ -1,7 +1,91 @@
+use std::collections::BTreeMap;
+
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 
-use crate::domain::payment::Payment;
+use crate::domain::payment::{Payment, PaymentStatus};
+
+/// Which payments `PaymentRepository::purge` deletes. Unset filters match
+/// every payment.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PurgeScope {
+	pub group:          Option<String>,
+	pub from:           Option<OffsetDateTime>,
+	pub to:             Option<OffsetDateTime>,
+	/// Keeps payments that are still waiting in the queue or in flight.
+	pub preserve_queue: bool,
+}
+
+impl PurgeScope {
+	pub fn is_everything(&self) -> bool {
+		self.group.is_none() && self.from.is_none() && self.to.is_none()
+	}
+}
+
+/// The processed payments of a group as the summary breakdown sees them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AmountsBreakdown {
+	/// Payments counted and summed in cents per bucket, keyed by its start in
+	/// nanoseconds since the epoch.
+	pub buckets: BTreeMap<i128, (usize, i64)>,
+	/// Payments counted per amount in cents.
+	pub amounts: BTreeMap<i64, usize>,
+}
+
+/// Fees of the payments stamped with a rate when processed, and the gross
+/// amount of those that were not.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ProcessedFees {
+	pub fees:           f64,
+	pub unrated_amount: f64,
+}
+
+/// The processed payments of a group within a window, counted, summed and
+/// with their fees.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GroupSummary {
+	pub total_requests: usize,
+	pub total_amount:   f64,
+	pub fees:           ProcessedFees,
+}
+
+/// Resumes a paginated read past the first `skip` payments at `position`, in
+/// the order the read walks its index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaymentsCursor {
+	pub position: f64,
+	pub skip:     usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PaymentsPage {
+	pub payments: Vec<Payment>,
+	/// `None` once the window is exhausted.
+	pub next:     Option<PaymentsCursor>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+	Asc,
+	#[default]
+	Desc,
+}
+
+/// Which payments `PaymentRepository::search_payments` returns. Unset filters
+/// match every payment; bounds are inclusive.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PaymentSearch {
+	pub processor:  Option<String>,
+	pub status:     PaymentStatus,
+	pub from:       Option<OffsetDateTime>,
+	pub to:         Option<OffsetDateTime>,
+	pub min_amount: Option<f64>,
+	pub max_amount: Option<f64>,
+	/// By the timestamp the summaries filter on.
+	pub order:      SortOrder,
+}
 
 #[async_trait]
 pub trait PaymentRepository: Send + Sync + 'static {
@@ -9,12 +93,67 @@ pub trait PaymentRepository: Send + Sync + 'static {
 		&self,
 		payment: Payment,
 	) -> Result<(), Box<dyn std::error::Error + Send>>;
+	/// Windows are inclusive, an unset bound leaves that side open. Compacted
+	/// payments count when their whole bucket lies within the window.
 	async fn get_summary_by_group(
 		&self,
 		group: &str,
-		from_ts: OffsetDateTime,
-		to_ts: OffsetDateTime,
+		from_ts: Option<OffsetDateTime>,
+		to_ts: Option<OffsetDateTime>,
 	) -> Result<(usize, f64), Box<dyn std::error::Error + Send>>;
+	/// The payments of `group` requested within the window, aggregated per
+	/// `bucket`-long window by the store a page at a time. `None` once more
+	/// than `max_buckets` buckets hold payments. Compacted payments are left
+	/// out.
+	async fn get_breakdown_by_group(
+		&self,
+		group: &str,
+		from_ts: Option<OffsetDateTime>,
+		to_ts: Option<OffsetDateTime>,
+		bucket: std::time::Duration,
+		max_buckets: usize,
+	) -> Result<Option<AmountsBreakdown>, Box<dyn std::error::Error + Send>>;
+	/// Like `get_summary_by_group`, along with the fees, read in the same
+	/// pass so they cover the same payments.
+	async fn get_summary_with_fees_by_group(
+		&self,
+		group: &str,
+		from_ts: Option<OffsetDateTime>,
+		to_ts: Option<OffsetDateTime>,
+	) -> Result<GroupSummary, Box<dyn std::error::Error + Send>>;
+	/// Up to `limit` processed payments of the window, oldest first, starting
+	/// at `cursor` or at the start of the window. Compacted payments are left
+	/// out.
+	async fn get_processed_payments(
+		&self,
+		from_ts: Option<OffsetDateTime>,
+		to_ts: Option<OffsetDateTime>,
+		cursor: Option<PaymentsCursor>,
+		limit: usize,
+	) -> Result<PaymentsPage, Box<dyn std::error::Error + Send>>;
+	/// Up to `limit` payments matching `search`, starting at `cursor`. A page
+	/// may come back short, or even empty, while `next` is still set: each
+	/// call only looks at a bounded number of candidates. Compacted payments
+	/// are left out.
+	async fn search_payments(
+		&self,
+		search: &PaymentSearch,
+		cursor: Option<PaymentsCursor>,
+		limit: usize,
+	) -> Result<PaymentsPage, Box<dyn std::error::Error + Send>>;
+	/// Records that the payment was sent to `processor`. Saving or
+	/// re-queueing the payment settles it.
+	async fn mark_in_flight(
+		&self,
+		payment: &Payment,
+		processor: &str,
+	) -> Result<(), Box<dyn std::error::Error + Send>>;
+	/// Payments requested within the window that are not settled yet.
+	async fn count_in_flight(
+		&self,
+		from_ts: Option<OffsetDateTime>,
+		to_ts: Option<OffsetDateTime>,
+	) -> Result<usize, Box<dyn std::error::Error + Send>>;
 	async fn get_payment_summary(
 		&self,
 		group: &str,
@@ -24,5 +163,36 @@ pub trait PaymentRepository: Send + Sync + 'static {
 		&self,
 		payment_id: &str,
 	) -> Result<bool, Box<dyn std::error::Error + Send>>;
-	async fn clear(&self) -> Result<(), Box<dyn std::error::Error + Send>>;
+	/// Takes a payment for processing. `false` means it is already processed
+	/// or another worker holds it; the claim ends when the payment is saved or
+	/// re-queued.
+	async fn claim(
+		&self,
+		payment_id: &str,
+	) -> Result<bool, Box<dyn std::error::Error + Send>>;
+	/// Keeps a claim, and the in-flight record of its payment, from expiring
+	/// while the payment is still being processed. `false` means the claim is
+	/// already gone.
+	async fn extend_claim(
+		&self,
+		payment_id: &str,
+	) -> Result<bool, Box<dyn std::error::Error + Send>>;
+	/// Folds up to `limit` of the payments submitted before `before` into
+	/// per-group aggregates of `bucket`-long windows, which summaries keep
+	/// counting, and deletes their details. Only their id is kept, so they
+	/// still count as processed, until they were submitted more than
+	/// `dedup_window` before `before`. Returns how many it went through, so
+	/// fewer than `limit` means none are left.
+	async fn compact(
+		&self,
+		before: OffsetDateTime,
+		bucket: std::time::Duration,
+		dedup_window: std::time::Duration,
+		limit: usize,
+	) -> Result<usize, Box<dyn std::error::Error + Send>>;
+	/// Deletes the payments in `scope`, returning how many were deleted.
+	async fn purge(
+		&self,
+		scope: &PurgeScope,
+	) -> Result<usize, Box<dyn std::error::Error + Send>>;
 }
@@ -0,0 +1,111 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::domain::payment::{Payment, PaymentTimestamp};
+use crate::domain::queue::Message;
+use crate::domain::repository::PaymentsCursor;
+
+/// Bumped whenever a record changes shape. Restores refuse snapshots written
+/// by a newer version. Version 2 added `InFlight` records.
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+/// One line of a snapshot file: a header, then the processed payments, the
+/// queued messages oldest first, the payments in flight and the buckets of
+/// compacted payments.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SnapshotRecord {
+	Header {
+		version:    u32,
+		#[serde(rename = "createdAt", with = "time::serde::rfc3339")]
+		created_at: OffsetDateTime,
+	},
+	Payment {
+		#[serde(flatten)]
+		payment:  Payment,
+		/// The rate stamped on the payment when it was processed, if any.
+		#[serde(
+			rename = "feeRate",
+			skip_serializing_if = "Option::is_none",
+			default
+		)]
+		fee_rate: Option<f64>,
+	},
+	Queued {
+		message: Message<Payment>,
+	},
+	/// A payment sent to a processor but not settled yet. It is restored as a
+	/// queued message, as if its worker had died, since whether the processor
+	/// got it is unknown.
+	InFlight {
+		#[serde(flatten)]
+		payment: Payment,
+	},
+	Bucket(PaymentBucket),
+}
+
+/// The compacted payments of a group whose `timestamp` lies within `start`
+/// and `end`, in nanoseconds since the epoch.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PaymentBucket {
+	pub timestamp:      PaymentTimestamp,
+	pub group:          String,
+	pub start:          i64,
+	pub end:            i64,
+	pub count:          u64,
+	pub amount:         f64,
+	pub fees:           f64,
+	#[serde(rename = "unratedAmount")]
+	pub unrated_amount: f64,
+}
+
+/// Raw access to everything a payment store holds, for snapshots. Reads are
+/// not isolated from concurrent writes, so a consistent snapshot needs the
+/// workers stopped.
+#[async_trait]
+pub trait SnapshotStore: Send + Sync + 'static {
+	/// A page of processed payments as `Payment` records, oldest submitted
+	/// first, and where the next one starts.
+	async fn read_payments(
+		&self,
+		cursor: Option<PaymentsCursor>,
+		limit: usize,
+	) -> Result<
+		(Vec<SnapshotRecord>, Option<PaymentsCursor>),
+		Box<dyn std::error::Error + Send>,
+	>;
+	/// Up to `limit` queued messages, oldest first, past the `skip` oldest.
+	/// A message that no longer decodes is an error rather than left out.
+	async fn read_queue(
+		&self,
+		skip: usize,
+		limit: usize,
+	) -> Result<Vec<Message<Payment>>, Box<dyn std::error::Error + Send>>;
+	/// The payments sent to a processor and not settled yet, as
+	/// `mark_in_flight` recorded them.
+	async fn read_in_flight(
+		&self,
+	) -> Result<Vec<Payment>, Box<dyn std::error::Error + Send>>;
+	/// Takes up to `count` of the oldest queued messages off the queue,
+	/// oldest first, without waiting for any. They are held aside until
+	/// `release_queue`, and handed out again instead of new ones until then,
+	/// so a taker that stopped before writing them elsewhere gets them back.
+	async fn take_queue(
+		&self,
+		count: usize,
+	) -> Result<Vec<Message<Payment>>, Box<dyn std::error::Error + Send>>;
+	/// Drops the messages held by `take_queue`, once they are safe elsewhere.
+	async fn release_queue(&self) -> Result<(), Box<dyn std::error::Error + Send>>;
+	async fn read_buckets(
+		&self,
+	) -> Result<Vec<PaymentBucket>, Box<dyn std::error::Error + Send>>;
+	/// Adds the records to what the store holds, queueing `Queued` messages
+	/// and `InFlight` payments behind those already waiting. Payments and
+	/// buckets replace those with the same id or start, so writing them again
+	/// is harmless. Headers are ignored.
+	async fn write(
+		&self,
+		records: Vec<SnapshotRecord>,
+	) -> Result<(), Box<dyn std::error::Error + Send>>;
+}
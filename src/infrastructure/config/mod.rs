@@ -1,2 +1,3 @@
 pub mod redis;
+pub mod routing_reloader;
 pub mod settings;
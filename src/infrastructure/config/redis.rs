@@ -1,24 +1,777 @@
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
-use redis::RedisError;
-use redis::aio::MultiplexedConnection;
+use redis::aio::{ConnectionLike, ConnectionManager, ConnectionManagerConfig};
+use redis::cluster::ClusterClient;
+use redis::cluster_async::ClusterConnection;
+use redis::cluster_routing::{
+	Route, RoutingInfo, SingleNodeRoutingInfo, SlotAddr, get_slot,
+};
+use redis::sentinel::{
+	SentinelClient, SentinelNodeConnectionInfo, SentinelServerType,
+};
+use redis::{
+	Cmd, ErrorKind, FromRedisValue, Pipeline, RedisConnectionInfo, RedisError,
+	RedisFuture, RedisResult, TlsMode, Value,
+};
+use serde::Deserialize;
+use tokio::time::{Instant, sleep};
+use tracing::{error, info, warn};
+
+use crate::domain::payment::PaymentTimestamp;
+use crate::infrastructure::metrics::redis_metrics::RedisConnectionMetrics;
 
 pub const PAYMENTS_QUEUE_KEY: &str = "payments_queue";
 pub const PROCESSED_PAYMENTS_SET_KEY: &str = "processed_payments";
+pub const PAYMENTS_IN_FLIGHT_SET_KEY: &str = "payments_in_flight";
+pub const RECEIVED_PAYMENTS_SET_KEY: &str = "processed_payments:received_at";
+pub const PROCESSED_AT_PAYMENTS_SET_KEY: &str = "processed_payments:processed_at";
+pub const COMPACTED_PAYMENTS_SET_KEY: &str = "processed_payments:compacted";
 pub const DEFAULT_PAYMENT_SUMMARY_KEY: &str = "payment_summary:default";
 pub const FALLBACK_PAYMENT_SUMMARY_KEY: &str = "payment_summary:fallback";
+const PAYMENT_KEY_PREFIX: &str = "payment_summary";
+const PAYMENT_CLAIM_KEY_PREFIX: &str = "payment_claim";
+const IN_FLIGHT_PAYMENT_KEY_PREFIX: &str = "payment_in_flight";
+const INDEXES_BACKFILLED_KEY: &str = "processed_payments:indexes:backfilled";
+/// Attempts a connection makes to come back before failing the request that
+/// noticed it broke; the next request starts over.
+const RECONNECT_RETRIES: usize = 2;
+const STARTUP_RETRY_INITIAL_DELAY: Duration = Duration::from_millis(100);
+
+/// Names every key this service owns in Redis, so several deployments can
+/// share one instance under different prefixes.
+///
+/// With `hash_tag` the prefix is wrapped in braces (`{staging}:`), which makes
+/// Redis Cluster hash only the prefix and keeps every key in the same slot, as
+/// the atomic pipelines and Lua scripts require.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RedisKeys {
+	namespace:          Arc<str>,
+	payments_queue:     Arc<str>,
+	processed_payments: Arc<str>,
+	payments_in_flight: Arc<str>,
+	received_payments:  Arc<str>,
+	processed_at_index: Arc<str>,
+	compacted_payments: Arc<str>,
+}
+
+impl RedisKeys {
+	pub fn new(prefix: &str, hash_tag: bool) -> Self {
+		let namespace = match (prefix.is_empty(), hash_tag) {
+			(true, _) => String::new(),
+			(false, true) => format!("{{{prefix}}}:"),
+			(false, false) => format!("{prefix}:"),
+		};
+
+		Self {
+			payments_queue:     Arc::from(format!(
+				"{namespace}{PAYMENTS_QUEUE_KEY}"
+			)),
+			processed_payments: Arc::from(format!(
+				"{namespace}{PROCESSED_PAYMENTS_SET_KEY}"
+			)),
+			payments_in_flight: Arc::from(format!(
+				"{namespace}{PAYMENTS_IN_FLIGHT_SET_KEY}"
+			)),
+			received_payments:  Arc::from(format!(
+				"{namespace}{RECEIVED_PAYMENTS_SET_KEY}"
+			)),
+			processed_at_index: Arc::from(format!(
+				"{namespace}{PROCESSED_AT_PAYMENTS_SET_KEY}"
+			)),
+			compacted_payments: Arc::from(format!(
+				"{namespace}{COMPACTED_PAYMENTS_SET_KEY}"
+			)),
+			namespace:          Arc::from(namespace),
+		}
+	}
+
+	/// Replaces the `payments_queue` list name, keeping the namespace.
+	pub fn with_payments_queue(mut self, name: &str) -> Self {
+		self.payments_queue = Arc::from(format!("{}{name}", self.namespace));
+		self
+	}
+
+	pub fn payments_queue(&self) -> &str {
+		&self.payments_queue
+	}
+
+	/// Messages taken off the queue by a migration and not known to be
+	/// written to its target yet.
+	pub fn migrating_queue(&self) -> String {
+		format!("{}:migrating", self.payments_queue)
+	}
+
+	/// Queued messages that no longer decode, kept aside for inspection.
+	pub fn dead_letter_queue(&self) -> String {
+		format!("{}:dead_letter", self.payments_queue)
+	}
+
+	pub fn processed_payments(&self) -> &str {
+		&self.processed_payments
+	}
+
+	/// The processed payments scored by `timestamp`. The processed set itself
+	/// is scored by `submitted_at`.
+	pub fn processed_payments_by(&self, timestamp: PaymentTimestamp) -> &str {
+		match timestamp {
+			PaymentTimestamp::Received => &self.received_payments,
+			PaymentTimestamp::Submitted => &self.processed_payments,
+			PaymentTimestamp::Processed => &self.processed_at_index,
+		}
+	}
+
+	/// The processed payments of `group`, scored like
+	/// `processed_payments_by(timestamp)`.
+	pub fn processed_payments_of(
+		&self,
+		timestamp: PaymentTimestamp,
+		group: &str,
+	) -> String {
+		format!(
+			"{}:processor:{group}",
+			self.processed_payments_by(timestamp)
+		)
+	}
+
+	/// Aggregates of the payments of `group` compacted out of
+	/// `processed_payments_by(timestamp)`, as `{start}:{field}` hash fields.
+	/// Scripts derive this name, and the next, from the index's.
+	pub fn payment_buckets(
+		&self,
+		timestamp: PaymentTimestamp,
+		group: &str,
+	) -> String {
+		format!("{}:buckets:{group}", self.processed_payments_by(timestamp))
+	}
+
+	/// The starts of the `payment_buckets`, each scored by itself.
+	pub fn payment_bucket_starts(
+		&self,
+		timestamp: PaymentTimestamp,
+		group: &str,
+	) -> String {
+		format!(
+			"{}:bucket_starts:{group}",
+			self.processed_payments_by(timestamp)
+		)
+	}
+
+	/// Ids of the compacted payments, scored by `submitted_at`, which still
+	/// count as processed for a while after their details are gone.
+	pub fn compacted_payments(&self) -> &str {
+		&self.compacted_payments
+	}
+
+	/// Payments sent to a processor and not saved or re-queued yet.
+	pub fn payments_in_flight(&self) -> &str {
+		&self.payments_in_flight
+	}
+
+	/// Prefix shared by all payment hashes, completed by
+	/// `:{group}:{payment_id}`.
+	pub fn payments(&self) -> String {
+		format!("{}{PAYMENT_KEY_PREFIX}", self.namespace)
+	}
+
+	/// Prefix of the payment hashes of `group`, completed by `:{payment_id}`.
+	pub fn payment_group(&self, group: &str) -> String {
+		format!("{}:{group}", self.payments())
+	}
+
+	pub fn payment(&self, group: &str, payment_id: &str) -> String {
+		format!("{}:{payment_id}", self.payment_group(group))
+	}
+
+	/// Prefix of the payment claims, completed by `:{payment_id}`.
+	pub fn payment_claims(&self) -> String {
+		format!("{}{PAYMENT_CLAIM_KEY_PREFIX}", self.namespace)
+	}
+
+	/// Marks a payment as taken by a worker until it is saved or re-queued.
+	pub fn payment_claim(&self, payment_id: &str) -> String {
+		format!("{}:{payment_id}", self.payment_claims())
+	}
+
+	/// Prefix of the payments held for searches while in flight, completed by
+	/// `:{payment_id}`.
+	pub fn in_flight_payments(&self) -> String {
+		format!("{}{IN_FLIGHT_PAYMENT_KEY_PREFIX}", self.namespace)
+	}
+
+	pub fn in_flight_payment(&self, payment_id: &str) -> String {
+		format!("{}:{payment_id}", self.in_flight_payments())
+	}
+
+	/// Set once the payments saved before the timestamp and per-processor
+	/// indexes existed are indexed.
+	pub fn indexes_backfilled(&self) -> String {
+		format!("{}{INDEXES_BACKFILLED_KEY}", self.namespace)
+	}
+
+	pub fn payment_summaries(&self) -> [String; 2] {
+		[
+			format!("{}{DEFAULT_PAYMENT_SUMMARY_KEY}", self.namespace),
+			format!("{}{FALLBACK_PAYMENT_SUMMARY_KEY}", self.namespace),
+		]
+	}
+}
+
+impl Default for RedisKeys {
+	fn default() -> Self {
+		Self::new("", false)
+	}
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RedisMode {
+	#[default]
+	Standalone,
+	/// Every key of a namespace is hash tagged into one slot, so a namespace
+	/// lives on a single master and its replicas: the cluster adds failover
+	/// and room for more namespaces, not write throughput for one of them.
+	Cluster,
+	Sentinel,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RedisOptions {
+	pub mode:                  RedisMode,
+	/// The server URL, or the seed nodes or sentinels in the other modes.
+	pub nodes:                 Vec<String>,
+	pub sentinel_service_name: Option<String>,
+	/// How to authenticate with the master the sentinels elect; the other
+	/// modes take these from the node URLs.
+	pub master_username:       Option<String>,
+	pub master_password:       Option<String>,
+	pub master_db:             i64,
+	pub master_tls:            bool,
+	/// Upper bound of the backoff between connection attempts.
+	pub reconnect_max_delay:   Duration,
+	/// How long `Redis::connect` keeps retrying before giving up.
+	pub startup_timeout:       Duration,
+}
+
+impl Default for RedisOptions {
+	fn default() -> Self {
+		Self {
+			mode:                  RedisMode::default(),
+			nodes:                 Vec::new(),
+			sentinel_service_name: None,
+			master_username:       None,
+			master_password:       None,
+			master_db:             0,
+			master_tls:            false,
+			reconnect_max_delay:   Duration::from_secs(5),
+			startup_timeout:       Duration::from_secs(30),
+		}
+	}
+}
+
+impl RedisOptions {
+	fn connection_manager_config(&self) -> ConnectionManagerConfig {
+		ConnectionManagerConfig::new()
+			.set_number_of_retries(RECONNECT_RETRIES)
+			.set_max_delay(self.reconnect_max_delay.as_millis() as u64)
+	}
+
+	fn master_connection_info(&self) -> SentinelNodeConnectionInfo {
+		SentinelNodeConnectionInfo {
+			tls_mode:              self.master_tls.then_some(TlsMode::Secure),
+			redis_connection_info: Some(RedisConnectionInfo {
+				db: self.master_db,
+				username: self.master_username.clone(),
+				password: self.master_password.clone(),
+				..RedisConnectionInfo::default()
+			}),
+		}
+	}
+}
+
+/// An async connection to whichever topology is configured. Clones share the
+/// underlying connections, which reconnect on their own after Redis restarts;
+/// requests issued while it is down fail instead of waiting.
+#[derive(Clone)]
+pub struct RedisConnection {
+	kind:   ConnectionKind,
+	health: Arc<ConnectionHealth>,
+}
+
+#[derive(Clone)]
+enum ConnectionKind {
+	Standalone(ConnectionManager),
+	Cluster(ClusterConnection),
+	Sentinel(Arc<SentinelConnection>),
+}
+
+impl RedisConnection {
+	fn new(kind: ConnectionKind, metrics: RedisConnectionMetrics) -> Self {
+		metrics.record_opened();
+		Self {
+			kind,
+			health: Arc::new(ConnectionHealth {
+				connected: AtomicBool::new(true),
+				metrics,
+			}),
+		}
+	}
+
+	/// Runs a keyless command such as `SCAN` on the node serving `key`'s slot.
+	/// Outside of cluster mode there is only one node to ask.
+	pub async fn query_on_node_of<T: FromRedisValue>(
+		&mut self,
+		cmd: &Cmd,
+		key: &str,
+	) -> RedisResult<T> {
+		let ConnectionKind::Cluster(con) = &mut self.kind else {
+			return cmd.query_async(self).await;
+		};
+
+		let route = Route::new(get_slot(key.as_bytes()), SlotAddr::Master);
+		let result = con
+			.route_command(
+				cmd,
+				RoutingInfo::SingleNode(SingleNodeRoutingInfo::SpecificNode(route)),
+			)
+			.await;
+		self.health.observe(&result);
+		T::from_redis_value(&result?)
+	}
+}
+
+impl ConnectionLike for RedisConnection {
+	fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+		Box::pin(async move {
+			let result = self.kind.req_packed_command(cmd).await;
+			self.health.observe(&result);
+			result
+		})
+	}
+
+	fn req_packed_commands<'a>(
+		&'a mut self,
+		cmd: &'a Pipeline,
+		offset: usize,
+		count: usize,
+	) -> RedisFuture<'a, Vec<Value>> {
+		Box::pin(async move {
+			let result = self.kind.req_packed_commands(cmd, offset, count).await;
+			self.health.observe(&result);
+			result
+		})
+	}
+
+	fn get_db(&self) -> i64 {
+		self.kind.get_db()
+	}
+}
+
+impl ConnectionLike for ConnectionKind {
+	fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+		match self {
+			Self::Standalone(con) => con.req_packed_command(cmd),
+			Self::Cluster(con) => con.req_packed_command(cmd),
+			Self::Sentinel(sentinel) => Box::pin(async move {
+				let (generation, mut con) = sentinel.master();
+				match con.req_packed_command(cmd).await {
+					Err(e) if lost_master(&e) => {
+						let mut con = sentinel.failover(generation, e).await?;
+						con.req_packed_command(cmd).await
+					}
+					result => result,
+				}
+			}),
+		}
+	}
+
+	fn req_packed_commands<'a>(
+		&'a mut self,
+		cmd: &'a Pipeline,
+		offset: usize,
+		count: usize,
+	) -> RedisFuture<'a, Vec<Value>> {
+		match self {
+			Self::Standalone(con) => con.req_packed_commands(cmd, offset, count),
+			Self::Cluster(con) => con.req_packed_commands(cmd, offset, count),
+			Self::Sentinel(sentinel) => Box::pin(async move {
+				let (generation, mut con) = sentinel.master();
+				match con.req_packed_commands(cmd, offset, count).await {
+					Err(e) if lost_master(&e) => {
+						let mut con = sentinel.failover(generation, e).await?;
+						con.req_packed_commands(cmd, offset, count).await
+					}
+					result => result,
+				}
+			}),
+		}
+	}
+
+	fn get_db(&self) -> i64 {
+		match self {
+			Self::Standalone(con) => con.get_db(),
+			Self::Cluster(con) => con.get_db(),
+			Self::Sentinel(sentinel) => sentinel.master().1.get_db(),
+		}
+	}
+}
+
+/// Tracks whether the last request on a connection found it usable, feeding
+/// the connection gauges on every transition.
+struct ConnectionHealth {
+	connected: AtomicBool,
+	metrics:   RedisConnectionMetrics,
+}
+
+impl ConnectionHealth {
+	fn observe<T>(&self, result: &RedisResult<T>) {
+		match result {
+			Err(e) if is_connection_error(e) => {
+				let was_connected = self.connected.swap(false, Ordering::Relaxed);
+				if was_connected {
+					warn!("Lost the Redis connection: {e}");
+				}
+				self.metrics.record_connection_error(was_connected);
+			}
+			Err(_) => {}
+			Ok(_) => {
+				if !self.connected.swap(true, Ordering::Relaxed) {
+					info!("Redis connection recovered");
+					self.metrics.record_reconnect();
+				}
+			}
+		}
+	}
+}
+
+impl Drop for ConnectionHealth {
+	fn drop(&mut self) {
+		self.metrics
+			.record_closed(self.connected.load(Ordering::Relaxed));
+	}
+}
+
+/// Connection to the master a set of sentinels currently elects. When the
+/// master stops accepting writes or goes away, the sentinels are asked for
+/// the new one and the connection is replaced for every clone.
+pub struct SentinelConnection {
+	sentinel: tokio::sync::Mutex<SentinelClient>,
+	config:   ConnectionManagerConfig,
+	/// The current master, with a generation bumped on every failover so
+	/// concurrent callers that saw the same broken connection only rediscover
+	/// once.
+	master:   RwLock<(u64, ConnectionManager)>,
+}
+
+impl SentinelConnection {
+	async fn connect(options: &RedisOptions) -> RedisResult<Self> {
+		let config = options.connection_manager_config();
+		let mut sentinel = SentinelClient::build(
+			options.nodes.clone(),
+			options.sentinel_service_name.clone().unwrap_or_default(),
+			Some(options.master_connection_info()),
+			SentinelServerType::Master,
+		)?;
+		let master = ConnectionManager::new_with_config(
+			sentinel.async_get_client().await?,
+			config.clone(),
+		)
+		.await?;
+
+		Ok(Self {
+			sentinel: tokio::sync::Mutex::new(sentinel),
+			config,
+			master: RwLock::new((0, master)),
+		})
+	}
+
+	fn master(&self) -> (u64, ConnectionManager) {
+		self.master.read().unwrap().clone()
+	}
+
+	/// Replaces the master seen at `generation`. `error` is returned when no
+	/// new master can be reached, or when the failed request may already have
+	/// been applied and so must not be retried.
+	async fn failover(
+		&self,
+		generation: u64,
+		error: RedisError,
+	) -> RedisResult<ConnectionManager> {
+		let mut sentinel = self.sentinel.lock().await;
+		let (current_generation, current) = self.master();
+		let master = if current_generation != generation {
+			current
+		} else {
+			let master = match sentinel.async_get_client().await {
+				Ok(client) => {
+					ConnectionManager::new_with_config(client, self.config.clone())
+						.await
+				}
+				Err(e) => Err(e),
+			};
+			let master = match master {
+				Ok(master) => master,
+				Err(e) => {
+					error!("Failed to rediscover the Redis master: {e}");
+					return Err(error);
+				}
+			};
+			*self.master.write().unwrap() = (generation + 1, master.clone());
+			warn!("Redis master changed ({error}), reconnected through sentinel");
+			master
+		};
+
+		if never_ran(&error) {
+			Ok(master)
+		} else {
+			Err(error)
+		}
+	}
+}
+
+fn is_connection_error(error: &RedisError) -> bool {
+	error.is_io_error() ||
+		error.is_connection_dropped() ||
+		error.is_connection_refusal()
+}
+
+fn lost_master(error: &RedisError) -> bool {
+	error.kind() == ErrorKind::ReadOnly || is_connection_error(error)
+}
+
+/// A read-only reply or a refused connection means the command never ran;
+/// anything else might have reached the old master.
+fn never_ran(error: &RedisError) -> bool {
+	error.kind() == ErrorKind::ReadOnly || error.is_connection_refusal()
+}
 
 #[derive(Clone)]
 pub struct Redis {
-	pub connection: Arc<MultiplexedConnection>,
+	pub connection: Arc<RedisConnection>,
+	metrics:        RedisConnectionMetrics,
 }
 
 impl Redis {
+	/// Connects to a single server, failing on the first unsuccessful attempt.
 	pub async fn new(redis_url: &str) -> Result<Self, RedisError> {
-		let client = redis::Client::open(redis_url)?;
-		let connection = client.get_multiplexed_async_connection().await?;
-		Ok(Self {
-			connection: Arc::new(connection),
+		Self::connect(
+			&RedisOptions {
+				nodes: vec![redis_url.to_string()],
+				startup_timeout: Duration::ZERO,
+				..RedisOptions::default()
+			},
+			RedisConnectionMetrics::default(),
+		)
+		.await
+	}
+
+	/// Connects to the configured topology, retrying with exponential backoff
+	/// for up to `startup_timeout` so the service can start before Redis does.
+	pub async fn connect(
+		options: &RedisOptions,
+		metrics: RedisConnectionMetrics,
+	) -> Result<Self, RedisError> {
+		let deadline = Instant::now() + options.startup_timeout;
+		let mut delay = STARTUP_RETRY_INITIAL_DELAY.min(options.reconnect_max_delay);
+		loop {
+			match Self::open(options).await {
+				Ok(kind) => {
+					return Ok(Self {
+						connection: Arc::new(RedisConnection::new(
+							kind,
+							metrics.clone(),
+						)),
+						metrics,
+					});
+				}
+				Err(e) if Instant::now() + delay < deadline => {
+					metrics.record_failed_connect();
+					warn!("Failed to connect to Redis, retrying in {delay:?}: {e}");
+					sleep(delay).await;
+					delay = (delay * 2).min(options.reconnect_max_delay);
+				}
+				Err(e) => {
+					metrics.record_failed_connect();
+					return Err(e);
+				}
+			}
+		}
+	}
+
+	pub fn metrics(&self) -> &RedisConnectionMetrics {
+		&self.metrics
+	}
+
+	async fn open(options: &RedisOptions) -> RedisResult<ConnectionKind> {
+		Ok(match options.mode {
+			RedisMode::Standalone => ConnectionKind::Standalone(
+				ConnectionManager::new_with_config(
+					redis::Client::open(
+						options.nodes.first().map_or("", String::as_str),
+					)?,
+					options.connection_manager_config(),
+				)
+				.await?,
+			),
+			RedisMode::Cluster => ConnectionKind::Cluster(
+				ClusterClient::builder(options.nodes.clone())
+					.retries(RECONNECT_RETRIES as u32)
+					.max_retry_wait(options.reconnect_max_delay.as_millis() as u64)
+					.build()?
+					.get_async_connection()
+					.await?,
+			),
+			RedisMode::Sentinel => ConnectionKind::Sentinel(Arc::new(
+				SentinelConnection::connect(options).await?,
+			)),
 		})
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_connection_health_tracks_disconnects_and_reconnects() {
+		let metrics = RedisConnectionMetrics::new();
+		metrics.record_opened();
+		let health = ConnectionHealth {
+			connected: AtomicBool::new(true),
+			metrics:   metrics.clone(),
+		};
+		let dropped: RedisResult<()> = Err(RedisError::from(std::io::Error::from(
+			std::io::ErrorKind::ConnectionReset,
+		)));
+
+		health.observe(&dropped);
+		health.observe(&dropped);
+		assert_eq!((metrics.connected(), metrics.disconnected()), (0, 1));
+
+		health.observe(&Ok(()));
+		assert_eq!((metrics.connected(), metrics.disconnected()), (1, 0));
+		assert_eq!(metrics.reconnects(), 1);
+
+		drop(health);
+		assert_eq!(metrics.connected(), 0);
+	}
+
+	#[test]
+	fn test_failover_only_retries_commands_that_never_ran() {
+		let read_only = RedisError::from((ErrorKind::ReadOnly, "replica"));
+		let refused = RedisError::from(std::io::Error::from(
+			std::io::ErrorKind::ConnectionRefused,
+		));
+		let reset = RedisError::from(std::io::Error::from(
+			std::io::ErrorKind::ConnectionReset,
+		));
+		let wrong_type = RedisError::from((ErrorKind::TypeError, "wrong type"));
+
+		assert!(lost_master(&read_only) && never_ran(&read_only));
+		assert!(lost_master(&refused) && never_ran(&refused));
+		// The command may have been applied before the connection broke.
+		assert!(lost_master(&reset) && !never_ran(&reset));
+		assert!(!lost_master(&wrong_type));
+	}
+
+	#[test]
+	fn test_master_connection_info_carries_the_master_options() {
+		let options = RedisOptions {
+			mode: RedisMode::Sentinel,
+			master_username: Some("app".to_string()),
+			master_password: Some("secret".to_string()),
+			master_db: 2,
+			master_tls: true,
+			..RedisOptions::default()
+		};
+
+		let info = options.master_connection_info();
+
+		assert!(info.tls_mode == Some(TlsMode::Secure));
+		let redis = info.redis_connection_info.unwrap();
+		assert_eq!(redis.username.as_deref(), Some("app"));
+		assert_eq!(redis.password.as_deref(), Some("secret"));
+		assert_eq!(redis.db, 2);
+		assert!(
+			RedisOptions::default()
+				.master_connection_info()
+				.tls_mode
+				.is_none()
+		);
+	}
+
+	#[tokio::test]
+	async fn test_connect_retries_until_the_startup_timeout() {
+		let metrics = RedisConnectionMetrics::new();
+		let options = RedisOptions {
+			// Nothing listens on port 1.
+			nodes: vec!["redis://127.0.0.1:1".to_string()],
+			reconnect_max_delay: Duration::from_millis(50),
+			// Each attempt retries on its own for over a second.
+			startup_timeout: Duration::from_secs(2),
+			..RedisOptions::default()
+		};
+
+		let started = Instant::now();
+		assert!(Redis::connect(&options, metrics.clone()).await.is_err());
+
+		assert!(started.elapsed() < Duration::from_secs(10));
+		assert!(metrics.failed_connects() >= 2);
+		assert_eq!(metrics.connected(), 0);
+	}
+
+	#[test]
+	fn test_default_keys_have_no_namespace() {
+		let keys = RedisKeys::default();
+
+		assert_eq!(keys.payments_queue(), "payments_queue");
+		assert_eq!(keys.processed_payments(), "processed_payments");
+		assert_eq!(keys.payment("default", "42"), "payment_summary:default:42");
+	}
+
+	#[test]
+	fn test_prefixed_keys() {
+		let keys = RedisKeys::new("staging", false).with_payments_queue("queue");
+
+		assert_eq!(keys.payments_queue(), "staging:queue");
+		assert_eq!(keys.processed_payments(), "staging:processed_payments");
+		assert_eq!(
+			keys.payment_group("fallback"),
+			"staging:payment_summary:fallback"
+		);
+	}
+
+	#[test]
+	fn test_hash_tagged_keys_share_a_slot() {
+		let keys = RedisKeys::new("staging", true);
+
+		assert_eq!(keys.payments_queue(), "{staging}:payments_queue");
+		assert_eq!(
+			keys.payment("default", "42"),
+			"{staging}:payment_summary:default:42"
+		);
+		assert_eq!(keys.payment_summaries(), [
+			"{staging}:payment_summary:default".to_string(),
+			"{staging}:payment_summary:fallback".to_string(),
+		]);
+	}
+
+	#[test]
+	fn test_secondary_keys_extend_their_index() {
+		let keys = RedisKeys::new("staging", true);
+
+		assert_eq!(
+			keys.processed_payments_of(PaymentTimestamp::Received, "default"),
+			"{staging}:processed_payments:received_at:processor:default"
+		);
+		assert_eq!(
+			keys.payment_buckets(PaymentTimestamp::Submitted, "fallback"),
+			"{staging}:processed_payments:buckets:fallback"
+		);
+		assert_eq!(
+			keys.payment_bucket_starts(PaymentTimestamp::Processed, "default"),
+			"{staging}:processed_payments:processed_at:bucket_starts:default"
+		);
+	}
+}
@@ -0,0 +1,256 @@
+use std::sync::{Arc, Mutex};
+
+use config::ConfigError;
+use serde::Serialize;
+use tracing::info;
+
+use crate::domain::payment_processor::PaymentProcessorKey;
+use crate::infrastructure::config::settings::Config;
+use crate::infrastructure::routing::in_memory_payment_router::{
+	InMemoryPaymentRouter, RoutingOptions,
+};
+use crate::infrastructure::workers::worker_pool::WorkerPool;
+
+/// The subset of `Config` that can change without a restart.
+#[derive(Debug, Clone, PartialEq)]
+struct RoutingConfig {
+	default_key:     Arc<PaymentProcessorKey>,
+	fallback_key:    Arc<PaymentProcessorKey>,
+	routing_options: RoutingOptions,
+	min_workers:     usize,
+	max_workers:     usize,
+}
+
+impl RoutingConfig {
+	fn from_config(config: &Config) -> Self {
+		let worker_pool_options = config.get_worker_pool_options();
+		Self {
+			default_key:     config.get_default_key(),
+			fallback_key:    config.get_fallback_key(),
+			routing_options: config.get_routing_options(),
+			min_workers:     worker_pool_options.min_workers,
+			max_workers:     worker_pool_options.max_workers,
+		}
+	}
+
+	fn changes_to(&self, next: &Self) -> Vec<&'static str> {
+		let current_options = &self.routing_options;
+		let next_options = &next.routing_options;
+
+		[
+			(
+				"default_payment_processor_url",
+				self.default_key != next.default_key,
+			),
+			(
+				"fallback_payment_processor_url",
+				self.fallback_key != next.fallback_key,
+			),
+			(
+				"routing_max_response_time_ms",
+				current_options.max_response_time != next_options.max_response_time,
+			),
+			(
+				"default_breaker",
+				current_options.default_breaker != next_options.default_breaker,
+			),
+			(
+				"fallback_breaker",
+				current_options.fallback_breaker != next_options.fallback_breaker,
+			),
+			(
+				"payment_processor_min_workers",
+				self.min_workers != next.min_workers,
+			),
+			(
+				"payment_processor_max_workers",
+				self.max_workers != next.max_workers,
+			),
+		]
+		.into_iter()
+		.filter_map(|(name, changed)| changed.then_some(name))
+		.collect()
+	}
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ReloadReport {
+	pub changed: Vec<&'static str>,
+}
+
+/// Re-reads the configuration and applies its routing subset to the router
+/// and the worker pool. Other settings only take effect after a restart.
+#[derive(Clone)]
+pub struct RoutingConfigReloader {
+	router:      InMemoryPaymentRouter,
+	worker_pool: WorkerPool,
+	current:     Arc<Mutex<RoutingConfig>>,
+}
+
+impl RoutingConfigReloader {
+	pub fn new(
+		router: InMemoryPaymentRouter,
+		worker_pool: WorkerPool,
+		config: &Config,
+	) -> Self {
+		Self {
+			router,
+			worker_pool,
+			current: Arc::new(Mutex::new(RoutingConfig::from_config(config))),
+		}
+	}
+
+	pub fn reload(&self) -> Result<ReloadReport, ConfigError> {
+		self.apply(&Config::load()?)
+	}
+
+	/// Applies `config` only once all of it validates; on error the running
+	/// configuration is left untouched.
+	pub fn apply(&self, config: &Config) -> Result<ReloadReport, ConfigError> {
+		config.validate()?;
+
+		let next = RoutingConfig::from_config(config);
+		let mut current = self.current.lock().unwrap();
+		let changed = current.changes_to(&next);
+		if changed.is_empty() {
+			return Ok(ReloadReport { changed });
+		}
+
+		self.router.reconfigure(
+			Arc::clone(&next.default_key),
+			Arc::clone(&next.fallback_key),
+			next.routing_options.clone(),
+		);
+		self.worker_pool
+			.set_bounds(next.min_workers, next.max_workers);
+		*current = next;
+
+		info!("Reloaded routing configuration: {}", changed.join(", "));
+		Ok(ReloadReport { changed })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+	use std::time::Duration;
+
+	use config::Environment;
+
+	use super::*;
+	use crate::domain::health_status::HealthStatus;
+	use crate::domain::payment_processor::PaymentProcessor;
+	use crate::infrastructure::workers::worker_pool::WorkerPoolOptions;
+
+	fn config(overrides: &[(&str, &str)]) -> Result<Config, ConfigError> {
+		let mut env = HashMap::from([
+			(
+				"APP_REDIS_URL".to_string(),
+				"redis://test_redis/".to_string(),
+			),
+			(
+				"APP_DEFAULT_PAYMENT_PROCESSOR_URL".to_string(),
+				"http://default/".to_string(),
+			),
+			(
+				"APP_FALLBACK_PAYMENT_PROCESSOR_URL".to_string(),
+				"http://fallback/".to_string(),
+			),
+			(
+				"APP_PAYMENT_PROCESSOR_WORKER_COUNT".to_string(),
+				"2".to_string(),
+			),
+		]);
+		for (name, value) in overrides {
+			env.insert(name.to_string(), value.to_string());
+		}
+
+		Config::load_from(None, Environment::with_prefix("APP").source(Some(env)))
+	}
+
+	fn reloader() -> (RoutingConfigReloader, InMemoryPaymentRouter, WorkerPool) {
+		let config = config(&[]).unwrap();
+		let router = InMemoryPaymentRouter::new(
+			config.get_default_key(),
+			config.get_fallback_key(),
+		);
+		let worker_pool = WorkerPool::new(WorkerPoolOptions {
+			min_workers:        2,
+			max_workers:        2,
+			scale_interval:     Duration::from_secs(1),
+			backlog_per_worker: 100,
+			max_latency:        Duration::from_millis(100),
+		});
+
+		(
+			RoutingConfigReloader::new(router.clone(), worker_pool.clone(), &config),
+			router,
+			worker_pool,
+		)
+	}
+
+	#[test]
+	fn test_apply_reconfigures_router_and_worker_pool() {
+		let (reloader, router, worker_pool) = reloader();
+		let default_breaker = router.default_breaker();
+		default_breaker.force_open();
+
+		let report = reloader
+			.apply(
+				&config(&[
+					("APP_FALLBACK_PAYMENT_PROCESSOR_URL", "http://fallback-2/"),
+					("APP_ROUTING_MAX_RESPONSE_TIME_MS", "250"),
+					("APP_FALLBACK_BREAKER_COOLDOWN_MS", "500"),
+					("APP_PAYMENT_PROCESSOR_MAX_WORKERS", "6"),
+				])
+				.unwrap(),
+			)
+			.unwrap();
+
+		assert_eq!(report.changed, vec![
+			"fallback_payment_processor_url",
+			"routing_max_response_time_ms",
+			"fallback_breaker",
+			"payment_processor_max_workers",
+		]);
+		assert_eq!(
+			router.fallback_processor.read().unwrap().key.url,
+			"http://fallback-2/"
+		);
+		assert_eq!(router.routing_options().max_response_time, 250);
+		assert_eq!(worker_pool.status().max_workers, 6);
+		// Untouched breakers keep their state.
+		assert_eq!(
+			router.default_breaker().current_state(),
+			default_breaker.current_state()
+		);
+	}
+
+	#[test]
+	fn test_apply_without_changes_keeps_processor_health() {
+		let (reloader, router, _worker_pool) = reloader();
+		router.update_processor_health(PaymentProcessor {
+			key:               router.processor_keys()[0].clone(),
+			health:            HealthStatus::Healthy,
+			min_response_time: 10,
+		});
+
+		let report = reloader.apply(&config(&[]).unwrap()).unwrap();
+
+		assert!(report.changed.is_empty());
+		assert!(router.default_processor.read().unwrap().health.is_healthy());
+	}
+
+	#[test]
+	fn test_apply_rejects_invalid_config() {
+		let (reloader, router, worker_pool) = reloader();
+		let mut invalid =
+			config(&[("APP_ROUTING_MAX_RESPONSE_TIME_MS", "250")]).unwrap();
+		invalid.payment_processor_min_workers = Some(8);
+		invalid.payment_processor_max_workers = Some(4);
+
+		assert!(reloader.apply(&invalid).is_err());
+		assert_eq!(router.routing_options(), RoutingOptions::default());
+		assert_eq!(worker_pool.status().max_workers, 2);
+	}
+}
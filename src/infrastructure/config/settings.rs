@@ -1,31 +1,736 @@
 use std::borrow::Cow;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
-use config::Environment;
+use config::{ConfigError, Environment, File, FileFormat, FileSourceFile};
+use redis::IntoConnectionInfo;
+use reqwest::Url;
 use serde::Deserialize;
 
+use crate::domain::fee_schedule::FeeRates;
+use crate::domain::payment::PaymentTimestamp;
 use crate::domain::payment_processor::PaymentProcessorKey;
+use crate::infrastructure::config::redis::{
+	PAYMENTS_QUEUE_KEY, RedisKeys, RedisMode, RedisOptions,
+};
+use crate::infrastructure::queue::admission_controller::{
+	AdmissionOptions, BackpressureMode,
+};
+use crate::infrastructure::routing::in_memory_payment_router::{
+	BreakerOptions, RoutingOptions,
+};
+use crate::infrastructure::routing::processor_concurrency_limiter::ConcurrencyOptions;
+use crate::infrastructure::telemetry::subscriber::{LogFormat, TelemetryOptions};
+use crate::infrastructure::wal::write_ahead_log::{FsyncPolicy, WalOptions};
+use crate::infrastructure::workers::mpsc_to_redis_worker::BatchOptions;
+use crate::infrastructure::workers::payment_retention_worker::RetentionOptions;
+use crate::infrastructure::workers::worker_pool::WorkerPoolOptions;
+
+/// Environment variable naming an optional TOML or YAML file layered under the
+/// `APP_*` variables.
+pub const CONFIG_FILE_ENV: &str = "APP_CONFIG_FILE";
+
+const DEFAULT_SERVER_KEEPALIVE_SECS: u64 = 5;
+const DEFAULT_PAYMENT_PROCESSOR_WORKER_COUNT: u64 = 4;
+const DEFAULT_SERVER_BIND_ADDRESS: &str = "0.0.0.0:9999";
+const DEFAULT_INGRESS_BUFFER_CAPACITY: usize = 100_000;
+const DEFAULT_PROCESSOR_HEALTH_CHECK_INTERVAL_MS: u64 = 5_000;
+const DEFAULT_PROCESSOR_FEE_REFRESH_INTERVAL_MS: u64 = 60_000;
+/// One leap year.
+const DEFAULT_SUMMARY_MAX_WINDOW_MS: u64 = 366 * 24 * 60 * 60 * 1_000;
+/// Long enough for most processor calls to return, short enough for clients
+/// to wait on.
+const DEFAULT_SUMMARY_CONSISTENCY_TIMEOUT_MS: u64 = 500;
+const DEFAULT_RETENTION_BUCKET_MS: u64 = 60_000;
+const DEFAULT_RETENTION_INTERVAL_MS: u64 = 60_000;
+const DEFAULT_RETENTION_BATCH_SIZE: usize = 500;
+const DEFAULT_RETENTION_DEDUP_WINDOW_MS: u64 = 24 * 60 * 60 * 1_000;
+/// Below this, payments could be compacted while still queued or in flight.
+const MIN_RETENTION_MAX_AGE_MS: u64 = 60 * 60 * 1_000;
+const DEFAULT_WAL_FSYNC_INTERVAL_MS: u64 = 10;
+const DEFAULT_WAL_SEGMENT_MAX_BYTES: u64 = 8 * 1024 * 1024;
+const DEFAULT_INGRESS_HIGH_WATER_MARK: usize = 80_000;
+const DEFAULT_INGRESS_MAX_WAIT_MS: u64 = 50;
+const DEFAULT_INGRESS_RETRY_AFTER_SECS: u64 = 1;
+const DEFAULT_INGRESS_BATCH_SIZE: usize = 128;
+/// Clients are answered once their payment is buffered, so lingering only
+/// delays the push to Redis, by less than one round trip to it.
+const DEFAULT_INGRESS_BATCH_LINGER_US: u64 = 200;
+const DEFAULT_PROCESSOR_BATCH_SIZE: usize = 16;
+const DEFAULT_PROCESSOR_MIN_CONCURRENCY: usize = 1;
+const DEFAULT_PROCESSOR_MAX_CONCURRENCY: usize = 64;
+const DEFAULT_PROCESSOR_TARGET_THROUGHPUT: f64 = 500.0;
+const DEFAULT_WORKER_POOL_SCALE_INTERVAL_MS: u64 = 1_000;
+const DEFAULT_WORKER_POOL_BACKLOG_PER_WORKER: usize = 1_000;
+const DEFAULT_WORKER_POOL_MAX_LATENCY_MS: u64 = 100;
+const DEFAULT_OTLP_SERVICE_NAME: &str = "rinha-de-backend";
+const DEFAULT_HEALTH_MAX_BUFFER_SATURATION: f64 = 0.9;
+const DEFAULT_CLUSTER_KEY_PREFIX: &str = "rinha";
+/// Commands are mostly run by hand next to a local Redis.
+const DEFAULT_COMMAND_REDIS_URL: &str = "redis://127.0.0.1:6379";
+
+/// What the configuration is loaded for. Commands only talk to Redis, so they
+/// need neither the payment processors nor an explicit `redis_url`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ConfigPurpose {
+	Serve,
+	Command,
+}
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
 	pub redis_url: Cow<'static, str>,
+	/// Only required to serve.
+	#[serde(default)]
 	pub default_payment_processor_url: Cow<'static, str>,
+	#[serde(default)]
 	pub fallback_payment_processor_url: Cow<'static, str>,
 	pub server_keepalive: u64,
 	pub report_url: Option<Cow<'static, str>>,
 	pub payment_processor_worker_count: usize,
+	pub wal_dir: Option<Cow<'static, str>>,
+	pub wal_fsync_policy: FsyncPolicy,
+	pub wal_fsync_interval_ms: u64,
+	pub wal_segment_max_bytes: u64,
+	pub ingress_backpressure_mode: BackpressureMode,
+	pub ingress_high_water_mark: usize,
+	pub ingress_max_wait_ms: u64,
+	pub ingress_max_queue_length: Option<usize>,
+	pub ingress_retry_after_secs: u64,
+	pub ingress_batch_size: usize,
+	pub ingress_batch_linger_us: u64,
+	pub payment_processor_batch_size: usize,
+	pub payment_processor_min_concurrency: usize,
+	pub payment_processor_max_concurrency: usize,
+	pub payment_processor_target_throughput: f64,
+	pub payment_processor_min_workers: Option<usize>,
+	pub payment_processor_max_workers: Option<usize>,
+	pub worker_pool_scale_interval_ms: u64,
+	pub worker_pool_backlog_per_worker: usize,
+	pub worker_pool_max_latency_ms: u64,
+	pub log_format: Option<LogFormat>,
+	pub otlp_endpoint: Option<Cow<'static, str>>,
+	pub otlp_service_name: Cow<'static, str>,
+	pub health_max_buffer_saturation: f64,
+	pub server_bind_address: Cow<'static, str>,
+	pub ingress_buffer_capacity: usize,
+	pub payments_queue_key: Cow<'static, str>,
+	pub processor_health_check_interval_ms: u64,
+	pub routing_max_response_time_ms: Option<u64>,
+	pub default_breaker_failure_threshold: Option<f64>,
+	pub default_breaker_min_throughput: Option<u64>,
+	pub default_breaker_probe_interval: Option<u32>,
+	pub default_breaker_cooldown_ms: Option<u64>,
+	pub fallback_breaker_failure_threshold: Option<f64>,
+	pub fallback_breaker_min_throughput: Option<u64>,
+	pub fallback_breaker_probe_interval: Option<u32>,
+	pub fallback_breaker_cooldown_ms: Option<u64>,
+	pub admin_token: Option<Cow<'static, str>>,
+	pub redis_key_prefix: Option<Cow<'static, str>>,
+	pub redis_key_hash_tag: Option<bool>,
+	pub redis_mode: Option<RedisMode>,
+	pub redis_sentinel_service_name: Option<Cow<'static, str>>,
+	/// Credentials, database and TLS of the master in sentinel mode, whose
+	/// address only the sentinels know.
+	pub redis_master_username: Option<Cow<'static, str>>,
+	pub redis_master_password: Option<Cow<'static, str>>,
+	pub redis_master_db: Option<i64>,
+	pub redis_master_tls: Option<bool>,
+	pub redis_reconnect_max_delay_ms: Option<u64>,
+	pub redis_startup_timeout_ms: Option<u64>,
+	pub default_processor_fee_rate: Option<f64>,
+	pub fallback_processor_fee_rate: Option<f64>,
+	pub processor_admin_token: Option<Cow<'static, str>>,
+	pub processor_fee_refresh_interval_ms: u64,
+	pub summary_max_window_ms: u64,
+	pub summary_consistency_timeout_ms: u64,
+	/// Which payment timestamp `/payments-summary` filters on. Payments saved
+	/// before every timestamp was indexed are backfilled at startup.
+	pub summary_timestamp: Option<PaymentTimestamp>,
+	/// Age past which processed payments are compacted into aggregates.
+	/// Unset keeps every payment in detail.
+	pub retention_max_age_ms: Option<u64>,
+	pub retention_bucket_ms: u64,
+	pub retention_interval_ms: u64,
+	pub retention_batch_size: usize,
+	pub retention_dedup_window_ms: u64,
 }
 
 impl Config {
-	pub fn load() -> Result<Self, config::ConfigError> {
-		Self::load_from(Environment::with_prefix("APP"))
+	/// Loads the configuration from, in increasing precedence: built-in
+	/// defaults, the file named by `APP_CONFIG_FILE` and `APP_*` variables.
+	pub fn load() -> Result<Self, ConfigError> {
+		Self::load_from_env(ConfigPurpose::Serve)
+	}
+
+	/// Like `load`, for the commands run instead of the server: the payment
+	/// processor URLs are not required and `redis_url` defaults to a local
+	/// Redis.
+	pub fn load_for_command() -> Result<Self, ConfigError> {
+		Self::load_from_env(ConfigPurpose::Command)
+	}
+
+	fn load_from_env(purpose: ConfigPurpose) -> Result<Self, ConfigError> {
+		let config_file = std::env::var(CONFIG_FILE_ENV)
+			.ok()
+			.map(|path| File::from(PathBuf::from(path)));
+
+		Self::build(config_file, Environment::with_prefix("APP"), purpose)
+	}
+
+	#[cfg(test)]
+	pub(crate) fn load_from(
+		config_file: Option<File<FileSourceFile, FileFormat>>,
+		environment: Environment,
+	) -> Result<Self, ConfigError> {
+		Self::build(config_file, environment, ConfigPurpose::Serve)
+	}
+
+	fn build(
+		config_file: Option<File<FileSourceFile, FileFormat>>,
+		environment: Environment,
+		purpose: ConfigPurpose,
+	) -> Result<Self, ConfigError> {
+		let mut config_builder = config::Config::builder()
+			.set_default("server_keepalive", DEFAULT_SERVER_KEEPALIVE_SECS)?
+			.set_default(
+				"payment_processor_worker_count",
+				DEFAULT_PAYMENT_PROCESSOR_WORKER_COUNT,
+			)?
+			.set_default("wal_fsync_policy", "interval")?
+			.set_default("wal_fsync_interval_ms", DEFAULT_WAL_FSYNC_INTERVAL_MS)?
+			.set_default("wal_segment_max_bytes", DEFAULT_WAL_SEGMENT_MAX_BYTES)?
+			.set_default("ingress_backpressure_mode", "block")?
+			.set_default(
+				"ingress_high_water_mark",
+				DEFAULT_INGRESS_HIGH_WATER_MARK as u64,
+			)?
+			.set_default("ingress_max_wait_ms", DEFAULT_INGRESS_MAX_WAIT_MS)?
+			.set_default(
+				"ingress_retry_after_secs",
+				DEFAULT_INGRESS_RETRY_AFTER_SECS,
+			)?
+			.set_default("ingress_batch_size", DEFAULT_INGRESS_BATCH_SIZE as u64)?
+			.set_default("ingress_batch_linger_us", DEFAULT_INGRESS_BATCH_LINGER_US)?
+			.set_default(
+				"payment_processor_batch_size",
+				DEFAULT_PROCESSOR_BATCH_SIZE as u64,
+			)?
+			.set_default(
+				"payment_processor_min_concurrency",
+				DEFAULT_PROCESSOR_MIN_CONCURRENCY as u64,
+			)?
+			.set_default(
+				"payment_processor_max_concurrency",
+				DEFAULT_PROCESSOR_MAX_CONCURRENCY as u64,
+			)?
+			.set_default(
+				"payment_processor_target_throughput",
+				DEFAULT_PROCESSOR_TARGET_THROUGHPUT,
+			)?
+			.set_default(
+				"worker_pool_scale_interval_ms",
+				DEFAULT_WORKER_POOL_SCALE_INTERVAL_MS,
+			)?
+			.set_default(
+				"worker_pool_backlog_per_worker",
+				DEFAULT_WORKER_POOL_BACKLOG_PER_WORKER as u64,
+			)?
+			.set_default(
+				"worker_pool_max_latency_ms",
+				DEFAULT_WORKER_POOL_MAX_LATENCY_MS,
+			)?
+			.set_default("otlp_service_name", DEFAULT_OTLP_SERVICE_NAME)?
+			.set_default(
+				"health_max_buffer_saturation",
+				DEFAULT_HEALTH_MAX_BUFFER_SATURATION,
+			)?
+			.set_default("server_bind_address", DEFAULT_SERVER_BIND_ADDRESS)?
+			.set_default(
+				"ingress_buffer_capacity",
+				DEFAULT_INGRESS_BUFFER_CAPACITY as u64,
+			)?
+			.set_default("payments_queue_key", PAYMENTS_QUEUE_KEY)?
+			.set_default(
+				"processor_health_check_interval_ms",
+				DEFAULT_PROCESSOR_HEALTH_CHECK_INTERVAL_MS,
+			)?
+			.set_default(
+				"processor_fee_refresh_interval_ms",
+				DEFAULT_PROCESSOR_FEE_REFRESH_INTERVAL_MS,
+			)?
+			.set_default("summary_max_window_ms", DEFAULT_SUMMARY_MAX_WINDOW_MS)?
+			.set_default(
+				"summary_consistency_timeout_ms",
+				DEFAULT_SUMMARY_CONSISTENCY_TIMEOUT_MS,
+			)?
+			.set_default("retention_bucket_ms", DEFAULT_RETENTION_BUCKET_MS)?
+			.set_default("retention_interval_ms", DEFAULT_RETENTION_INTERVAL_MS)?
+			.set_default(
+				"retention_batch_size",
+				DEFAULT_RETENTION_BATCH_SIZE as u64,
+			)?
+			.set_default(
+				"retention_dedup_window_ms",
+				DEFAULT_RETENTION_DEDUP_WINDOW_MS,
+			)?;
+		if purpose == ConfigPurpose::Command {
+			config_builder = config_builder
+				.set_default("redis_url", DEFAULT_COMMAND_REDIS_URL)?;
+		}
+		if let Some(config_file) = config_file {
+			config_builder = config_builder.add_source(config_file);
+		}
+
+		let config: Self = config_builder
+			.add_source(environment)
+			.build()?
+			.try_deserialize()?;
+		config.validate_for(purpose)?;
+		Ok(config)
+	}
+
+	/// Checks the values serde cannot, reporting every problem at once.
+	pub fn validate(&self) -> Result<(), ConfigError> {
+		self.validate_for(ConfigPurpose::Serve)
+	}
+
+	fn validate_for(&self, purpose: ConfigPurpose) -> Result<(), ConfigError> {
+		let mut errors = Vec::new();
+
+		let redis_options = self.get_redis_options();
+		for node in &redis_options.nodes {
+			if let Err(e) = node.as_str().into_connection_info() {
+				errors.push(format!("redis_url '{node}' is invalid: {e}"));
+			}
+		}
+		match redis_options.mode {
+			RedisMode::Standalone if redis_options.nodes.len() != 1 => {
+				errors.push(format!(
+					"redis_url must name exactly one server in standalone mode, \
+					 got '{}'",
+					self.redis_url
+				));
+			}
+			RedisMode::Cluster if self.redis_key_hash_tag == Some(false) => {
+				errors.push(
+					"redis_key_hash_tag cannot be disabled in cluster mode"
+						.to_string(),
+				);
+			}
+			RedisMode::Sentinel
+				if redis_options
+					.sentinel_service_name
+					.is_none_or(|name| name.is_empty()) =>
+			{
+				errors.push(
+					"redis_sentinel_service_name is required in sentinel mode"
+						.to_string(),
+				);
+			}
+			_ => {}
+		}
+		if redis_options.mode != RedisMode::Sentinel &&
+			(self.redis_master_username.is_some() ||
+				self.redis_master_password.is_some() ||
+				self.redis_master_db.is_some() ||
+				self.redis_master_tls.is_some())
+		{
+			errors.push(
+				"redis_master_* only apply in sentinel mode; set them in redis_url \
+				 instead"
+					.to_string(),
+			);
+		}
+		for (name, url) in [
+			(
+				"default_payment_processor_url",
+				&self.default_payment_processor_url,
+			),
+			(
+				"fallback_payment_processor_url",
+				&self.fallback_payment_processor_url,
+			),
+		] {
+			if !url.is_empty() {
+				check_http_url(&mut errors, name, url);
+			} else if purpose == ConfigPurpose::Serve {
+				errors.push(format!("{name} is required"));
+			}
+		}
+		if let Some(endpoint) = &self.otlp_endpoint {
+			check_http_url(&mut errors, "otlp_endpoint", endpoint);
+		}
+
+		let bind_address = self.get_bind_address();
+		if !bind_address.rsplit_once(':').is_some_and(|(host, port)| {
+			!host.is_empty() && port.parse::<u16>().is_ok()
+		}) {
+			errors.push(format!(
+				"server_bind_address '{bind_address}' must be of the form host:port"
+			));
+		}
+
+		if self
+			.admin_token
+			.as_ref()
+			.is_some_and(|token| token.trim().is_empty())
+		{
+			errors.push("admin_token must not be blank".to_string());
+		}
+
+		let key_prefix = self.redis_key_prefix.as_deref().unwrap_or_default();
+		if key_prefix.contains(['{', '}']) {
+			errors.push(format!(
+				"redis_key_prefix '{key_prefix}' must not contain braces, set \
+				 redis_key_hash_tag instead"
+			));
+		}
+		if self.redis_key_hash_tag.unwrap_or_default() &&
+			key_prefix.is_empty() &&
+			redis_options.mode != RedisMode::Cluster
+		{
+			errors
+				.push("redis_key_hash_tag requires a redis_key_prefix".to_string());
+		}
+
+		if self.get_payments_queue_key().is_empty() {
+			errors.push("payments_queue_key must not be empty".to_string());
+		}
+
+		let buffer_capacity = self.get_ingress_buffer_capacity();
+		for (name, value) in [
+			(
+				"payment_processor_worker_count",
+				self.payment_processor_worker_count,
+			),
+			("ingress_buffer_capacity", buffer_capacity),
+			("ingress_batch_size", self.get_batch_options().max_size),
+			(
+				"payment_processor_batch_size",
+				self.get_processor_batch_size(),
+			),
+			(
+				"payment_processor_min_concurrency",
+				self.get_concurrency_options().min_permits,
+			),
+			(
+				"worker_pool_backlog_per_worker",
+				self.get_worker_pool_options().backlog_per_worker,
+			),
+		] {
+			if value == 0 {
+				errors.push(format!("{name} must be greater than 0"));
+			}
+		}
+
+		let concurrency_options = self.get_concurrency_options();
+		if concurrency_options.min_permits > concurrency_options.max_permits {
+			errors.push(format!(
+				"payment_processor_min_concurrency ({}) must not exceed \
+				 payment_processor_max_concurrency ({})",
+				concurrency_options.min_permits, concurrency_options.max_permits
+			));
+		}
+		if concurrency_options.target_throughput.is_nan() ||
+			concurrency_options.target_throughput <= 0.0
+		{
+			errors.push(
+				"payment_processor_target_throughput must be greater than 0"
+					.to_string(),
+			);
+		}
+
+		if let (Some(min_workers), Some(max_workers)) = (
+			self.payment_processor_min_workers,
+			self.payment_processor_max_workers,
+		) && min_workers > max_workers
+		{
+			errors.push(format!(
+				"payment_processor_min_workers ({min_workers}) must not exceed \
+				 payment_processor_max_workers ({max_workers})"
+			));
+		}
+
+		let high_water_mark = self.get_admission_options().high_water_mark;
+		if high_water_mark > buffer_capacity {
+			errors.push(format!(
+				"ingress_high_water_mark ({high_water_mark}) must not exceed \
+				 ingress_buffer_capacity ({buffer_capacity})"
+			));
+		}
+
+		let max_buffer_saturation = self.get_max_buffer_saturation();
+		if !(max_buffer_saturation > 0.0 && max_buffer_saturation <= 1.0) {
+			errors.push(format!(
+				"health_max_buffer_saturation ({max_buffer_saturation}) must be in \
+				 (0, 1]"
+			));
+		}
+
+		let routing_options = self.get_routing_options();
+		if routing_options.max_response_time == 0 {
+			errors.push(
+				"routing_max_response_time_ms must be greater than 0".to_string(),
+			);
+		}
+		for (name, breaker) in [
+			("default_breaker", &routing_options.default_breaker),
+			("fallback_breaker", &routing_options.fallback_breaker),
+		] {
+			if !(breaker.failure_threshold > 0.0 && breaker.failure_threshold <= 1.0)
+			{
+				errors.push(format!(
+					"{name}_failure_threshold ({}) must be in (0, 1]",
+					breaker.failure_threshold
+				));
+			}
+			if breaker.probe_interval == 0 {
+				errors.push(format!("{name}_probe_interval must be greater than 0"));
+			}
+		}
+
+		if self.get_health_check_interval().is_zero() {
+			errors.push(
+				"processor_health_check_interval_ms must be greater than 0"
+					.to_string(),
+			);
+		}
+		let fee_rates = self.get_fee_rates();
+		for (name, rate) in [
+			("default_processor_fee_rate", fee_rates.default),
+			("fallback_processor_fee_rate", fee_rates.fallback),
+		] {
+			if !(0.0..=1.0).contains(&rate) {
+				errors.push(format!("{name} ({rate}) must be in [0, 1]"));
+			}
+		}
+		if self.get_summary_max_window().is_zero() {
+			errors.push("summary_max_window_ms must be greater than 0".to_string());
+		}
+		if self.get_fee_refresh_interval().is_zero() {
+			errors.push(
+				"processor_fee_refresh_interval_ms must be greater than 0"
+					.to_string(),
+			);
+		}
+		if let Some(retention_options) = self.get_retention_options() {
+			for (name, value) in [
+				("retention_bucket_ms", retention_options.bucket),
+				("retention_interval_ms", retention_options.interval),
+				("retention_dedup_window_ms", retention_options.dedup_window),
+			] {
+				if value.is_zero() {
+					errors.push(format!("{name} must be greater than 0"));
+				}
+			}
+			if retention_options.max_age <
+				Duration::from_millis(MIN_RETENTION_MAX_AGE_MS)
+			{
+				errors.push(format!(
+					"retention_max_age_ms must be at least \
+					 {MIN_RETENTION_MAX_AGE_MS}"
+				));
+			}
+			if retention_options.batch_size == 0 {
+				errors
+					.push("retention_batch_size must be greater than 0".to_string());
+			}
+		}
+
+		if redis_options.reconnect_max_delay.is_zero() {
+			errors.push(
+				"redis_reconnect_max_delay_ms must be greater than 0".to_string(),
+			);
+		}
+
+		if errors.is_empty() {
+			Ok(())
+		} else {
+			Err(ConfigError::Message(format!(
+				"invalid configuration:\n  - {}",
+				errors.join("\n  - ")
+			)))
+		}
 	}
 
-	fn load_from(environment: Environment) -> Result<Self, config::ConfigError> {
-		let config_builder =
-			config::Config::builder().add_source(environment).build()?;
+	pub fn get_bind_address(&self) -> &str {
+		&self.server_bind_address
+	}
 
-		config_builder.try_deserialize()
+	/// `redis_url` holds a comma-separated list of seed nodes in cluster mode
+	/// and of sentinels in sentinel mode.
+	pub fn get_redis_options(&self) -> RedisOptions {
+		let defaults = RedisOptions::default();
+		RedisOptions {
+			mode:                  self.redis_mode.unwrap_or_default(),
+			nodes:                 self
+				.redis_url
+				.split(',')
+				.map(str::trim)
+				.filter(|node| !node.is_empty())
+				.map(str::to_string)
+				.collect(),
+			sentinel_service_name: self
+				.redis_sentinel_service_name
+				.as_ref()
+				.map(|name| name.to_string()),
+			master_username:       self
+				.redis_master_username
+				.as_ref()
+				.map(|username| username.to_string()),
+			master_password:       self
+				.redis_master_password
+				.as_ref()
+				.map(|password| password.to_string()),
+			master_db:             self
+				.redis_master_db
+				.unwrap_or(defaults.master_db),
+			master_tls:            self
+				.redis_master_tls
+				.unwrap_or(defaults.master_tls),
+			reconnect_max_delay:   self
+				.redis_reconnect_max_delay_ms
+				.map(Duration::from_millis)
+				.unwrap_or(defaults.reconnect_max_delay),
+			startup_timeout:       self
+				.redis_startup_timeout_ms
+				.map(Duration::from_millis)
+				.unwrap_or(defaults.startup_timeout),
+		}
+	}
+
+	/// Keys under `redis_key_prefix`, hash tagged for Redis Cluster when
+	/// `redis_key_hash_tag` is set. Cluster mode always tags them, under
+	/// `rinha` when no prefix is configured, since the pipelines and scripts
+	/// need all keys in one slot.
+	pub fn get_redis_keys(&self) -> RedisKeys {
+		let prefix = self.redis_key_prefix.as_deref().unwrap_or_default();
+		let keys = match self.redis_mode.unwrap_or_default() {
+			RedisMode::Cluster if prefix.is_empty() => {
+				RedisKeys::new(DEFAULT_CLUSTER_KEY_PREFIX, true)
+			}
+			RedisMode::Cluster => RedisKeys::new(prefix, true),
+			_ => RedisKeys::new(prefix, self.redis_key_hash_tag.unwrap_or_default()),
+		};
+		keys.with_payments_queue(self.get_payments_queue_key())
+	}
+
+	/// Capacity of the MPSC channel between the HTTP handlers and Redis.
+	pub fn get_ingress_buffer_capacity(&self) -> usize {
+		self.ingress_buffer_capacity
+	}
+
+	pub fn get_payments_queue_key(&self) -> &str {
+		&self.payments_queue_key
+	}
+
+	/// The processors rate limit their health endpoint to one call every five
+	/// seconds, hence the default.
+	pub fn get_health_check_interval(&self) -> Duration {
+		Duration::from_millis(self.processor_health_check_interval_ms)
+	}
+
+	/// Configured rates, which fees start from until the processors report
+	/// theirs.
+	pub fn get_fee_rates(&self) -> FeeRates {
+		FeeRates {
+			default:  self.default_processor_fee_rate.unwrap_or_default(),
+			fallback: self.fallback_processor_fee_rate.unwrap_or_default(),
+		}
+	}
+
+	pub fn get_fee_refresh_interval(&self) -> Duration {
+		Duration::from_millis(self.processor_fee_refresh_interval_ms)
+	}
+
+	/// Longest window `/payments-summary` accepts between `from` and `to`.
+	pub fn get_summary_max_window(&self) -> Duration {
+		Duration::from_millis(self.summary_max_window_ms)
+	}
+
+	/// Compacted payments keep counting in summaries, whose bounds past the
+	/// age must then fall on `retention_bucket_ms` edges, but no longer show up
+	/// in searches, exports or snapshots, and breakdowns refuse windows
+	/// reaching past the age. Their ids count as
+	/// processed for `retention_dedup_window_ms` more, so redeliveries and
+	/// replays within that are not charged again.
+	pub fn get_retention_options(&self) -> Option<RetentionOptions> {
+		self.retention_max_age_ms.map(|max_age| RetentionOptions {
+			max_age:      Duration::from_millis(max_age),
+			bucket:       Duration::from_millis(self.retention_bucket_ms),
+			dedup_window: Duration::from_millis(self.retention_dedup_window_ms),
+			interval:     Duration::from_millis(self.retention_interval_ms),
+			batch_size:   self.retention_batch_size,
+		})
+	}
+
+	pub fn get_summary_timestamp(&self) -> PaymentTimestamp {
+		self.summary_timestamp.unwrap_or_default()
+	}
+
+	/// How long `consistent=true` summaries wait at most for in-flight payments
+	/// to settle when they find any.
+	pub fn get_summary_consistency_timeout(&self) -> Duration {
+		Duration::from_millis(self.summary_consistency_timeout_ms)
+	}
+
+	pub fn get_telemetry_options(&self) -> TelemetryOptions {
+		TelemetryOptions {
+			log_format:    self.log_format.unwrap_or_default(),
+			otlp_endpoint: self
+				.otlp_endpoint
+				.as_ref()
+				.map(|endpoint| endpoint.to_string()),
+			service_name:  self.otlp_service_name.to_string(),
+		}
+	}
+
+	/// Share of the ingress buffer in use above which the instance reports
+	/// itself as not ready.
+	pub fn get_max_buffer_saturation(&self) -> f64 {
+		self.health_max_buffer_saturation
+	}
+
+	/// Routing thresholds and breaker settings; unset values keep the
+	/// router's built-in defaults.
+	pub fn get_routing_options(&self) -> RoutingOptions {
+		let defaults = RoutingOptions::default();
+		let breaker_options =
+			|defaults: BreakerOptions,
+			 failure_threshold: Option<f64>,
+			 min_throughput: Option<u64>,
+			 probe_interval: Option<u32>,
+			 cooldown_ms: Option<u64>| BreakerOptions {
+				failure_threshold: failure_threshold
+					.unwrap_or(defaults.failure_threshold),
+				min_throughput:    min_throughput.unwrap_or(defaults.min_throughput),
+				probe_interval:    probe_interval.unwrap_or(defaults.probe_interval),
+				cooldown:          cooldown_ms
+					.map(Duration::from_millis)
+					.unwrap_or(defaults.cooldown),
+			};
+
+		RoutingOptions {
+			max_response_time: self
+				.routing_max_response_time_ms
+				.unwrap_or(defaults.max_response_time),
+			default_breaker:   breaker_options(
+				defaults.default_breaker,
+				self.default_breaker_failure_threshold,
+				self.default_breaker_min_throughput,
+				self.default_breaker_probe_interval,
+				self.default_breaker_cooldown_ms,
+			),
+			fallback_breaker:  breaker_options(
+				defaults.fallback_breaker,
+				self.fallback_breaker_failure_threshold,
+				self.fallback_breaker_min_throughput,
+				self.fallback_breaker_probe_interval,
+				self.fallback_breaker_cooldown_ms,
+			),
+		}
 	}
 
 	pub fn get_default_key(&self) -> Arc<PaymentProcessorKey> {
@@ -41,6 +746,78 @@ impl Config {
 			self.fallback_payment_processor_url.clone(),
 		))
 	}
+
+	pub fn get_wal_options(&self) -> Option<WalOptions> {
+		self.wal_dir.as_ref().map(|dir| WalOptions {
+			dir:               PathBuf::from(dir.as_ref()),
+			fsync_policy:      self.wal_fsync_policy,
+			fsync_interval:    Duration::from_millis(self.wal_fsync_interval_ms),
+			max_segment_bytes: self.wal_segment_max_bytes,
+		})
+	}
+
+	pub fn get_admission_options(&self) -> AdmissionOptions {
+		AdmissionOptions {
+			mode:             self.ingress_backpressure_mode,
+			high_water_mark:  self.ingress_high_water_mark,
+			max_wait:         Duration::from_millis(self.ingress_max_wait_ms),
+			max_queue_length: self.ingress_max_queue_length,
+			retry_after:      Duration::from_secs(self.ingress_retry_after_secs),
+		}
+	}
+
+	/// A batch takes whatever is already buffered, then waits up to the
+	/// linger for more; with no linger, batches only form under load.
+	pub fn get_batch_options(&self) -> BatchOptions {
+		BatchOptions {
+			max_size:   self.ingress_batch_size,
+			max_linger: Duration::from_micros(self.ingress_batch_linger_us),
+		}
+	}
+
+	pub fn get_processor_batch_size(&self) -> usize {
+		self.payment_processor_batch_size
+	}
+
+	pub fn get_concurrency_options(&self) -> ConcurrencyOptions {
+		ConcurrencyOptions {
+			min_permits:       self.payment_processor_min_concurrency,
+			max_permits:       self.payment_processor_max_concurrency,
+			target_throughput: self.payment_processor_target_throughput,
+		}
+	}
+
+	/// Without explicit bounds the pool stays at
+	/// `payment_processor_worker_count` workers.
+	pub fn get_worker_pool_options(&self) -> WorkerPoolOptions {
+		let min_workers = self
+			.payment_processor_min_workers
+			.unwrap_or(self.payment_processor_worker_count);
+
+		WorkerPoolOptions {
+			min_workers,
+			max_workers: self
+				.payment_processor_max_workers
+				.unwrap_or(self.payment_processor_worker_count)
+				.max(min_workers),
+			scale_interval: Duration::from_millis(
+				self.worker_pool_scale_interval_ms,
+			),
+			backlog_per_worker: self.worker_pool_backlog_per_worker,
+			max_latency: Duration::from_millis(self.worker_pool_max_latency_ms),
+		}
+	}
+}
+
+fn check_http_url(errors: &mut Vec<String>, name: &str, url: &str) {
+	match Url::parse(url) {
+		Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+		Ok(url) => errors.push(format!(
+			"{name} must use http or https, got '{}'",
+			url.scheme()
+		)),
+		Err(e) => errors.push(format!("{name} '{url}' is invalid: {e}")),
+	}
 }
 
 #[cfg(test)]
@@ -69,7 +846,7 @@ mod tests {
 			env
 		}));
 
-		Config::load_from(source).expect("Failed to load config in test")
+		Config::load_from(None, source).expect("Failed to load config in test")
 	}
 
 	#[test]
@@ -114,7 +891,7 @@ mod tests {
 		}));
 
 		let config =
-			Config::load_from(source).expect("Failed to load config in test");
+			Config::load_from(None, source).expect("Failed to load config in test");
 
 		assert_eq!(config.redis_url, "redis://test_redis_no_report/");
 		assert_eq!(
@@ -130,6 +907,565 @@ mod tests {
 		assert_eq!(config.payment_processor_worker_count, 4);
 	}
 
+	#[test]
+	fn test_get_wal_options_disabled_by_default() {
+		let config = create_config_for_test();
+
+		assert!(config.get_wal_options().is_none());
+	}
+
+	#[test]
+	fn test_get_wal_options() {
+		let source = Environment::with_prefix(APP_PREFIX).source(Some({
+			let mut env = HashMap::new();
+			env.insert("APP_REDIS_URL".into(), "redis://test_redis/".into());
+			env.insert(
+				"APP_DEFAULT_PAYMENT_PROCESSOR_URL".into(),
+				"http://test_default/".into(),
+			);
+			env.insert(
+				"APP_FALLBACK_PAYMENT_PROCESSOR_URL".into(),
+				"http://test_fallback/".into(),
+			);
+			env.insert("APP_SERVER_KEEPALIVE".into(), "120".into());
+			env.insert("APP_PAYMENT_PROCESSOR_WORKER_COUNT".into(), "4".into());
+			env.insert("APP_WAL_DIR".into(), "/var/lib/rinha/wal".into());
+			env.insert("APP_WAL_FSYNC_POLICY".into(), "always".into());
+			env.insert("APP_WAL_SEGMENT_MAX_BYTES".into(), "4096".into());
+			env
+		}));
+
+		let config =
+			Config::load_from(None, source).expect("Failed to load config in test");
+		let wal_options = config.get_wal_options().unwrap();
+
+		assert_eq!(wal_options.dir, PathBuf::from("/var/lib/rinha/wal"));
+		assert_eq!(wal_options.fsync_policy, FsyncPolicy::Always);
+		assert_eq!(
+			wal_options.fsync_interval,
+			Duration::from_millis(DEFAULT_WAL_FSYNC_INTERVAL_MS)
+		);
+		assert_eq!(wal_options.max_segment_bytes, 4096);
+	}
+
+	#[test]
+	fn test_get_admission_options_defaults_to_blocking() {
+		let config = create_config_for_test();
+		let admission_options = config.get_admission_options();
+
+		assert_eq!(admission_options.mode, BackpressureMode::Block);
+		assert_eq!(
+			admission_options.high_water_mark,
+			DEFAULT_INGRESS_HIGH_WATER_MARK
+		);
+		assert_eq!(admission_options.max_queue_length, None);
+	}
+
+	#[test]
+	fn test_get_admission_options() {
+		let source = Environment::with_prefix(APP_PREFIX).source(Some({
+			let mut env = HashMap::new();
+			env.insert("APP_REDIS_URL".into(), "redis://test_redis/".into());
+			env.insert(
+				"APP_DEFAULT_PAYMENT_PROCESSOR_URL".into(),
+				"http://test_default/".into(),
+			);
+			env.insert(
+				"APP_FALLBACK_PAYMENT_PROCESSOR_URL".into(),
+				"http://test_fallback/".into(),
+			);
+			env.insert("APP_SERVER_KEEPALIVE".into(), "120".into());
+			env.insert("APP_PAYMENT_PROCESSOR_WORKER_COUNT".into(), "4".into());
+			env.insert("APP_INGRESS_BACKPRESSURE_MODE".into(), "fail_fast".into());
+			env.insert("APP_INGRESS_HIGH_WATER_MARK".into(), "500".into());
+			env.insert("APP_INGRESS_MAX_QUEUE_LENGTH".into(), "10000".into());
+			env.insert("APP_INGRESS_RETRY_AFTER_SECS".into(), "5".into());
+			env
+		}));
+
+		let config =
+			Config::load_from(None, source).expect("Failed to load config in test");
+		let admission_options = config.get_admission_options();
+
+		assert_eq!(admission_options.mode, BackpressureMode::FailFast);
+		assert_eq!(admission_options.high_water_mark, 500);
+		assert_eq!(admission_options.max_queue_length, Some(10_000));
+		assert_eq!(admission_options.retry_after, Duration::from_secs(5));
+	}
+
+	#[test]
+	fn test_get_batch_options() {
+		let mut config = create_config_for_test();
+
+		let batch_options = config.get_batch_options();
+		assert_eq!(batch_options.max_size, DEFAULT_INGRESS_BATCH_SIZE);
+		assert_eq!(
+			batch_options.max_linger,
+			Duration::from_micros(DEFAULT_INGRESS_BATCH_LINGER_US)
+		);
+
+		config.ingress_batch_size = 32;
+		config.ingress_batch_linger_us = 500;
+
+		let batch_options = config.get_batch_options();
+		assert_eq!(batch_options.max_size, 32);
+		assert_eq!(batch_options.max_linger, Duration::from_micros(500));
+	}
+
+	#[test]
+	fn test_get_concurrency_options() {
+		let mut config = create_config_for_test();
+
+		assert_eq!(
+			config.get_processor_batch_size(),
+			DEFAULT_PROCESSOR_BATCH_SIZE
+		);
+		let concurrency_options = config.get_concurrency_options();
+		assert_eq!(
+			concurrency_options.min_permits,
+			DEFAULT_PROCESSOR_MIN_CONCURRENCY
+		);
+		assert_eq!(
+			concurrency_options.max_permits,
+			DEFAULT_PROCESSOR_MAX_CONCURRENCY
+		);
+
+		config.payment_processor_batch_size = 8;
+		config.payment_processor_max_concurrency = 16;
+		config.payment_processor_target_throughput = 100.0;
+
+		assert_eq!(config.get_processor_batch_size(), 8);
+		let concurrency_options = config.get_concurrency_options();
+		assert_eq!(concurrency_options.max_permits, 16);
+		assert_eq!(concurrency_options.target_throughput, 100.0);
+	}
+
+	#[test]
+	fn test_get_worker_pool_options() {
+		let mut config = create_config_for_test();
+
+		let worker_pool_options = config.get_worker_pool_options();
+		assert_eq!(worker_pool_options.min_workers, 8);
+		assert_eq!(worker_pool_options.max_workers, 8);
+
+		config.payment_processor_min_workers = Some(2);
+		config.payment_processor_max_workers = Some(16);
+		config.worker_pool_backlog_per_worker = 50;
+
+		let worker_pool_options = config.get_worker_pool_options();
+		assert_eq!(worker_pool_options.min_workers, 2);
+		assert_eq!(worker_pool_options.max_workers, 16);
+		assert_eq!(worker_pool_options.backlog_per_worker, 50);
+		assert_eq!(
+			worker_pool_options.scale_interval,
+			Duration::from_millis(DEFAULT_WORKER_POOL_SCALE_INTERVAL_MS)
+		);
+	}
+
+	#[test]
+	fn test_get_telemetry_options() {
+		let mut config = create_config_for_test();
+
+		let telemetry_options = config.get_telemetry_options();
+		assert_eq!(telemetry_options.log_format, LogFormat::Text);
+		assert_eq!(telemetry_options.otlp_endpoint, None);
+		assert_eq!(telemetry_options.service_name, DEFAULT_OTLP_SERVICE_NAME);
+
+		config.log_format = Some(LogFormat::Json);
+		config.otlp_endpoint = Some("http://collector:4318".into());
+
+		let telemetry_options = config.get_telemetry_options();
+		assert_eq!(telemetry_options.log_format, LogFormat::Json);
+		assert_eq!(
+			telemetry_options.otlp_endpoint.as_deref(),
+			Some("http://collector:4318")
+		);
+	}
+
+	#[test]
+	fn test_config_load_applies_defaults() {
+		let source = Environment::with_prefix(APP_PREFIX).source(Some({
+			let mut env = HashMap::new();
+			env.insert("APP_REDIS_URL".into(), "redis://test_redis/".into());
+			env.insert(
+				"APP_DEFAULT_PAYMENT_PROCESSOR_URL".into(),
+				"http://test_default/".into(),
+			);
+			env.insert(
+				"APP_FALLBACK_PAYMENT_PROCESSOR_URL".into(),
+				"http://test_fallback/".into(),
+			);
+			env
+		}));
+
+		let config =
+			Config::load_from(None, source).expect("Failed to load config in test");
+
+		assert_eq!(config.server_keepalive, DEFAULT_SERVER_KEEPALIVE_SECS);
+		assert_eq!(config.payment_processor_worker_count, 4);
+		assert_eq!(config.get_bind_address(), DEFAULT_SERVER_BIND_ADDRESS);
+		assert_eq!(
+			config.get_ingress_buffer_capacity(),
+			DEFAULT_INGRESS_BUFFER_CAPACITY
+		);
+		assert_eq!(config.get_payments_queue_key(), PAYMENTS_QUEUE_KEY);
+		assert_eq!(config.get_health_check_interval(), Duration::from_secs(5));
+	}
+
+	#[test]
+	fn test_config_load_layers_env_over_file() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("rinha.toml");
+		std::fs::write(
+			&path,
+			r#"
+redis_url = "redis://file_redis/"
+default_payment_processor_url = "http://file_default/"
+fallback_payment_processor_url = "http://file_fallback/"
+server_bind_address = "127.0.0.1:8080"
+ingress_buffer_capacity = 1000
+ingress_high_water_mark = 800
+payments_queue_key = "file_queue"
+"#,
+		)
+		.unwrap();
+		let source = Environment::with_prefix(APP_PREFIX).source(Some({
+			let mut env = HashMap::new();
+			env.insert("APP_REDIS_URL".into(), "redis://env_redis/".into());
+			env.insert(
+				"APP_PROCESSOR_HEALTH_CHECK_INTERVAL_MS".into(),
+				"10000".into(),
+			);
+			env
+		}));
+
+		let config = Config::load_from(Some(File::from(path)), source)
+			.expect("Failed to load config in test");
+
+		assert_eq!(config.redis_url, "redis://env_redis/");
+		assert_eq!(config.default_payment_processor_url, "http://file_default/");
+		assert_eq!(config.get_bind_address(), "127.0.0.1:8080");
+		assert_eq!(config.get_ingress_buffer_capacity(), 1000);
+		assert_eq!(config.get_payments_queue_key(), "file_queue");
+		assert_eq!(config.get_health_check_interval(), Duration::from_secs(10));
+	}
+
+	#[test]
+	fn test_config_load_from_yaml_file() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("rinha.yaml");
+		std::fs::write(
+			&path,
+			"redis_url: redis://file_redis/\ndefault_payment_processor_url: \
+			 http://file_default/\nfallback_payment_processor_url: \
+			 http://file_fallback/\npayment_processor_worker_count: 2\n",
+		)
+		.unwrap();
+
+		let config = Config::load_from(
+			Some(File::from(path)),
+			Environment::with_prefix(APP_PREFIX).source(Some(HashMap::new())),
+		)
+		.expect("Failed to load config in test");
+
+		assert_eq!(config.redis_url, "redis://file_redis/");
+		assert_eq!(config.payment_processor_worker_count, 2);
+	}
+
+	#[test]
+	fn test_config_load_fails_when_config_file_is_missing() {
+		let result = Config::load_from(
+			Some(File::from(PathBuf::from("/nonexistent/rinha.toml"))),
+			Environment::with_prefix(APP_PREFIX).source(Some(HashMap::new())),
+		);
+
+		assert!(result.is_err());
+	}
+
+	#[test]
+	fn test_commands_need_neither_processors_nor_redis_url() {
+		let env = || {
+			Environment::with_prefix(APP_PREFIX).source(Some(HashMap::from([(
+				"APP_REDIS_KEY_PREFIX".to_string(),
+				"staging".to_string(),
+			)])))
+		};
+
+		let config = Config::build(None, env(), ConfigPurpose::Command)
+			.expect("Failed to load config in test");
+		assert_eq!(config.redis_url, DEFAULT_COMMAND_REDIS_URL);
+		assert_eq!(config.default_payment_processor_url, "");
+
+		let mut config = config;
+		config.redis_url = "redis://test_redis/".into();
+		let message = config.validate().unwrap_err().to_string();
+		assert!(message.contains("default_payment_processor_url is required"));
+		assert!(message.contains("fallback_payment_processor_url is required"));
+	}
+
+	#[test]
+	fn test_validate_reports_every_invalid_setting() {
+		let mut config = create_config_for_test();
+		config.redis_url = "not a url".into();
+		config.fallback_payment_processor_url = "ftp://test_fallback/".into();
+		config.server_bind_address = "0.0.0.0".into();
+		config.payment_processor_min_concurrency = 32;
+		config.payment_processor_max_concurrency = 16;
+		config.ingress_buffer_capacity = 100;
+		config.health_max_buffer_saturation = 1.5;
+
+		let message = config.validate().unwrap_err().to_string();
+
+		assert!(message.contains("redis_url 'not a url' is invalid"));
+		assert!(message.contains(
+			"fallback_payment_processor_url must use http or https, got 'ftp'"
+		));
+		assert!(message.contains("server_bind_address '0.0.0.0'"));
+		assert!(message.contains(
+			"payment_processor_min_concurrency (32) must not exceed \
+			 payment_processor_max_concurrency (16)"
+		));
+		assert!(message.contains(
+			"ingress_high_water_mark (80000) must not exceed \
+			 ingress_buffer_capacity (100)"
+		));
+		assert!(message.contains("health_max_buffer_saturation (1.5)"));
+		assert!(!message.contains("default_payment_processor_url"));
+	}
+
+	#[test]
+	fn test_validate_rejects_zero_sizes() {
+		let mut config = create_config_for_test();
+		config.payment_processor_worker_count = 0;
+		config.payment_processor_batch_size = 0;
+
+		let message = config.validate().unwrap_err().to_string();
+
+		assert!(
+			message
+				.contains("payment_processor_worker_count must be greater than 0")
+		);
+		assert!(
+			message.contains("payment_processor_batch_size must be greater than 0")
+		);
+	}
+
+	#[test]
+	fn test_get_routing_options() {
+		let mut config = create_config_for_test();
+
+		assert_eq!(config.get_routing_options(), RoutingOptions::default());
+
+		config.routing_max_response_time_ms = Some(250);
+		config.fallback_breaker_cooldown_ms = Some(500);
+
+		let routing_options = config.get_routing_options();
+		assert_eq!(routing_options.max_response_time, 250);
+		assert_eq!(
+			routing_options.fallback_breaker.cooldown,
+			Duration::from_millis(500)
+		);
+		assert_eq!(
+			routing_options.default_breaker,
+			RoutingOptions::default().default_breaker
+		);
+	}
+
+	#[test]
+	fn test_validate_rejects_invalid_breaker_threshold() {
+		let mut config = create_config_for_test();
+		config.default_breaker_failure_threshold = Some(0.0);
+
+		let message = config.validate().unwrap_err().to_string();
+
+		assert!(
+			message
+				.contains("default_breaker_failure_threshold (0) must be in (0, 1]")
+		);
+	}
+
+	#[test]
+	fn test_get_redis_keys() {
+		let mut config = create_config_for_test();
+
+		assert_eq!(config.get_redis_keys(), RedisKeys::default());
+
+		config.redis_key_prefix = Some("staging".into());
+		config.redis_key_hash_tag = Some(true);
+		config.payments_queue_key = "queue".into();
+
+		assert_eq!(config.get_redis_keys().payments_queue(), "{staging}:queue");
+		assert!(config.validate().is_ok());
+
+		config.redis_key_prefix = None;
+		let message = config.validate().unwrap_err().to_string();
+		assert!(message.contains("redis_key_hash_tag requires a redis_key_prefix"));
+	}
+
+	#[test]
+	fn test_cluster_mode_splits_nodes_and_tags_keys() {
+		let mut config = create_config_for_test();
+		config.redis_mode = Some(RedisMode::Cluster);
+		config.redis_url = "redis://node-1:7000/, redis://node-2:7000/".into();
+
+		assert_eq!(config.get_redis_options().nodes, vec![
+			"redis://node-1:7000/".to_string(),
+			"redis://node-2:7000/".to_string(),
+		]);
+		assert_eq!(
+			config.get_redis_keys().processed_payments(),
+			"{rinha}:processed_payments"
+		);
+		assert!(config.validate().is_ok());
+
+		config.redis_key_hash_tag = Some(false);
+		let message = config.validate().unwrap_err().to_string();
+		assert!(
+			message
+				.contains("redis_key_hash_tag cannot be disabled in cluster mode")
+		);
+	}
+
+	#[test]
+	fn test_get_redis_options_reconnect_settings() {
+		let mut config = create_config_for_test();
+
+		assert_eq!(
+			config.get_redis_options().startup_timeout,
+			Duration::from_secs(30)
+		);
+
+		config.redis_reconnect_max_delay_ms = Some(0);
+		config.redis_startup_timeout_ms = Some(1_000);
+
+		assert_eq!(
+			config.get_redis_options().startup_timeout,
+			Duration::from_secs(1)
+		);
+		let message = config.validate().unwrap_err().to_string();
+		assert!(
+			message.contains("redis_reconnect_max_delay_ms must be greater than 0")
+		);
+	}
+
+	#[test]
+	fn test_get_summary_timestamp() {
+		assert_eq!(
+			create_config_for_test().get_summary_timestamp(),
+			PaymentTimestamp::Submitted
+		);
+
+		let source = Environment::with_prefix(APP_PREFIX).source(Some({
+			let mut env = HashMap::new();
+			env.insert("APP_REDIS_URL".into(), "redis://test_redis/".into());
+			env.insert(
+				"APP_DEFAULT_PAYMENT_PROCESSOR_URL".into(),
+				"http://test_default/".into(),
+			);
+			env.insert(
+				"APP_FALLBACK_PAYMENT_PROCESSOR_URL".into(),
+				"http://test_fallback/".into(),
+			);
+			env.insert("APP_SUMMARY_TIMESTAMP".into(), "received".into());
+			env
+		}));
+
+		let config =
+			Config::load_from(None, source).expect("Failed to load config in test");
+
+		assert_eq!(config.get_summary_timestamp(), PaymentTimestamp::Received);
+	}
+
+	#[test]
+	fn test_get_summary_max_window() {
+		let mut config = create_config_for_test();
+
+		assert_eq!(
+			config.get_summary_max_window(),
+			Duration::from_secs(366 * 24 * 60 * 60)
+		);
+
+		config.summary_max_window_ms = 0;
+		let message = config.validate().unwrap_err().to_string();
+		assert!(message.contains("summary_max_window_ms must be greater than 0"));
+	}
+
+	#[test]
+	fn test_get_retention_options() {
+		let mut config = create_config_for_test();
+
+		assert_eq!(config.get_retention_options(), None);
+
+		config.retention_max_age_ms = Some(3_600_000);
+		config.retention_bucket_ms = 1_000;
+
+		assert_eq!(
+			config.get_retention_options(),
+			Some(RetentionOptions {
+				max_age:      Duration::from_secs(3_600),
+				bucket:       Duration::from_secs(1),
+				dedup_window: Duration::from_millis(
+					DEFAULT_RETENTION_DEDUP_WINDOW_MS
+				),
+				interval:     Duration::from_millis(DEFAULT_RETENTION_INTERVAL_MS),
+				batch_size:   DEFAULT_RETENTION_BATCH_SIZE,
+			})
+		);
+
+		config.retention_bucket_ms = 0;
+		let message = config.validate().unwrap_err().to_string();
+		assert!(message.contains("retention_bucket_ms must be greater than 0"));
+
+		config.retention_max_age_ms = Some(60_000);
+		let message = config.validate().unwrap_err().to_string();
+		assert!(message.contains("retention_max_age_ms must be at least 3600000"));
+	}
+
+	#[test]
+	fn test_get_fee_rates() {
+		let mut config = create_config_for_test();
+
+		assert_eq!(config.get_fee_rates(), FeeRates::default());
+
+		config.default_processor_fee_rate = Some(0.05);
+		config.fallback_processor_fee_rate = Some(1.5);
+
+		assert_eq!(config.get_fee_rates().default, 0.05);
+		let message = config.validate().unwrap_err().to_string();
+		assert!(
+			message.contains("fallback_processor_fee_rate (1.5) must be in [0, 1]")
+		);
+	}
+
+	#[test]
+	fn test_validate_redis_topology() {
+		let mut config = create_config_for_test();
+		config.redis_url = "redis://a/,redis://b/".into();
+
+		let message = config.validate().unwrap_err().to_string();
+		assert!(message.contains("exactly one server in standalone mode"));
+
+		config.redis_mode = Some(RedisMode::Sentinel);
+		let message = config.validate().unwrap_err().to_string();
+		assert!(
+			message.contains(
+				"redis_sentinel_service_name is required in sentinel mode"
+			)
+		);
+
+		config.redis_sentinel_service_name = Some("mymaster".into());
+		config.redis_master_password = Some("secret".into());
+		assert!(config.validate().is_ok());
+		assert_eq!(
+			config.get_redis_options().master_password.as_deref(),
+			Some("secret")
+		);
+
+		config.redis_mode = Some(RedisMode::Cluster);
+		let message = config.validate().unwrap_err().to_string();
+		assert!(message.contains("redis_master_* only apply in sentinel mode"));
+	}
+
 	#[test]
 	fn test_get_default_key() {
 		let config = create_config_for_test();
@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+
+use crate::domain::payment_router::PaymentRouter;
+use crate::infrastructure::config::redis::Redis;
+use crate::infrastructure::queue::buffered_payment::BufferedPayment;
+use crate::infrastructure::routing::in_memory_payment_router::InMemoryPaymentRouter;
+use crate::infrastructure::workers::worker_pool::WorkerPool;
+
+const REDIS_PING_TIMEOUT: Duration = Duration::from_millis(500);
+/// Workers beat at least every couple of seconds, even while idle or with all
+/// their slots busy, so longer silences mean they are stuck or gone.
+const WORKER_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeStatus {
+	Up,
+	Down,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct CheckResult {
+	pub status: ProbeStatus,
+	pub detail: String,
+}
+
+impl CheckResult {
+	fn new(healthy: bool, detail: String) -> Self {
+		Self {
+			status: if healthy {
+				ProbeStatus::Up
+			} else {
+				ProbeStatus::Down
+			},
+			detail,
+		}
+	}
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ReadinessReport {
+	pub status: ProbeStatus,
+	pub checks: BTreeMap<&'static str, CheckResult>,
+}
+
+impl ReadinessReport {
+	pub fn new(checks: BTreeMap<&'static str, CheckResult>) -> Self {
+		let status = if checks.values().all(|check| check.status == ProbeStatus::Up)
+		{
+			ProbeStatus::Up
+		} else {
+			ProbeStatus::Down
+		};
+
+		Self { status, checks }
+	}
+
+	pub fn is_ready(&self) -> bool {
+		self.status == ProbeStatus::Up
+	}
+}
+
+/// Decides whether this instance should receive traffic, checking every
+/// dependency a payment needs on its way from `POST /payments` to a processor.
+#[derive(Clone)]
+pub struct ReadinessProbe {
+	redis:                 Arc<Redis>,
+	payment_sender:        mpsc::Sender<BufferedPayment>,
+	worker_pool:           WorkerPool,
+	router:                InMemoryPaymentRouter,
+	max_buffer_saturation: f64,
+}
+
+impl ReadinessProbe {
+	pub fn new(
+		redis: Arc<Redis>,
+		payment_sender: mpsc::Sender<BufferedPayment>,
+		worker_pool: WorkerPool,
+		router: InMemoryPaymentRouter,
+		max_buffer_saturation: f64,
+	) -> Self {
+		Self {
+			redis,
+			payment_sender,
+			worker_pool,
+			router,
+			max_buffer_saturation,
+		}
+	}
+
+	pub async fn check(&self) -> ReadinessReport {
+		ReadinessReport::new(BTreeMap::from([
+			("redis", self.check_redis().await),
+			("ingress_buffer", self.check_ingress_buffer()),
+			("workers", self.check_workers()),
+			("payment_processors", self.check_payment_processors().await),
+		]))
+	}
+
+	async fn check_redis(&self) -> CheckResult {
+		let mut con = self.redis.connection.as_ref().clone();
+		let ping = redis::cmd("PING");
+
+		match timeout(REDIS_PING_TIMEOUT, ping.query_async::<String>(&mut con)).await
+		{
+			Ok(Ok(_)) => CheckResult::new(true, "PING succeeded".to_string()),
+			Ok(Err(e)) => CheckResult::new(false, format!("PING failed: {e}")),
+			Err(_) => CheckResult::new(
+				false,
+				format!("PING timed out after {REDIS_PING_TIMEOUT:?}"),
+			),
+		}
+	}
+
+	fn check_ingress_buffer(&self) -> CheckResult {
+		if self.payment_sender.is_closed() {
+			return CheckResult::new(
+				false,
+				"MPSC to Redis worker stopped".to_string(),
+			);
+		}
+
+		let max_capacity = self.payment_sender.max_capacity();
+		let buffered = max_capacity - self.payment_sender.capacity();
+		let saturation = buffered as f64 / max_capacity as f64;
+
+		CheckResult::new(
+			saturation < self.max_buffer_saturation,
+			format!("{buffered} of {max_capacity} slots in use"),
+		)
+	}
+
+	fn check_workers(&self) -> CheckResult {
+		let status = self.worker_pool.status();
+		let Some(since_last_heartbeat) = self.worker_pool.since_last_heartbeat()
+		else {
+			return CheckResult::new(
+				false,
+				"no payment processing worker has started".to_string(),
+			);
+		};
+
+		CheckResult::new(
+			status.size > 0 && since_last_heartbeat < WORKER_HEARTBEAT_TIMEOUT,
+			format!(
+				"{} payment processing workers running, last seen {}ms ago, {} \
+				 restarts",
+				status.size,
+				since_last_heartbeat.as_millis(),
+				status.restarts
+			),
+		)
+	}
+
+	async fn check_payment_processors(&self) -> CheckResult {
+		match self.router.get_processor_for_payment().await {
+			Some((key, _)) => {
+				CheckResult::new(true, format!("routing to {}", key.name))
+			}
+			None => CheckResult::new(
+				false,
+				"no payment processor is routable".to_string(),
+			),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_report_is_ready_only_when_every_check_is_up() {
+		let ready = ReadinessReport::new(BTreeMap::from([
+			("redis", CheckResult::new(true, String::new())),
+			("workers", CheckResult::new(true, String::new())),
+		]));
+		let not_ready = ReadinessReport::new(BTreeMap::from([
+			("redis", CheckResult::new(true, String::new())),
+			("workers", CheckResult::new(false, String::new())),
+		]));
+
+		assert!(ready.is_ready());
+		assert!(!not_ready.is_ready());
+		assert_eq!(not_ready.status, ProbeStatus::Down);
+	}
+}
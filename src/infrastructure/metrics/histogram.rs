@@ -0,0 +1,75 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Fixed-bucket histogram rendered in the Prometheus text format.
+pub struct Histogram {
+	bounds: &'static [u64],
+	counts: Vec<AtomicU64>,
+	sum:    AtomicU64,
+	count:  AtomicU64,
+}
+
+impl Histogram {
+	pub fn new(bounds: &'static [u64]) -> Self {
+		Self {
+			bounds,
+			counts: bounds.iter().map(|_| AtomicU64::new(0)).collect(),
+			sum: AtomicU64::new(0),
+			count: AtomicU64::new(0),
+		}
+	}
+
+	pub fn observe(&self, value: u64) {
+		if let Some(index) = self.bounds.iter().position(|bound| value <= *bound) {
+			self.counts[index].fetch_add(1, Ordering::Relaxed);
+		}
+		self.sum.fetch_add(value, Ordering::Relaxed);
+		self.count.fetch_add(1, Ordering::Relaxed);
+	}
+
+	pub fn count(&self) -> u64 {
+		self.count.load(Ordering::Relaxed)
+	}
+
+	pub fn sum(&self) -> u64 {
+		self.sum.load(Ordering::Relaxed)
+	}
+
+	pub fn render(&self, name: &str, help: &str, out: &mut String) {
+		let _ = writeln!(out, "# HELP {name} {help}");
+		let _ = writeln!(out, "# TYPE {name} histogram");
+
+		let mut cumulative = 0;
+		for (bound, count) in self.bounds.iter().zip(&self.counts) {
+			cumulative += count.load(Ordering::Relaxed);
+			let _ = writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {cumulative}");
+		}
+		let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {}", self.count());
+		let _ = writeln!(out, "{name}_sum {}", self.sum());
+		let _ = writeln!(out, "{name}_count {}", self.count());
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_observe_and_render() {
+		let histogram = Histogram::new(&[1, 10]);
+		histogram.observe(1);
+		histogram.observe(5);
+		histogram.observe(50);
+
+		let mut out = String::new();
+		histogram.render("batch_size", "Batch sizes.", &mut out);
+
+		assert_eq!(
+			out,
+			"# HELP batch_size Batch sizes.\n# TYPE batch_size \
+			 histogram\nbatch_size_bucket{le=\"1\"} \
+			 1\nbatch_size_bucket{le=\"10\"} 2\nbatch_size_bucket{le=\"+Inf\"} \
+			 3\nbatch_size_sum 56\nbatch_size_count 3\n"
+		);
+	}
+}
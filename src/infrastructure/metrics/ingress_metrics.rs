@@ -0,0 +1,86 @@
+use std::fmt::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::infrastructure::metrics::histogram::Histogram;
+use crate::infrastructure::metrics::registry::MetricsSource;
+
+const BATCH_SIZE_BUCKETS: &[u64] = &[1, 2, 4, 8, 16, 32, 64, 128, 256, 512];
+
+/// Metrics of the MPSC to Redis hand-off.
+#[derive(Clone)]
+pub struct IngressMetrics {
+	inner: Arc<IngressMetricsInner>,
+}
+
+struct IngressMetricsInner {
+	batch_size:      Histogram,
+	failed_batches:  AtomicU64,
+	failed_payments: AtomicU64,
+}
+
+impl IngressMetrics {
+	pub fn new() -> Self {
+		Self {
+			inner: Arc::new(IngressMetricsInner {
+				batch_size:      Histogram::new(BATCH_SIZE_BUCKETS),
+				failed_batches:  AtomicU64::new(0),
+				failed_payments: AtomicU64::new(0),
+			}),
+		}
+	}
+
+	pub fn record_batch(&self, size: usize) {
+		self.inner.batch_size.observe(size as u64);
+	}
+
+	pub fn record_failed_batch(&self, size: usize) {
+		self.inner.failed_batches.fetch_add(1, Ordering::Relaxed);
+		self.inner
+			.failed_payments
+			.fetch_add(size as u64, Ordering::Relaxed);
+	}
+
+	pub fn batches(&self) -> u64 {
+		self.inner.batch_size.count()
+	}
+
+	pub fn payments(&self) -> u64 {
+		self.inner.batch_size.sum()
+	}
+
+	pub fn failed_payments(&self) -> u64 {
+		self.inner.failed_payments.load(Ordering::Relaxed)
+	}
+}
+
+impl Default for IngressMetrics {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl MetricsSource for IngressMetrics {
+	fn render(&self, out: &mut String) {
+		self.inner.batch_size.render(
+			"rinha_ingress_batch_size",
+			"Payments pushed to Redis per batch.",
+			out,
+		);
+		let _ = writeln!(
+			out,
+			"# HELP rinha_ingress_failed_batches_total Batch pushes to Redis that \
+			 failed and were retried.\n# TYPE rinha_ingress_failed_batches_total \
+			 counter\nrinha_ingress_failed_batches_total {}",
+			self.inner.failed_batches.load(Ordering::Relaxed)
+		);
+		let _ = writeln!(
+			out,
+			"# HELP rinha_ingress_failed_payments_total Payments of the batch \
+			 pushes to Redis that failed and were retried.\n# TYPE \
+			 rinha_ingress_failed_payments_total \
+			 counter\nrinha_ingress_failed_payments_total {}",
+			self.failed_payments()
+		);
+	}
+}
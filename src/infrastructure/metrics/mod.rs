@@ -0,0 +1,4 @@
+pub mod histogram;
+pub mod ingress_metrics;
+pub mod redis_metrics;
+pub mod registry;
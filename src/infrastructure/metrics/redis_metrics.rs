@@ -0,0 +1,113 @@
+use std::fmt::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
+use crate::infrastructure::metrics::registry::MetricsSource;
+
+/// State of the Redis connections shared by every `Redis` handle.
+#[derive(Clone, Default)]
+pub struct RedisConnectionMetrics {
+	inner: Arc<RedisConnectionMetricsInner>,
+}
+
+#[derive(Default)]
+struct RedisConnectionMetricsInner {
+	connected:         AtomicI64,
+	disconnected:      AtomicI64,
+	reconnects:        AtomicU64,
+	connection_errors: AtomicU64,
+	failed_connects:   AtomicU64,
+}
+
+impl RedisConnectionMetrics {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn record_opened(&self) {
+		self.inner.connected.fetch_add(1, Ordering::Relaxed);
+	}
+
+	pub fn record_closed(&self, connected: bool) {
+		self.gauge(connected).fetch_sub(1, Ordering::Relaxed);
+	}
+
+	pub fn record_failed_connect(&self) {
+		self.inner.failed_connects.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// A request failed because the connection broke.
+	pub fn record_connection_error(&self, was_connected: bool) {
+		self.inner.connection_errors.fetch_add(1, Ordering::Relaxed);
+		if was_connected {
+			self.inner.connected.fetch_sub(1, Ordering::Relaxed);
+			self.inner.disconnected.fetch_add(1, Ordering::Relaxed);
+		}
+	}
+
+	/// A request succeeded again on a connection that had broken.
+	pub fn record_reconnect(&self) {
+		self.inner.reconnects.fetch_add(1, Ordering::Relaxed);
+		self.inner.disconnected.fetch_sub(1, Ordering::Relaxed);
+		self.inner.connected.fetch_add(1, Ordering::Relaxed);
+	}
+
+	pub fn connected(&self) -> i64 {
+		self.inner.connected.load(Ordering::Relaxed)
+	}
+
+	pub fn disconnected(&self) -> i64 {
+		self.inner.disconnected.load(Ordering::Relaxed)
+	}
+
+	pub fn reconnects(&self) -> u64 {
+		self.inner.reconnects.load(Ordering::Relaxed)
+	}
+
+	pub fn failed_connects(&self) -> u64 {
+		self.inner.failed_connects.load(Ordering::Relaxed)
+	}
+
+	fn gauge(&self, connected: bool) -> &AtomicI64 {
+		if connected {
+			&self.inner.connected
+		} else {
+			&self.inner.disconnected
+		}
+	}
+}
+
+impl MetricsSource for RedisConnectionMetrics {
+	fn render(&self, out: &mut String) {
+		let _ = writeln!(
+			out,
+			"# HELP rinha_redis_connections Redis connections by state.\n# TYPE \
+			 rinha_redis_connections \
+			 gauge\nrinha_redis_connections{{state=\"connected\"}} \
+			 {}\nrinha_redis_connections{{state=\"disconnected\"}} {}",
+			self.connected(),
+			self.disconnected()
+		);
+		let _ = writeln!(
+			out,
+			"# HELP rinha_redis_reconnects_total Redis connections that recovered \
+			 after breaking.\n# TYPE rinha_redis_reconnects_total \
+			 counter\nrinha_redis_reconnects_total {}",
+			self.reconnects()
+		);
+		let _ = writeln!(
+			out,
+			"# HELP rinha_redis_connection_errors_total Requests that failed on a \
+			 broken Redis connection.\n# TYPE rinha_redis_connection_errors_total \
+			 counter\nrinha_redis_connection_errors_total {}",
+			self.inner.connection_errors.load(Ordering::Relaxed)
+		);
+		let _ = writeln!(
+			out,
+			"# HELP rinha_redis_failed_connects_total Attempts to open a Redis \
+			 connection that failed.\n# TYPE rinha_redis_failed_connects_total \
+			 counter\nrinha_redis_failed_connects_total {}",
+			self.inner.failed_connects.load(Ordering::Relaxed)
+		);
+	}
+}
@@ -0,0 +1,29 @@
+use std::sync::{Arc, RwLock};
+
+pub trait MetricsSource: Send + Sync + 'static {
+	fn render(&self, out: &mut String);
+}
+
+/// Collects the metrics of every component and renders them for `/metrics`.
+#[derive(Clone, Default)]
+pub struct MetricsRegistry {
+	sources: Arc<RwLock<Vec<Arc<dyn MetricsSource>>>>,
+}
+
+impl MetricsRegistry {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn register(&self, source: Arc<dyn MetricsSource>) {
+		self.sources.write().unwrap().push(source);
+	}
+
+	pub fn render(&self) -> String {
+		let mut out = String::new();
+		for source in self.sources.read().unwrap().iter() {
+			source.render(&mut out);
+		}
+		out
+	}
+}
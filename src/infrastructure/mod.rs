@@ -1,6 +1,10 @@
 pub mod config;
+pub mod health;
 pub mod math;
+pub mod metrics;
 pub mod persistence;
 pub mod queue;
 pub mod routing;
+pub mod telemetry;
+pub mod wal;
 pub mod workers;
@@ -1 +1,2 @@
 pub mod redis_payment_repository;
+pub mod redis_scripts;
@@ -1,106 +1,503 @@
+use std::collections::HashMap;
 use std::error::Error;
-use std::sync::Arc;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
 
 use async_trait::async_trait;
-use log::{error, info};
-use redis::aio::MultiplexedConnection;
-use redis::{AsyncCommands, Script};
+use redis::{AsyncCommands, ScriptInvocation};
 use time::OffsetDateTime;
+use time::format_description::BorrowedFormatItem;
 use time::format_description::well_known::Rfc3339;
+use tracing::{error, info};
 
-use crate::domain::payment::Payment;
-use crate::domain::repository::PaymentRepository;
-use crate::infrastructure::config::redis::{PROCESSED_PAYMENTS_SET_KEY, Redis};
+use crate::domain::fee_schedule::FeeSchedule;
+use crate::domain::payment::{Payment, PaymentStatus, PaymentTimestamp};
+use crate::domain::queue::Message;
+use crate::domain::repository::{
+	AmountsBreakdown, GroupSummary, PaymentRepository, PaymentSearch,
+	PaymentsCursor, PaymentsPage, ProcessedFees, PurgeScope, SortOrder,
+};
+use crate::domain::snapshot::{PaymentBucket, SnapshotRecord, SnapshotStore};
+use crate::infrastructure::config::redis::{Redis, RedisConnection, RedisKeys};
+use crate::infrastructure::persistence::redis_scripts::RedisScripts;
+
+/// Groups whose hashes a page of processed payments is looked up in.
+const PAYMENT_GROUPS: [&str; 2] = ["default", "fallback"];
+/// Fields per payment in a `payments_page` or `search_payments` reply.
+const PAGE_ENTRY_FIELDS: usize = 7;
+/// Index entries a search looks at per round trip at least, so filters that
+/// match few payments do not hold Redis for long.
+const SEARCH_SCAN_BUDGET: usize = 2_000;
+/// Index entries a breakdown aggregates per round trip, so long windows do not
+/// hold Redis for long.
+const BREAKDOWN_PAGE_SIZE: usize = 5_000;
+/// Keys inspected per `SCAN` round trip while purging.
+const PURGE_SCAN_COUNT: usize = 500;
+/// How long a claim keeps other workers off a payment whose worker died
+/// before saving or re-queueing it. Live workers extend it while they wait
+/// for a processor permit and on the processor.
+const PAYMENT_CLAIM_TTL: Duration = Duration::from_secs(30);
+/// How older versions stored timestamps, `OffsetDateTime`'s `Display`.
+static LEGACY_TIMESTAMP_FORMAT: LazyLock<Vec<BorrowedFormatItem<'static>>> =
+	LazyLock::new(|| {
+		time::format_description::parse(
+			"[year]-[month]-[day] [hour \
+			 padding:none]:[minute]:[second].[subsecond] [offset_hour \
+			 sign:mandatory]:[offset_minute]:[offset_second]",
+		)
+		.expect("Valid format description")
+	});
 
 #[derive(Clone)]
 pub struct RedisPaymentRepository {
-	redis: Arc<Redis>,
+	redis:             Arc<Redis>,
+	keys:              RedisKeys,
+	fee_schedule:      Option<FeeSchedule>,
+	summary_timestamp: PaymentTimestamp,
 }
 
 impl RedisPaymentRepository {
 	pub fn new(redis: Arc<Redis>) -> Self {
-		Self { redis }
+		Self {
+			redis,
+			keys: RedisKeys::default(),
+			fee_schedule: None,
+			summary_timestamp: PaymentTimestamp::default(),
+		}
+	}
+
+	/// Picks the timestamp summaries, breakdowns and in-flight counts filter
+	/// on. Every timestamp is indexed when saving, and `backfill_indexes`
+	/// indexes the payments saved before, so this can change between restarts
+	/// once it went through them. Payments saved without a `received_at` are
+	/// left out of summaries on it.
+	pub fn with_summary_timestamp(mut self, timestamp: PaymentTimestamp) -> Self {
+		self.summary_timestamp = timestamp;
+		self
+	}
+
+	fn summary_index(&self) -> &str {
+		self.keys.processed_payments_by(self.summary_timestamp)
+	}
+
+	pub fn with_keys(mut self, keys: RedisKeys) -> Self {
+		self.keys = keys;
+		self
+	}
+
+	/// Stamps saved payments with their processor's fee rate at that moment.
+	pub fn with_fee_schedule(mut self, fee_schedule: FeeSchedule) -> Self {
+		self.fee_schedule = Some(fee_schedule);
+		self
+	}
+
+	/// Deletes one `SCAN` batch of payment hashes along with their entries in
+	/// the processed set, skipping payments outside the scope's time window.
+	async fn purge_payment_keys(
+		&self,
+		con: &mut RedisConnection,
+		mut keys: Vec<String>,
+		scope: &PurgeScope,
+	) -> redis::RedisResult<usize> {
+		if keys.is_empty() {
+			return Ok(0);
+		}
+
+		if scope.from.is_some() || scope.to.is_some() {
+			let ids: Vec<&str> = keys.iter().map(|key| payment_id_of(key)).collect();
+			let scores: Vec<Option<f64>> = redis::cmd("ZMSCORE")
+				.arg(self.keys.processed_payments())
+				.arg(&ids)
+				.query_async(con)
+				.await?;
+
+			let from = scope.from.map(|ts| ts.unix_timestamp_nanos() as f64);
+			let to = scope.to.map(|ts| ts.unix_timestamp_nanos() as f64);
+			let mut scores = scores.into_iter();
+			keys.retain(|_| {
+				scores.next().flatten().is_some_and(|score| {
+					from.is_none_or(|from| score >= from) &&
+						to.is_none_or(|to| score <= to)
+				})
+			});
+			if keys.is_empty() {
+				return Ok(0);
+			}
+		}
+
+		let ids: Vec<&str> = keys.iter().map(|key| payment_id_of(key)).collect();
+		let mut pipe = redis::pipe();
+		pipe.unlink(&keys).ignore();
+		for index in PAYMENT_TIMESTAMPS {
+			pipe.zrem(self.keys.processed_payments_by(index), &ids)
+				.ignore();
+			for key in &keys {
+				pipe.zrem(
+					self.keys.processed_payments_of(index, group_of(key)),
+					payment_id_of(key),
+				)
+				.ignore();
+			}
+		}
+		pipe.query_async::<()>(con).await?;
+
+		Ok(keys.len())
+	}
+
+	/// Unlinks every key matching `pattern`, one `SCAN` batch at a time.
+	async fn unlink_matching(
+		&self,
+		con: &mut RedisConnection,
+		pattern: &str,
+	) -> redis::RedisResult<()> {
+		let mut cursor: u64 = 0;
+		loop {
+			let (next_cursor, keys): (u64, Vec<String>) = con
+				.query_on_node_of(
+					redis::cmd("SCAN")
+						.arg(cursor)
+						.arg("MATCH")
+						.arg(pattern)
+						.arg("COUNT")
+						.arg(PURGE_SCAN_COUNT),
+					self.keys.processed_payments(),
+				)
+				.await?;
+			if !keys.is_empty() {
+				con.unlink::<_, ()>(&keys).await?;
+			}
+
+			if next_cursor == 0 {
+				return Ok(());
+			}
+			cursor = next_cursor;
+		}
+	}
+
+	/// The `save_payment` call for a payment stamped with `fee_rate`.
+	fn save_invocation(
+		&self,
+		payment: &Payment,
+		fee_rate: Option<f64>,
+	) -> ScriptInvocation<'static> {
+		let payment_id = payment.correlation_id.to_string();
+		// Payments without a `received_at` or `processed_at` stay out of its
+		// indexes, as `backfill_indexes` leaves them.
+		let [received_score, processed_score] =
+			[payment.received_at, payment.processed_at]
+				.map(|timestamp| timestamp.map(|ts| score(Some(ts)).to_string()));
+		let payment_group = payment.processed_by.clone().unwrap_or_default();
+		let format = |timestamp: Option<OffsetDateTime>| {
+			timestamp
+				.and_then(|ts| ts.format(&Rfc3339).ok())
+				.unwrap_or_default()
+		};
+
+		let mut invocation = RedisScripts::get()
+			.save_payment
+			.key(self.keys.payment(&payment_group, &payment_id));
+		invocation
+			.key(self.keys.processed_payments())
+			.key(self.keys.payment_claim(&payment_id))
+			.key(self.keys.payments_in_flight())
+			.key(self.keys.processed_payments_by(PaymentTimestamp::Received))
+			.key(self.keys.processed_payments_by(PaymentTimestamp::Processed))
+			.key(
+				self.keys.processed_payments_of(
+					PaymentTimestamp::Received,
+					&payment_group,
+				),
+			)
+			.key(
+				self.keys.processed_payments_of(
+					PaymentTimestamp::Submitted,
+					&payment_group,
+				),
+			)
+			.key(
+				self.keys.processed_payments_of(
+					PaymentTimestamp::Processed,
+					&payment_group,
+				),
+			)
+			.key(self.keys.in_flight_payment(&payment_id))
+			.arg(format!("{:.2}", payment.amount))
+			.arg(format(payment.submitted_at))
+			.arg(format(payment.processed_at))
+			.arg(payment_group)
+			.arg(&payment_id)
+			.arg(score(payment.submitted_at))
+			.arg(fee_rate.map(|rate| rate.to_string()).unwrap_or_default())
+			.arg(format(payment.received_at))
+			.arg(received_score.unwrap_or_default())
+			.arg(processed_score.unwrap_or_default());
+		invocation
+	}
+
+	/// Adds the payments saved before the `received_at` and `processed_at`
+	/// indexes or the per-processor ones existed to them, `batch_size` at a
+	/// time, then records that it is done so later starts skip it. Returns how
+	/// many payments it went through.
+	pub async fn backfill_indexes(
+		&self,
+		batch_size: usize,
+	) -> Result<usize, Box<dyn Error + Send>> {
+		let mut con = self.redis.connection.as_ref().clone();
+		let done_key = self.keys.indexes_backfilled();
+
+		let done: bool = con
+			.exists(&done_key)
+			.await
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+		if done {
+			return Ok(0);
+		}
+
+		let mut backfilled = 0;
+		let mut cursor = None;
+		loop {
+			let (entries, next) = self
+				.read_page(
+					self.keys.processed_payments(),
+					score_range(None, None),
+					cursor,
+					batch_size,
+				)
+				.await
+				.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+			let entries: Vec<&[String]> =
+				entries.chunks_exact(PAGE_ENTRY_FIELDS).collect();
+
+			if !entries.is_empty() {
+				let ids: Vec<&str> =
+					entries.iter().map(|entry| entry[0].as_str()).collect();
+				let mut scores = redis::pipe();
+				for index in PAYMENT_TIMESTAMPS {
+					scores
+						.cmd("ZMSCORE")
+						.arg(self.keys.processed_payments_by(index))
+						.arg(&ids);
+				}
+				let scores: Vec<Vec<Option<f64>>> = scores
+					.query_async(&mut con)
+					.await
+					.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+
+				let mut index_payments = redis::pipe();
+				for (index, scores) in PAYMENT_TIMESTAMPS.into_iter().zip(scores) {
+					let field = match index {
+						PaymentTimestamp::Submitted => 3,
+						PaymentTimestamp::Processed => 4,
+						PaymentTimestamp::Received => 5,
+					};
+					for (entry, score) in entries.iter().zip(scores) {
+						let (id, group) = (&entry[0], &entry[1]);
+						let score = match score {
+							Some(score) => score,
+							// Saved before the index existed, so read from its hash.
+							None => match parse_stored_timestamp(&entry[field]) {
+								Some(ts) => {
+									let score = ts.unix_timestamp_nanos() as f64;
+									index_payments
+										.zadd(
+											self.keys.processed_payments_by(index),
+											id,
+											score,
+										)
+										.ignore();
+									score
+								}
+								None => continue,
+							},
+						};
+						index_payments
+							.zadd(
+								self.keys.processed_payments_of(index, group),
+								id,
+								score,
+							)
+							.ignore();
+					}
+				}
+				index_payments
+					.query_async::<()>(&mut con)
+					.await
+					.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+				backfilled += entries.len();
+			}
+
+			match next {
+				Some(next) => cursor = Some(next),
+				None => break,
+			}
+		}
+
+		con.set::<_, _, ()>(&done_key, 1)
+			.await
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+		Ok(backfilled)
+	}
+
+	/// Reads a `payments_page` of `index` within a score range, returning its
+	/// entries and where the next page starts.
+	async fn read_page(
+		&self,
+		index: &str,
+		(mut min_score, max_score): (String, String),
+		cursor: Option<PaymentsCursor>,
+		limit: usize,
+	) -> redis::RedisResult<(Vec<String>, Option<PaymentsCursor>)> {
+		let mut con = self.redis.connection.as_ref().clone();
+
+		let mut skip = 0;
+		if let Some(cursor) = cursor {
+			min_score = cursor.position.to_string();
+			skip = cursor.skip;
+		}
+		let mut reply: Vec<String> = RedisScripts::get()
+			.payments_page
+			.key(index)
+			.arg(&min_score)
+			.arg(&max_score)
+			.arg(skip)
+			.arg(limit)
+			.arg(self.keys.payments())
+			.arg(&PAYMENT_GROUPS)
+			.invoke_async(&mut con)
+			.await?;
+
+		let [read, last_score, at_last_score] =
+			[0, 1, 2].map(|i| reply.get(i).map(String::as_str).unwrap_or_default());
+		let next = (read.parse::<usize>().ok() == Some(limit))
+			.then(|| next_cursor(cursor, last_score, at_last_score))
+			.flatten();
+
+		let entries = reply.split_off(reply.len().min(3));
+		Ok((entries, next))
 	}
 
 	async fn calculate_payments_summary_using_lua(
-		con: &mut MultiplexedConnection,
+		&self,
+		con: &mut RedisConnection,
 		group: &str,
-		from_ts: i128,
-		to_ts: i128,
-	) -> redis::RedisResult<(usize, f64)> {
-		let lua = Script::new(
-			r#"
-            local ids = redis.call("ZRANGEBYSCORE", KEYS[1], ARGV[1], ARGV[2])
-            local total_requests = 0
-            local total_amount = 0.0
-
-            for i, id in ipairs(ids) do
-                local key = ARGV[3] .. ":" .. id
-                local amount = redis.call("HGET", key, "amount")
-                if amount then
-                    total_requests = total_requests + 1
-                    total_amount = total_amount + tonumber(amount)
-                end
-            end
-
-            return {tostring(total_requests), tostring(total_amount)}
-        "#,
-		);
-
-		let response: (String, String) = lua
-			.key(PROCESSED_PAYMENTS_SET_KEY)
-			.arg(from_ts)
-			.arg(to_ts)
-			.arg(format!("payment_summary:{group}"))
+		(min_score, max_score): (String, String),
+	) -> redis::RedisResult<GroupSummary> {
+		let response: (String, String, String, String) = RedisScripts::get()
+			.payments_summary
+			.key(self.summary_index())
+			.key(
+				self.keys
+					.payment_bucket_starts(self.summary_timestamp, group),
+			)
+			.key(self.keys.payment_buckets(self.summary_timestamp, group))
+			.arg(min_score)
+			.arg(max_score)
+			.arg(self.keys.payment_group(group))
 			.invoke_async(con)
 			.await?;
 
-		Ok((
-			response.0.parse().unwrap_or_default(),
-			response.1.parse().unwrap_or_default(),
-		))
+		Ok(GroupSummary {
+			total_requests: response.0.parse().unwrap_or_default(),
+			total_amount:   response.1.parse().unwrap_or_default(),
+			fees:           ProcessedFees {
+				fees:           response.2.parse().unwrap_or_default(),
+				unrated_amount: response.3.parse().unwrap_or_default(),
+			},
+		})
 	}
 }
 
+const PAYMENT_TIMESTAMPS: [PaymentTimestamp; 3] = [
+	PaymentTimestamp::Received,
+	PaymentTimestamp::Submitted,
+	PaymentTimestamp::Processed,
+];
+
+/// Reads a timestamp of a payment hash, stored as RFC 3339 or, by older
+/// versions, in the legacy format.
+fn parse_stored_timestamp(value: &str) -> Option<OffsetDateTime> {
+	OffsetDateTime::parse(value, &Rfc3339)
+		.or_else(|_| OffsetDateTime::parse(value, &LEGACY_TIMESTAMP_FORMAT))
+		.ok()
+}
+
+fn score(timestamp: Option<OffsetDateTime>) -> i128 {
+	timestamp
+		.map(|ts| ts.unix_timestamp_nanos())
+		.unwrap_or_default()
+}
+
+/// Where the next page starts: past the entries of this page sharing its
+/// last score, and past those the previous pages already read at that score.
+fn next_cursor(
+	cursor: Option<PaymentsCursor>,
+	last_score: &str,
+	at_last_score: &str,
+) -> Option<PaymentsCursor> {
+	let position = last_score.parse::<f64>().ok()?;
+	let mut skip = at_last_score.parse().unwrap_or_default();
+	// The whole page sat at the cursor's position.
+	if let Some(cursor) = cursor &&
+		cursor.position == position
+	{
+		skip += cursor.skip;
+	}
+	Some(PaymentsCursor { position, skip })
+}
+
+/// Reads the `[id, group, amount, submitted_at, processed_at, received_at,
+/// fee_rate, ...]` entries of a page, along with their fee rate.
+fn rated_payments_of(entries: &[String]) -> Vec<(Payment, Option<f64>)> {
+	let parse_timestamp = parse_stored_timestamp;
+	entries
+		.chunks_exact(PAGE_ENTRY_FIELDS)
+		.filter_map(|entry| {
+			let payment = Payment {
+				correlation_id: entry[0].parse().ok()?,
+				amount:         entry[2].parse().ok()?,
+				submitted_at:   parse_timestamp(&entry[3]),
+				processed_at:   parse_timestamp(&entry[4]),
+				processed_by:   Some(entry[1].clone()),
+				received_at:    parse_timestamp(&entry[5]),
+			};
+			Some((payment, entry[6].parse().ok()))
+		})
+		.collect()
+}
+
+fn payments_of(entries: &[String]) -> Vec<Payment> {
+	rated_payments_of(entries)
+		.into_iter()
+		.map(|(payment, _)| payment)
+		.collect()
+}
+
+/// `ZRANGEBYSCORE` bounds of a window, open sides reaching `-inf`/`+inf`.
+fn score_range(
+	from: Option<OffsetDateTime>,
+	to: Option<OffsetDateTime>,
+) -> (String, String) {
+	let score = |ts: OffsetDateTime| ts.unix_timestamp_nanos().to_string();
+	(
+		from.map_or("-inf".to_string(), score),
+		to.map_or("+inf".to_string(), score),
+	)
+}
+
 #[async_trait]
 impl PaymentRepository for RedisPaymentRepository {
+	#[tracing::instrument(name = "save_payment", skip_all)]
 	async fn save(&self, payment: Payment) -> Result<(), Box<dyn Error + Send>> {
 		let mut con = self.redis.connection.as_ref().clone();
 
-		let payment_id = payment.correlation_id.to_string();
-		let payment_group = payment.processed_by.unwrap_or_default();
-		let payment_key = format!("payment_summary:{payment_group}:{payment_id}");
+		let fee_rate = self.fee_schedule.as_ref().and_then(|fee_schedule| {
+			fee_schedule
+				.rates()
+				.for_group(payment.processed_by.as_deref().unwrap_or_default())
+		});
 
-		redis::pipe()
-			.atomic()
-			.hset(&payment_key, "amount", format!("{:.2}", payment.amount))
-			.hset_multiple(&payment_key, &[
-				(
-					"requested_at",
-					payment
-						.requested_at
-						.map(|ts| ts.to_string())
-						.unwrap_or_default(),
-				),
-				(
-					"processed_at",
-					payment
-						.processed_at
-						.map(|ts| ts.to_string())
-						.unwrap_or_default(),
-				),
-				("processed_by", payment_group),
-			])
-			.ignore()
-			.zadd(
-				PROCESSED_PAYMENTS_SET_KEY,
-				payment_id,
-				payment
-					.requested_at
-					.map(|ts| ts.unix_timestamp_nanos())
-					.unwrap_or_default(),
-			)
-			.query_async::<()>(&mut con)
+		self.save_invocation(&payment, fee_rate)
+			.invoke_async::<()>(&mut con)
 			.await
 			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
 
@@ -110,20 +507,271 @@ impl PaymentRepository for RedisPaymentRepository {
 	async fn get_summary_by_group(
 		&self,
 		group: &str,
-		from_ts: OffsetDateTime,
-		to_ts: OffsetDateTime,
+		from_ts: Option<OffsetDateTime>,
+		to_ts: Option<OffsetDateTime>,
 	) -> Result<(usize, f64), Box<dyn Error + Send>> {
 		let mut con = self.redis.connection.as_ref().clone();
 
-		let (req, amt) = Self::calculate_payments_summary_using_lua(
+		let summary = self
+			.calculate_payments_summary_using_lua(
+				&mut con,
+				group,
+				score_range(from_ts, to_ts),
+			)
+			.await
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+		Ok((summary.total_requests, summary.total_amount))
+	}
+
+	async fn get_breakdown_by_group(
+		&self,
+		group: &str,
+		from_ts: Option<OffsetDateTime>,
+		to_ts: Option<OffsetDateTime>,
+		bucket: Duration,
+		max_buckets: usize,
+	) -> Result<Option<AmountsBreakdown>, Box<dyn Error + Send>> {
+		let mut con = self.redis.connection.as_ref().clone();
+
+		let (min_score, max_score) = score_range(from_ts, to_ts);
+		let width = bucket.as_nanos().max(1);
+		let mut breakdown = AmountsBreakdown::default();
+		let mut cursor: Option<PaymentsCursor> = None;
+		loop {
+			let (from, skip) = match cursor {
+				Some(cursor) => (cursor.position.to_string(), cursor.skip),
+				None => (min_score.clone(), 0),
+			};
+			let reply: Vec<String> = RedisScripts::get()
+				.payments_breakdown
+				.key(self.summary_index())
+				.arg(&from)
+				.arg(&max_score)
+				.arg(skip)
+				.arg(BREAKDOWN_PAGE_SIZE)
+				.arg(self.keys.payment_group(group))
+				.arg(width.to_string())
+				.invoke_async(&mut con)
+				.await
+				.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+
+			let mut fields = reply.into_iter();
+			let [read, last_score, at_last_score, buckets]: [String; 4] =
+				std::array::from_fn(|_| fields.next().unwrap_or_default());
+			let fields: Vec<String> = fields.collect();
+			let (bucket_fields, amount_fields) = fields.split_at(
+				(buckets.parse::<usize>().unwrap_or_default() * 3).min(fields.len()),
+			);
+
+			for entry in bucket_fields.chunks_exact(3) {
+				let (Ok(start), Ok(count), Ok(cents)) = (
+					entry[0].parse(),
+					entry[1].parse::<usize>(),
+					entry[2].parse::<i64>(),
+				) else {
+					continue;
+				};
+				let bucket = breakdown.buckets.entry(start).or_default();
+				bucket.0 += count;
+				bucket.1 += cents;
+			}
+			for entry in amount_fields.chunks_exact(2) {
+				if let (Ok(cents), Ok(count)) =
+					(entry[0].parse(), entry[1].parse::<usize>())
+				{
+					*breakdown.amounts.entry(cents).or_default() += count;
+				}
+			}
+			if breakdown.buckets.len() > max_buckets {
+				return Ok(None);
+			}
+
+			cursor = (read.parse::<usize>().ok() == Some(BREAKDOWN_PAGE_SIZE))
+				.then(|| next_cursor(cursor, &last_score, &at_last_score))
+				.flatten();
+			if cursor.is_none() {
+				return Ok(Some(breakdown));
+			}
+		}
+	}
+
+	async fn get_summary_with_fees_by_group(
+		&self,
+		group: &str,
+		from_ts: Option<OffsetDateTime>,
+		to_ts: Option<OffsetDateTime>,
+	) -> Result<GroupSummary, Box<dyn Error + Send>> {
+		let mut con = self.redis.connection.as_ref().clone();
+
+		self.calculate_payments_summary_using_lua(
 			&mut con,
 			group,
-			from_ts.unix_timestamp_nanos(),
-			to_ts.unix_timestamp_nanos(),
+			score_range(from_ts, to_ts),
 		)
 		.await
+		.map_err(|e| Box::new(e) as Box<dyn Error + Send>)
+	}
+
+	async fn get_processed_payments(
+		&self,
+		from_ts: Option<OffsetDateTime>,
+		to_ts: Option<OffsetDateTime>,
+		cursor: Option<PaymentsCursor>,
+		limit: usize,
+	) -> Result<PaymentsPage, Box<dyn Error + Send>> {
+		let (entries, next) = self
+			.read_page(
+				self.summary_index(),
+				score_range(from_ts, to_ts),
+				cursor,
+				limit,
+			)
+			.await
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+
+		Ok(PaymentsPage {
+			payments: payments_of(&entries),
+			next,
+		})
+	}
+
+	/// Processed payments are walked through the time index of their
+	/// processor when the search names one, or else the overall one, and
+	/// in-flight payments through the in-flight set. Payments saved before
+	/// the per-processor indexes existed only show up in searches naming a
+	/// processor once `backfill_indexes` went through them.
+	async fn search_payments(
+		&self,
+		search: &PaymentSearch,
+		cursor: Option<PaymentsCursor>,
+		limit: usize,
+	) -> Result<PaymentsPage, Box<dyn Error + Send>> {
+		let mut con = self.redis.connection.as_ref().clone();
+
+		let (index, key_prefix, status) = match (search.status, &search.processor) {
+			(PaymentStatus::Processed, Some(processor)) => (
+				self.keys
+					.processed_payments_of(self.summary_timestamp, processor),
+				self.keys.payments(),
+				"processed",
+			),
+			(PaymentStatus::Processed, None) => (
+				self.summary_index().to_string(),
+				self.keys.payments(),
+				"processed",
+			),
+			(PaymentStatus::InFlight, _) => (
+				self.keys.payments_in_flight().to_string(),
+				self.keys.in_flight_payments(),
+				"in_flight",
+			),
+		};
+		let groups = match &search.processor {
+			Some(processor) => vec![processor.as_str()],
+			None => PAYMENT_GROUPS.to_vec(),
+		};
+
+		let (mut min_score, mut max_score) = score_range(search.from, search.to);
+		let order = match search.order {
+			SortOrder::Asc => "asc",
+			SortOrder::Desc => "desc",
+		};
+		let mut skip = 0;
+		if let Some(cursor) = cursor {
+			let position = cursor.position.to_string();
+			match search.order {
+				SortOrder::Asc => min_score = position,
+				SortOrder::Desc => max_score = position,
+			}
+			skip = cursor.skip;
+		}
+		let amount_bound =
+			|amount: Option<f64>| amount.map(|a| a.to_string()).unwrap_or_default();
+
+		let reply: Vec<String> = RedisScripts::get()
+			.search_payments
+			.key(index)
+			.arg(&min_score)
+			.arg(&max_score)
+			.arg(skip)
+			.arg(limit)
+			.arg(limit.max(SEARCH_SCAN_BUDGET))
+			.arg(order)
+			.arg(status)
+			.arg(key_prefix)
+			.arg(amount_bound(search.min_amount))
+			.arg(amount_bound(search.max_amount))
+			.arg(&groups)
+			.invoke_async(&mut con)
+			.await
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+
+		let [more, last_score, at_last_score] =
+			[0, 1, 2].map(|i| reply.get(i).map(String::as_str).unwrap_or_default());
+		let next = (more == "1")
+			.then(|| next_cursor(cursor, last_score, at_last_score))
+			.flatten();
+
+		Ok(PaymentsPage {
+			payments: payments_of(reply.get(3..).unwrap_or_default()),
+			next,
+		})
+	}
+
+	/// Not processed yet, payments are scored by `submitted_at` when
+	/// summaries filter on `processed_at`.
+	/// The payment is held for searches to read under its own key, which
+	/// expires with the claim when its worker dies before saving or
+	/// re-queueing it.
+	async fn mark_in_flight(
+		&self,
+		payment: &Payment,
+		processor: &str,
+	) -> Result<(), Box<dyn Error + Send>> {
+		let mut con = self.redis.connection.as_ref().clone();
+
+		let payment_id = payment.correlation_id.to_string();
+		let in_flight = serde_json::to_string(&Payment {
+			processed_by: Some(processor.to_string()),
+			..payment.clone()
+		})
 		.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
-		Ok((req, amt))
+
+		redis::pipe()
+			.zadd(
+				self.keys.payments_in_flight(),
+				&payment_id,
+				score(payment.timestamp(self.summary_timestamp)),
+			)
+			.ignore()
+			.pset_ex(
+				self.keys.in_flight_payment(&payment_id),
+				in_flight,
+				PAYMENT_CLAIM_TTL.as_millis() as u64,
+			)
+			.ignore()
+			.query_async::<()>(&mut con)
+			.await
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)
+	}
+
+	async fn count_in_flight(
+		&self,
+		from_ts: Option<OffsetDateTime>,
+		to_ts: Option<OffsetDateTime>,
+	) -> Result<usize, Box<dyn Error + Send>> {
+		let mut con = self.redis.connection.as_ref().clone();
+
+		let (min_score, max_score) = score_range(from_ts, to_ts);
+		RedisScripts::get()
+			.payments_in_flight
+			.key(self.keys.payments_in_flight())
+			.arg(min_score)
+			.arg(max_score)
+			.arg(self.keys.payment_claims())
+			.invoke_async(&mut con)
+			.await
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)
 	}
 
 	async fn get_payment_summary(
@@ -133,7 +781,7 @@ impl PaymentRepository for RedisPaymentRepository {
 	) -> Result<Payment, Box<dyn Error + Send>> {
 		let mut con = self.redis.connection.as_ref().clone();
 
-		let payment_key = format!("payment_summary:{group}:{payment_id}");
+		let payment_key = self.keys.payment(group, payment_id);
 		let payment_data: Option<std::collections::HashMap<String, String>> =
 			con.hgetall(&payment_key).await.ok();
 
@@ -141,21 +789,24 @@ impl PaymentRepository for RedisPaymentRepository {
 			let Some(amount_str) = map.get("amount") &&
 			let Ok(amount) = amount_str.parse::<f64>()
 		{
-			let requested_at = map
-				.get("requested_at")
-				.and_then(|odt| OffsetDateTime::parse(odt, &Rfc3339).ok());
-			let processed_at = map
-				.get("processed_at")
-				.and_then(|odt| OffsetDateTime::parse(odt, &Rfc3339).ok());
+			let parse_timestamp = |field: &str| {
+				map.get(field).and_then(|odt| parse_stored_timestamp(odt))
+			};
+			// Payments saved by older versions name it `requested_at`.
+			let submitted_at = parse_timestamp("submitted_at")
+				.or_else(|| parse_timestamp("requested_at"));
+			let received_at = parse_timestamp("received_at");
+			let processed_at = parse_timestamp("processed_at");
 			let processed_by = map.get("processed_by").cloned();
 
 			let payment = Payment {
 				correlation_id: uuid::Uuid::parse_str(payment_id)
 					.expect("Valid UUID"),
 				amount,
-				requested_at,
+				submitted_at,
 				processed_at,
 				processed_by,
+				received_at,
 			};
 			return Ok(payment);
 		}
@@ -172,26 +823,591 @@ impl PaymentRepository for RedisPaymentRepository {
 	) -> Result<bool, Box<dyn Error + Send>> {
 		let mut con = self.redis.connection.as_ref().clone();
 
-		let is_already_processed: Option<f64> = con
-			.zscore(PROCESSED_PAYMENTS_SET_KEY, payment_id)
+		let (processed, compacted): (Option<f64>, Option<f64>) = redis::pipe()
+			.zscore(self.keys.processed_payments(), payment_id)
+			.zscore(self.keys.compacted_payments(), payment_id)
+			.query_async(&mut con)
+			.await
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+
+		Ok(processed.is_some() || compacted.is_some())
+	}
+
+	async fn claim(&self, payment_id: &str) -> Result<bool, Box<dyn Error + Send>> {
+		let mut con = self.redis.connection.as_ref().clone();
+
+		RedisScripts::get()
+			.claim_payment
+			.key(self.keys.processed_payments())
+			.key(self.keys.payment_claim(payment_id))
+			.key(self.keys.compacted_payments())
+			.arg(payment_id)
+			.arg(PAYMENT_CLAIM_TTL.as_millis() as u64)
+			.invoke_async(&mut con)
+			.await
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)
+	}
+
+	async fn extend_claim(
+		&self,
+		payment_id: &str,
+	) -> Result<bool, Box<dyn Error + Send>> {
+		let mut con = self.redis.connection.as_ref().clone();
+
+		let ttl = PAYMENT_CLAIM_TTL.as_millis() as i64;
+		let (extended, _): (bool, bool) = redis::pipe()
+			.pexpire(self.keys.payment_claim(payment_id), ttl)
+			.pexpire(self.keys.in_flight_payment(payment_id), ttl)
+			.query_async(&mut con)
 			.await
-			.ok();
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+		Ok(extended)
+	}
 
-		Ok(is_already_processed.is_some())
+	async fn compact(
+		&self,
+		before: OffsetDateTime,
+		bucket: Duration,
+		dedup_window: Duration,
+		limit: usize,
+	) -> Result<usize, Box<dyn Error + Send>> {
+		let mut con = self.redis.connection.as_ref().clone();
+
+		let [received_index, processed_set, processed_index] =
+			PAYMENT_TIMESTAMPS.map(|index| self.keys.processed_payments_by(index));
+		let forget_before = before - dedup_window;
+		RedisScripts::get()
+			.compact_payments
+			.key(received_index)
+			.key(processed_set)
+			.key(processed_index)
+			.key(self.keys.compacted_payments())
+			.arg(before.unix_timestamp_nanos().to_string())
+			.arg(limit)
+			.arg(bucket.as_nanos().max(1).to_string())
+			.arg(self.keys.payments())
+			.arg(forget_before.unix_timestamp_nanos().to_string())
+			.arg(&PAYMENT_GROUPS)
+			.invoke_async(&mut con)
+			.await
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)
 	}
 
-	async fn clear(&self) -> Result<(), Box<dyn Error + Send>> {
+	/// Walks the payment hashes with `SCAN` instead of `FLUSHDB`, so keys that
+	/// belong to other services in the same database survive.
+	async fn purge(
+		&self,
+		scope: &PurgeScope,
+	) -> Result<usize, Box<dyn Error + Send>> {
 		let mut con = self.redis.connection.as_ref().clone();
 
-		match AsyncCommands::flushdb::<String>(&mut con).await {
-			Ok(_) => {
-				info!("Redis database cleared successfully.");
-				Ok(())
+		let pattern = match &scope.group {
+			Some(group) => {
+				format!("{}:*", escape_glob(&self.keys.payment_group(group)))
 			}
-			Err(e) => {
-				error!("Redis database cleared failed: {e}");
-				Err(Box::new(e) as Box<dyn Error + Send>)
+			None => format!("{}:*:*", escape_glob(&self.keys.payments())),
+		};
+
+		let mut deleted = 0;
+		let mut cursor: u64 = 0;
+		loop {
+			// In a cluster every key of a hash-tagged namespace lives on the node
+			// owning the processed set's slot, so that node is the one to scan.
+			let (next_cursor, keys): (u64, Vec<String>) = con
+				.query_on_node_of(
+					redis::cmd("SCAN")
+						.arg(cursor)
+						.arg("MATCH")
+						.arg(&pattern)
+						.arg("COUNT")
+						.arg(PURGE_SCAN_COUNT),
+					self.keys.processed_payments(),
+				)
+				.await
+				.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+
+			deleted += self
+				.purge_payment_keys(&mut con, keys, scope)
+				.await
+				.map_err(|e| {
+					error!("Failed to purge payments: {e}");
+					Box::new(e) as Box<dyn Error + Send>
+				})?;
+
+			if next_cursor == 0 {
+				break;
 			}
+			cursor = next_cursor;
 		}
+
+		// Processed entries whose hash is already gone, like compacted buckets,
+		// are only reachable through their sets.
+		let mut cleanup = redis::pipe();
+		if scope.is_everything() {
+			let [default_summary, fallback_summary] = self.keys.payment_summaries();
+			cleanup
+				.del(&[
+					self.keys.processed_payments_by(PaymentTimestamp::Received),
+					self.keys.processed_payments(),
+					self.keys.processed_payments_by(PaymentTimestamp::Processed),
+					self.keys.compacted_payments(),
+					&default_summary,
+					&fallback_summary,
+				])
+				.ignore();
+			for index in PAYMENT_TIMESTAMPS {
+				for group in PAYMENT_GROUPS {
+					cleanup
+						.del(&[
+							self.keys.processed_payments_of(index, group),
+							self.keys.payment_buckets(index, group),
+							self.keys.payment_bucket_starts(index, group),
+						])
+						.ignore();
+				}
+			}
+		} else if scope.group.is_none() {
+			let (min_score, max_score) = score_range(scope.from, scope.to);
+			cleanup
+				.zrembyscore(self.keys.processed_payments(), &min_score, &max_score)
+				.ignore()
+				.zrembyscore(self.keys.compacted_payments(), min_score, max_score)
+				.ignore();
+		}
+		if !scope.is_everything() {
+			let (min_score, max_score) = score_range(scope.from, scope.to);
+			let groups = match &scope.group {
+				Some(group) => vec![group.as_str()],
+				None => PAYMENT_GROUPS.to_vec(),
+			};
+			// Pipelined scripts are not loaded on demand.
+			cleanup
+				.load_script(&RedisScripts::get().purge_buckets)
+				.ignore();
+			for index in PAYMENT_TIMESTAMPS {
+				for group in &groups {
+					cleanup
+						.invoke_script(
+							RedisScripts::get()
+								.purge_buckets
+								.key(self.keys.payment_bucket_starts(index, group))
+								.key(self.keys.payment_buckets(index, group))
+								.arg(&min_score)
+								.arg(&max_score),
+						)
+						.ignore();
+				}
+			}
+		}
+		if !scope.preserve_queue {
+			cleanup
+				.del(&[self.keys.payments_queue(), self.keys.payments_in_flight()])
+				.ignore();
+		}
+		cleanup
+			.query_async::<()>(&mut con)
+			.await
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+
+		// Dropping the claims along with the queue lets the same payments be
+		// submitted again right away; workers still holding one save it or
+		// re-queue it as usual.
+		if !scope.preserve_queue {
+			for prefix in
+				[self.keys.payment_claims(), self.keys.in_flight_payments()]
+			{
+				self.unlink_matching(
+					&mut con,
+					&format!("{}:*", escape_glob(&prefix)),
+				)
+				.await
+				.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+			}
+		}
+
+		info!("Purged {deleted} payments ({scope:?})");
+		Ok(deleted)
+	}
+}
+
+#[async_trait]
+impl SnapshotStore for RedisPaymentRepository {
+	/// Walks the processed set, which every saved payment is indexed in
+	/// whatever the summary timestamp.
+	async fn read_payments(
+		&self,
+		cursor: Option<PaymentsCursor>,
+		limit: usize,
+	) -> Result<(Vec<SnapshotRecord>, Option<PaymentsCursor>), Box<dyn Error + Send>>
+	{
+		let (entries, next) = self
+			.read_page(
+				self.keys.processed_payments(),
+				score_range(None, None),
+				cursor,
+				limit,
+			)
+			.await
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+
+		let records = rated_payments_of(&entries)
+			.into_iter()
+			.map(|(payment, fee_rate)| SnapshotRecord::Payment { payment, fee_rate })
+			.collect();
+		Ok((records, next))
+	}
+
+	/// Producers push on the left, so the page is read from the right end,
+	/// where the oldest messages are.
+	async fn read_queue(
+		&self,
+		skip: usize,
+		limit: usize,
+	) -> Result<Vec<Message<Payment>>, Box<dyn Error + Send>> {
+		let mut con = self.redis.connection.as_ref().clone();
+
+		let last = -(skip as isize) - 1;
+		let entries: Vec<Vec<u8>> = con
+			.lrange(
+				self.keys.payments_queue(),
+				last - limit.max(1) as isize + 1,
+				last,
+			)
+			.await
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+
+		entries
+			.iter()
+			.rev()
+			.map(|entry| {
+				rmp_serde::from_slice(entry)
+					.map_err(|e| Box::new(e) as Box<dyn Error + Send>)
+			})
+			.collect()
+	}
+
+	/// In-flight payments whose held copy already expired were abandoned by
+	/// their worker and are left out.
+	async fn read_in_flight(&self) -> Result<Vec<Payment>, Box<dyn Error + Send>> {
+		let mut con = self.redis.connection.as_ref().clone();
+
+		let ids: Vec<String> = con
+			.zrange(self.keys.payments_in_flight(), 0, -1)
+			.await
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+		if ids.is_empty() {
+			return Ok(Vec::new());
+		}
+
+		let keys: Vec<String> = ids
+			.iter()
+			.map(|id| self.keys.in_flight_payment(id))
+			.collect();
+		let held: Vec<Option<String>> = con
+			.mget(&keys)
+			.await
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+
+		held.into_iter()
+			.flatten()
+			.map(|payment| {
+				serde_json::from_str(&payment)
+					.map_err(|e| Box::new(e) as Box<dyn Error + Send>)
+			})
+			.collect()
+	}
+
+	/// Moved to the staging list atomically, where they stay until
+	/// `release_queue`. Messages that no longer decode are moved on to the
+	/// dead-letter list.
+	async fn take_queue(
+		&self,
+		count: usize,
+	) -> Result<Vec<Message<Payment>>, Box<dyn Error + Send>> {
+		let mut con = self.redis.connection.as_ref().clone();
+
+		let migrating = self.keys.migrating_queue();
+		let entries: Vec<Vec<u8>> = RedisScripts::get()
+			.take_queue
+			.key(self.keys.payments_queue())
+			.key(&migrating)
+			.arg(count.max(1))
+			.invoke_async(&mut con)
+			.await
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+
+		let mut messages = Vec::with_capacity(entries.len());
+		let mut unreadable = 0;
+		let mut dead_letters = redis::pipe();
+		dead_letters.atomic();
+		// Staged newest first, like the queue itself.
+		for entry in entries.into_iter().rev() {
+			match rmp_serde::from_slice(&entry) {
+				Ok(message) => messages.push(message),
+				Err(e) => {
+					error!(
+						"Moving unreadable queued message to the dead letters: {e}"
+					);
+					unreadable += 1;
+					dead_letters
+						.lrem(&migrating, 1, &entry)
+						.ignore()
+						.lpush(self.keys.dead_letter_queue(), &entry)
+						.ignore();
+				}
+			}
+		}
+		if unreadable > 0 {
+			dead_letters
+				.query_async::<()>(&mut con)
+				.await
+				.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+		}
+
+		Ok(messages)
+	}
+
+	async fn release_queue(&self) -> Result<(), Box<dyn Error + Send>> {
+		let mut con = self.redis.connection.as_ref().clone();
+
+		con.del(self.keys.migrating_queue())
+			.await
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)
+	}
+
+	async fn read_buckets(
+		&self,
+	) -> Result<Vec<PaymentBucket>, Box<dyn Error + Send>> {
+		let mut con = self.redis.connection.as_ref().clone();
+
+		let mut buckets = Vec::new();
+		for timestamp in PAYMENT_TIMESTAMPS {
+			for group in PAYMENT_GROUPS {
+				let fields: HashMap<String, String> = con
+					.hgetall(self.keys.payment_buckets(timestamp, group))
+					.await
+					.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+				buckets.extend(buckets_of(timestamp, group, &fields));
+			}
+		}
+
+		Ok(buckets)
+	}
+
+	/// Writes each batch in one pipeline. Payments keep the fee rate they were
+	/// snapshotted with rather than taking the current schedule's.
+	async fn write(
+		&self,
+		records: Vec<SnapshotRecord>,
+	) -> Result<(), Box<dyn Error + Send>> {
+		let mut con = self.redis.connection.as_ref().clone();
+
+		let mut pipe = redis::pipe();
+		// Pipelined scripts are not loaded on demand.
+		pipe.load_script(&RedisScripts::get().save_payment).ignore();
+		for record in records {
+			match record {
+				SnapshotRecord::Header { .. } => {}
+				SnapshotRecord::Payment { payment, fee_rate } => {
+					pipe.invoke_script(&self.save_invocation(&payment, fee_rate))
+						.ignore();
+				}
+				SnapshotRecord::Queued { message } => {
+					let message = rmp_serde::to_vec_named(&message)
+						.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+					pipe.lpush(self.keys.payments_queue(), message).ignore();
+				}
+				SnapshotRecord::InFlight { payment } => {
+					let message = rmp_serde::to_vec_named(&Message::with(
+						payment.correlation_id,
+						Payment {
+							processed_by: None,
+							..payment
+						},
+					))
+					.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+					pipe.lpush(self.keys.payments_queue(), message).ignore();
+				}
+				SnapshotRecord::Bucket(bucket) => {
+					let buckets =
+						self.keys.payment_buckets(bucket.timestamp, &bucket.group);
+					let field = |name: &str| format!("{}:{name}", bucket.start);
+					pipe.hset_multiple(&buckets, &[
+						(field("count"), bucket.count.to_string()),
+						(field("amount"), bucket.amount.to_string()),
+						(field("fees"), bucket.fees.to_string()),
+						(field("unrated_amount"), bucket.unrated_amount.to_string()),
+						(field("end"), bucket.end.to_string()),
+					])
+					.ignore()
+					.zadd(
+						self.keys
+							.payment_bucket_starts(bucket.timestamp, &bucket.group),
+						bucket.start,
+						bucket.start,
+					)
+					.ignore();
+				}
+			}
+		}
+
+		pipe.query_async::<()>(&mut con)
+			.await
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)
+	}
+}
+
+/// Reads the `{start}:{field}` fields of a buckets hash.
+fn buckets_of(
+	timestamp: PaymentTimestamp,
+	group: &str,
+	fields: &HashMap<String, String>,
+) -> Vec<PaymentBucket> {
+	let mut buckets: Vec<PaymentBucket> = fields
+		.iter()
+		.filter(|(field, _)| field.ends_with(":end"))
+		.filter_map(|(field, end)| {
+			let start = field.strip_suffix(":end")?;
+			let value = |name: &str| {
+				fields
+					.get(&format!("{start}:{name}"))
+					.and_then(|value| value.parse::<f64>().ok())
+					.unwrap_or_default()
+			};
+			Some(PaymentBucket {
+				timestamp,
+				group: group.to_string(),
+				start: start.parse().ok()?,
+				end: end.parse().ok()?,
+				count: value("count") as u64,
+				amount: value("amount"),
+				fees: value("fees"),
+				unrated_amount: value("unrated_amount"),
+			})
+		})
+		.collect();
+	buckets.sort_by_key(|bucket| bucket.start);
+	buckets
+}
+
+/// Payment hashes are keyed `{namespace}payment_summary:{group}:{payment_id}`.
+fn payment_id_of(key: &str) -> &str {
+	key.rsplit_once(':')
+		.map_or(key, |(_, payment_id)| payment_id)
+}
+
+fn group_of(key: &str) -> &str {
+	key.rsplit(':').nth(1).unwrap_or_default()
+}
+
+/// Escapes the characters `SCAN MATCH` treats as glob syntax.
+fn escape_glob(value: &str) -> String {
+	let mut escaped = String::with_capacity(value.len());
+	for c in value.chars() {
+		if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+			escaped.push('\\');
+		}
+		escaped.push(c);
+	}
+	escaped
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_payment_id_of() {
+		assert_eq!(payment_id_of("payment_summary:default:42"), "42");
+		assert_eq!(payment_id_of("payment_summary::42"), "42");
+	}
+
+	#[test]
+	fn test_group_of() {
+		assert_eq!(group_of("staging:payment_summary:default:42"), "default");
+		assert_eq!(group_of("payment_summary::42"), "");
+	}
+
+	#[test]
+	fn test_parse_stored_timestamp_reads_the_legacy_format() {
+		let timestamp = OffsetDateTime::parse("2025-07-01T09:05:03.25Z", &Rfc3339)
+			.unwrap()
+			.to_offset(time::UtcOffset::from_hms(-3, 0, 0).unwrap());
+
+		assert_eq!(
+			parse_stored_timestamp(&timestamp.format(&Rfc3339).unwrap()),
+			Some(timestamp)
+		);
+		assert_eq!(
+			parse_stored_timestamp(&timestamp.to_string()),
+			Some(timestamp)
+		);
+		assert_eq!(parse_stored_timestamp(""), None);
+	}
+
+	#[test]
+	fn test_next_cursor_carries_the_skip_at_an_unchanged_position() {
+		let cursor = PaymentsCursor {
+			position: 10.0,
+			skip:     3,
+		};
+
+		assert_eq!(
+			next_cursor(Some(cursor), "10", "2"),
+			Some(PaymentsCursor {
+				position: 10.0,
+				skip:     5,
+			})
+		);
+		assert_eq!(
+			next_cursor(Some(cursor), "12", "2"),
+			Some(PaymentsCursor {
+				position: 12.0,
+				skip:     2,
+			})
+		);
+		assert_eq!(next_cursor(None, "", "0"), None);
+	}
+
+	#[test]
+	fn test_buckets_of_reads_complete_buckets() {
+		let fields: HashMap<String, String> = [
+			("120:count", "2"),
+			("120:amount", "30.5"),
+			("120:fees", "1.5"),
+			("120:end", "179"),
+			("60:count", "1"),
+			("60:amount", "10"),
+			("60:end", "119"),
+			// Left behind without an end, so not a bucket.
+			("0:count", "4"),
+		]
+		.into_iter()
+		.map(|(field, value)| (field.to_string(), value.to_string()))
+		.collect();
+
+		let buckets = buckets_of(PaymentTimestamp::Received, "default", &fields);
+
+		assert_eq!(
+			buckets
+				.iter()
+				.map(|bucket| (
+					bucket.start,
+					bucket.end,
+					bucket.count,
+					bucket.amount
+				))
+				.collect::<Vec<_>>(),
+			vec![(60, 119, 1, 10.0), (120, 179, 2, 30.5)]
+		);
+		assert_eq!(buckets[1].fees, 1.5);
+		assert_eq!(buckets[1].unrated_amount, 0.0);
+		assert_eq!(buckets[1].timestamp, PaymentTimestamp::Received);
+	}
+
+	#[test]
+	fn test_escape_glob() {
+		assert_eq!(escape_glob("default"), "default");
+		assert_eq!(escape_glob("a*b?[c]"), "a\\*b\\?\\[c\\]");
 	}
 }
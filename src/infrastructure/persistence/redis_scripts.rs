@@ -0,0 +1,531 @@
+use std::sync::LazyLock;
+
+use redis::aio::ConnectionLike;
+use redis::{RedisResult, Script};
+use tracing::info;
+
+/// Every server-side script, hashed once per process. `invoke_async` runs them
+/// with `EVALSHA` and only sends the source again when Redis answers
+/// `NOSCRIPT`, as it does after a restart or a failover.
+///
+/// Keys a script builds from ARGV instead of declaring in KEYS are only valid
+/// under Redis Cluster because the hash-tagged namespace puts them in the slot
+/// of the declared ones; `Config` enforces the tag in cluster mode.
+pub struct RedisScripts {
+	/// Writes the payment hash, indexes it in the processed set and by its
+	/// other timestamps, overall and per processor, and drops its claim and
+	/// in-flight entry and payment.
+	///
+	/// KEYS: payment hash, processed set, claim, in-flight set, received_at
+	/// index, processed_at index, then the processor's received_at,
+	/// submitted_at and processed_at indexes, in-flight payment. ARGV: amount,
+	/// submitted_at, processed_at, processed_by, payment id, processed set
+	/// score, fee rate (empty when unknown), received_at, received_at score,
+	/// processed_at score (both empty when the timestamp is unknown, which
+	/// leaves the payment out of the index).
+	pub save_payment:       Script,
+	/// Claims a payment for processing unless it is already processed, or
+	/// compacted, or claimed by another worker. Returns 1 when claimed.
+	///
+	/// KEYS: processed set, claim, compacted set. ARGV: payment id, claim TTL
+	/// in milliseconds.
+	pub claim_payment:      Script,
+	/// Releases a claim, in-flight entry and payment and puts the message back
+	/// on the queue, unless the payment got processed, or compacted,
+	/// meanwhile. Returns 1 when re-queued.
+	///
+	/// KEYS: queue, processed set, claim, in-flight set, compacted set,
+	/// in-flight payment. ARGV: payment id, message.
+	pub requeue_payment:    Script,
+	/// Counts and sums the payments of a group within a time window, along
+	/// with their fees at the rates stamped on each payment and the amount of
+	/// those saved without one, adding the compacted buckets that lie entirely
+	/// within it.
+	///
+	/// KEYS: processed set, bucket starts, buckets. ARGV: from, to, payment
+	/// group key prefix.
+	pub payments_summary:   Script,
+	/// Aggregates a page of the payments of a group within a time window per
+	/// bucket and per amount. Returns the number of entries read, the score
+	/// of the last one, how many entries of the page share it and the number
+	/// of buckets, followed by `[start, count, cents, ...]` for the buckets
+	/// and `[cents, count, ...]` for the amounts.
+	///
+	/// KEYS: processed set. ARGV: from, to, offset, count, payment group key
+	/// prefix, bucket size in nanoseconds.
+	pub payments_breakdown: Script,
+	/// Counts the in-flight payments within a time window. Entries whose claim
+	/// expired belong to a worker that died mid-flight and are dropped.
+	///
+	/// KEYS: in-flight set. ARGV: from, to, claim key prefix.
+	pub payments_in_flight: Script,
+	/// Reads a page of the payments within a time window. Returns the number
+	/// of entries read, the score of the last one and how many entries of the
+	/// page share it, followed by `[id, group, amount, submitted_at,
+	/// processed_at, received_at, fee_rate, ...]` for the entries whose hash
+	/// exists.
+	///
+	/// KEYS: processed set. ARGV: from, to, offset, count, payment key prefix,
+	/// groups.
+	pub payments_page:      Script,
+	/// Walks a time index from a position, in either direction, until it
+	/// found `limit` payments matching the amount and processor filters or
+	/// looked at `budget` entries. Processed payments are read from their
+	/// hash, in-flight ones from the JSON held aside for them. Returns whether
+	/// entries may remain, the score of the last entry looked at and how many
+	/// of those looked at share it, followed by `[id, group, amount,
+	/// submitted_at, processed_at, received_at, fee_rate, ...]` for the
+	/// matches.
+	///
+	/// KEYS: index. ARGV: from, to, offset, limit, budget, `asc` or `desc`,
+	/// `processed` or `in_flight`, payment or in-flight payment key prefix, min
+	/// amount, max amount (empty when open), groups.
+	pub search_payments:    Script,
+	/// Folds the payments submitted up to a cutoff into buckets of every
+	/// timestamp index, per group, then deletes their hash and index entries,
+	/// moving their id to the compacted set. Ids submitted before the second
+	/// cutoff are dropped from the compacted set. Returns how many processed
+	/// set entries it read.
+	///
+	/// KEYS: received_at index, processed set, processed_at index, compacted
+	/// set. ARGV: cutoff score, limit, bucket size in nanoseconds, payment key
+	/// prefix, compacted set cutoff score, groups.
+	pub compact_payments:   Script,
+	/// Deletes the buckets lying entirely within a time window.
+	///
+	/// KEYS: bucket starts, buckets. ARGV: from, to.
+	pub purge_buckets:      Script,
+	/// Moves up to a count of the oldest queued messages to a staging list,
+	/// unless it still holds some, and returns what it holds, newest first.
+	///
+	/// KEYS: queue, staging list. ARGV: count.
+	pub take_queue:         Script,
+}
+
+static SCRIPTS: LazyLock<RedisScripts> = LazyLock::new(|| RedisScripts {
+	save_payment:       Script::new(
+		r#"
+            redis.call("HSET", KEYS[1],
+                "amount", ARGV[1],
+                "submitted_at", ARGV[2],
+                "processed_at", ARGV[3],
+                "processed_by", ARGV[4],
+                "received_at", ARGV[8])
+            if ARGV[7] ~= "" then
+                redis.call("HSET", KEYS[1], "fee_rate", ARGV[7])
+            end
+            redis.call("ZADD", KEYS[2], ARGV[6], ARGV[5])
+            redis.call("ZADD", KEYS[8], ARGV[6], ARGV[5])
+            if ARGV[9] ~= "" then
+                redis.call("ZADD", KEYS[5], ARGV[9], ARGV[5])
+                redis.call("ZADD", KEYS[7], ARGV[9], ARGV[5])
+            end
+            if ARGV[10] ~= "" then
+                redis.call("ZADD", KEYS[6], ARGV[10], ARGV[5])
+                redis.call("ZADD", KEYS[9], ARGV[10], ARGV[5])
+            end
+            redis.call("DEL", KEYS[3], KEYS[10])
+            redis.call("ZREM", KEYS[4], ARGV[5])
+            return 1
+        "#,
+	),
+	claim_payment:      Script::new(
+		r#"
+            if redis.call("ZSCORE", KEYS[1], ARGV[1]) or
+                redis.call("ZSCORE", KEYS[3], ARGV[1]) then
+                return 0
+            end
+            if redis.call("SET", KEYS[2], "1", "NX", "PX", ARGV[2]) then
+                return 1
+            end
+            return 0
+        "#,
+	),
+	requeue_payment:    Script::new(
+		r#"
+            redis.call("DEL", KEYS[3], KEYS[6])
+            redis.call("ZREM", KEYS[4], ARGV[1])
+            if redis.call("ZSCORE", KEYS[2], ARGV[1]) or
+                redis.call("ZSCORE", KEYS[5], ARGV[1]) then
+                return 0
+            end
+            redis.call("LPUSH", KEYS[1], ARGV[2])
+            return 1
+        "#,
+	),
+	payments_summary:   Script::new(
+		r#"
+            local ids = redis.call("ZRANGEBYSCORE", KEYS[1], ARGV[1], ARGV[2])
+            local total_requests = 0
+            local total_amount = 0.0
+            local fees = 0.0
+            local unrated_amount = 0.0
+
+            for i, id in ipairs(ids) do
+                local key = ARGV[3] .. ":" .. id
+                local values = redis.call("HMGET", key, "amount", "fee_rate")
+                if values[1] then
+                    local amount = tonumber(values[1])
+                    total_requests = total_requests + 1
+                    total_amount = total_amount + amount
+                    if values[2] then
+                        fees = fees + amount * tonumber(values[2])
+                    else
+                        unrated_amount = unrated_amount + amount
+                    end
+                end
+            end
+
+            local starts = redis.call("ZRANGEBYSCORE", KEYS[2], ARGV[1], ARGV[2])
+            for i, start in ipairs(starts) do
+                local bucket = redis.call("HMGET", KEYS[3], start .. ":end",
+                    start .. ":count", start .. ":amount", start .. ":fees",
+                    start .. ":unrated_amount")
+                if ARGV[2] == "+inf" or tonumber(bucket[1]) <= tonumber(ARGV[2]) then
+                    total_requests = total_requests + tonumber(bucket[2] or 0)
+                    total_amount = total_amount + tonumber(bucket[3] or 0)
+                    fees = fees + tonumber(bucket[4] or 0)
+                    unrated_amount = unrated_amount + tonumber(bucket[5] or 0)
+                end
+            end
+
+            return {tostring(total_requests), tostring(total_amount),
+                tostring(fees), tostring(unrated_amount)}
+        "#,
+	),
+	payments_breakdown: Script::new(
+		r#"
+            local entries = redis.call("ZRANGEBYSCORE", KEYS[1], ARGV[1], ARGV[2],
+                "WITHSCORES", "LIMIT", ARGV[3], ARGV[4])
+            local read = #entries / 2
+            local last_score = ""
+            local at_last_score = 0
+
+            if read > 0 then
+                last_score = entries[#entries]
+                for i = #entries, 2, -2 do
+                    if entries[i] ~= last_score then
+                        break
+                    end
+                    at_last_score = at_last_score + 1
+                end
+            end
+
+            local width = tonumber(ARGV[6])
+            local buckets, starts = {}, {}
+            local amounts, cents_seen = {}, {}
+            for i = 1, #entries, 2 do
+                local amount = redis.call("HGET", ARGV[5] .. ":" .. entries[i],
+                    "amount")
+                if amount then
+                    local score = tonumber(entries[i + 1])
+                    local start = score - score % width
+                    if start > score then
+                        start = start - width
+                    end
+                    start = string.format("%.0f", start)
+                    local cents = math.floor(tonumber(amount) * 100 + 0.5)
+
+                    if not buckets[start] then
+                        buckets[start] = {0, 0}
+                        starts[#starts + 1] = start
+                    end
+                    buckets[start][1] = buckets[start][1] + 1
+                    buckets[start][2] = buckets[start][2] + cents
+                    if not amounts[cents] then
+                        amounts[cents] = 0
+                        cents_seen[#cents_seen + 1] = cents
+                    end
+                    amounts[cents] = amounts[cents] + 1
+                end
+            end
+
+            local result = {tostring(read), last_score, tostring(at_last_score),
+                tostring(#starts)}
+            for _, start in ipairs(starts) do
+                result[#result + 1] = start
+                result[#result + 1] = string.format("%d", buckets[start][1])
+                result[#result + 1] = string.format("%d", buckets[start][2])
+            end
+            for _, cents in ipairs(cents_seen) do
+                result[#result + 1] = string.format("%d", cents)
+                result[#result + 1] = string.format("%d", amounts[cents])
+            end
+
+            return result
+        "#,
+	),
+	payments_in_flight: Script::new(
+		r#"
+            local ids = redis.call("ZRANGEBYSCORE", KEYS[1], ARGV[1], ARGV[2])
+            local pending = 0
+
+            for i, id in ipairs(ids) do
+                if redis.call("EXISTS", ARGV[3] .. ":" .. id) == 1 then
+                    pending = pending + 1
+                else
+                    redis.call("ZREM", KEYS[1], id)
+                end
+            end
+
+            return pending
+        "#,
+	),
+	payments_page:      Script::new(
+		r#"
+            local entries = redis.call("ZRANGEBYSCORE", KEYS[1], ARGV[1], ARGV[2],
+                "WITHSCORES", "LIMIT", ARGV[3], ARGV[4])
+            local read = #entries / 2
+            local last_score = ""
+            local at_last_score = 0
+            local result = {}
+
+            if read > 0 then
+                last_score = entries[#entries]
+                for i = #entries, 2, -2 do
+                    if entries[i] ~= last_score then
+                        break
+                    end
+                    at_last_score = at_last_score + 1
+                end
+            end
+            result[1] = tostring(read)
+            result[2] = last_score
+            result[3] = tostring(at_last_score)
+
+            for i = 1, #entries, 2 do
+                local id = entries[i]
+                for g = 6, #ARGV do
+                    local values = redis.call("HMGET",
+                        ARGV[5] .. ":" .. ARGV[g] .. ":" .. id,
+                        "amount", "submitted_at", "processed_at", "received_at",
+                        "requested_at", "fee_rate")
+                    if values[1] then
+                        values[2] = values[2] or values[5]
+                        values[5] = values[6]
+                        result[#result + 1] = id
+                        result[#result + 1] = ARGV[g]
+                        for v = 1, 5 do
+                            result[#result + 1] = values[v] or ""
+                        end
+                        break
+                    end
+                end
+            end
+
+            return result
+        "#,
+	),
+	search_payments:    Script::new(
+		r#"
+            local offset = tonumber(ARGV[3])
+            local limit = tonumber(ARGV[4])
+            local budget = tonumber(ARGV[5])
+            local min_amount = tonumber(ARGV[9])
+            local max_amount = tonumber(ARGV[10])
+
+            local function in_range(amount)
+                return amount and (not min_amount or amount >= min_amount)
+                    and (not max_amount or amount <= max_amount)
+            end
+
+            local function processed(id)
+                for g = 11, #ARGV do
+                    local values = redis.call("HMGET",
+                        ARGV[8] .. ":" .. ARGV[g] .. ":" .. id,
+                        "amount", "submitted_at", "processed_at", "received_at",
+                        "requested_at", "fee_rate")
+                    if values[1] then
+                        if not in_range(tonumber(values[1])) then
+                            return nil
+                        end
+                        return {id, ARGV[g], values[1],
+                            values[2] or values[5] or "", values[3] or "",
+                            values[4] or "", values[6] or ""}
+                    end
+                end
+                return nil
+            end
+
+            local function in_flight(id)
+                local value = redis.call("GET", ARGV[8] .. ":" .. id)
+                if not value then
+                    return nil
+                end
+                local ok, payment = pcall(cjson.decode, value)
+                if not ok or type(payment) ~= "table"
+                    or not in_range(tonumber(payment.amount)) then
+                    return nil
+                end
+                for g = 11, #ARGV do
+                    if payment.processed_by == ARGV[g] then
+                        return {id, ARGV[g], tostring(payment.amount),
+                            payment.submittedAt or "", "",
+                            payment.receivedAt or "", ""}
+                    end
+                end
+                return nil
+            end
+
+            local command, first, last = "ZRANGEBYSCORE", ARGV[1], ARGV[2]
+            if ARGV[6] == "desc" then
+                command, first, last = "ZREVRANGEBYSCORE", ARGV[2], ARGV[1]
+            end
+            local lookup = processed
+            if ARGV[7] == "in_flight" then
+                lookup = in_flight
+            end
+
+            local result = {"1", "", "0"}
+            local scanned, matched, at_last_score = 0, 0, 0
+            while matched < limit and scanned < budget do
+                local batch = math.min(budget - scanned, 100)
+                local entries = redis.call(command, KEYS[1], first, last,
+                    "WITHSCORES", "LIMIT", offset + scanned, batch)
+                local taken = 0
+                for i = 1, #entries, 2 do
+                    taken = taken + 1
+                    if entries[i + 1] == result[2] then
+                        at_last_score = at_last_score + 1
+                    else
+                        result[2] = entries[i + 1]
+                        at_last_score = 1
+                    end
+                    local entry = lookup(entries[i])
+                    if entry then
+                        matched = matched + 1
+                        for _, value in ipairs(entry) do
+                            result[#result + 1] = value
+                        end
+                        if matched == limit then
+                            break
+                        end
+                    end
+                end
+                scanned = scanned + taken
+                if #entries < batch * 2 and taken * 2 == #entries then
+                    result[1] = "0"
+                    break
+                end
+            end
+            result[3] = tostring(at_last_score)
+
+            return result
+        "#,
+	),
+	compact_payments:   Script::new(
+		r#"
+            local ids = redis.call("ZRANGEBYSCORE", KEYS[2], "-inf", ARGV[1],
+                "LIMIT", 0, ARGV[2])
+            local size = tonumber(ARGV[3])
+
+            for _, id in ipairs(ids) do
+                redis.call("ZADD", KEYS[4], redis.call("ZSCORE", KEYS[2], id), id)
+                for g = 6, #ARGV do
+                    local key = ARGV[4] .. ":" .. ARGV[g] .. ":" .. id
+                    local payment = redis.call("HMGET", key, "amount", "fee_rate")
+                    if payment[1] then
+                        local amount = tonumber(payment[1])
+                        for k = 1, 3 do
+                            local score = redis.call("ZSCORE", KEYS[k], id)
+                            if score then
+                                score = tonumber(score)
+                                local first = score - math.fmod(score, size)
+                                local start = string.format("%.0f", first)
+                                local buckets = KEYS[k] .. ":buckets:" .. ARGV[g]
+                                redis.call("HINCRBY", buckets, start .. ":count", 1)
+                                redis.call("HINCRBYFLOAT", buckets,
+                                    start .. ":amount", payment[1])
+                                if payment[2] then
+                                    redis.call("HINCRBYFLOAT", buckets,
+                                        start .. ":fees",
+                                        tostring(amount * tonumber(payment[2])))
+                                else
+                                    redis.call("HINCRBYFLOAT", buckets,
+                                        start .. ":unrated_amount", payment[1])
+                                end
+                                redis.call("HSET", buckets, start .. ":end",
+                                    string.format("%.0f", first + size - 1))
+                                redis.call("ZADD",
+                                    KEYS[k] .. ":bucket_starts:" .. ARGV[g],
+                                    start, start)
+                            end
+                            redis.call("ZREM",
+                                KEYS[k] .. ":processor:" .. ARGV[g], id)
+                        end
+                        redis.call("DEL", key)
+                        break
+                    end
+                end
+                for k = 1, 3 do
+                    redis.call("ZREM", KEYS[k], id)
+                end
+            end
+            redis.call("ZREMRANGEBYSCORE", KEYS[4], "-inf", "(" .. ARGV[5])
+
+            return #ids
+        "#,
+	),
+	purge_buckets:      Script::new(
+		r#"
+            local starts = redis.call("ZRANGEBYSCORE", KEYS[1], ARGV[1], ARGV[2])
+            local purged = 0
+
+            for _, start in ipairs(starts) do
+                local last = redis.call("HGET", KEYS[2], start .. ":end")
+                if ARGV[2] == "+inf" or tonumber(last) <= tonumber(ARGV[2]) then
+                    redis.call("HDEL", KEYS[2], start .. ":end",
+                        start .. ":count", start .. ":amount", start .. ":fees",
+                        start .. ":unrated_amount")
+                    redis.call("ZREM", KEYS[1], start)
+                    purged = purged + 1
+                end
+            end
+
+            return purged
+        "#,
+	),
+	take_queue:         Script::new(
+		r#"
+            if redis.call("LLEN", KEYS[2]) == 0 then
+                for i = 1, tonumber(ARGV[1]) do
+                    if not redis.call("LMOVE", KEYS[1], KEYS[2], "RIGHT", "LEFT") then
+                        break
+                    end
+                end
+            end
+
+            return redis.call("LRANGE", KEYS[2], 0, -1)
+        "#,
+	),
+});
+
+impl RedisScripts {
+	pub fn get() -> &'static Self {
+		&SCRIPTS
+	}
+
+	/// Loads every script up front, so the first calls do not each pay for a
+	/// `NOSCRIPT` round trip. In a cluster `SCRIPT LOAD` reaches every master.
+	pub async fn load<C: ConnectionLike>(con: &mut C) -> RedisResult<()> {
+		let scripts = Self::get();
+		for script in [
+			&scripts.save_payment,
+			&scripts.claim_payment,
+			&scripts.requeue_payment,
+			&scripts.payments_summary,
+			&scripts.payments_breakdown,
+			&scripts.payments_in_flight,
+			&scripts.payments_page,
+			&scripts.search_payments,
+			&scripts.compact_payments,
+			&scripts.purge_buckets,
+			&scripts.take_queue,
+		] {
+			script.load_async(con).await?;
+		}
+
+		info!("Loaded Redis scripts");
+		Ok(())
+	}
+}
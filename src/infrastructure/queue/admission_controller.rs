@@ -0,0 +1,129 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::domain::payment_producer::PaymentProducerError;
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BackpressureMode {
+	/// Wait for a free slot in the buffer for as long as it takes.
+	Block,
+	/// Reject as soon as the buffer reaches the high-water mark.
+	FailFast,
+	/// Wait up to `AdmissionOptions::max_wait` for a free slot.
+	BoundedWait,
+}
+
+#[derive(Debug, Clone)]
+pub struct AdmissionOptions {
+	pub mode:             BackpressureMode,
+	pub high_water_mark:  usize,
+	pub max_wait:         Duration,
+	pub max_queue_length: Option<usize>,
+	pub retry_after:      Duration,
+}
+
+/// Decides whether a new payment may enter the ingress buffer, based on the
+/// buffer occupancy and the last observed length of the Redis payments queue.
+#[derive(Clone)]
+pub struct AdmissionController {
+	options:      AdmissionOptions,
+	queue_length: Arc<AtomicUsize>,
+}
+
+impl AdmissionController {
+	pub fn new(options: AdmissionOptions) -> Self {
+		Self {
+			options,
+			queue_length: Arc::new(AtomicUsize::new(0)),
+		}
+	}
+
+	pub fn mode(&self) -> BackpressureMode {
+		self.options.mode
+	}
+
+	pub fn max_wait(&self) -> Duration {
+		self.options.max_wait
+	}
+
+	pub fn tracks_queue_length(&self) -> bool {
+		self.options.max_queue_length.is_some()
+	}
+
+	pub fn record_queue_length(&self, queue_length: usize) {
+		self.queue_length.store(queue_length, Ordering::Relaxed);
+	}
+
+	pub fn admit(&self, buffered: usize) -> Result<(), PaymentProducerError> {
+		let buffer_saturated = self.options.mode == BackpressureMode::FailFast &&
+			buffered >= self.options.high_water_mark;
+		let queue_saturated = self.options.max_queue_length.is_some_and(|max| {
+			self.queue_length.load(Ordering::Relaxed) + buffered >= max
+		});
+
+		if buffer_saturated || queue_saturated {
+			return Err(self.overloaded());
+		}
+
+		Ok(())
+	}
+
+	pub fn overloaded(&self) -> PaymentProducerError {
+		PaymentProducerError::Overloaded {
+			retry_after: self.options.retry_after,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn options(mode: BackpressureMode) -> AdmissionOptions {
+		AdmissionOptions {
+			mode,
+			high_water_mark: 10,
+			max_wait: Duration::from_millis(50),
+			max_queue_length: None,
+			retry_after: Duration::from_secs(2),
+		}
+	}
+
+	#[test]
+	fn test_fail_fast_rejects_above_high_water_mark() {
+		let controller =
+			AdmissionController::new(options(BackpressureMode::FailFast));
+
+		assert!(controller.admit(9).is_ok());
+
+		let PaymentProducerError::Overloaded { retry_after } =
+			controller.admit(10).unwrap_err();
+		assert_eq!(retry_after, Duration::from_secs(2));
+	}
+
+	#[test]
+	fn test_block_ignores_high_water_mark() {
+		let controller = AdmissionController::new(options(BackpressureMode::Block));
+
+		assert!(controller.admit(1_000).is_ok());
+	}
+
+	#[test]
+	fn test_rejects_when_redis_queue_is_too_long() {
+		let controller = AdmissionController::new(AdmissionOptions {
+			max_queue_length: Some(100),
+			..options(BackpressureMode::BoundedWait)
+		});
+
+		controller.record_queue_length(95);
+		assert!(controller.admit(4).is_ok());
+		assert!(controller.admit(5).is_err());
+
+		controller.record_queue_length(0);
+		assert!(controller.admit(5).is_ok());
+	}
+}
@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+
+use crate::domain::payment::Payment;
+use crate::domain::queue::TraceContext;
+use crate::infrastructure::wal::record::WalRecord;
+
+/// A payment travelling through the in-process MPSC buffer, tagged with its
+/// write-ahead log sequence when the log is enabled and with the trace context
+/// of the request that accepted it.
+#[derive(Debug, Clone)]
+pub struct BufferedPayment {
+	pub payment:       Payment,
+	pub wal_sequence:  Option<u64>,
+	pub trace_context: TraceContext,
+}
+
+impl BufferedPayment {
+	pub fn new(payment: Payment, wal_sequence: Option<u64>) -> Self {
+		Self {
+			payment,
+			wal_sequence,
+			trace_context: TraceContext::default(),
+		}
+	}
+
+	pub fn with_trace_context(mut self, trace_context: TraceContext) -> Self {
+		self.trace_context = trace_context;
+		self
+	}
+}
+
+/// What the write-ahead log keeps of a buffered payment.
+#[derive(Deserialize, Serialize)]
+struct LoggedPayment {
+	payment:       Payment,
+	trace_context: TraceContext,
+}
+
+impl BufferedPayment {
+	/// The payload logged for `payment` before it is buffered.
+	pub fn wal_payload(
+		payment: &Payment,
+		trace_context: &TraceContext,
+	) -> Result<Vec<u8>, rmp_serde::encode::Error> {
+		rmp_serde::to_vec_named(&LoggedPayment {
+			payment:       payment.clone(),
+			trace_context: trace_context.clone(),
+		})
+	}
+
+	/// Rebuilds a payment logged by `wal_payload`, or by earlier versions that
+	/// logged the bare payment.
+	pub fn from_wal_record(
+		record: &WalRecord,
+	) -> Result<Self, rmp_serde::decode::Error> {
+		let logged = rmp_serde::from_slice::<LoggedPayment>(&record.payload)
+			.or_else(|_| {
+				rmp_serde::from_slice::<Payment>(&record.payload).map(|payment| {
+					LoggedPayment {
+						payment,
+						trace_context: TraceContext::default(),
+					}
+				})
+			})?;
+
+		Ok(Self::new(logged.payment, Some(record.sequence))
+			.with_trace_context(logged.trace_context))
+	}
+}
+
+impl From<Payment> for BufferedPayment {
+	fn from(payment: Payment) -> Self {
+		Self::new(payment, None)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+
+	use uuid::Uuid;
+
+	use super::*;
+
+	fn payment() -> Payment {
+		Payment {
+			correlation_id: Uuid::new_v4(),
+			amount:         19.9,
+			submitted_at:   None,
+			processed_at:   None,
+			processed_by:   None,
+			received_at:    None,
+		}
+	}
+
+	#[test]
+	fn test_wal_payload_keeps_the_trace_context() {
+		let payment = payment();
+		let trace_context = TraceContext(HashMap::from([(
+			"traceparent".to_string(),
+			"00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".to_string(),
+		)]));
+		let payload =
+			BufferedPayment::wal_payload(&payment, &trace_context).unwrap();
+
+		let buffered =
+			BufferedPayment::from_wal_record(&WalRecord::new(7, payload)).unwrap();
+
+		assert_eq!(buffered.payment.correlation_id, payment.correlation_id);
+		assert_eq!(buffered.wal_sequence, Some(7));
+		assert_eq!(buffered.trace_context, trace_context);
+	}
+
+	#[test]
+	fn test_bare_payments_logged_earlier_are_still_read() {
+		let payment = payment();
+		let payload = rmp_serde::to_vec_named(&payment).unwrap();
+
+		let buffered =
+			BufferedPayment::from_wal_record(&WalRecord::new(3, payload)).unwrap();
+
+		assert_eq!(buffered.payment.correlation_id, payment.correlation_id);
+		assert_eq!(buffered.trace_context, TraceContext::default());
+	}
+}
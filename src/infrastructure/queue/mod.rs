@@ -1,2 +1,4 @@
+pub mod admission_controller;
+pub mod buffered_payment;
 pub mod mpsc_payment_producer;
 pub mod redis_payment_queue;
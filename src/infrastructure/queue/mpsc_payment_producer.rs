@@ -1,17 +1,109 @@
 use async_trait::async_trait;
 use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::{SendTimeoutError, TrySendError};
+use tracing::{Span, error};
 
 use crate::domain::payment::Payment;
 use crate::domain::payment_producer::PaymentProducer;
+use crate::domain::queue::TraceContext;
+use crate::infrastructure::queue::admission_controller::{
+	AdmissionController, BackpressureMode,
+};
+use crate::infrastructure::queue::buffered_payment::BufferedPayment;
+use crate::infrastructure::telemetry::propagation::trace_context_of;
+use crate::infrastructure::wal::write_ahead_log::WriteAheadLog;
 
 #[derive(Clone)]
 pub struct MpscPaymentProducer {
-	sender: mpsc::Sender<Payment>,
+	sender:               mpsc::Sender<BufferedPayment>,
+	write_ahead_log:      Option<WriteAheadLog>,
+	admission_controller: Option<AdmissionController>,
 }
 
 impl MpscPaymentProducer {
-	pub fn new(sender: mpsc::Sender<Payment>) -> Self {
-		Self { sender }
+	pub fn new(sender: mpsc::Sender<BufferedPayment>) -> Self {
+		Self {
+			sender,
+			write_ahead_log: None,
+			admission_controller: None,
+		}
+	}
+
+	pub fn with_write_ahead_log(mut self, write_ahead_log: WriteAheadLog) -> Self {
+		self.write_ahead_log = Some(write_ahead_log);
+		self
+	}
+
+	pub fn with_admission_controller(
+		mut self,
+		admission_controller: AdmissionController,
+	) -> Self {
+		self.admission_controller = Some(admission_controller);
+		self
+	}
+
+	pub fn buffered(&self) -> usize {
+		self.sender.max_capacity() - self.sender.capacity()
+	}
+
+	async fn dispatch(
+		&self,
+		buffered_payment: BufferedPayment,
+	) -> Result<(), Box<dyn std::error::Error + Send>> {
+		let Some(admission_controller) = &self.admission_controller else {
+			return self
+				.sender
+				.send(buffered_payment)
+				.await
+				.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>);
+		};
+
+		match admission_controller.mode() {
+			BackpressureMode::Block => self
+				.sender
+				.send(buffered_payment)
+				.await
+				.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>),
+			BackpressureMode::FailFast => {
+				match self.sender.try_send(buffered_payment) {
+					Ok(()) => Ok(()),
+					Err(TrySendError::Full(_)) => {
+						Err(Box::new(admission_controller.overloaded()))
+					}
+					Err(e) => Err(Box::new(e)),
+				}
+			}
+			BackpressureMode::BoundedWait => match self
+				.sender
+				.send_timeout(buffered_payment, admission_controller.max_wait())
+				.await
+			{
+				Ok(()) => Ok(()),
+				Err(SendTimeoutError::Timeout(_)) => {
+					Err(Box::new(admission_controller.overloaded()))
+				}
+				Err(e) => Err(Box::new(e)),
+			},
+		}
+	}
+
+	async fn append_to_write_ahead_log(
+		&self,
+		payment: &Payment,
+		trace_context: &TraceContext,
+	) -> Result<Option<u64>, Box<dyn std::error::Error + Send>> {
+		let Some(write_ahead_log) = &self.write_ahead_log else {
+			return Ok(None);
+		};
+
+		let payload = BufferedPayment::wal_payload(payment, trace_context)
+			.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+
+		write_ahead_log
+			.submit(payload)
+			.await
+			.map(Some)
+			.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)
 	}
 }
 
@@ -21,10 +113,33 @@ impl PaymentProducer for MpscPaymentProducer {
 		&self,
 		payment: Payment,
 	) -> Result<(), Box<dyn std::error::Error + Send>> {
-		self.sender
-			.send(payment)
-			.await
-			.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+		if let Some(admission_controller) = &self.admission_controller {
+			admission_controller
+				.admit(self.buffered())
+				.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+		}
+
+		let trace_context = trace_context_of(&Span::current());
+		let wal_sequence = self
+			.append_to_write_ahead_log(&payment, &trace_context)
+			.await?;
+		let buffered_payment = BufferedPayment::new(payment, wal_sequence)
+			.with_trace_context(trace_context);
+
+		if let Err(e) = self.dispatch(buffered_payment).await {
+			// The client is told the payment failed, so it must not be replayed.
+			if let (Some(write_ahead_log), Some(sequence)) =
+				(&self.write_ahead_log, wal_sequence) &&
+				let Err(wal_error) = write_ahead_log.commit(&[sequence])
+			{
+				error!(
+					"Failed to discard payment from write-ahead log: {wal_error}"
+				);
+			}
+
+			return Err(e);
+		}
+
 		Ok(())
 	}
 }
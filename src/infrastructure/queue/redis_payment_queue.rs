@@ -1,20 +1,47 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use redis::AsyncCommands;
+use redis::{AsyncCommands, Direction};
+use tracing::{error, info};
 
 use crate::domain::payment::Payment;
 use crate::domain::queue::{Message, Queue};
-use crate::infrastructure::config::redis::{PAYMENTS_QUEUE_KEY, Redis};
+use crate::infrastructure::config::redis::{Redis, RedisKeys};
+use crate::infrastructure::persistence::redis_scripts::RedisScripts;
 
+/// Messages are stored as msgpack maps, since `Payment` skips its unset
+/// timestamps and an array would shift the ones after them. Arrays queued by
+/// older versions still decode.
 #[derive(Clone)]
 pub struct PaymentQueue {
 	redis: Arc<Redis>,
+	keys:  RedisKeys,
 }
 
 impl PaymentQueue {
 	pub fn new(redis: Arc<Redis>) -> Self {
-		Self { redis }
+		Self {
+			redis,
+			keys: RedisKeys::default(),
+		}
+	}
+
+	pub fn with_keys(mut self, keys: RedisKeys) -> Self {
+		self.keys = keys;
+		self
+	}
+
+	/// Keeps messages that no longer decode for inspection instead of
+	/// dropping them.
+	async fn dead_letter(
+		&self,
+		messages: &[Vec<u8>],
+	) -> Result<(), Box<dyn std::error::Error + Send>> {
+		let mut con = self.redis.connection.as_ref().clone();
+
+		con.lpush(self.keys.dead_letter_queue(), messages)
+			.await
+			.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)
 	}
 }
 
@@ -26,7 +53,7 @@ impl Queue<Payment> for PaymentQueue {
 		let mut con = self.redis.connection.as_ref().clone();
 
 		let popped_value: Option<(String, Vec<u8>)> = con
-			.brpop(PAYMENTS_QUEUE_KEY, 1.0)
+			.brpop(self.keys.payments_queue(), 1.0)
 			.await
 			.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
 
@@ -37,10 +64,57 @@ impl Queue<Payment> for PaymentQueue {
 				return Ok(None);
 			};
 
-		let message: Message<Payment> = rmp_serde::from_slice(&message_bytes)
+		match rmp_serde::from_slice(&message_bytes) {
+			Ok(message) => Ok(Some(message)),
+			Err(e) => {
+				self.dead_letter(&[message_bytes]).await?;
+				Err(Box::new(e) as Box<dyn std::error::Error + Send>)
+			}
+		}
+	}
+
+	async fn pop_batch(
+		&self,
+		count: usize,
+	) -> Result<Vec<Message<Payment>>, Box<dyn std::error::Error + Send>> {
+		let mut con = self.redis.connection.as_ref().clone();
+
+		// Popping from the right keeps the FIFO order of the LPUSH producers.
+		let popped_values: Option<(String, Vec<Vec<u8>>)> = con
+			.blmpop(
+				1.0,
+				1,
+				self.keys.payments_queue(),
+				Direction::Right,
+				count.max(1),
+			)
+			.await
 			.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
 
-		Ok(Some(message))
+		let Some((_queue_name, serialized_messages)) = popped_values else {
+			return Ok(Vec::new());
+		};
+
+		// The batch is already off the queue, so one unreadable message must
+		// not take the rest of it down.
+		let mut messages = Vec::with_capacity(serialized_messages.len());
+		let mut unreadable = Vec::new();
+		for message_bytes in serialized_messages {
+			match rmp_serde::from_slice(&message_bytes) {
+				Ok(message) => messages.push(message),
+				Err(e) => {
+					error!("Moving unreadable message to the dead letters: {e}");
+					unreadable.push(message_bytes);
+				}
+			}
+		}
+		if !unreadable.is_empty() &&
+			let Err(e) = self.dead_letter(&unreadable).await
+		{
+			error!("Failed to keep unreadable messages: {e}");
+		}
+
+		Ok(messages)
 	}
 
 	async fn push(
@@ -48,13 +122,76 @@ impl Queue<Payment> for PaymentQueue {
 		message: Message<Payment>,
 	) -> Result<(), Box<dyn std::error::Error + Send>> {
 		let mut con = self.redis.connection.as_ref().clone();
-		let serialized_message = rmp_serde::to_vec(&message)
+		let serialized_message = rmp_serde::to_vec_named(&message)
 			.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
 
 		let _: () = con
-			.lpush(PAYMENTS_QUEUE_KEY, serialized_message)
+			.lpush(self.keys.payments_queue(), serialized_message)
+			.await
+			.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+		Ok(())
+	}
+
+	async fn push_batch(
+		&self,
+		messages: Vec<Message<Payment>>,
+	) -> Result<(), Box<dyn std::error::Error + Send>> {
+		if messages.is_empty() {
+			return Ok(());
+		}
+
+		let mut con = self.redis.connection.as_ref().clone();
+		let serialized_messages = messages
+			.iter()
+			.map(rmp_serde::to_vec_named)
+			.collect::<Result<Vec<_>, _>>()
+			.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+
+		// A single multi-value LPUSH keeps FIFO order for BRPOP consumers.
+		let _: () = con
+			.lpush(self.keys.payments_queue(), serialized_messages)
+			.await
+			.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+		Ok(())
+	}
+
+	async fn length(&self) -> Result<usize, Box<dyn std::error::Error + Send>> {
+		let mut con = self.redis.connection.as_ref().clone();
+
+		con.llen(self.keys.payments_queue())
+			.await
+			.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)
+	}
+
+	/// Releases the payment's claim and pushes it back in one step, skipping
+	/// payments another worker processed, or that got compacted, meanwhile.
+	async fn requeue(
+		&self,
+		message: Message<Payment>,
+	) -> Result<(), Box<dyn std::error::Error + Send>> {
+		let mut con = self.redis.connection.as_ref().clone();
+		let payment_id = message.body.correlation_id.to_string();
+		let serialized_message = rmp_serde::to_vec_named(&message)
+			.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+
+		let requeued: bool = RedisScripts::get()
+			.requeue_payment
+			.key(self.keys.payments_queue())
+			.key(self.keys.processed_payments())
+			.key(self.keys.payment_claim(&payment_id))
+			.key(self.keys.payments_in_flight())
+			.key(self.keys.compacted_payments())
+			.key(self.keys.in_flight_payment(&payment_id))
+			.arg(&payment_id)
+			.arg(serialized_message)
+			.invoke_async(&mut con)
 			.await
 			.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+		if !requeued {
+			info!(
+				"Payment {payment_id} was processed meanwhile, not re-queueing it"
+			);
+		}
 		Ok(())
 	}
 }
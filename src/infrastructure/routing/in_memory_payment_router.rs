@@ -9,12 +9,77 @@ use crate::domain::payment_processor::{PaymentProcessor, PaymentProcessorKey};
 use crate::domain::payment_router::PaymentRouter;
 use crate::use_cases::process_payment::PaymentProcessingError;
 
+type PaymentBreaker = CircuitBreaker<DefaultPolicy, PaymentProcessingError>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BreakerOptions {
+	pub failure_threshold: f64,
+	pub min_throughput:    u64,
+	pub probe_interval:    u32,
+	pub cooldown:          Duration,
+}
+
+impl BreakerOptions {
+	fn build(&self) -> PaymentBreaker {
+		PaymentBreaker::builder()
+			.failure_threshold(self.failure_threshold)
+			.min_throughput(self.min_throughput)
+			.probe_interval(self.probe_interval)
+			.cooldown(self.cooldown)
+			.build()
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutingOptions {
+	/// Processors reporting a `minResponseTime` at or above this many
+	/// milliseconds are skipped.
+	pub max_response_time: u64,
+	pub default_breaker:   BreakerOptions,
+	pub fallback_breaker:  BreakerOptions,
+}
+
+impl Default for RoutingOptions {
+	fn default() -> Self {
+		Self {
+			max_response_time: 100,
+			default_breaker:   BreakerOptions {
+				failure_threshold: 0.5,
+				min_throughput:    5,
+				probe_interval:    10,
+				cooldown:          Duration::from_secs(3),
+			},
+			fallback_breaker:  BreakerOptions {
+				failure_threshold: 0.1,
+				min_throughput:    10,
+				probe_interval:    5,
+				cooldown:          Duration::from_secs(10),
+			},
+		}
+	}
+}
+
+struct RoutingTable {
+	options:          RoutingOptions,
+	default_breaker:  PaymentBreaker,
+	fallback_breaker: PaymentBreaker,
+}
+
+impl RoutingTable {
+	fn new(options: RoutingOptions) -> Self {
+		Self {
+			default_breaker: options.default_breaker.build(),
+			fallback_breaker: options.fallback_breaker.build(),
+			options,
+		}
+	}
+}
+
 #[derive(Clone)]
 pub struct InMemoryPaymentRouter {
 	pub default_processor:  Arc<RwLock<PaymentProcessor>>,
 	pub fallback_processor: Arc<RwLock<PaymentProcessor>>,
-	pub default_breaker:    CircuitBreaker<DefaultPolicy, PaymentProcessingError>,
-	pub fallback_breaker:   CircuitBreaker<DefaultPolicy, PaymentProcessingError>,
+	routing:                Arc<RwLock<RoutingTable>>,
 }
 
 impl InMemoryPaymentRouter {
@@ -33,25 +98,74 @@ impl InMemoryPaymentRouter {
 				health:            HealthStatus::Failing,
 				min_response_time: 0,
 			})),
-			default_breaker:    CircuitBreaker::<
-				DefaultPolicy,
-				PaymentProcessingError,
-			>::builder()
-			.failure_threshold(0.5)
-			.min_throughput(5)
-			.probe_interval(10)
-			.cooldown(Duration::from_secs(3))
-			.build(),
-			fallback_breaker:   CircuitBreaker::<
-				DefaultPolicy,
-				PaymentProcessingError,
-			>::builder()
-			.failure_threshold(0.1)
-			.cooldown(Duration::from_secs(10))
-			.build(),
+			routing:            Arc::new(RwLock::new(RoutingTable::new(
+				RoutingOptions::default(),
+			))),
 		}
 	}
 
+	pub fn with_routing_options(self, options: RoutingOptions) -> Self {
+		*self.routing.write().unwrap() = RoutingTable::new(options);
+		self
+	}
+
+	pub fn routing_options(&self) -> RoutingOptions {
+		self.routing.read().unwrap().options.clone()
+	}
+
+	pub fn default_breaker(&self) -> PaymentBreaker {
+		self.routing.read().unwrap().default_breaker.clone()
+	}
+
+	pub fn fallback_breaker(&self) -> PaymentBreaker {
+		self.routing.read().unwrap().fallback_breaker.clone()
+	}
+
+	pub fn processor_keys(&self) -> [Arc<PaymentProcessorKey>; 2] {
+		[
+			Arc::clone(&self.default_processor.read().unwrap().key),
+			Arc::clone(&self.fallback_processor.read().unwrap().key),
+		]
+	}
+
+	/// Swaps processor URLs and routing options in one step, so no payment is
+	/// routed with half of the new configuration.
+	///
+	/// A processor whose URL changed is marked failing until its next health
+	/// check, and a breaker is only rebuilt, losing its state, when its own
+	/// options changed.
+	pub fn reconfigure(
+		&self,
+		default_key: Arc<PaymentProcessorKey>,
+		fallback_key: Arc<PaymentProcessorKey>,
+		options: RoutingOptions,
+	) {
+		let mut routing = self.routing.write().unwrap();
+		let mut default_processor = self.default_processor.write().unwrap();
+		let mut fallback_processor = self.fallback_processor.write().unwrap();
+
+		for (processor, key) in [
+			(&mut *default_processor, default_key),
+			(&mut *fallback_processor, fallback_key),
+		] {
+			if processor.key != key {
+				*processor = PaymentProcessor {
+					key,
+					health: HealthStatus::Failing,
+					min_response_time: 0,
+				};
+			}
+		}
+
+		if routing.options.default_breaker != options.default_breaker {
+			routing.default_breaker = options.default_breaker.build();
+		}
+		if routing.options.fallback_breaker != options.fallback_breaker {
+			routing.fallback_breaker = options.fallback_breaker.build();
+		}
+		routing.options = options;
+	}
+
 	pub fn update_processor_health(&self, processor: PaymentProcessor) {
 		match processor.key.name {
 			"default" => {
@@ -64,12 +178,28 @@ impl InMemoryPaymentRouter {
 		}
 	}
 
+	/// Like `update_processor_health`, but drops results for a URL the
+	/// processor was reconfigured away from while the check ran.
+	pub fn record_health_check(&self, processor: PaymentProcessor) {
+		let current = match processor.key.name {
+			"default" => &self.default_processor,
+			"fallback" => &self.fallback_processor,
+			_ => return,
+		};
+
+		let mut current = current.write().unwrap();
+		if current.key == processor.key {
+			*current = processor;
+		}
+	}
+
 	fn can_process_payments(
 		payment_processor: &RwLockReadGuard<PaymentProcessor>,
-		circuit_breaker: &CircuitBreaker<DefaultPolicy, PaymentProcessingError>,
+		circuit_breaker: &PaymentBreaker,
+		max_response_time: u64,
 	) -> bool {
 		payment_processor.health.is_healthy() &&
-			payment_processor.min_response_time < 100 &&
+			payment_processor.min_response_time < max_response_time &&
 			!matches!(circuit_breaker.current_state(), State::Open)
 	}
 }
@@ -87,23 +217,32 @@ impl Default for InMemoryPaymentRouter {
 impl PaymentRouter for InMemoryPaymentRouter {
 	async fn get_processor_for_payment(
 		&self,
-	) -> Option<(
-		Arc<PaymentProcessorKey>,
-		CircuitBreaker<DefaultPolicy, PaymentProcessingError>,
-	)> {
+	) -> Option<(Arc<PaymentProcessorKey>, PaymentBreaker)> {
+		// Locks are taken in the same order as `reconfigure`.
+		let routing = self.routing.read().unwrap();
+		let max_response_time = routing.options.max_response_time;
+
 		let default_processor = self.default_processor.read().unwrap();
-		if Self::can_process_payments(&default_processor, &self.default_breaker) {
+		if Self::can_process_payments(
+			&default_processor,
+			&routing.default_breaker,
+			max_response_time,
+		) {
 			return Some((
 				default_processor.key.clone(),
-				self.default_breaker.clone(),
+				routing.default_breaker.clone(),
 			));
 		}
 
 		let fallback_processor = self.fallback_processor.read().unwrap();
-		if Self::can_process_payments(&fallback_processor, &self.fallback_breaker) {
+		if Self::can_process_payments(
+			&fallback_processor,
+			&routing.fallback_breaker,
+			max_response_time,
+		) {
 			return Some((
 				fallback_processor.key.clone(),
-				self.fallback_breaker.clone(),
+				routing.fallback_breaker.clone(),
 			));
 		}
 
@@ -122,7 +261,9 @@ mod tests {
 		PaymentProcessor, PaymentProcessorKey,
 	};
 	use rinha_de_backend::domain::payment_router::PaymentRouter;
-	use rinha_de_backend::infrastructure::routing::in_memory_payment_router::InMemoryPaymentRouter;
+	use rinha_de_backend::infrastructure::routing::in_memory_payment_router::{
+		InMemoryPaymentRouter, RoutingOptions,
+	};
 
 	#[tokio::test]
 	async fn test_get_processor_for_payment_default_healthy() {
@@ -208,7 +349,7 @@ mod tests {
 			health:            HealthStatus::Healthy,
 			min_response_time: 50,
 		});
-		router.default_breaker.force_open();
+		router.default_breaker().force_open();
 
 		// Fallback is healthy
 		let fallback_processor = PaymentProcessor {
@@ -240,7 +381,7 @@ mod tests {
 			health:            HealthStatus::Healthy,
 			min_response_time: 50,
 		});
-		router.default_breaker.force_open();
+		router.default_breaker().force_open();
 
 		// Fallback is unhealthy
 		router.update_processor_health(PaymentProcessor {
@@ -281,4 +422,57 @@ mod tests {
 		assert_eq!(default_processor.key.name, "default");
 		assert_eq!(default_processor.key.url, "http://test.com");
 	}
+
+	#[tokio::test]
+	async fn test_routing_options_set_response_time_cutoff() {
+		let router =
+			InMemoryPaymentRouter::default().with_routing_options(RoutingOptions {
+				max_response_time: 200,
+				..RoutingOptions::default()
+			});
+		router.update_processor_health(PaymentProcessor {
+			key:               Arc::new(PaymentProcessorKey::new(
+				"default",
+				"http://default.com".into(),
+			)),
+			health:            HealthStatus::Healthy,
+			min_response_time: 150,
+		});
+
+		let (key, _) = router.get_processor_for_payment().await.unwrap();
+		assert_eq!(key.name, "default");
+	}
+
+	#[tokio::test]
+	async fn test_reconfigure_marks_moved_processor_failing() {
+		let router = InMemoryPaymentRouter::default();
+		let old_key = Arc::new(PaymentProcessorKey::new(
+			"default",
+			"http://default.com".into(),
+		));
+		router.update_processor_health(PaymentProcessor {
+			key:               Arc::clone(&old_key),
+			health:            HealthStatus::Healthy,
+			min_response_time: 50,
+		});
+
+		router.reconfigure(
+			Arc::new(PaymentProcessorKey::new(
+				"default",
+				"http://default-2.com".into(),
+			)),
+			router.processor_keys()[1].clone(),
+			RoutingOptions::default(),
+		);
+		// A check that started before the reload must not restore the old URL.
+		router.record_health_check(PaymentProcessor {
+			key:               old_key,
+			health:            HealthStatus::Healthy,
+			min_response_time: 50,
+		});
+
+		let default_processor = router.default_processor.read().unwrap();
+		assert_eq!(default_processor.key.url, "http://default-2.com");
+		assert_eq!(default_processor.health, HealthStatus::Failing);
+	}
 }
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::infrastructure::metrics::registry::MetricsSource;
+
+/// Weight of the newest sample in the latency moving average.
+const LATENCY_SMOOTHING: f64 = 0.2;
+
+#[derive(Debug, Clone)]
+pub struct ConcurrencyOptions {
+	pub min_permits:       usize,
+	pub max_permits:       usize,
+	/// Requests per second each processor is expected to sustain.
+	pub target_throughput: f64,
+}
+
+struct ProcessorPermits {
+	semaphore: Arc<Semaphore>,
+	state:     Mutex<LimitState>,
+}
+
+struct LimitState {
+	latency: Option<Duration>,
+	permits: usize,
+}
+
+/// Bounds the number of in-flight requests to each payment processor.
+///
+/// Every processor gets its own semaphore, resized after each request to
+/// `target_throughput * latency` permits (Little's law), where `latency` is a
+/// moving average of the observed response times.
+#[derive(Clone)]
+pub struct ProcessorConcurrencyLimiter {
+	options:    ConcurrencyOptions,
+	processors: Arc<RwLock<HashMap<&'static str, Arc<ProcessorPermits>>>>,
+}
+
+impl ProcessorConcurrencyLimiter {
+	pub fn new(options: ConcurrencyOptions) -> Self {
+		Self {
+			options,
+			processors: Arc::new(RwLock::new(HashMap::new())),
+		}
+	}
+
+	pub async fn acquire(&self, processor: &'static str) -> OwnedSemaphorePermit {
+		self.permits_of(processor)
+			.semaphore
+			.clone()
+			.acquire_owned()
+			.await
+			.expect("processor semaphores are never closed")
+	}
+
+	pub fn record_latency(&self, processor: &'static str, latency: Duration) {
+		let permits = self.permits_of(processor);
+		let mut state = permits.state.lock().unwrap();
+
+		let latency = match state.latency {
+			Some(average) => {
+				average.mul_f64(1.0 - LATENCY_SMOOTHING) +
+					latency.mul_f64(LATENCY_SMOOTHING)
+			}
+			None => latency,
+		};
+		state.latency = Some(latency);
+
+		let target = self.target_permits(latency);
+		if target > state.permits {
+			permits.semaphore.add_permits(target - state.permits);
+			state.permits = target;
+		} else if target < state.permits {
+			// Only idle permits can be forgotten; the rest are reclaimed on a
+			// later sample, once the requests holding them complete.
+			state.permits -=
+				permits.semaphore.forget_permits(state.permits - target);
+		}
+	}
+
+	pub fn permits(&self, processor: &'static str) -> usize {
+		self.permits_of(processor).state.lock().unwrap().permits
+	}
+
+	/// Moving average latency of the slowest processor seen so far.
+	pub fn slowest_latency(&self) -> Option<Duration> {
+		self.processors
+			.read()
+			.unwrap()
+			.values()
+			.filter_map(|permits| permits.state.lock().unwrap().latency)
+			.max()
+	}
+
+	fn target_permits(&self, latency: Duration) -> usize {
+		let permits =
+			(self.options.target_throughput * latency.as_secs_f64()).ceil() as usize;
+
+		permits.clamp(self.options.min_permits, self.options.max_permits)
+	}
+
+	fn permits_of(&self, processor: &'static str) -> Arc<ProcessorPermits> {
+		if let Some(permits) = self.processors.read().unwrap().get(processor) {
+			return Arc::clone(permits);
+		}
+
+		let min_permits = self.options.min_permits;
+		Arc::clone(
+			self.processors
+				.write()
+				.unwrap()
+				.entry(processor)
+				.or_insert_with(|| {
+					Arc::new(ProcessorPermits {
+						semaphore: Arc::new(Semaphore::new(min_permits)),
+						state:     Mutex::new(LimitState {
+							latency: None,
+							permits: min_permits,
+						}),
+					})
+				}),
+		)
+	}
+}
+
+impl MetricsSource for ProcessorConcurrencyLimiter {
+	fn render(&self, out: &mut String) {
+		let processors = self.processors.read().unwrap();
+
+		let _ = writeln!(
+			out,
+			"# HELP rinha_processor_concurrency_limit In-flight requests allowed \
+			 per payment processor.\n# TYPE rinha_processor_concurrency_limit gauge"
+		);
+		for (name, permits) in processors.iter() {
+			let _ = writeln!(
+				out,
+				"rinha_processor_concurrency_limit{{processor=\"{name}\"}} {}",
+				permits.state.lock().unwrap().permits
+			);
+		}
+
+		let _ = writeln!(
+			out,
+			"# HELP rinha_processor_latency_seconds Moving average of the payment \
+			 processor response time.\n# TYPE rinha_processor_latency_seconds gauge"
+		);
+		for (name, permits) in processors.iter() {
+			if let Some(latency) = permits.state.lock().unwrap().latency {
+				let _ = writeln!(
+					out,
+					"rinha_processor_latency_seconds{{processor=\"{name}\"}} {}",
+					latency.as_secs_f64()
+				);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn limiter() -> ProcessorConcurrencyLimiter {
+		ProcessorConcurrencyLimiter::new(ConcurrencyOptions {
+			min_permits:       2,
+			max_permits:       50,
+			target_throughput: 1_000.0,
+		})
+	}
+
+	#[test]
+	fn test_starts_with_min_permits() {
+		assert_eq!(limiter().permits("default"), 2);
+	}
+
+	#[test]
+	fn test_grows_and_shrinks_with_latency() {
+		let limiter = limiter();
+
+		limiter.record_latency("default", Duration::from_micros(19_500));
+		assert_eq!(limiter.permits("default"), 20);
+
+		for _ in 0..100 {
+			limiter.record_latency("default", Duration::from_micros(4_500));
+		}
+		assert_eq!(limiter.permits("default"), 5);
+		assert_eq!(limiter.permits("fallback"), 2);
+	}
+
+	#[test]
+	fn test_slowest_latency() {
+		let limiter = limiter();
+		assert_eq!(limiter.slowest_latency(), None);
+
+		limiter.record_latency("default", Duration::from_millis(5));
+		limiter.record_latency("fallback", Duration::from_millis(30));
+
+		assert_eq!(limiter.slowest_latency(), Some(Duration::from_millis(30)));
+	}
+
+	#[test]
+	fn test_permits_are_clamped() {
+		let limiter = limiter();
+
+		limiter.record_latency("default", Duration::from_secs(1));
+		assert_eq!(limiter.permits("default"), 50);
+
+		for _ in 0..100 {
+			limiter.record_latency("default", Duration::ZERO);
+		}
+		assert_eq!(limiter.permits("default"), 2);
+	}
+
+	#[tokio::test]
+	async fn test_shrinking_waits_for_in_flight_permits() {
+		let limiter = limiter();
+		limiter.record_latency("default", Duration::from_micros(3_500));
+
+		let held = acquire_permits(&limiter, 4).await;
+		for _ in 0..10 {
+			limiter.record_latency("default", Duration::ZERO);
+		}
+		assert_eq!(limiter.permits("default"), 4);
+
+		drop(held);
+		limiter.record_latency("default", Duration::ZERO);
+		assert_eq!(limiter.permits("default"), 2);
+	}
+
+	async fn acquire_permits(
+		limiter: &ProcessorConcurrencyLimiter,
+		count: usize,
+	) -> Vec<OwnedSemaphorePermit> {
+		let mut permits = Vec::with_capacity(count);
+		for _ in 0..count {
+			permits.push(limiter.acquire("default").await);
+		}
+		permits
+	}
+}
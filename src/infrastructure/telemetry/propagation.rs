@@ -0,0 +1,28 @@
+use std::collections::HashMap;
+
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::domain::queue::TraceContext;
+
+/// Trace context fields an upstream caller may send along with a request.
+pub const TRACE_CONTEXT_HEADERS: [&str; 2] = ["traceparent", "tracestate"];
+
+/// Captures the trace context of `span`, empty when no exporter is installed.
+pub fn trace_context_of(span: &Span) -> TraceContext {
+	let mut carrier = HashMap::new();
+	TraceContextPropagator::new().inject_context(&span.context(), &mut carrier);
+	TraceContext(carrier)
+}
+
+/// Makes `span` a child of the span `trace_context` was captured from.
+pub fn follow_trace_context(span: &Span, trace_context: &TraceContext) {
+	if trace_context.0.is_empty() {
+		return;
+	}
+
+	let parent = TraceContextPropagator::new().extract(&trace_context.0);
+	let _ = span.set_parent(parent);
+}
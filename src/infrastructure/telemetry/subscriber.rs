@@ -0,0 +1,103 @@
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{Protocol, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use serde::Deserialize;
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{Layer, fmt};
+
+/// Used when `RUST_LOG` is not set, matching the former `env_logger` default.
+const DEFAULT_LOG_FILTER: &str = "error";
+const TRACER_NAME: &str = "rinha-de-backend";
+const OTLP_TRACES_PATH: &str = "/v1/traces";
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+	#[default]
+	Text,
+	Json,
+}
+
+#[derive(Debug, Clone)]
+pub struct TelemetryOptions {
+	pub log_format:    LogFormat,
+	/// Base URL of an OTLP/HTTP collector; spans are only exported when set.
+	pub otlp_endpoint: Option<String>,
+	pub service_name:  String,
+}
+
+/// Flushes the spans still buffered for the collector when dropped.
+pub struct TelemetryGuard {
+	tracer_provider: Option<SdkTracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+	fn drop(&mut self) {
+		if let Some(tracer_provider) = self.tracer_provider.take() &&
+			let Err(e) = tracer_provider.shutdown()
+		{
+			eprintln!("Failed to flush pending spans: {e}");
+		}
+	}
+}
+
+/// Installs the global `tracing` subscriber: logs filtered by `RUST_LOG` in
+/// the configured format, plus span export to OTLP when an endpoint is set.
+/// Records emitted through the `log` crate by dependencies are forwarded too.
+pub fn init_telemetry(
+	options: &TelemetryOptions,
+) -> Result<TelemetryGuard, Box<dyn std::error::Error + Send>> {
+	let log_filter = EnvFilter::try_from_default_env()
+		.unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+	let log_layer = match options.log_format {
+		LogFormat::Text => fmt::layer().boxed(),
+		LogFormat::Json => fmt::layer().json().boxed(),
+	}
+	.with_filter(log_filter);
+
+	let tracer_provider = options
+		.otlp_endpoint
+		.as_deref()
+		.map(|endpoint| build_tracer_provider(endpoint, &options.service_name))
+		.transpose()?;
+	let trace_layer = tracer_provider.as_ref().map(|tracer_provider| {
+		tracing_opentelemetry::layer()
+			.with_tracer(tracer_provider.tracer(TRACER_NAME))
+			.with_filter(LevelFilter::INFO)
+	});
+
+	tracing_subscriber::registry()
+		.with(log_layer)
+		.with(trace_layer)
+		.try_init()
+		.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+
+	Ok(TelemetryGuard { tracer_provider })
+}
+
+fn build_tracer_provider(
+	endpoint: &str,
+	service_name: &str,
+) -> Result<SdkTracerProvider, Box<dyn std::error::Error + Send>> {
+	let exporter = SpanExporter::builder()
+		.with_http()
+		.with_protocol(Protocol::HttpJson)
+		.with_endpoint(format!(
+			"{}{OTLP_TRACES_PATH}",
+			endpoint.trim_end_matches('/')
+		))
+		.build()
+		.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+
+	Ok(SdkTracerProvider::builder()
+		.with_batch_exporter(exporter)
+		.with_resource(
+			Resource::builder()
+				.with_service_name(service_name.to_string())
+				.build(),
+		)
+		.build())
+}
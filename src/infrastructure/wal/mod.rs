@@ -0,0 +1,3 @@
+pub mod record;
+pub mod segment;
+pub mod write_ahead_log;
@@ -0,0 +1,126 @@
+use derive_more::derive::{Display, Error};
+
+/// Length prefix (`u32`) followed by the CRC32 (`u32`) of the record body.
+pub const RECORD_HEADER_LEN: usize = 8;
+const SEQUENCE_LEN: usize = 8;
+const MAX_RECORD_BODY_LEN: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Display, Error, PartialEq)]
+pub enum RecordError {
+	#[display("Record is truncated.")]
+	Incomplete,
+	#[display("Record checksum does not match its content.")]
+	ChecksumMismatch,
+	#[display("Record header is malformed.")]
+	Malformed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WalRecord {
+	pub sequence: u64,
+	pub payload:  Vec<u8>,
+}
+
+impl WalRecord {
+	pub fn new(sequence: u64, payload: Vec<u8>) -> Self {
+		Self { sequence, payload }
+	}
+
+	/// Layout: `[body_len: u32][crc32(body): u32][sequence: u64][payload]`, all
+	/// integers little-endian.
+	pub fn encode(&self) -> Vec<u8> {
+		let body_len = SEQUENCE_LEN + self.payload.len();
+		let mut buffer = Vec::with_capacity(RECORD_HEADER_LEN + body_len);
+
+		buffer.extend_from_slice(&(body_len as u32).to_le_bytes());
+		buffer.extend_from_slice(&[0; 4]);
+		buffer.extend_from_slice(&self.sequence.to_le_bytes());
+		buffer.extend_from_slice(&self.payload);
+
+		let checksum = crc32fast::hash(&buffer[RECORD_HEADER_LEN..]);
+		buffer[4..RECORD_HEADER_LEN].copy_from_slice(&checksum.to_le_bytes());
+
+		buffer
+	}
+
+	/// Decodes the record at the start of `bytes`, returning it together with
+	/// the number of bytes it occupies.
+	pub fn decode(bytes: &[u8]) -> Result<(Self, usize), RecordError> {
+		if bytes.len() < RECORD_HEADER_LEN {
+			return Err(RecordError::Incomplete);
+		}
+
+		let body_len = u32::from_le_bytes(bytes[0..4].try_into().unwrap()) as usize;
+		if !(SEQUENCE_LEN..=MAX_RECORD_BODY_LEN).contains(&body_len) {
+			return Err(RecordError::Malformed);
+		}
+
+		let record_len = RECORD_HEADER_LEN + body_len;
+		if bytes.len() < record_len {
+			return Err(RecordError::Incomplete);
+		}
+
+		let checksum = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+		let body = &bytes[RECORD_HEADER_LEN..record_len];
+		if crc32fast::hash(body) != checksum {
+			return Err(RecordError::ChecksumMismatch);
+		}
+
+		let sequence = u64::from_le_bytes(body[..SEQUENCE_LEN].try_into().unwrap());
+
+		Ok((
+			Self::new(sequence, body[SEQUENCE_LEN..].to_vec()),
+			record_len,
+		))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_encode_and_decode_round_trip() {
+		let record = WalRecord::new(42, b"payment".to_vec());
+		let encoded = record.encode();
+
+		let (decoded, consumed) = WalRecord::decode(&encoded).unwrap();
+
+		assert_eq!(decoded, record);
+		assert_eq!(consumed, encoded.len());
+	}
+
+	#[test]
+	fn test_decode_truncated_record() {
+		let encoded = WalRecord::new(1, b"payment".to_vec()).encode();
+
+		assert_eq!(
+			WalRecord::decode(&encoded[..encoded.len() - 1]),
+			Err(RecordError::Incomplete)
+		);
+		assert_eq!(
+			WalRecord::decode(&encoded[..4]),
+			Err(RecordError::Incomplete)
+		);
+	}
+
+	#[test]
+	fn test_decode_detects_corruption() {
+		let mut encoded = WalRecord::new(1, b"payment".to_vec()).encode();
+		let last = encoded.len() - 1;
+		encoded[last] ^= 0xFF;
+
+		assert_eq!(
+			WalRecord::decode(&encoded),
+			Err(RecordError::ChecksumMismatch)
+		);
+	}
+
+	#[test]
+	fn test_decode_rejects_invalid_length() {
+		let mut encoded = WalRecord::new(1, Vec::new()).encode();
+		encoded[0..4].copy_from_slice(&2_u32.to_le_bytes());
+
+		assert_eq!(WalRecord::decode(&encoded), Err(RecordError::Malformed));
+	}
+}
@@ -0,0 +1,146 @@
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use crate::infrastructure::wal::record::{RecordError, WalRecord};
+
+const SEGMENT_EXTENSION: &str = "wal";
+
+#[derive(Debug, Clone)]
+pub struct Segment {
+	pub path:           PathBuf,
+	pub first_sequence: u64,
+	pub last_sequence:  Option<u64>,
+	pub size:           u64,
+}
+
+impl Segment {
+	pub fn new(dir: &Path, first_sequence: u64) -> Self {
+		Self {
+			path: dir.join(segment_file_name(first_sequence)),
+			first_sequence,
+			last_sequence: None,
+			size: 0,
+		}
+	}
+}
+
+pub struct SegmentScan {
+	pub records:   Vec<WalRecord>,
+	/// Byte length of the readable prefix of the segment.
+	pub valid_len: u64,
+	pub error:     Option<RecordError>,
+}
+
+pub fn segment_file_name(first_sequence: u64) -> String {
+	format!("{first_sequence:020}.{SEGMENT_EXTENSION}")
+}
+
+pub fn parse_segment_file_name(file_name: &str) -> Option<u64> {
+	file_name
+		.strip_suffix(SEGMENT_EXTENSION)?
+		.strip_suffix('.')?
+		.parse()
+		.ok()
+}
+
+/// Lists the segment files in `dir`, ordered by their first sequence.
+pub fn list_segments(dir: &Path) -> io::Result<Vec<Segment>> {
+	let mut segments = Vec::new();
+
+	for entry in fs::read_dir(dir)? {
+		let entry = entry?;
+		let file_name = entry.file_name();
+		if let Some(first_sequence) =
+			file_name.to_str().and_then(parse_segment_file_name)
+		{
+			segments.push(Segment {
+				path: entry.path(),
+				first_sequence,
+				last_sequence: None,
+				size: entry.metadata()?.len(),
+			});
+		}
+	}
+
+	segments.sort_by_key(|segment| segment.first_sequence);
+	Ok(segments)
+}
+
+/// Reads every intact record of a segment, stopping at the first torn or
+/// corrupted one.
+pub fn scan_segment(path: &Path) -> io::Result<SegmentScan> {
+	let bytes = fs::read(path)?;
+	let mut records = Vec::new();
+	let mut offset = 0;
+	let mut error = None;
+
+	while offset < bytes.len() {
+		match WalRecord::decode(&bytes[offset..]) {
+			Ok((record, consumed)) => {
+				records.push(record);
+				offset += consumed;
+			}
+			Err(e) => {
+				error = Some(e);
+				break;
+			}
+		}
+	}
+
+	Ok(SegmentScan {
+		records,
+		valid_len: offset as u64,
+		error,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Write;
+
+	use super::*;
+
+	#[test]
+	fn test_segment_file_name_round_trip() {
+		let file_name = segment_file_name(17);
+
+		assert_eq!(file_name, "00000000000000000017.wal");
+		assert_eq!(parse_segment_file_name(&file_name), Some(17));
+		assert_eq!(parse_segment_file_name("checkpoint"), None);
+		assert_eq!(parse_segment_file_name("17.log"), None);
+	}
+
+	#[test]
+	fn test_list_segments_sorted_by_first_sequence() {
+		let dir = tempfile::tempdir().unwrap();
+		fs::write(dir.path().join(segment_file_name(10)), b"").unwrap();
+		fs::write(dir.path().join(segment_file_name(2)), b"").unwrap();
+		fs::write(dir.path().join("checkpoint"), b"").unwrap();
+
+		let segments = list_segments(dir.path()).unwrap();
+
+		let sequences: Vec<u64> = segments
+			.iter()
+			.map(|segment| segment.first_sequence)
+			.collect();
+		assert_eq!(sequences, vec![2, 10]);
+	}
+
+	#[test]
+	fn test_scan_segment_stops_at_torn_record() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join(segment_file_name(1));
+		let first = WalRecord::new(1, b"first".to_vec()).encode();
+		let second = WalRecord::new(2, b"second".to_vec()).encode();
+
+		let mut file = fs::File::create(&path).unwrap();
+		file.write_all(&first).unwrap();
+		file.write_all(&second[..second.len() - 3]).unwrap();
+
+		let scan = scan_segment(&path).unwrap();
+
+		assert_eq!(scan.records, vec![WalRecord::new(1, b"first".to_vec())]);
+		assert_eq!(scan.valid_len, first.len() as u64);
+		assert_eq!(scan.error, Some(RecordError::Incomplete));
+	}
+}
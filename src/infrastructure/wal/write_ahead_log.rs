@@ -0,0 +1,564 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use tokio::sync::oneshot;
+use tracing::{error, info, warn};
+
+use crate::infrastructure::wal::record::WalRecord;
+use crate::infrastructure::wal::segment::{Segment, list_segments, scan_segment};
+
+const CHECKPOINT_FILE_NAME: &str = "checkpoint";
+const CHECKPOINT_INTERVAL: Duration = Duration::from_millis(200);
+/// Most appends written under one `fsync` by the writer thread.
+const MAX_GROUP_SIZE: usize = 512;
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FsyncPolicy {
+	/// `fsync` after every append.
+	Always,
+	/// `fsync` at most once per `WalOptions::fsync_interval`.
+	Interval,
+	/// Leave flushing to the operating system.
+	Never,
+}
+
+#[derive(Debug, Clone)]
+pub struct WalOptions {
+	pub dir:               PathBuf,
+	pub fsync_policy:      FsyncPolicy,
+	pub fsync_interval:    Duration,
+	pub max_segment_bytes: u64,
+}
+
+/// Append-only, segmented log of payments accepted by the HTTP edge but not
+/// yet pushed to Redis.
+///
+/// Records are identified by a monotonically increasing sequence. Once every
+/// record of a sealed segment is committed the segment file is removed, and
+/// the highest contiguous committed sequence is persisted in a checkpoint
+/// file so replay can skip committed records of the remaining segments.
+///
+/// `submit` hands appends to a dedicated writer thread, which writes every
+/// append queued meanwhile before syncing once, so async callers never block
+/// on the disk.
+#[derive(Clone)]
+pub struct WriteAheadLog {
+	state:  Arc<Mutex<WalState>>,
+	writer: mpsc::Sender<PendingAppend>,
+}
+
+struct PendingAppend {
+	payload: Vec<u8>,
+	reply:   oneshot::Sender<io::Result<u64>>,
+}
+
+struct WalState {
+	options:               WalOptions,
+	active_file:           File,
+	active_segment:        Segment,
+	sealed_segments:       VecDeque<Segment>,
+	next_sequence:         u64,
+	outstanding:           BTreeSet<u64>,
+	checkpoint:            u64,
+	last_checkpoint_write: Instant,
+	last_sync:             Instant,
+}
+
+impl WriteAheadLog {
+	/// Opens (or creates) the log in `options.dir`, returning it along with
+	/// every uncommitted record found on disk, in sequence order.
+	pub fn open(options: WalOptions) -> io::Result<(Self, Vec<WalRecord>)> {
+		fs::create_dir_all(&options.dir)?;
+
+		let checkpoint = read_checkpoint(&options.dir)?;
+		let segments = list_segments(&options.dir)?;
+		let last_index = segments.len().checked_sub(1);
+
+		let mut pending = Vec::new();
+		let mut sealed_segments = VecDeque::new();
+		let mut highest_sequence = checkpoint;
+
+		for (index, mut segment) in segments.into_iter().enumerate() {
+			let scan = scan_segment(&segment.path)?;
+
+			if let Some(e) = scan.error {
+				warn!(
+					"WAL segment {} is damaged after {} bytes: {e}",
+					segment.path.display(),
+					scan.valid_len
+				);
+				if Some(index) == last_index {
+					OpenOptions::new()
+						.write(true)
+						.open(&segment.path)?
+						.set_len(scan.valid_len)?;
+				}
+			}
+
+			segment.last_sequence =
+				scan.records.iter().map(|record| record.sequence).max();
+			segment.size = scan.valid_len;
+
+			match segment.last_sequence {
+				Some(last) if last > checkpoint => {
+					highest_sequence = highest_sequence.max(last);
+					pending.extend(
+						scan.records
+							.into_iter()
+							.filter(|record| record.sequence > checkpoint),
+					);
+					sealed_segments.push_back(segment);
+				}
+				_ => fs::remove_file(&segment.path)?,
+			}
+		}
+
+		pending.sort_by_key(|record| record.sequence);
+		if !pending.is_empty() {
+			info!(
+				"Replaying {} payments from the write-ahead log",
+				pending.len()
+			);
+		}
+
+		let next_sequence = highest_sequence + 1;
+		let active_segment = Segment::new(&options.dir, next_sequence);
+		let active_file = open_segment_file(&active_segment.path)?;
+
+		let state = WalState {
+			options,
+			active_file,
+			active_segment,
+			sealed_segments,
+			next_sequence,
+			outstanding: pending.iter().map(|record| record.sequence).collect(),
+			checkpoint,
+			last_checkpoint_write: Instant::now(),
+			last_sync: Instant::now(),
+		};
+
+		let state = Arc::new(Mutex::new(state));
+		let (writer, appends) = mpsc::channel();
+		let writer_state = Arc::clone(&state);
+		std::thread::Builder::new()
+			.name("wal-writer".to_string())
+			.spawn(move || write_appends(&writer_state, &appends))?;
+
+		Ok((Self { state, writer }, pending))
+	}
+
+	/// Durably appends `payload` (as far as the fsync policy allows) and
+	/// returns the sequence assigned to it, blocking the calling thread.
+	pub fn append(&self, payload: &[u8]) -> io::Result<u64> {
+		self.state
+			.lock()
+			.unwrap()
+			.append_group(&[payload])
+			.pop()
+			.unwrap()
+	}
+
+	/// Like `append`, but written by the writer thread alongside the other
+	/// appends submitted meanwhile.
+	pub async fn submit(&self, payload: Vec<u8>) -> io::Result<u64> {
+		let (reply, response) = oneshot::channel();
+		self.writer
+			.send(PendingAppend { payload, reply })
+			.map_err(|_| io::Error::other("WAL writer thread stopped"))?;
+		response
+			.await
+			.map_err(|_| io::Error::other("WAL writer thread stopped"))?
+	}
+
+	/// Marks records as safely handed off, releasing the segments and advancing
+	/// the checkpoint once no earlier record is outstanding.
+	pub fn commit(&self, sequences: &[u64]) -> io::Result<()> {
+		self.state.lock().unwrap().commit(sequences)
+	}
+
+	pub fn sync(&self) -> io::Result<()> {
+		let mut state = self.state.lock().unwrap();
+		state.active_file.sync_data()?;
+		state.last_sync = Instant::now();
+		Ok(())
+	}
+
+	pub fn outstanding_count(&self) -> usize {
+		self.state.lock().unwrap().outstanding.len()
+	}
+}
+
+/// Runs on the writer thread until every `WriteAheadLog` handle is dropped.
+fn write_appends(state: &Mutex<WalState>, appends: &mpsc::Receiver<PendingAppend>) {
+	while let Ok(first) = appends.recv() {
+		let mut group = vec![first];
+		while group.len() < MAX_GROUP_SIZE &&
+			let Ok(next) = appends.try_recv()
+		{
+			group.push(next);
+		}
+
+		let payloads: Vec<&[u8]> = group
+			.iter()
+			.map(|append| append.payload.as_slice())
+			.collect();
+		let results = state.lock().unwrap().append_group(&payloads);
+		for (append, result) in group.into_iter().zip(results) {
+			// The submitter may have given up waiting, its record stays logged.
+			let _ = append.reply.send(result);
+		}
+	}
+}
+
+impl WalState {
+	/// Appends every payload in order and syncs once for all of them, returning
+	/// one result per payload. When any write or the sync fails, every record
+	/// of the group is released and reported as failed, as its submitter will
+	/// tell the client, and the segment is truncated back to where the group
+	/// started so replay does not enqueue them anyway.
+	fn append_group(&mut self, payloads: &[&[u8]]) -> Vec<io::Result<u64>> {
+		let group_start = self.active_segment.size;
+		let last_sequence = self.active_segment.last_sequence;
+		let mut sequences = Vec::with_capacity(payloads.len());
+		let mut written = Ok(());
+		for payload in payloads {
+			match self.write_record(payload) {
+				Ok(sequence) => sequences.push(sequence),
+				Err(e) => {
+					written = Err(e);
+					break;
+				}
+			}
+		}
+
+		match written.and_then(|_| self.sync_group()) {
+			Ok(()) => sequences.into_iter().map(Ok).collect(),
+			Err(e) => {
+				for sequence in &sequences {
+					self.outstanding.remove(sequence);
+				}
+				if let Err(truncate_error) = self.active_file.set_len(group_start) {
+					error!(
+						"Failed to drop a failed append group from the WAL, its \
+						 records may be replayed: {truncate_error}"
+					);
+				}
+				self.active_segment.size = group_start;
+				self.active_segment.last_sequence = last_sequence;
+				payloads
+					.iter()
+					.map(|_| Err(io::Error::new(e.kind(), e.to_string())))
+					.collect()
+			}
+		}
+	}
+
+	fn sync_group(&mut self) -> io::Result<()> {
+		match self.options.fsync_policy {
+			FsyncPolicy::Always => self.sync_active()?,
+			FsyncPolicy::Interval
+				if self.last_sync.elapsed() >= self.options.fsync_interval =>
+			{
+				self.sync_active()?
+			}
+			_ => {}
+		}
+
+		if self.active_segment.size >= self.options.max_segment_bytes {
+			self.rotate()?;
+		}
+
+		Ok(())
+	}
+
+	fn write_record(&mut self, payload: &[u8]) -> io::Result<u64> {
+		let sequence = self.next_sequence;
+		let encoded = WalRecord::new(sequence, payload.to_vec()).encode();
+
+		self.active_file.write_all(&encoded)?;
+
+		self.next_sequence += 1;
+		self.active_segment.size += encoded.len() as u64;
+		self.active_segment.last_sequence = Some(sequence);
+		self.outstanding.insert(sequence);
+
+		Ok(sequence)
+	}
+
+	fn commit(&mut self, sequences: &[u64]) -> io::Result<()> {
+		for sequence in sequences {
+			self.outstanding.remove(sequence);
+		}
+
+		let watermark = self
+			.outstanding
+			.first()
+			.map_or(self.next_sequence - 1, |oldest| oldest - 1);
+
+		let releasable = self
+			.sealed_segments
+			.iter()
+			.take_while(|segment| {
+				segment.last_sequence.is_none_or(|last| last <= watermark)
+			})
+			.count();
+
+		if watermark > self.checkpoint &&
+			(releasable > 0 ||
+				self.last_checkpoint_write.elapsed() >= CHECKPOINT_INTERVAL)
+		{
+			self.write_checkpoint(watermark)?;
+		}
+
+		for segment in self.sealed_segments.drain(..releasable) {
+			fs::remove_file(&segment.path)?;
+		}
+
+		Ok(())
+	}
+
+	fn rotate(&mut self) -> io::Result<()> {
+		if self.options.fsync_policy != FsyncPolicy::Never {
+			self.sync_active()?;
+		}
+
+		let segment = Segment::new(&self.options.dir, self.next_sequence);
+		self.active_file = open_segment_file(&segment.path)?;
+		let sealed = std::mem::replace(&mut self.active_segment, segment);
+		self.sealed_segments.push_back(sealed);
+
+		Ok(())
+	}
+
+	fn sync_active(&mut self) -> io::Result<()> {
+		self.active_file.sync_data()?;
+		self.last_sync = Instant::now();
+		Ok(())
+	}
+
+	fn write_checkpoint(&mut self, watermark: u64) -> io::Result<()> {
+		let mut content = watermark.to_le_bytes().to_vec();
+		content.extend_from_slice(&crc32fast::hash(&content).to_le_bytes());
+
+		let path = self.options.dir.join(CHECKPOINT_FILE_NAME);
+		let temp_path = path.with_extension("tmp");
+		let mut file = File::create(&temp_path)?;
+		file.write_all(&content)?;
+		if self.options.fsync_policy != FsyncPolicy::Never {
+			file.sync_data()?;
+		}
+		fs::rename(&temp_path, &path)?;
+
+		self.checkpoint = watermark;
+		self.last_checkpoint_write = Instant::now();
+		Ok(())
+	}
+}
+
+fn open_segment_file(path: &Path) -> io::Result<File> {
+	OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn read_checkpoint(dir: &Path) -> io::Result<u64> {
+	let content = match fs::read(dir.join(CHECKPOINT_FILE_NAME)) {
+		Ok(content) => content,
+		Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+		Err(e) => return Err(e),
+	};
+
+	if content.len() == 12 &&
+		crc32fast::hash(&content[..8]).to_le_bytes() == content[8..12]
+	{
+		return Ok(u64::from_le_bytes(content[..8].try_into().unwrap()));
+	}
+
+	warn!("WAL checkpoint is corrupted, replaying every segment");
+	Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::infrastructure::wal::segment::segment_file_name;
+
+	fn options_for(dir: &Path, max_segment_bytes: u64) -> WalOptions {
+		WalOptions {
+			dir: dir.to_path_buf(),
+			fsync_policy: FsyncPolicy::Always,
+			fsync_interval: Duration::from_millis(10),
+			max_segment_bytes,
+		}
+	}
+
+	fn segment_count(dir: &Path) -> usize {
+		list_segments(dir).unwrap().len()
+	}
+
+	#[test]
+	fn test_open_empty_directory() {
+		let dir = tempfile::tempdir().unwrap();
+
+		let (wal, pending) =
+			WriteAheadLog::open(options_for(dir.path(), 1024)).unwrap();
+
+		assert!(pending.is_empty());
+		assert_eq!(wal.outstanding_count(), 0);
+		assert_eq!(wal.append(b"first").unwrap(), 1);
+	}
+
+	#[test]
+	fn test_uncommitted_records_are_replayed() {
+		let dir = tempfile::tempdir().unwrap();
+
+		{
+			let (wal, _) =
+				WriteAheadLog::open(options_for(dir.path(), 1024)).unwrap();
+			wal.append(b"first").unwrap();
+			wal.append(b"second").unwrap();
+			wal.append(b"third").unwrap();
+			wal.commit(&[2]).unwrap();
+		}
+
+		let (wal, pending) =
+			WriteAheadLog::open(options_for(dir.path(), 1024)).unwrap();
+
+		assert_eq!(pending, vec![
+			WalRecord::new(1, b"first".to_vec()),
+			WalRecord::new(2, b"second".to_vec()),
+			WalRecord::new(3, b"third".to_vec()),
+		]);
+		assert_eq!(wal.outstanding_count(), 3);
+		assert_eq!(wal.append(b"fourth").unwrap(), 4);
+	}
+
+	#[test]
+	fn test_committed_records_are_not_replayed() {
+		let dir = tempfile::tempdir().unwrap();
+
+		{
+			let (wal, _) =
+				WriteAheadLog::open(options_for(dir.path(), 1024)).unwrap();
+			wal.append(b"first").unwrap();
+			wal.append(b"second").unwrap();
+			wal.append(b"third").unwrap();
+			wal.commit(&[1, 2]).unwrap();
+			wal.state.lock().unwrap().write_checkpoint(2).unwrap();
+		}
+
+		let (_, pending) =
+			WriteAheadLog::open(options_for(dir.path(), 1024)).unwrap();
+
+		assert_eq!(pending, vec![WalRecord::new(3, b"third".to_vec())]);
+	}
+
+	#[test]
+	fn test_segments_rotate_and_are_truncated_after_commit() {
+		let dir = tempfile::tempdir().unwrap();
+		let (wal, _) = WriteAheadLog::open(options_for(dir.path(), 1)).unwrap();
+
+		let first = wal.append(b"first").unwrap();
+		let second = wal.append(b"second").unwrap();
+		assert_eq!(segment_count(dir.path()), 3);
+
+		wal.commit(&[second]).unwrap();
+		assert_eq!(segment_count(dir.path()), 3);
+
+		wal.commit(&[first]).unwrap();
+		assert_eq!(segment_count(dir.path()), 1);
+		assert_eq!(wal.outstanding_count(), 0);
+	}
+
+	#[test]
+	fn test_torn_tail_is_discarded_on_replay() {
+		let dir = tempfile::tempdir().unwrap();
+
+		{
+			let (wal, _) =
+				WriteAheadLog::open(options_for(dir.path(), 1024)).unwrap();
+			wal.append(b"first").unwrap();
+		}
+
+		let path = dir.path().join(segment_file_name(1));
+		let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+		let torn = WalRecord::new(2, b"second".to_vec()).encode();
+		file.write_all(&torn[..torn.len() / 2]).unwrap();
+
+		let (wal, pending) =
+			WriteAheadLog::open(options_for(dir.path(), 1024)).unwrap();
+
+		assert_eq!(pending, vec![WalRecord::new(1, b"first".to_vec())]);
+		assert_eq!(
+			fs::metadata(&path).unwrap().len(),
+			WalRecord::new(1, b"first".to_vec()).encode().len() as u64
+		);
+		assert_eq!(wal.append(b"second").unwrap(), 2);
+	}
+
+	#[test]
+	fn test_failed_appends_are_not_replayed() {
+		let dir = tempfile::tempdir().unwrap();
+
+		{
+			let (wal, _) =
+				WriteAheadLog::open(options_for(dir.path(), 1024)).unwrap();
+			wal.append(b"first").unwrap();
+
+			// The write succeeds, but rotating to a new segment fails.
+			let mut state = wal.state.lock().unwrap();
+			state.options.max_segment_bytes = 1;
+			state.options.dir = dir.path().join("missing");
+			assert!(state.append_group(&[b"second", b"third"])[0].is_err());
+			assert_eq!(state.outstanding.len(), 1);
+		}
+
+		let (wal, pending) =
+			WriteAheadLog::open(options_for(dir.path(), 1024)).unwrap();
+
+		assert_eq!(pending, vec![WalRecord::new(1, b"first".to_vec())]);
+		assert_eq!(wal.append(b"fourth").unwrap(), 2);
+	}
+
+	#[tokio::test]
+	async fn test_submitted_appends_are_grouped_by_the_writer_thread() {
+		let dir = tempfile::tempdir().unwrap();
+		let (wal, _) = WriteAheadLog::open(options_for(dir.path(), 1024)).unwrap();
+
+		let (first, second) = tokio::join!(
+			wal.submit(b"first".to_vec()),
+			wal.submit(b"second".to_vec())
+		);
+		let mut sequences = vec![first.unwrap(), second.unwrap()];
+		sequences.sort();
+
+		assert_eq!(sequences, vec![1, 2]);
+		assert_eq!(wal.outstanding_count(), 2);
+		drop(wal);
+
+		let (_, pending) =
+			WriteAheadLog::open(options_for(dir.path(), 1024)).unwrap();
+		assert_eq!(pending.len(), 2);
+	}
+
+	#[test]
+	fn test_corrupted_checkpoint_replays_everything() {
+		let dir = tempfile::tempdir().unwrap();
+
+		{
+			let (wal, _) =
+				WriteAheadLog::open(options_for(dir.path(), 1024)).unwrap();
+			wal.append(b"first").unwrap();
+		}
+		fs::write(dir.path().join(CHECKPOINT_FILE_NAME), b"garbage").unwrap();
+
+		let (_, pending) =
+			WriteAheadLog::open(options_for(dir.path(), 1024)).unwrap();
+
+		assert_eq!(pending.len(), 1);
+	}
+}
@@ -0,0 +1,39 @@
+#[cfg(unix)]
+use tokio::signal::unix::{SignalKind, signal};
+#[cfg(unix)]
+use tracing::error;
+use tracing::info;
+
+use crate::infrastructure::config::routing_reloader::RoutingConfigReloader;
+
+/// Reloads the routing configuration every time the process receives SIGHUP.
+#[cfg(unix)]
+pub async fn config_reload_worker(reloader: RoutingConfigReloader) {
+	let mut hangup = match signal(SignalKind::hangup()) {
+		Ok(hangup) => hangup,
+		Err(e) => {
+			error!(
+				"Failed to listen for SIGHUP, configuration reloads disabled: {e}"
+			);
+			return;
+		}
+	};
+
+	while hangup.recv().await.is_some() {
+		info!("Received SIGHUP, reloading routing configuration");
+		if let Err(e) = reloader.reload() {
+			error!("Rejected configuration reload, keeping the running one: {e}");
+		}
+	}
+}
+
+/// There is no SIGHUP to reload on outside Unix, only `POST
+/// /admin/config/reload`, so the worker just waits for Ctrl+C to stop.
+#[cfg(not(unix))]
+pub async fn config_reload_worker(_reloader: RoutingConfigReloader) {
+	info!(
+		"SIGHUP reloads are unavailable on this platform, use POST \
+		 /admin/config/reload"
+	);
+	let _ = tokio::signal::ctrl_c().await;
+}
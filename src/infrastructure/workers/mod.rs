@@ -1,3 +1,9 @@
+pub mod config_reload_worker;
 pub mod mpsc_to_redis_worker;
 pub mod payment_processor_worker;
+pub mod payment_retention_worker;
+pub mod processor_fee_rate_worker;
 pub mod processor_health_monitor_worker;
+pub mod queue_depth_monitor_worker;
+pub mod worker_pool;
+pub mod worker_pool_supervisor;
@@ -1,24 +1,96 @@
-use log::{error, info};
 use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant, sleep, timeout_at};
+use tracing::{Instrument, error, info, info_span};
 
 use crate::domain::payment::Payment;
-use crate::domain::queue::Queue;
+use crate::domain::queue::{Queue, TraceContext};
+use crate::infrastructure::metrics::ingress_metrics::IngressMetrics;
+use crate::infrastructure::queue::buffered_payment::BufferedPayment;
+use crate::infrastructure::wal::write_ahead_log::WriteAheadLog;
 use crate::use_cases::create_payment::CreatePaymentUseCase;
 
+const INITIAL_RETRY_BACKOFF: Duration = Duration::from_millis(50);
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy)]
+pub struct BatchOptions {
+	pub max_size:   usize,
+	pub max_linger: Duration,
+}
+
+impl Default for BatchOptions {
+	fn default() -> Self {
+		Self {
+			max_size:   1,
+			max_linger: Duration::ZERO,
+		}
+	}
+}
+
 pub async fn mpsc_to_redis_worker<Q>(
-	mut receiver: mpsc::Receiver<Payment>,
+	mut receiver: mpsc::Receiver<BufferedPayment>,
 	create_payment_use_case: CreatePaymentUseCase<Q>,
+	write_ahead_log: Option<WriteAheadLog>,
+	batch_options: BatchOptions,
+	metrics: IngressMetrics,
 ) where
 	Q: Queue<Payment> + Clone + Send + Sync + 'static,
 {
 	info!("Starting MPSC to Redis worker...");
-	loop {
-		while let Some(payment) = receiver.recv().await {
-			if let Err(e) = create_payment_use_case.execute(payment).await {
-				error!("Failed to push payment to Redis queue: {e:?}");
+	let max_size = batch_options.max_size.max(1);
+	let mut batch = Vec::with_capacity(max_size);
+
+	while let Some(buffered_payment) = receiver.recv().await {
+		batch.push(buffered_payment);
+
+		let deadline = Instant::now() + batch_options.max_linger;
+		while batch.len() < max_size {
+			match timeout_at(deadline, receiver.recv()).await {
+				Ok(Some(buffered_payment)) => batch.push(buffered_payment),
+				_ => break,
 			}
 		}
+
+		let batch_size = batch.len();
+		let wal_sequences: Vec<u64> = batch
+			.iter()
+			.filter_map(|buffered_payment| buffered_payment.wal_sequence)
+			.collect();
+		let payments: Vec<(Payment, TraceContext)> = batch
+			.drain(..)
+			.map(|buffered_payment| {
+				(buffered_payment.payment, buffered_payment.trace_context)
+			})
+			.collect();
+
+		// A batch is retried until pushed rather than dropped: its clients were
+		// already answered, and its write-ahead log records must not stay
+		// outstanding. The buffer filling up meanwhile pushes back on ingress.
+		let mut backoff = INITIAL_RETRY_BACKOFF;
+		while let Err(e) = create_payment_use_case
+			.execute_batch(payments.clone())
+			.instrument(info_span!("push_payment_batch", batch_size))
+			.await
+		{
+			error!(
+				"Failed to push payment batch of {batch_size} to Redis queue: \
+				 {e:?}, retrying in {backoff:?}"
+			);
+			metrics.record_failed_batch(batch_size);
+			sleep(backoff).await;
+			backoff = (backoff * 2).min(MAX_RETRY_BACKOFF);
+		}
+		metrics.record_batch(batch_size);
+
+		if let Some(write_ahead_log) = &write_ahead_log &&
+			!wal_sequences.is_empty() &&
+			let Err(e) = write_ahead_log.commit(&wal_sequences)
+		{
+			error!("Failed to truncate write-ahead log: {e}");
+		}
 	}
+
+	info!("Payment channel closed, stopping MPSC to Redis worker.");
 }
 
 #[cfg(test)]
@@ -30,29 +102,31 @@ mod tests {
 	use async_trait::async_trait;
 	use rinha_de_backend::domain::payment::Payment;
 	use rinha_de_backend::domain::queue::{Message, Queue};
-	use rinha_de_backend::infrastructure::workers::mpsc_to_redis_worker::mpsc_to_redis_worker;
+	use rinha_de_backend::infrastructure::metrics::ingress_metrics::IngressMetrics;
+	use rinha_de_backend::infrastructure::queue::buffered_payment::BufferedPayment;
+	use rinha_de_backend::infrastructure::wal::write_ahead_log::{
+		FsyncPolicy, WalOptions, WriteAheadLog,
+	};
+	use rinha_de_backend::infrastructure::workers::mpsc_to_redis_worker::{
+		BatchOptions, mpsc_to_redis_worker,
+	};
 	use rinha_de_backend::use_cases::create_payment::CreatePaymentUseCase;
 	use tokio::sync::mpsc;
 	use tokio::time::timeout;
 	use uuid::Uuid;
 
-	struct TestLogger {
-		output: Arc<Mutex<Vec<u8>>>,
-	}
+	#[derive(Clone)]
+	struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
 
-	impl log::Log for TestLogger {
-		fn enabled(&self, metadata: &log::Metadata) -> bool {
-			metadata.level() <= log::Level::Error
+	impl Write for CapturedLogs {
+		fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+			self.0.lock().unwrap().extend_from_slice(buf);
+			Ok(buf.len())
 		}
 
-		fn log(&self, record: &log::Record) {
-			if self.enabled(record.metadata()) {
-				let mut output = self.output.lock().unwrap();
-				writeln!(output, "{} - {}", record.level(), record.args()).unwrap();
-			}
+		fn flush(&mut self) -> std::io::Result<()> {
+			Ok(())
 		}
-
-		fn flush(&self) {}
 	}
 
 	#[derive(Clone)]
@@ -76,6 +150,13 @@ mod tests {
 			Ok(None)
 		}
 
+		async fn pop_batch(
+			&self,
+			_count: usize,
+		) -> Result<Vec<Message<Payment>>, Box<dyn std::error::Error + Send>> {
+			Ok(Vec::new())
+		}
+
 		async fn push(
 			&self,
 			message: Message<Payment>,
@@ -83,6 +164,18 @@ mod tests {
 			self.payments.lock().unwrap().push(message);
 			Ok(())
 		}
+
+		async fn push_batch(
+			&self,
+			messages: Vec<Message<Payment>>,
+		) -> Result<(), Box<dyn std::error::Error + Send>> {
+			self.payments.lock().unwrap().extend(messages);
+			Ok(())
+		}
+
+		async fn length(&self) -> Result<usize, Box<dyn std::error::Error + Send>> {
+			Ok(self.payments.lock().unwrap().len())
+		}
 	}
 
 	#[derive(Clone)]
@@ -97,11 +190,77 @@ mod tests {
 			Err(Box::new(std::io::Error::other("Mock push error")))
 		}
 
+		async fn push_batch(
+			&self,
+			_messages: Vec<Message<Payment>>,
+		) -> Result<(), Box<dyn std::error::Error + Send>> {
+			Err(Box::new(std::io::Error::other("Mock push error")))
+		}
+
 		async fn pop(
 			&self,
 		) -> Result<Option<Message<Payment>>, Box<dyn std::error::Error + Send>> {
 			Ok(None)
 		}
+
+		async fn pop_batch(
+			&self,
+			_count: usize,
+		) -> Result<Vec<Message<Payment>>, Box<dyn std::error::Error + Send>> {
+			Ok(Vec::new())
+		}
+
+		async fn length(&self) -> Result<usize, Box<dyn std::error::Error + Send>> {
+			Ok(0)
+		}
+	}
+
+	/// Fails the first `failures` pushes, then behaves as `MockPaymentQueue`.
+	#[derive(Clone)]
+	struct MockFlakyPaymentQueue {
+		failures: Arc<Mutex<usize>>,
+		queue:    MockPaymentQueue,
+	}
+
+	#[async_trait]
+	impl Queue<Payment> for MockFlakyPaymentQueue {
+		async fn push(
+			&self,
+			message: Message<Payment>,
+		) -> Result<(), Box<dyn std::error::Error + Send>> {
+			self.push_batch(vec![message]).await
+		}
+
+		async fn push_batch(
+			&self,
+			messages: Vec<Message<Payment>>,
+		) -> Result<(), Box<dyn std::error::Error + Send>> {
+			{
+				let mut failures = self.failures.lock().unwrap();
+				if *failures > 0 {
+					*failures -= 1;
+					return Err(Box::new(std::io::Error::other("Mock push error")));
+				}
+			}
+			self.queue.push_batch(messages).await
+		}
+
+		async fn pop(
+			&self,
+		) -> Result<Option<Message<Payment>>, Box<dyn std::error::Error + Send>> {
+			Ok(None)
+		}
+
+		async fn pop_batch(
+			&self,
+			_count: usize,
+		) -> Result<Vec<Message<Payment>>, Box<dyn std::error::Error + Send>> {
+			Ok(Vec::new())
+		}
+
+		async fn length(&self) -> Result<usize, Box<dyn std::error::Error + Send>> {
+			self.queue.length().await
+		}
 	}
 
 	#[tokio::test]
@@ -110,17 +269,24 @@ mod tests {
 		let mock_queue = MockPaymentQueue::new();
 		let create_payment_use_case = CreatePaymentUseCase::new(mock_queue.clone());
 
-		tokio::spawn(mpsc_to_redis_worker(receiver, create_payment_use_case));
+		tokio::spawn(mpsc_to_redis_worker(
+			receiver,
+			create_payment_use_case,
+			None,
+			BatchOptions::default(),
+			IngressMetrics::new(),
+		));
 
 		let payment = Payment {
 			correlation_id: Uuid::new_v4(),
 			amount:         100.00,
-			requested_at:   None,
+			submitted_at:   None,
 			processed_at:   None,
 			processed_by:   None,
+			received_at:    None,
 		};
 
-		sender.send(payment.clone()).await.unwrap();
+		sender.send(payment.clone().into()).await.unwrap();
 
 		timeout(Duration::from_secs(1), async {
 			loop {
@@ -140,6 +306,252 @@ mod tests {
 		assert_eq!(payments[0].body.amount, payment.amount);
 	}
 
+	#[tokio::test]
+	async fn test_mpsc_to_redis_worker_pushes_payments_in_batches() {
+		let (sender, receiver) = mpsc::channel(10);
+		let mock_queue = MockPaymentQueue::new();
+		let create_payment_use_case = CreatePaymentUseCase::new(mock_queue.clone());
+		let metrics = IngressMetrics::new();
+
+		let mut correlation_ids = Vec::new();
+		for _ in 0..5 {
+			let payment = Payment {
+				correlation_id: Uuid::new_v4(),
+				amount:         10.0,
+				submitted_at:   None,
+				processed_at:   None,
+				processed_by:   None,
+				received_at:    None,
+			};
+			correlation_ids.push(payment.correlation_id);
+			sender.send(payment.into()).await.unwrap();
+		}
+
+		tokio::spawn(mpsc_to_redis_worker(
+			receiver,
+			create_payment_use_case,
+			None,
+			BatchOptions {
+				max_size:   3,
+				max_linger: Duration::from_millis(10),
+			},
+			metrics.clone(),
+		));
+
+		timeout(Duration::from_secs(1), async {
+			while metrics.payments() < 5 {
+				tokio::time::sleep(Duration::from_millis(10)).await;
+			}
+		})
+		.await
+		.expect("Timeout waiting for payments to be pushed to the queue");
+
+		assert_eq!(metrics.batches(), 2);
+		let pushed_ids: Vec<Uuid> = mock_queue
+			.payments
+			.lock()
+			.unwrap()
+			.iter()
+			.map(|message| message.id)
+			.collect();
+		assert_eq!(pushed_ids, correlation_ids);
+	}
+
+	fn open_write_ahead_log(dir: &tempfile::TempDir) -> WriteAheadLog {
+		let (write_ahead_log, _) = WriteAheadLog::open(WalOptions {
+			dir:               dir.path().to_path_buf(),
+			fsync_policy:      FsyncPolicy::Never,
+			fsync_interval:    Duration::from_millis(10),
+			max_segment_bytes: 1024,
+		})
+		.unwrap();
+		write_ahead_log
+	}
+
+	#[tokio::test]
+	async fn test_mpsc_to_redis_worker_truncates_write_ahead_log_after_push() {
+		let dir = tempfile::tempdir().unwrap();
+		let write_ahead_log = open_write_ahead_log(&dir);
+		let (sender, receiver) = mpsc::channel(1);
+		let mock_queue = MockPaymentQueue::new();
+		let create_payment_use_case = CreatePaymentUseCase::new(mock_queue.clone());
+
+		tokio::spawn(mpsc_to_redis_worker(
+			receiver,
+			create_payment_use_case,
+			Some(write_ahead_log.clone()),
+			BatchOptions::default(),
+			IngressMetrics::new(),
+		));
+
+		let payment = Payment {
+			correlation_id: Uuid::new_v4(),
+			amount:         100.00,
+			submitted_at:   None,
+			processed_at:   None,
+			processed_by:   None,
+			received_at:    None,
+		};
+		let sequence = write_ahead_log.append(b"payment").unwrap();
+
+		sender
+			.send(BufferedPayment::new(payment, Some(sequence)))
+			.await
+			.unwrap();
+
+		timeout(Duration::from_secs(1), async {
+			while write_ahead_log.outstanding_count() > 0 {
+				tokio::time::sleep(Duration::from_millis(10)).await;
+			}
+		})
+		.await
+		.expect("Timeout waiting for the write-ahead log to be truncated");
+
+		assert_eq!(mock_queue.payments.lock().unwrap().len(), 1);
+	}
+
+	#[tokio::test]
+	async fn test_mpsc_to_redis_worker_keeps_write_ahead_log_on_push_failure() {
+		let dir = tempfile::tempdir().unwrap();
+		let write_ahead_log = open_write_ahead_log(&dir);
+		let (sender, receiver) = mpsc::channel(1);
+		let create_payment_use_case =
+			CreatePaymentUseCase::new(MockFailingPaymentQueue);
+
+		tokio::spawn(mpsc_to_redis_worker(
+			receiver,
+			create_payment_use_case,
+			Some(write_ahead_log.clone()),
+			BatchOptions::default(),
+			IngressMetrics::new(),
+		));
+
+		let payment = Payment {
+			correlation_id: Uuid::new_v4(),
+			amount:         100.00,
+			submitted_at:   None,
+			processed_at:   None,
+			processed_by:   None,
+			received_at:    None,
+		};
+		let sequence = write_ahead_log.append(b"payment").unwrap();
+
+		sender
+			.send(BufferedPayment::new(payment, Some(sequence)))
+			.await
+			.unwrap();
+
+		tokio::time::sleep(Duration::from_millis(100)).await;
+
+		assert_eq!(write_ahead_log.outstanding_count(), 1);
+	}
+
+	#[tokio::test]
+	async fn test_mpsc_to_redis_worker_retries_failed_batch_until_pushed() {
+		let dir = tempfile::tempdir().unwrap();
+		let write_ahead_log = open_write_ahead_log(&dir);
+		let (sender, receiver) = mpsc::channel(1);
+		let mock_queue = MockFlakyPaymentQueue {
+			failures: Arc::new(Mutex::new(2)),
+			queue:    MockPaymentQueue::new(),
+		};
+		let create_payment_use_case = CreatePaymentUseCase::new(mock_queue.clone());
+		let metrics = IngressMetrics::new();
+
+		tokio::spawn(mpsc_to_redis_worker(
+			receiver,
+			create_payment_use_case,
+			Some(write_ahead_log.clone()),
+			BatchOptions::default(),
+			metrics.clone(),
+		));
+
+		let payment = Payment {
+			correlation_id: Uuid::new_v4(),
+			amount:         100.00,
+			submitted_at:   None,
+			processed_at:   None,
+			processed_by:   None,
+			received_at:    None,
+		};
+		let sequence = write_ahead_log.append(b"payment").unwrap();
+
+		sender
+			.send(BufferedPayment::new(payment.clone(), Some(sequence)))
+			.await
+			.unwrap();
+
+		timeout(Duration::from_secs(2), async {
+			while write_ahead_log.outstanding_count() > 0 {
+				tokio::time::sleep(Duration::from_millis(10)).await;
+			}
+		})
+		.await
+		.expect("Timeout waiting for the failed batch to be retried");
+
+		assert_eq!(metrics.failed_payments(), 2);
+		let payments = mock_queue.queue.payments.lock().unwrap();
+		assert_eq!(payments.len(), 1);
+		assert_eq!(payments[0].id, payment.correlation_id);
+	}
+
+	#[tokio::test]
+	async fn test_mpsc_to_redis_worker_retries_the_whole_failed_batch() {
+		let (sender, receiver) = mpsc::channel(10);
+		let mock_queue = MockFlakyPaymentQueue {
+			failures: Arc::new(Mutex::new(1)),
+			queue:    MockPaymentQueue::new(),
+		};
+		let create_payment_use_case = CreatePaymentUseCase::new(mock_queue.clone());
+		let metrics = IngressMetrics::new();
+
+		let mut correlation_ids = Vec::new();
+		for _ in 0..3 {
+			let payment = Payment {
+				correlation_id: Uuid::new_v4(),
+				amount:         10.0,
+				submitted_at:   None,
+				processed_at:   None,
+				processed_by:   None,
+				received_at:    None,
+			};
+			correlation_ids.push(payment.correlation_id);
+			sender.send(payment.into()).await.unwrap();
+		}
+
+		tokio::spawn(mpsc_to_redis_worker(
+			receiver,
+			create_payment_use_case,
+			None,
+			BatchOptions {
+				max_size:   3,
+				max_linger: Duration::from_millis(10),
+			},
+			metrics.clone(),
+		));
+
+		timeout(Duration::from_secs(2), async {
+			while metrics.payments() < 3 {
+				tokio::time::sleep(Duration::from_millis(10)).await;
+			}
+		})
+		.await
+		.expect("Timeout waiting for the failed batch to be retried");
+
+		// Pushed once, whole and in order, after failing once.
+		assert_eq!(metrics.failed_payments(), 3);
+		assert_eq!(metrics.batches(), 1);
+		let pushed_ids: Vec<Uuid> = mock_queue
+			.queue
+			.payments
+			.lock()
+			.unwrap()
+			.iter()
+			.map(|message| message.id)
+			.collect();
+		assert_eq!(pushed_ids, correlation_ids);
+	}
+
 	#[tokio::test]
 	async fn test_mpsc_to_redis_worker_logs_error_on_push_failure() {
 		let (sender, receiver) = mpsc::channel(1);
@@ -147,34 +559,43 @@ mod tests {
 		let create_payment_use_case =
 			CreatePaymentUseCase::new(mock_failing_queue.clone());
 
-		let _worker_handle =
-			tokio::spawn(mpsc_to_redis_worker(receiver, create_payment_use_case));
+		let _worker_handle = tokio::spawn(mpsc_to_redis_worker(
+			receiver,
+			create_payment_use_case,
+			None,
+			BatchOptions::default(),
+			IngressMetrics::new(),
+		));
 
 		let payment = Payment {
 			correlation_id: Uuid::new_v4(),
 			amount:         100.0,
-			requested_at:   None,
+			submitted_at:   None,
 			processed_at:   None,
 			processed_by:   None,
+			received_at:    None,
 		};
 
-		// Use a test logger to capture log output
+		// Use a test subscriber to capture log output
 		let log_output = Arc::new(Mutex::new(Vec::<u8>::new()));
-		let logger = TestLogger {
-			output: log_output.clone(),
-		};
-		log::set_boxed_logger(Box::new(logger)).unwrap();
-		log::set_max_level(log::LevelFilter::Error);
+		let captured_logs = CapturedLogs(log_output.clone());
+		let subscriber = tracing_subscriber::fmt()
+			.with_max_level(tracing::Level::ERROR)
+			.with_ansi(false)
+			.with_writer(move || captured_logs.clone())
+			.finish();
+		let _subscriber_guard = tracing::subscriber::set_default(subscriber);
 
-		sender.send(payment.clone()).await.unwrap();
+		sender.send(payment.clone().into()).await.unwrap();
 
 		// Give the worker some time to process and log the error
 		tokio::time::sleep(Duration::from_millis(100)).await;
 
 		let logs = String::from_utf8(log_output.lock().unwrap().clone()).unwrap();
+		assert!(logs.contains("ERROR"));
 		assert!(logs.contains(
-			"ERROR - Failed to push payment to Redis queue: Custom { kind: Other, \
-			 error: \"Mock push error\" }"
+			"Failed to push payment batch of 1 to Redis queue: Custom { kind: \
+			 Other, error: \"Mock push error\" }"
 		));
 	}
 }
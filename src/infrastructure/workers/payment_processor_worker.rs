@@ -1,89 +1,212 @@
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use circuitbreaker_rs::State;
-use log::{error, info, warn};
-use tokio::time::sleep;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, watch};
+use tokio::task::{JoinError, JoinSet};
+use tokio::time::{MissedTickBehavior, interval, sleep};
+use tracing::{Instrument, error, info, info_span, warn};
 
 use crate::domain::payment::Payment;
 use crate::domain::payment_router::PaymentRouter;
-use crate::domain::queue::Queue;
+use crate::domain::queue::{Message, Queue};
 use crate::domain::repository::PaymentRepository;
+use crate::infrastructure::routing::processor_concurrency_limiter::ProcessorConcurrencyLimiter;
+use crate::infrastructure::telemetry::propagation::follow_trace_context;
+use crate::infrastructure::workers::worker_pool::WorkerPool;
 use crate::use_cases::process_payment::ProcessPaymentUseCase;
 
+/// How often a worker waiting for a free slot still reports it is alive.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Pops up to `batch_size` payments at a time and processes them concurrently,
+/// keeping at most `batch_size` payments in flight per worker. Requests to each
+/// payment processor are further bounded by the shared `concurrency_limiter`.
+///
+/// Every pass of its loop, and every `HEARTBEAT_INTERVAL` while all its slots
+/// are busy, the worker beats on the `pool`, which readiness checks. A payment
+/// task that panics is logged and counted in the `pool` metrics without
+/// stopping the worker.
+///
+/// Once `shutdown` flips to `true` the worker stops popping and returns after
+/// its in-flight payments complete, so no popped payment is lost.
+#[allow(clippy::too_many_arguments)]
 pub async fn payment_processing_worker<Q, PR, R>(
 	queue: Q,
 	payment_repo: PR,
 	process_payment_use_case: ProcessPaymentUseCase<PR>,
 	router: R,
+	concurrency_limiter: ProcessorConcurrencyLimiter,
+	pool: WorkerPool,
+	batch_size: usize,
+	mut shutdown: watch::Receiver<bool>,
 ) where
 	Q: Queue<Payment> + Clone + Send + Sync + 'static,
 	PR: PaymentRepository + Clone + Send + Sync + 'static,
 	R: PaymentRouter + Clone + Send + Sync + 'static,
 {
-	loop {
-		let message = match queue.pop().await {
-			Ok(Some(val)) => val,
-			Ok(None) => {
+	let in_flight = Arc::new(Semaphore::new(batch_size.max(1)));
+	let mut tasks = JoinSet::new();
+	let mut heartbeat = interval(HEARTBEAT_INTERVAL);
+	heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+	while !*shutdown.borrow() {
+		pool.record_heartbeat();
+		while let Some(joined) = tasks.try_join_next() {
+			record_panic(joined, &pool);
+		}
+
+		let first_permit = tokio::select! {
+			permit = Arc::clone(&in_flight).acquire_owned() => {
+				permit.expect("in-flight semaphore is never closed")
+			}
+			_ = heartbeat.tick() => continue,
+			Ok(_) = shutdown.wait_for(|stop| *stop) => break,
+		};
+		let free_slots = 1 + in_flight.available_permits();
+
+		let messages = match queue.pop_batch(free_slots).await {
+			Ok(messages) if messages.is_empty() => {
 				info!("No payments in queue, waiting...");
+				drop(first_permit);
 				sleep(Duration::from_secs(1)).await;
 				continue;
 			}
+			Ok(messages) => messages,
 			Err(e) => {
 				error!("Failed to pop from payments queue: {e}");
+				drop(first_permit);
 				sleep(Duration::from_secs(1)).await;
 				continue;
 			}
 		};
 
-		let message_id = message.id;
-
-		info!("Started processing message with id '{}'", &message_id);
+		let mut first_permit = Some(first_permit);
+		for message in messages {
+			// Only this loop acquires in-flight permits, so the slots counted
+			// above are still free.
+			let permit = first_permit.take().unwrap_or_else(|| {
+				Arc::clone(&in_flight)
+					.try_acquire_owned()
+					.expect("popped more payments than free slots")
+			});
 
-		let payment: Payment = message.body.clone();
+			let span = info_span!(
+				"process_payment",
+				correlation_id = %message.body.correlation_id,
+				message_id = %message.id,
+			);
+			follow_trace_context(&span, &message.trace_context);
 
-		if let Ok(true) = payment_repo
-			.is_already_processed(&payment.correlation_id.to_string())
-			.await
-		{
-			info!("Payment already processed. Skipping it.");
-			continue;
+			tasks.spawn(
+				process_message(
+					message,
+					permit,
+					queue.clone(),
+					payment_repo.clone(),
+					process_payment_use_case.clone(),
+					router.clone(),
+					concurrency_limiter.clone(),
+				)
+				.instrument(span),
+			);
 		}
+	}
 
-		let mut processed = false;
+	info!("Stopping payment processing worker...");
+	while let Some(joined) = tasks.join_next().await {
+		record_panic(joined, &pool);
+	}
+}
 
-		if let Some((key, mut circuit_breaker)) =
-			router.get_processor_for_payment().await
-		{
-			if circuit_breaker.current_state() == State::Open {
-				if let Err(e) = queue.push(message).await {
-					error!("Failed to re-queue payment: {e}");
-				}
-				sleep(Duration::from_millis(250)).await;
-				continue;
-			}
+fn record_panic(joined: Result<(), JoinError>, pool: &WorkerPool) {
+	if let Err(e) = joined &&
+		e.is_panic()
+	{
+		error!("Payment processing task panicked: {e}");
+		pool.record_task_panic();
+	}
+}
 
-			processed = process_payment_use_case
-				.execute(
-					payment.clone(),
-					key.url.to_string(),
-					key.name.to_string(),
-					&mut circuit_breaker,
-				)
-				.await
-				.unwrap_or(false);
-		}
+async fn process_message<Q, PR, R>(
+	message: Message<Payment>,
+	_in_flight_permit: OwnedSemaphorePermit,
+	queue: Q,
+	payment_repo: PR,
+	process_payment_use_case: ProcessPaymentUseCase<PR>,
+	router: R,
+	concurrency_limiter: ProcessorConcurrencyLimiter,
+) where
+	Q: Queue<Payment>,
+	PR: PaymentRepository,
+	R: PaymentRouter,
+{
+	let message_id = message.id;
 
-		if !processed {
-			warn!(
-				"Payment {} could not be processed by any processor. Re-queueing.",
-				payment.correlation_id
-			);
+	info!("Started processing message with id '{}'", &message_id);
+
+	let payment: Payment = message.body.clone();
+
+	match payment_repo
+		.claim(&payment.correlation_id.to_string())
+		.await
+	{
+		Ok(true) => {}
+		Ok(false) => {
+			info!("Payment already processed or claimed. Skipping it.");
+			return;
+		}
+		Err(e) => {
+			error!("Failed to claim payment: {e}");
 			if let Err(e) = queue.push(message).await {
 				error!("Failed to re-queue payment: {e}");
 			}
 			sleep(Duration::from_millis(250)).await;
+			return;
+		}
+	}
+
+	let mut processed = false;
+
+	if let Some((key, mut circuit_breaker)) =
+		router.get_processor_for_payment().await
+	{
+		if circuit_breaker.current_state() == State::Open {
+			if let Err(e) = queue.requeue(message).await {
+				error!("Failed to re-queue payment: {e}");
+			}
+			sleep(Duration::from_millis(250)).await;
+			return;
 		}
 
-		info!("Message with id '{}' processed.", &message_id);
+		let _processor_permit = process_payment_use_case
+			.keep_claimed(&payment, concurrency_limiter.acquire(key.name))
+			.await;
+		let started_at = Instant::now();
+
+		processed = process_payment_use_case
+			.execute(
+				payment.clone(),
+				key.url.to_string(),
+				key.name.to_string(),
+				&mut circuit_breaker,
+			)
+			.await
+			.unwrap_or(false);
+
+		concurrency_limiter.record_latency(key.name, started_at.elapsed());
 	}
+
+	if !processed {
+		warn!(
+			"Payment {} could not be processed by any processor. Re-queueing.",
+			payment.correlation_id
+		);
+		if let Err(e) = queue.requeue(message).await {
+			error!("Failed to re-queue payment: {e}");
+		}
+		sleep(Duration::from_millis(250)).await;
+	}
+
+	info!("Message with id '{}' processed.", &message_id);
 }
@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+use time::OffsetDateTime;
+use tokio::time::sleep;
+use tracing::{error, info};
+
+use crate::domain::repository::PaymentRepository;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetentionOptions {
+	/// How long processed payments keep their details.
+	pub max_age:      Duration,
+	/// Width of the windows older payments are folded into. Summaries count a
+	/// window only when it lies entirely within theirs.
+	pub bucket:       Duration,
+	/// How long compacted payments still count as processed past `max_age`,
+	/// so redelivered or replayed ones are not charged twice.
+	pub dedup_window: Duration,
+	pub interval:     Duration,
+	/// Payments compacted per Redis round trip.
+	pub batch_size:   usize,
+}
+
+/// Keeps Redis from growing without bound by compacting processed payments
+/// older than `max_age`, while summaries still count them.
+pub async fn payment_retention_worker<R: PaymentRepository>(
+	payment_repo: R,
+	options: RetentionOptions,
+) {
+	loop {
+		let before = OffsetDateTime::now_utc() - options.max_age;
+		let mut compacted = 0;
+		loop {
+			match payment_repo
+				.compact(
+					before,
+					options.bucket,
+					options.dedup_window,
+					options.batch_size,
+				)
+				.await
+			{
+				Ok(read) => {
+					compacted += read;
+					if read < options.batch_size {
+						break;
+					}
+				}
+				Err(e) => {
+					error!("Failed to compact payments: {e}");
+					break;
+				}
+			}
+		}
+		if compacted > 0 {
+			info!("Compacted {compacted} payments submitted before {before}");
+		}
+
+		sleep(options.interval).await;
+	}
+}
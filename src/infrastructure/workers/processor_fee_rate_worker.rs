@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use reqwest::Client;
+use tokio::time::{Duration, sleep};
+use tracing::{error, info};
+
+use crate::domain::fee_schedule::FeeSchedule;
+use crate::domain::payment_processor::PaymentProcessorKey;
+use crate::infrastructure::routing::in_memory_payment_router::InMemoryPaymentRouter;
+
+const ADMIN_TOKEN_HEADER: &str = "X-Rinha-Token";
+
+/// Keeps `fee_schedule` in line with the `feePerTransaction` each processor
+/// reports on its admin summary endpoint.
+pub async fn processor_fee_rate_worker(
+	router: InMemoryPaymentRouter,
+	http_client: Client,
+	fee_schedule: FeeSchedule,
+	admin_token: String,
+	interval: Duration,
+) {
+	loop {
+		// Keys are read every round to follow processor URL reloads.
+		for key in router.processor_keys() {
+			refresh_fee_rate(&http_client, &fee_schedule, &admin_token, key).await;
+		}
+
+		sleep(interval).await;
+	}
+}
+
+async fn refresh_fee_rate(
+	http_client: &Client,
+	fee_schedule: &FeeSchedule,
+	admin_token: &str,
+	key: Arc<PaymentProcessorKey>,
+) {
+	let summary_url = format!("{}/admin/payments-summary", key.url);
+
+	let response = match http_client
+		.get(&summary_url)
+		.header(ADMIN_TOKEN_HEADER, admin_token)
+		.send()
+		.await
+		.and_then(|response| response.error_for_status())
+	{
+		Ok(response) => response,
+		Err(e) => {
+			error!("Failed to fetch the fee rate of {}: {e}", key.name);
+			return;
+		}
+	};
+
+	match response.json::<serde_json::Value>().await {
+		Ok(json) => match json["feePerTransaction"].as_f64() {
+			Some(rate) if (0.0..=1.0).contains(&rate) => {
+				if fee_schedule.rates().for_group(key.name) != Some(rate) {
+					info!("Fee rate of {} is now {rate}", key.name);
+					fee_schedule.set_rate(key.name, rate);
+				}
+			}
+			_ => error!(
+				"Admin summary of {} has no valid feePerTransaction",
+				key.name
+			),
+		},
+		Err(e) => {
+			error!("Failed to parse the admin summary of {}: {e}", key.name);
+		}
+	}
+}
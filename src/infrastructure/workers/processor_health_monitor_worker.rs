@@ -1,8 +1,8 @@
 use std::sync::Arc;
 
-use log::error;
 use reqwest::Client;
 use tokio::time::{Duration, sleep};
+use tracing::error;
 
 use crate::domain::health_status::HealthStatus;
 use crate::domain::payment_processor::{PaymentProcessor, PaymentProcessorKey};
@@ -11,19 +11,15 @@ use crate::infrastructure::routing::in_memory_payment_router::InMemoryPaymentRou
 pub async fn processor_health_monitor_worker(
 	router: InMemoryPaymentRouter,
 	http_client: Client,
+	interval: Duration,
 ) {
-	let processor_keys = [
-		Arc::clone(&router.default_processor.read().unwrap().key),
-		Arc::clone(&router.fallback_processor.read().unwrap().key),
-	];
-
 	loop {
-		for key in &processor_keys {
-			compute_processor_health(&router, &http_client, Arc::clone(key)).await;
+		// Keys are read every round to follow processor URL reloads.
+		for key in router.processor_keys() {
+			compute_processor_health(&router, &http_client, key).await;
 		}
 
-		// Respect the 5-second rate limit for health checks
-		sleep(Duration::from_secs(5)).await;
+		sleep(interval).await;
 	}
 }
 
@@ -49,7 +45,7 @@ async fn compute_processor_health(
 							HealthStatus::Healthy
 						};
 
-						router.update_processor_health(PaymentProcessor {
+						router.record_health_check(PaymentProcessor {
 							key: Arc::clone(&key),
 							health: health_status.clone(),
 							min_response_time,
@@ -63,7 +59,7 @@ async fn compute_processor_health(
 					}
 				}
 			} else {
-				router.update_processor_health(PaymentProcessor {
+				router.record_health_check(PaymentProcessor {
 					key:               Arc::clone(&key),
 					health:            HealthStatus::Failing,
 					min_response_time: 0,
@@ -77,7 +73,7 @@ async fn compute_processor_health(
 				health:            HealthStatus::Failing,
 				min_response_time: 0,
 			};
-			router.update_processor_health(processor);
+			router.record_health_check(processor);
 		}
 	}
 }
@@ -0,0 +1,26 @@
+use tokio::time::{Duration, sleep};
+use tracing::error;
+
+use crate::domain::payment::Payment;
+use crate::domain::queue::Queue;
+use crate::infrastructure::queue::admission_controller::AdmissionController;
+
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+pub async fn queue_depth_monitor_worker<Q>(
+	queue: Q,
+	admission_controller: AdmissionController,
+) where
+	Q: Queue<Payment> + Clone + Send + Sync + 'static,
+{
+	loop {
+		match queue.length().await {
+			Ok(queue_length) => {
+				admission_controller.record_queue_length(queue_length)
+			}
+			Err(e) => error!("Failed to read payments queue length: {e}"),
+		}
+
+		sleep(SAMPLE_INTERVAL).await;
+	}
+}
@@ -0,0 +1,279 @@
+use std::fmt::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::infrastructure::metrics::registry::MetricsSource;
+
+#[derive(Debug, Clone)]
+pub struct WorkerPoolOptions {
+	pub min_workers:        usize,
+	pub max_workers:        usize,
+	pub scale_interval:     Duration,
+	/// Queued payments one worker is expected to keep up with.
+	pub backlog_per_worker: usize,
+	/// Above this processor latency adding workers only piles more requests
+	/// onto a saturated processor, so the pool stops growing.
+	pub max_latency:        Duration,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct WorkerPoolStatus {
+	pub size:         usize,
+	pub desired_size: usize,
+	pub min_workers:  usize,
+	pub max_workers:  usize,
+	pub restarts:     u64,
+	pub task_panics:  u64,
+}
+
+/// Shared view of the payment processing worker pool, sized by the
+/// `worker_pool_supervisor`.
+#[derive(Clone)]
+pub struct WorkerPool {
+	options: WorkerPoolOptions,
+	state:   Arc<WorkerPoolState>,
+}
+
+struct WorkerPoolState {
+	started:        Instant,
+	/// Milliseconds from `started` to the last worker heartbeat, 0 before the
+	/// first.
+	last_heartbeat: AtomicU64,
+	size:           AtomicUsize,
+	desired_size:   AtomicUsize,
+	restarts:       AtomicU64,
+	task_panics:    AtomicU64,
+	min_workers:    AtomicUsize,
+	max_workers:    AtomicUsize,
+}
+
+impl WorkerPool {
+	pub fn new(options: WorkerPoolOptions) -> Self {
+		let min_workers = options.min_workers;
+		let max_workers = options.max_workers;
+		Self {
+			options,
+			state: Arc::new(WorkerPoolState {
+				started:        Instant::now(),
+				last_heartbeat: AtomicU64::new(0),
+				size:           AtomicUsize::new(0),
+				desired_size:   AtomicUsize::new(min_workers),
+				restarts:       AtomicU64::new(0),
+				task_panics:    AtomicU64::new(0),
+				min_workers:    AtomicUsize::new(min_workers),
+				max_workers:    AtomicUsize::new(max_workers),
+			}),
+		}
+	}
+
+	/// Options the pool was created with; the bounds may since have been
+	/// changed by `set_bounds`.
+	pub fn options(&self) -> &WorkerPoolOptions {
+		&self.options
+	}
+
+	/// Changes the pool bounds; the supervisor converges to them on its next
+	/// tick.
+	pub fn set_bounds(&self, min_workers: usize, max_workers: usize) {
+		self.state.min_workers.store(min_workers, Ordering::Relaxed);
+		self.state
+			.max_workers
+			.store(max_workers.max(min_workers), Ordering::Relaxed);
+	}
+
+	/// Size the pool should converge to: enough workers for the backlog,
+	/// without growing while the processors are already slow.
+	pub fn target_size(
+		&self,
+		current_size: usize,
+		queue_length: usize,
+		processor_latency: Option<Duration>,
+	) -> usize {
+		let mut target =
+			queue_length.div_ceil(self.options.backlog_per_worker.max(1));
+
+		if processor_latency
+			.is_some_and(|latency| latency > self.options.max_latency)
+		{
+			target = target.min(current_size);
+		}
+
+		let min_workers = self.state.min_workers.load(Ordering::Relaxed);
+		let max_workers = self.state.max_workers.load(Ordering::Relaxed);
+		target.clamp(min_workers, max_workers.max(min_workers))
+	}
+
+	pub fn record_size(&self, size: usize, desired_size: usize) {
+		self.state.size.store(size, Ordering::Relaxed);
+		self.state
+			.desired_size
+			.store(desired_size, Ordering::Relaxed);
+	}
+
+	pub fn record_restart(&self) {
+		self.state.restarts.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// A worker's loop went around, so it is neither stuck nor gone.
+	pub fn record_heartbeat(&self) {
+		let elapsed = self.state.started.elapsed().as_millis() as u64;
+		self.state
+			.last_heartbeat
+			.store(elapsed.max(1), Ordering::Relaxed);
+	}
+
+	/// How long ago a worker last beat, `None` if none ever did.
+	pub fn since_last_heartbeat(&self) -> Option<Duration> {
+		match self.state.last_heartbeat.load(Ordering::Relaxed) {
+			0 => None,
+			last_heartbeat => Some(
+				self.state
+					.started
+					.elapsed()
+					.saturating_sub(Duration::from_millis(last_heartbeat)),
+			),
+		}
+	}
+
+	/// A worker's payment task panicked; the worker itself keeps running. Only
+	/// seen in builds that unwind, as the release profile aborts on panic.
+	pub fn record_task_panic(&self) {
+		self.state.task_panics.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// Whether the pool was scaled down to no worker, which operations
+	/// rewriting the store wait for.
+	pub fn is_stopped(&self) -> bool {
+		self.state.max_workers.load(Ordering::Relaxed) == 0 &&
+			self.state.size.load(Ordering::Relaxed) == 0
+	}
+
+	pub fn status(&self) -> WorkerPoolStatus {
+		WorkerPoolStatus {
+			size:         self.state.size.load(Ordering::Relaxed),
+			desired_size: self.state.desired_size.load(Ordering::Relaxed),
+			min_workers:  self.state.min_workers.load(Ordering::Relaxed),
+			max_workers:  self.state.max_workers.load(Ordering::Relaxed),
+			restarts:     self.state.restarts.load(Ordering::Relaxed),
+			task_panics:  self.state.task_panics.load(Ordering::Relaxed),
+		}
+	}
+}
+
+impl MetricsSource for WorkerPool {
+	fn render(&self, out: &mut String) {
+		let status = self.status();
+		let _ = writeln!(
+			out,
+			"# HELP rinha_worker_pool_size Running payment processing workers.\n# \
+			 TYPE rinha_worker_pool_size gauge\nrinha_worker_pool_size {}",
+			status.size
+		);
+		let _ = writeln!(
+			out,
+			"# HELP rinha_worker_pool_restarts_total Payment processing workers \
+			 restarted after exiting.\n# TYPE rinha_worker_pool_restarts_total \
+			 counter\nrinha_worker_pool_restarts_total {}",
+			status.restarts
+		);
+		let _ = writeln!(
+			out,
+			"# HELP rinha_worker_task_panics_total Payment processing tasks that \
+			 panicked.\n# TYPE rinha_worker_task_panics_total \
+			 counter\nrinha_worker_task_panics_total {}",
+			status.task_panics
+		);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn pool() -> WorkerPool {
+		WorkerPool::new(WorkerPoolOptions {
+			min_workers:        2,
+			max_workers:        8,
+			scale_interval:     Duration::from_millis(10),
+			backlog_per_worker: 100,
+			max_latency:        Duration::from_millis(100),
+		})
+	}
+
+	#[test]
+	fn test_target_size_follows_queue_length() {
+		let pool = pool();
+
+		assert_eq!(pool.target_size(2, 0, None), 2);
+		assert_eq!(pool.target_size(2, 450, None), 5);
+		assert_eq!(pool.target_size(2, 10_000, None), 8);
+	}
+
+	#[test]
+	fn test_target_size_does_not_grow_with_slow_processors() {
+		let pool = pool();
+		let slow = Some(Duration::from_millis(500));
+
+		assert_eq!(pool.target_size(3, 10_000, slow), 3);
+		assert_eq!(pool.target_size(3, 0, slow), 2);
+		assert_eq!(
+			pool.target_size(3, 10_000, Some(Duration::from_millis(20))),
+			8
+		);
+	}
+
+	#[test]
+	fn test_set_bounds() {
+		let pool = pool();
+		pool.set_bounds(1, 3);
+
+		assert_eq!(pool.target_size(2, 0, None), 1);
+		assert_eq!(pool.target_size(2, 10_000, None), 3);
+		assert_eq!(pool.status().max_workers, 3);
+	}
+
+	#[test]
+	fn test_status() {
+		let pool = pool();
+		pool.record_size(3, 4);
+		pool.record_restart();
+		pool.record_task_panic();
+
+		assert_eq!(pool.status(), WorkerPoolStatus {
+			size:         3,
+			desired_size: 4,
+			min_workers:  2,
+			max_workers:  8,
+			restarts:     1,
+			task_panics:  1,
+		});
+	}
+
+	#[test]
+	fn test_since_last_heartbeat() {
+		let pool = pool();
+		assert_eq!(pool.since_last_heartbeat(), None);
+
+		pool.record_heartbeat();
+		assert!(
+			pool.since_last_heartbeat()
+				.is_some_and(|since| since < Duration::from_secs(1))
+		);
+	}
+
+	#[test]
+	fn test_is_stopped_once_scaled_to_zero() {
+		let pool = pool();
+		pool.record_size(2, 2);
+		assert!(!pool.is_stopped());
+
+		pool.set_bounds(0, 0);
+		assert!(!pool.is_stopped());
+
+		pool.record_size(0, 0);
+		assert!(pool.is_stopped());
+	}
+}
@@ -0,0 +1,298 @@
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, sleep};
+use tracing::{error, info, warn};
+
+use crate::domain::payment::Payment;
+use crate::domain::queue::Queue;
+use crate::infrastructure::routing::processor_concurrency_limiter::ProcessorConcurrencyLimiter;
+use crate::infrastructure::workers::worker_pool::WorkerPool;
+
+/// Delay before restarting a worker that exited, doubled for every worker in
+/// a row that exits sooner than `MAX_RESTART_BACKOFF` after starting.
+const INITIAL_RESTART_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(30);
+
+struct RunningWorker {
+	shutdown:   watch::Sender<bool>,
+	handle:     JoinHandle<()>,
+	started_at: Instant,
+}
+
+/// Keeps the payment processing pool between its min and max size, following
+/// the queue length and processor latency, and restarts workers that exit.
+///
+/// `spawn_worker` builds a worker future that must return once the shutdown
+/// receiver it is given flips to `true`. Workers are started at once when the
+/// pool grows, but stopped one per `scale_interval` when it shrinks.
+///
+/// A worker is restarted whenever its task ends, whether it returned or failed
+/// with a `JoinError`, after a backoff growing while workers keep exiting
+/// early, as they do while Redis is unreachable. The release profile aborts on
+/// panic, so there a panicking worker takes the process down with it.
+pub async fn worker_pool_supervisor<Q, F, Fut>(
+	pool: WorkerPool,
+	queue: Q,
+	concurrency_limiter: ProcessorConcurrencyLimiter,
+	spawn_worker: F,
+) where
+	Q: Queue<Payment> + Clone + Send + Sync + 'static,
+	F: Fn(watch::Receiver<bool>) -> Fut + Send + Sync + 'static,
+	Fut: Future<Output = ()> + Send + 'static,
+{
+	// Stopping a worker still waiting out its backoff does not wait for it.
+	let start_worker_after = |delay: Duration| {
+		let (shutdown, shutdown_receiver) = watch::channel(false);
+		let mut stopped = shutdown_receiver.clone();
+		let worker = spawn_worker(shutdown_receiver);
+		RunningWorker {
+			shutdown,
+			handle: tokio::spawn(async move {
+				let stopped = async move {
+					let _ = stopped.wait_for(|stop| *stop).await;
+				};
+				tokio::select! {
+					_ = sleep(delay) => worker.await,
+					_ = stopped => {}
+				}
+			}),
+			started_at: Instant::now() + delay,
+		}
+	};
+	let start_worker = || start_worker_after(Duration::ZERO);
+	let mut workers: Vec<RunningWorker> = Vec::new();
+	let mut stopping: Vec<JoinHandle<()>> = Vec::new();
+	let mut restart_backoff = INITIAL_RESTART_BACKOFF;
+
+	loop {
+		for worker in workers.iter_mut() {
+			if !worker.handle.is_finished() {
+				continue;
+			}
+
+			if worker.started_at.elapsed() >= MAX_RESTART_BACKOFF {
+				restart_backoff = INITIAL_RESTART_BACKOFF;
+			}
+			match (&mut worker.handle).await {
+				Err(e) => error!(
+					"Payment processing worker failed, restarting it in \
+					 {restart_backoff:?}: {e}"
+				),
+				Ok(()) => warn!(
+					"Payment processing worker exited, restarting it in \
+					 {restart_backoff:?}."
+				),
+			}
+			pool.record_restart();
+			*worker = start_worker_after(restart_backoff);
+			restart_backoff = (restart_backoff * 2).min(MAX_RESTART_BACKOFF);
+		}
+		stopping.retain(|handle| !handle.is_finished());
+
+		let target_size = match queue.length().await {
+			Ok(queue_length) => pool.target_size(
+				workers.len(),
+				queue_length,
+				concurrency_limiter.slowest_latency(),
+			),
+			Err(e) => {
+				error!("Failed to read payments queue length: {e}");
+				pool.target_size(workers.len(), 0, None).max(workers.len())
+			}
+		};
+
+		if target_size > workers.len() {
+			info!(
+				"Scaling payment processing workers from {} to {target_size}",
+				workers.len()
+			);
+			workers.resize_with(target_size, start_worker);
+		} else if target_size < workers.len() &&
+			let Some(worker) = workers.pop()
+		{
+			info!(
+				"Scaling payment processing workers down to {}",
+				workers.len()
+			);
+			let _ = worker.shutdown.send(true);
+			stopping.push(worker.handle);
+		}
+
+		pool.record_size(workers.len(), target_size);
+		sleep(pool.options().scale_interval).await;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::sync::atomic::{AtomicUsize, Ordering};
+	use std::time::Duration;
+
+	use async_trait::async_trait;
+
+	use super::*;
+	use crate::domain::queue::Message;
+	use crate::infrastructure::routing::processor_concurrency_limiter::ConcurrencyOptions;
+	use crate::infrastructure::workers::worker_pool::WorkerPoolOptions;
+
+	#[derive(Clone, Default)]
+	struct MockPaymentQueue {
+		length: Arc<AtomicUsize>,
+	}
+
+	#[async_trait]
+	impl Queue<Payment> for MockPaymentQueue {
+		async fn pop(
+			&self,
+		) -> Result<Option<Message<Payment>>, Box<dyn std::error::Error + Send>> {
+			Ok(None)
+		}
+
+		async fn pop_batch(
+			&self,
+			_count: usize,
+		) -> Result<Vec<Message<Payment>>, Box<dyn std::error::Error + Send>> {
+			Ok(Vec::new())
+		}
+
+		async fn push(
+			&self,
+			_message: Message<Payment>,
+		) -> Result<(), Box<dyn std::error::Error + Send>> {
+			Ok(())
+		}
+
+		async fn push_batch(
+			&self,
+			_messages: Vec<Message<Payment>>,
+		) -> Result<(), Box<dyn std::error::Error + Send>> {
+			Ok(())
+		}
+
+		async fn length(&self) -> Result<usize, Box<dyn std::error::Error + Send>> {
+			Ok(self.length.load(Ordering::Relaxed))
+		}
+	}
+
+	fn pool() -> WorkerPool {
+		WorkerPool::new(WorkerPoolOptions {
+			min_workers:        1,
+			max_workers:        4,
+			scale_interval:     Duration::from_millis(10),
+			backlog_per_worker: 100,
+			max_latency:        Duration::from_millis(100),
+		})
+	}
+
+	fn concurrency_limiter() -> ProcessorConcurrencyLimiter {
+		ProcessorConcurrencyLimiter::new(ConcurrencyOptions {
+			min_permits:       1,
+			max_permits:       8,
+			target_throughput: 100.0,
+		})
+	}
+
+	async fn wait_until(condition: impl Fn() -> bool) {
+		tokio::time::timeout(Duration::from_secs(5), async {
+			while !condition() {
+				tokio::time::sleep(Duration::from_millis(5)).await;
+			}
+		})
+		.await
+		.expect("condition not met in time");
+	}
+
+	#[tokio::test]
+	async fn test_supervisor_scales_with_queue_length() {
+		let pool = pool();
+		let queue = MockPaymentQueue::default();
+		let running = Arc::new(AtomicUsize::new(0));
+
+		let running_workers = Arc::clone(&running);
+		let supervisor = tokio::spawn(worker_pool_supervisor(
+			pool.clone(),
+			queue.clone(),
+			concurrency_limiter(),
+			move |mut shutdown| {
+				let running = Arc::clone(&running_workers);
+				async move {
+					running.fetch_add(1, Ordering::SeqCst);
+					let _ = shutdown.wait_for(|stop| *stop).await;
+					running.fetch_sub(1, Ordering::SeqCst);
+				}
+			},
+		));
+
+		wait_until(|| pool.status().size == 1).await;
+
+		queue.length.store(350, Ordering::Relaxed);
+		wait_until(|| running.load(Ordering::SeqCst) == 4).await;
+		assert_eq!(pool.status().size, 4);
+
+		queue.length.store(0, Ordering::Relaxed);
+		wait_until(|| running.load(Ordering::SeqCst) == 1).await;
+		assert_eq!(pool.status().size, 1);
+		assert_eq!(pool.status().restarts, 0);
+
+		supervisor.abort();
+	}
+
+	#[tokio::test]
+	async fn test_supervisor_restarts_exited_workers() {
+		let pool = pool();
+		let started = Arc::new(AtomicUsize::new(0));
+
+		let started_workers = Arc::clone(&started);
+		let supervisor = tokio::spawn(worker_pool_supervisor(
+			pool.clone(),
+			MockPaymentQueue::default(),
+			concurrency_limiter(),
+			move |_shutdown| {
+				let started = Arc::clone(&started_workers);
+				async move {
+					if started.fetch_add(1, Ordering::SeqCst) == 0 {
+						panic!("worker failure");
+					}
+					std::future::pending::<()>().await;
+				}
+			},
+		));
+
+		wait_until(|| started.load(Ordering::SeqCst) == 2).await;
+
+		assert_eq!(pool.status().restarts, 1);
+		assert_eq!(pool.status().size, 1);
+
+		supervisor.abort();
+	}
+
+	#[tokio::test]
+	async fn test_supervisor_backs_off_workers_that_keep_exiting() {
+		let pool = pool();
+		let started = Arc::new(AtomicUsize::new(0));
+
+		let started_workers = Arc::clone(&started);
+		let supervisor = tokio::spawn(worker_pool_supervisor(
+			pool.clone(),
+			MockPaymentQueue::default(),
+			concurrency_limiter(),
+			move |_shutdown| {
+				let started = Arc::clone(&started_workers);
+				async move {
+					started.fetch_add(1, Ordering::SeqCst);
+				}
+			},
+		));
+
+		// Restarted after 100, 200 and 400ms rather than every 10ms tick.
+		tokio::time::sleep(Duration::from_secs(1)).await;
+		supervisor.abort();
+
+		let started = started.load(Ordering::SeqCst);
+		assert!((3..=6).contains(&started), "{started} workers started");
+	}
+}
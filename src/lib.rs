@@ -2,75 +2,237 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use actix_web::{App, HttpServer, web};
-use log::info;
 use reqwest::Client;
 use tokio::sync::mpsc;
+use tracing::{error, info};
 
 pub mod adapters;
 pub mod domain;
 pub mod infrastructure;
 pub mod use_cases;
 
-use crate::adapters::web::handlers::{payments, payments_purge, payments_summary};
-use crate::domain::payment::Payment;
+use crate::adapters::web::admin_auth::{AdminToken, admin_scope};
+use crate::adapters::web::handlers::{
+	health_live, health_ready, metrics, payments, payments_search, payments_summary,
+};
+use crate::domain::fee_schedule::FeeSchedule;
 use crate::domain::payment_producer::PaymentProducer;
 use crate::infrastructure::config::redis::Redis;
+use crate::infrastructure::config::routing_reloader::RoutingConfigReloader;
 use crate::infrastructure::config::settings::Config;
+use crate::infrastructure::health::readiness_probe::ReadinessProbe;
+use crate::infrastructure::metrics::registry::MetricsRegistry;
 use crate::infrastructure::persistence::redis_payment_repository::RedisPaymentRepository;
+use crate::infrastructure::queue::admission_controller::AdmissionController;
+use crate::infrastructure::queue::buffered_payment::BufferedPayment;
 use crate::infrastructure::queue::mpsc_payment_producer::MpscPaymentProducer;
 use crate::infrastructure::queue::redis_payment_queue::PaymentQueue;
 use crate::infrastructure::routing::in_memory_payment_router::InMemoryPaymentRouter;
+use crate::infrastructure::routing::processor_concurrency_limiter::ProcessorConcurrencyLimiter;
+use crate::infrastructure::wal::write_ahead_log::WriteAheadLog;
+use crate::infrastructure::workers::config_reload_worker::config_reload_worker;
 use crate::infrastructure::workers::payment_processor_worker::payment_processing_worker;
+use crate::infrastructure::workers::payment_retention_worker::payment_retention_worker;
+use crate::infrastructure::workers::processor_fee_rate_worker::processor_fee_rate_worker;
 use crate::infrastructure::workers::processor_health_monitor_worker::processor_health_monitor_worker;
+use crate::infrastructure::workers::queue_depth_monitor_worker::queue_depth_monitor_worker;
+use crate::infrastructure::workers::worker_pool::WorkerPool;
+use crate::infrastructure::workers::worker_pool_supervisor::worker_pool_supervisor;
+use crate::use_cases::export_payments::ExportPaymentsUseCase;
 use crate::use_cases::get_payment_summary::GetPaymentSummaryUseCase;
 use crate::use_cases::process_payment::ProcessPaymentUseCase;
 use crate::use_cases::purge_payments::PurgePaymentsUseCase;
+use crate::use_cases::search_payments::SearchPaymentsUseCase;
+use crate::use_cases::snapshot::SnapshotUseCase;
+
+/// Payments indexed per round trip while backfilling the timestamp and
+/// per-processor indexes.
+const INDEX_BACKFILL_BATCH_SIZE: usize = 500;
 
 pub async fn run(
 	config: Arc<Config>,
-	payment_sender: mpsc::Sender<Payment>,
+	payment_sender: mpsc::Sender<BufferedPayment>,
 	redis: Arc<Redis>,
+	write_ahead_log: Option<WriteAheadLog>,
+	metrics_registry: MetricsRegistry,
 ) -> std::io::Result<()> {
-	env_logger::init();
-
 	let http_client = Client::new();
 
 	let in_memory_router = InMemoryPaymentRouter::new(
 		config.get_default_key(),
 		config.get_fallback_key(),
-	);
+	)
+	.with_routing_options(config.get_routing_options());
 
 	info!("Starting health check worker...");
 	tokio::spawn(processor_health_monitor_worker(
 		in_memory_router.clone(),
 		http_client.clone(),
+		config.get_health_check_interval(),
 	));
 
+	let fee_schedule = FeeSchedule::new(config.get_fee_rates());
+	if let Some(processor_admin_token) = &config.processor_admin_token {
+		info!("Starting processor fee rate worker...");
+		tokio::spawn(processor_fee_rate_worker(
+			in_memory_router.clone(),
+			http_client.clone(),
+			fee_schedule.clone(),
+			processor_admin_token.to_string(),
+			config.get_fee_refresh_interval(),
+		));
+	}
+
 	let process_payment_use_case = ProcessPaymentUseCase::new(
-		RedisPaymentRepository::new(Arc::clone(&redis)).clone(),
+		RedisPaymentRepository::new(Arc::clone(&redis))
+			.with_keys(config.get_redis_keys())
+			.with_fee_schedule(fee_schedule.clone())
+			.with_summary_timestamp(config.get_summary_timestamp()),
 		http_client.clone(),
 	);
 
-	info!("Starting payment processing workers...");
-	for _ in 0..config.payment_processor_worker_count {
-		let redis_for_worker =
-			Arc::new(Redis::new(config.redis_url.as_ref()).await.unwrap());
+	let concurrency_limiter =
+		ProcessorConcurrencyLimiter::new(config.get_concurrency_options());
+	metrics_registry.register(Arc::new(concurrency_limiter.clone()));
 
-		tokio::spawn(payment_processing_worker(
-			PaymentQueue::new(Arc::clone(&redis_for_worker)),
-			RedisPaymentRepository::new(Arc::clone(&redis_for_worker)),
-			process_payment_use_case.clone(),
-			in_memory_router.clone(),
+	let worker_pool = WorkerPool::new(config.get_worker_pool_options());
+	metrics_registry.register(Arc::new(worker_pool.clone()));
+	metrics_registry.register(Arc::new(redis.metrics().clone()));
+
+	info!("Starting payment processing worker pool supervisor...");
+	let worker_config = Arc::clone(&config);
+	let worker_router = in_memory_router.clone();
+	let worker_limiter = concurrency_limiter.clone();
+	let worker_redis_metrics = redis.metrics().clone();
+	let supervised_pool = worker_pool.clone();
+	tokio::spawn(worker_pool_supervisor(
+		worker_pool.clone(),
+		PaymentQueue::new(Arc::clone(&redis)).with_keys(config.get_redis_keys()),
+		concurrency_limiter,
+		move |shutdown| {
+			let config = Arc::clone(&worker_config);
+			let process_payment_use_case = process_payment_use_case.clone();
+			let router = worker_router.clone();
+			let concurrency_limiter = worker_limiter.clone();
+			let redis_metrics = worker_redis_metrics.clone();
+			let pool = supervised_pool.clone();
+			async move {
+				let redis_for_worker = match Redis::connect(
+					&config.get_redis_options(),
+					redis_metrics.clone(),
+				)
+				.await
+				{
+					Ok(redis) => Arc::new(redis),
+					Err(e) => {
+						error!("Failed to connect payment processing worker: {e}");
+						return;
+					}
+				};
+
+				payment_processing_worker(
+					PaymentQueue::new(Arc::clone(&redis_for_worker))
+						.with_keys(config.get_redis_keys()),
+					RedisPaymentRepository::new(Arc::clone(&redis_for_worker))
+						.with_keys(config.get_redis_keys()),
+					process_payment_use_case,
+					router,
+					concurrency_limiter,
+					pool,
+					config.get_processor_batch_size(),
+					shutdown,
+				)
+				.await
+			}
+		},
+	));
+
+	let config_reloader = RoutingConfigReloader::new(
+		in_memory_router.clone(),
+		worker_pool.clone(),
+		&config,
+	);
+	info!("Starting configuration reload worker...");
+	tokio::spawn(config_reload_worker(config_reloader.clone()));
+
+	let payment_repo = RedisPaymentRepository::new(Arc::clone(&redis))
+		.with_keys(config.get_redis_keys())
+		.with_summary_timestamp(config.get_summary_timestamp());
+	// Indexing a payment twice is harmless, so every instance may run it.
+	let backfill_repo = payment_repo.clone();
+	tokio::spawn(async move {
+		match backfill_repo
+			.backfill_indexes(INDEX_BACKFILL_BATCH_SIZE)
+			.await
+		{
+			Ok(0) => {}
+			Ok(backfilled) => {
+				info!("Indexed {backfilled} older payments")
+			}
+			Err(e) => error!("Failed to index older payments: {e}"),
+		}
+	});
+	// Every instance may run it: each batch is compacted atomically.
+	if let Some(retention_options) = config.get_retention_options() {
+		info!("Starting payment retention worker...");
+		tokio::spawn(payment_retention_worker(
+			payment_repo.clone(),
+			retention_options,
+		));
+	}
+	let admission_controller =
+		AdmissionController::new(config.get_admission_options());
+	if admission_controller.tracks_queue_length() {
+		info!("Starting queue depth monitor worker...");
+		tokio::spawn(queue_depth_monitor_worker(
+			PaymentQueue::new(Arc::clone(&redis)).with_keys(config.get_redis_keys()),
+			admission_controller.clone(),
 		));
 	}
 
-	let payment_repo = RedisPaymentRepository::new(Arc::clone(&redis));
-	let payment_producer = MpscPaymentProducer::new(payment_sender);
+	let readiness_probe = ReadinessProbe::new(
+		Arc::clone(&redis),
+		payment_sender.clone(),
+		worker_pool.clone(),
+		in_memory_router.clone(),
+		config.get_max_buffer_saturation(),
+	);
+
+	let mut payment_producer = MpscPaymentProducer::new(payment_sender)
+		.with_admission_controller(admission_controller);
+	if let Some(write_ahead_log) = write_ahead_log {
+		info!("Write-ahead log enabled for the ingress buffer");
+		payment_producer = payment_producer.with_write_ahead_log(write_ahead_log);
+	}
 	let get_payment_summary_use_case =
-		GetPaymentSummaryUseCase::new(payment_repo.clone());
+		GetPaymentSummaryUseCase::new(payment_repo.clone())
+			.with_fee_schedule(fee_schedule)
+			.with_max_window(
+				time::Duration::try_from(config.get_summary_max_window())
+					.unwrap_or(time::Duration::MAX),
+			)
+			.with_consistency_timeout(config.get_summary_consistency_timeout());
+	let get_payment_summary_use_case = match config.get_retention_options() {
+		Some(retention_options) => get_payment_summary_use_case.with_compaction(
+			time::Duration::try_from(retention_options.max_age)
+				.unwrap_or(time::Duration::MAX),
+			time::Duration::try_from(retention_options.bucket)
+				.unwrap_or(time::Duration::MAX),
+		),
+		None => get_payment_summary_use_case,
+	};
 	let purge_payments_use_case = PurgePaymentsUseCase::new(payment_repo.clone());
+	let export_payments_use_case = ExportPaymentsUseCase::new(payment_repo.clone());
+	let search_payments_use_case = SearchPaymentsUseCase::new(payment_repo.clone());
+	let snapshot_use_case = SnapshotUseCase::new(payment_repo.clone());
+
+	let admin_token = AdminToken::new(config.admin_token.as_deref());
+	if config.admin_token.is_none() {
+		info!("No admin token configured, the /admin API is disabled");
+	}
 
-	info!("Starting Actix-Web server on 0.0.0.0:9999...");
+	let bind_address = config.get_bind_address().to_string();
+	info!("Starting Actix-Web server on {bind_address}...");
 	HttpServer::new(move || {
 		App::new()
 			.app_data(web::Data::new(
@@ -78,12 +240,24 @@ pub async fn run(
 			))
 			.app_data(web::Data::new(get_payment_summary_use_case.clone()))
 			.app_data(web::Data::new(purge_payments_use_case.clone()))
+			.app_data(web::Data::new(export_payments_use_case.clone()))
+			.app_data(web::Data::new(search_payments_use_case.clone()))
+			.app_data(web::Data::new(snapshot_use_case.clone()))
+			.app_data(web::Data::new(metrics_registry.clone()))
+			.app_data(web::Data::new(worker_pool.clone()))
+			.app_data(web::Data::new(readiness_probe.clone()))
+			.app_data(web::Data::new(config_reloader.clone()))
+			.app_data(web::Data::new(admin_token.clone()))
 			.service(payments)
+			.service(payments_search)
 			.service(payments_summary)
-			.service(payments_purge)
+			.service(metrics)
+			.service(health_live)
+			.service(health_ready)
+			.service(admin_scope())
 	})
 	.keep_alive(Duration::from_secs(config.server_keepalive))
-	.bind(("0.0.0.0", 9999))?
+	.bind(bind_address)?
 	.run()
 	.await
 }
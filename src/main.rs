@@ -1,15 +1,40 @@
+use std::io::{Read, Write};
 use std::sync::Arc;
 
 #[cfg(feature = "perf")]
 use pprof::flamegraph::Options;
-use rinha_de_backend::domain::payment::Payment;
-use rinha_de_backend::infrastructure::config::redis::Redis;
+use rinha_de_backend::adapters::cli::export_command::{
+	EXPORT_USAGE, export_payments, parse_export_args,
+};
+use rinha_de_backend::adapters::cli::migrate_command::{
+	MIGRATE_USAGE, migrate_payments, parse_migrate_args,
+};
+use rinha_de_backend::adapters::cli::snapshot_command::{
+	RESTORE_USAGE, SNAPSHOT_USAGE, parse_file_arg, restore_snapshot, write_snapshot,
+};
+use rinha_de_backend::domain::payment::PaymentTimestamp;
+use rinha_de_backend::infrastructure::config::redis::{
+	Redis, RedisKeys, RedisMode, RedisOptions,
+};
 use rinha_de_backend::infrastructure::config::settings::Config;
+use rinha_de_backend::infrastructure::metrics::ingress_metrics::IngressMetrics;
+use rinha_de_backend::infrastructure::metrics::redis_metrics::RedisConnectionMetrics;
+use rinha_de_backend::infrastructure::metrics::registry::MetricsRegistry;
+use rinha_de_backend::infrastructure::persistence::redis_payment_repository::RedisPaymentRepository;
+use rinha_de_backend::infrastructure::persistence::redis_scripts::RedisScripts;
+use rinha_de_backend::infrastructure::queue::buffered_payment::BufferedPayment;
 use rinha_de_backend::infrastructure::queue::redis_payment_queue::PaymentQueue;
+use rinha_de_backend::infrastructure::telemetry::subscriber::init_telemetry;
+use rinha_de_backend::infrastructure::wal::record::WalRecord;
+use rinha_de_backend::infrastructure::wal::write_ahead_log::WriteAheadLog;
 use rinha_de_backend::infrastructure::workers::mpsc_to_redis_worker::mpsc_to_redis_worker;
 use rinha_de_backend::run;
 use rinha_de_backend::use_cases::create_payment::CreatePaymentUseCase;
+use rinha_de_backend::use_cases::export_payments::ExportPaymentsUseCase;
+use rinha_de_backend::use_cases::migrate_payments::MigratePaymentsUseCase;
+use rinha_de_backend::use_cases::snapshot::SnapshotUseCase;
 use tokio::sync::mpsc;
+use tracing::{error, warn};
 
 #[global_allocator]
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
@@ -23,20 +48,89 @@ async fn main() -> std::io::Result<()> {
 		.build()
 		.unwrap();
 
-	let config = Arc::new(Config::load().expect("Failed to load configuration"));
-	let redis = Arc::new(Redis::new(config.redis_url.as_ref()).await.unwrap());
+	// Commands run before telemetry starts, so no log line lands in their output.
+	let mut args = std::env::args().skip(1);
+	let command = args.next();
+	if let Some(command @ ("export" | "snapshot" | "restore" | "migrate")) =
+		command.as_deref()
+	{
+		let config = load_config(Config::load_for_command());
+		return match command {
+			"export" => export_command(&config, args).await,
+			"snapshot" => snapshot_command(&config, args).await,
+			"restore" => restore_command(&config, args).await,
+			_ => migrate_command(&config, args).await,
+		};
+	}
+	let config = Arc::new(load_config(Config::load()));
+
+	let _telemetry_guard = init_telemetry(&config.get_telemetry_options())
+		.expect("Failed to initialise telemetry");
+	let redis = match Redis::connect(
+		&config.get_redis_options(),
+		RedisConnectionMetrics::new(),
+	)
+	.await
+	{
+		Ok(redis) => Arc::new(redis),
+		Err(e) => {
+			error!("Failed to connect to Redis: {e}");
+			std::process::exit(1);
+		}
+	};
 
-	let payment_queue = PaymentQueue::new(Arc::clone(&redis));
+	if let Err(e) = RedisScripts::load(&mut redis.connection.as_ref().clone()).await
+	{
+		warn!("Failed to preload Redis scripts, loading them on first use: {e}");
+	}
+
+	let payment_queue =
+		PaymentQueue::new(Arc::clone(&redis)).with_keys(config.get_redis_keys());
 	let create_payment_use_case = CreatePaymentUseCase::new(payment_queue.clone());
 
-	let (payment_sender, payment_receiver) = mpsc::channel::<Payment>(100_000);
+	let (payment_sender, payment_receiver) =
+		mpsc::channel::<BufferedPayment>(config.get_ingress_buffer_capacity());
+
+	let (write_ahead_log, pending_records) = match config.get_wal_options() {
+		Some(options) => {
+			let (write_ahead_log, pending_records) = WriteAheadLog::open(options)?;
+			(Some(write_ahead_log), pending_records)
+		}
+		None => (None, Vec::new()),
+	};
+
+	let metrics_registry = MetricsRegistry::new();
+	let ingress_metrics = IngressMetrics::new();
+	metrics_registry.register(Arc::new(ingress_metrics.clone()));
 
 	tokio::spawn(mpsc_to_redis_worker(
 		payment_receiver,
 		create_payment_use_case.clone(),
+		write_ahead_log.clone(),
+		config.get_batch_options(),
+		ingress_metrics,
 	));
 
-	let result = run(config.clone(), payment_sender, redis).await;
+	if let Some(write_ahead_log) = &write_ahead_log {
+		tokio::spawn(replay_write_ahead_log(
+			pending_records,
+			payment_sender.clone(),
+			write_ahead_log.clone(),
+		));
+	}
+
+	let result = run(
+		config.clone(),
+		payment_sender,
+		redis,
+		write_ahead_log.clone(),
+		metrics_registry,
+	)
+	.await;
+
+	if let Some(write_ahead_log) = &write_ahead_log {
+		write_ahead_log.sync()?;
+	}
 
 	#[cfg(feature = "perf")]
 	if let Ok(report) = guard.report().build() {
@@ -54,3 +148,235 @@ async fn main() -> std::io::Result<()> {
 
 	result
 }
+
+fn load_config(config: Result<Config, config::ConfigError>) -> Config {
+	config.unwrap_or_else(|e| {
+		eprintln!("Failed to load configuration: {e}");
+		std::process::exit(1);
+	})
+}
+
+/// Writes the processed payments of a window to stdout, see
+/// `parse_export_args`.
+async fn export_command(
+	config: &Config,
+	args: impl Iterator<Item = String>,
+) -> std::io::Result<()> {
+	let query = match parse_export_args(args) {
+		Ok(query) => query,
+		Err(e) => {
+			eprintln!("{e}\n{EXPORT_USAGE}");
+			std::process::exit(2);
+		}
+	};
+	let export_payments_use_case =
+		ExportPaymentsUseCase::new(command_repository(config).await);
+	let mut stdout = std::io::BufWriter::new(std::io::stdout());
+	if let Err(e) =
+		export_payments(&export_payments_use_case, query, &mut stdout).await
+	{
+		eprintln!("Failed to export payments: {e}");
+		std::process::exit(1);
+	}
+	Ok(())
+}
+
+/// Writes a snapshot of the store to stdout or the `--output` file.
+async fn snapshot_command(
+	config: &Config,
+	args: impl Iterator<Item = String>,
+) -> std::io::Result<()> {
+	let output = match parse_file_arg(args, "--output") {
+		Ok(output) => output,
+		Err(e) => {
+			eprintln!("{e}\n{SNAPSHOT_USAGE}");
+			std::process::exit(2);
+		}
+	};
+	let out: Box<dyn Write> = match output {
+		Some(path) => Box::new(std::fs::File::create(path)?),
+		None => Box::new(std::io::stdout()),
+	};
+
+	let snapshot_use_case = SnapshotUseCase::new(command_repository(config).await);
+	let mut out = std::io::BufWriter::new(out);
+	if let Err(e) = write_snapshot(&snapshot_use_case, &mut out).await {
+		eprintln!("Failed to snapshot payments: {e}");
+		std::process::exit(1);
+	}
+	Ok(())
+}
+
+/// Replaces the store's content with the snapshot read from stdin or the
+/// `--input` file.
+async fn restore_command(
+	config: &Config,
+	args: impl Iterator<Item = String>,
+) -> std::io::Result<()> {
+	let input = match parse_file_arg(args, "--input") {
+		Ok(input) => input,
+		Err(e) => {
+			eprintln!("{e}\n{RESTORE_USAGE}");
+			std::process::exit(2);
+		}
+	};
+	let mut input: Box<dyn Read> = match input {
+		Some(path) => Box::new(std::fs::File::open(path)?),
+		None => Box::new(std::io::stdin()),
+	};
+
+	let snapshot_use_case = SnapshotUseCase::new(command_repository(config).await);
+	match restore_snapshot(&snapshot_use_case, &mut input).await {
+		Ok(result) => {
+			eprintln!(
+				"Restored {} payments, {} queued messages and {} buckets",
+				result.payments, result.queued, result.buckets
+			);
+			Ok(())
+		}
+		Err(e) => {
+			eprintln!("Failed to restore payments: {e}");
+			std::process::exit(1);
+		}
+	}
+}
+
+/// Copies the configured store to another Redis or key layout, see
+/// `parse_migrate_args`. Exits with 1 when the stores differ afterwards.
+async fn migrate_command(
+	config: &Config,
+	args: impl Iterator<Item = String>,
+) -> std::io::Result<()> {
+	let args = match parse_migrate_args(args) {
+		Ok(args) => args,
+		Err(e) => {
+			eprintln!("{e}\n{MIGRATE_USAGE}");
+			std::process::exit(2);
+		}
+	};
+	let source_options = config.get_redis_options();
+	let target_options = RedisOptions {
+		nodes: args
+			.target_url
+			.split(',')
+			.map(str::trim)
+			.filter(|node| !node.is_empty())
+			.map(str::to_string)
+			.collect(),
+		..source_options.clone()
+	};
+	let target_keys = match &args.target_prefix {
+		Some(prefix) => RedisKeys::new(
+			prefix,
+			args.target_hash_tag || source_options.mode == RedisMode::Cluster,
+		)
+		.with_payments_queue(config.get_payments_queue_key()),
+		None => config.get_redis_keys(),
+	};
+	if target_options.nodes == source_options.nodes &&
+		target_keys == config.get_redis_keys()
+	{
+		eprintln!("The target is the configured store itself\n{MIGRATE_USAGE}");
+		std::process::exit(2);
+	}
+	// Payments compacted once copied would be counted twice by the target.
+	if config.get_retention_options().is_some() {
+		eprintln!(
+			"Retention must be off while migrating: unset retention_max_age_ms \
+			 here and on every instance"
+		);
+		std::process::exit(2);
+	}
+
+	// Verification compares summaries by the timestamp the walk follows.
+	let source = command_repository(config)
+		.await
+		.with_summary_timestamp(PaymentTimestamp::Submitted);
+	let target = RedisPaymentRepository::new(command_redis(&target_options).await)
+		.with_keys(target_keys)
+		.with_summary_timestamp(PaymentTimestamp::Submitted);
+	let mut migrate_payments_use_case = MigratePaymentsUseCase::new(source, target);
+	if let Some(batch_size) = args.batch_size {
+		migrate_payments_use_case =
+			migrate_payments_use_case.with_batch_size(batch_size);
+	}
+
+	match migrate_payments(
+		&migrate_payments_use_case,
+		&args.checkpoint,
+		args.restart,
+	)
+	.await
+	{
+		Ok((checkpoint, report)) => {
+			eprintln!(
+				"Copied {} payments and {} buckets, and moved {} queued messages, \
+				 since the migration started",
+				checkpoint.payments, checkpoint.buckets, checkpoint.queued
+			);
+			for group in &report.groups {
+				eprintln!(
+					"{}: {} payments for {:.2} in the source, {} for {:.2} in the \
+					 target",
+					group.group,
+					group.source.total_requests,
+					group.source.total_amount,
+					group.target.total_requests,
+					group.target.total_amount
+				);
+			}
+			if !report.matches() {
+				eprintln!("The stores differ up to {}", report.up_to);
+				std::process::exit(1);
+			}
+			Ok(())
+		}
+		Err(e) => {
+			eprintln!("Failed to migrate payments: {e}");
+			std::process::exit(1);
+		}
+	}
+}
+
+/// The repository commands read and write, exiting when Redis is unreachable.
+async fn command_repository(config: &Config) -> RedisPaymentRepository {
+	RedisPaymentRepository::new(command_redis(&config.get_redis_options()).await)
+		.with_keys(config.get_redis_keys())
+		.with_summary_timestamp(config.get_summary_timestamp())
+}
+
+async fn command_redis(options: &RedisOptions) -> Arc<Redis> {
+	match Redis::connect(options, RedisConnectionMetrics::new()).await {
+		Ok(redis) => Arc::new(redis),
+		Err(e) => {
+			eprintln!("Failed to connect to Redis: {e}");
+			std::process::exit(1);
+		}
+	}
+}
+
+async fn replay_write_ahead_log(
+	pending_records: Vec<WalRecord>,
+	payment_sender: mpsc::Sender<BufferedPayment>,
+	write_ahead_log: WriteAheadLog,
+) {
+	for record in pending_records {
+		match BufferedPayment::from_wal_record(&record) {
+			Ok(buffered_payment) => {
+				if payment_sender.send(buffered_payment).await.is_err() {
+					error!("Payment channel closed while replaying write-ahead log");
+					return;
+				}
+			}
+			Err(e) => {
+				error!(
+					"Discarding unreadable write-ahead log record {}: {e}",
+					record.sequence
+				);
+				if let Err(e) = write_ahead_log.commit(&[record.sequence]) {
+					error!("Failed to truncate write-ahead log: {e}");
+				}
+			}
+		}
+	}
+}
@@ -1,5 +1,5 @@
 use crate::domain::payment::Payment;
-use crate::domain::queue::{Message, Queue};
+use crate::domain::queue::{Message, Queue, TraceContext};
 
 #[derive(Clone)]
 pub struct CreatePaymentUseCase<Q: Queue<Payment>> {
@@ -19,4 +19,21 @@ impl<Q: Queue<Payment>> CreatePaymentUseCase<Q> {
 			.push(Message::with(payment.correlation_id, payment))
 			.await
 	}
+
+	pub async fn execute_batch(
+		&self,
+		payments: Vec<(Payment, TraceContext)>,
+	) -> Result<(), Box<dyn std::error::Error + Send>> {
+		self.payment_queue
+			.push_batch(
+				payments
+					.into_iter()
+					.map(|(payment, trace_context)| {
+						Message::with(payment.correlation_id, payment)
+							.with_trace_context(trace_context)
+					})
+					.collect(),
+			)
+			.await
+	}
 }
@@ -2,30 +2,294 @@ use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 use uuid::Uuid;
 
+use crate::domain::payment::{Payment, PaymentStatus};
+use crate::domain::queue::Message;
+use crate::domain::repository::SortOrder;
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct CreatePaymentCommand {
 	pub correlation_id: Uuid,
 	pub amount:         f64,
 }
 
+/// Where the fees of a payments summary take their rates from.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FeeBasis {
+	/// The rates in effect now, applied to every payment.
+	Current,
+	/// The rate each payment was stamped with when it was processed.
+	Processing,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct GetPaymentSummaryQuery {
-	pub from: Option<OffsetDateTime>,
-	pub to:   Option<OffsetDateTime>,
+	pub from:       Option<OffsetDateTime>,
+	pub to:         Option<OffsetDateTime>,
+	/// Adds fees, net amounts and fee rates to the summary.
+	pub fees:       Option<FeeBasis>,
+	/// Waits for the payments in flight within the window to settle first.
+	pub consistent: bool,
+}
+
+/// Encoding of a payments export.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+	#[default]
+	Csv,
+	/// One JSON object per line.
+	Ndjson,
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ExportPaymentsQuery {
+	pub from:   Option<OffsetDateTime>,
+	pub to:     Option<OffsetDateTime>,
+	pub format: ExportFormat,
+}
+
+/// A payment as exported, one per CSV row or NDJSON line, or as found by a
+/// search. In-flight payments carry the processor they were sent to.
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PaymentRecord {
+	#[serde(rename = "correlationId")]
+	pub correlation_id: Uuid,
+	#[serde(rename = "processedBy")]
+	pub processed_by:   String,
+	pub amount:         f64,
+	#[serde(rename = "receivedAt", with = "time::serde::rfc3339::option")]
+	pub received_at:    Option<OffsetDateTime>,
+	#[serde(rename = "submittedAt", with = "time::serde::rfc3339::option")]
+	pub submitted_at:   Option<OffsetDateTime>,
+	#[serde(rename = "processedAt", with = "time::serde::rfc3339::option")]
+	pub processed_at:   Option<OffsetDateTime>,
+}
+
+impl From<Payment> for PaymentRecord {
+	fn from(payment: Payment) -> Self {
+		Self {
+			correlation_id: payment.correlation_id,
+			processed_by:   payment.processed_by.unwrap_or_default(),
+			amount:         payment.amount,
+			received_at:    payment.received_at,
+			submitted_at:   payment.submitted_at,
+			processed_at:   payment.processed_at,
+		}
+	}
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct SearchPaymentsQuery {
+	pub processor:  Option<String>,
+	pub status:     PaymentStatus,
+	pub from:       Option<OffsetDateTime>,
+	pub to:         Option<OffsetDateTime>,
+	pub min_amount: Option<f64>,
+	pub max_amount: Option<f64>,
+	pub order:      SortOrder,
+	/// The `nextCursor` of the previous page.
+	pub cursor:     Option<String>,
+	pub limit:      Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct SearchPaymentsResponse {
+	pub payments:    Vec<PaymentRecord>,
+	/// Unset on the last page.
+	#[serde(rename = "nextCursor")]
+	pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct PurgePaymentsCommand {
+	pub group:          Option<String>,
+	pub from:           Option<OffsetDateTime>,
+	pub to:             Option<OffsetDateTime>,
+	/// Whether to keep the queued and in-flight payments. Unset, they are
+	/// kept by purges scoped with any filter and dropped by full ones.
+	pub preserve_queue: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PurgePaymentsResult {
+	#[serde(rename = "deletedPayments")]
+	pub deleted_payments: usize,
+	#[serde(rename = "queuePurged")]
+	pub queue_purged:     bool,
+}
+
+/// What a restore wrote, by record type.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+pub struct RestoreSnapshotResult {
+	pub payments: usize,
+	pub queued:   usize,
+	pub buckets:  usize,
+}
+
+/// Where a migration stands. Saved after every step, so a stopped migration
+/// resumes from it.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct MigrationCheckpoint {
+	/// Where the next page of processed payments starts, `None` before the
+	/// first one.
+	pub position:   Option<f64>,
+	pub skip:       usize,
+	pub payments:   usize,
+	pub queued:     usize,
+	pub buckets:    usize,
+	/// Messages taken off the source queue and not written to the target
+	/// yet.
+	#[serde(rename = "inTransit", default)]
+	pub in_transit: Vec<Message<Payment>>,
+	/// Whether the messages last written to the target are still held aside
+	/// by the source, to be dropped once this checkpoint is saved.
+	#[serde(rename = "toRelease", default)]
+	pub to_release: bool,
+	/// How many compacted payments the source's buckets held when the
+	/// migration started.
+	#[serde(default)]
+	pub compacted:  Option<u64>,
+}
+
+/// The summaries of a group in both stores, up to a migration's start.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct MigratedGroup {
+	pub group:  String,
+	pub source: PaymentSummaryResult,
+	pub target: PaymentSummaryResult,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct MigrationReport {
+	#[serde(rename = "upTo", with = "time::serde::rfc3339")]
+	pub up_to:  OffsetDateTime,
+	pub groups: Vec<MigratedGroup>,
+}
+
+impl MigrationReport {
+	/// Whether every group has as many payments, for the same amount, in
+	/// both stores.
+	pub fn matches(&self) -> bool {
+		let cents = |amount: f64| (amount * 100.0).round() as i64;
+		self.groups.iter().all(|group| {
+			group.source.total_requests == group.target.total_requests &&
+				cents(group.source.total_amount) ==
+					cents(group.target.total_amount)
+		})
+	}
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
 pub struct PaymentSummaryResult {
 	#[serde(rename = "totalRequests")]
 	pub total_requests: usize,
 	#[serde(rename = "totalAmount")]
 	pub total_amount:   f64,
+	#[serde(rename = "totalFee", skip_serializing_if = "Option::is_none", default)]
+	pub total_fee:      Option<f64>,
+	#[serde(rename = "netAmount", skip_serializing_if = "Option::is_none", default)]
+	pub net_amount:     Option<f64>,
+	/// Fees over gross amount.
+	#[serde(rename = "feeRate", skip_serializing_if = "Option::is_none", default)]
+	pub fee_rate:       Option<f64>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct PaymentsSummaryResponse {
-	pub default:  PaymentSummaryResult,
-	pub fallback: PaymentSummaryResult,
+	pub default:          PaymentSummaryResult,
+	pub fallback:         PaymentSummaryResult,
+	/// Fees over gross amount across both processors.
+	#[serde(
+		rename = "blendedFeeRate",
+		skip_serializing_if = "Option::is_none",
+		default
+	)]
+	pub blended_fee_rate: Option<f64>,
+	/// Payments of the window still in flight when a consistent summary
+	/// stopped waiting.
+	#[serde(
+		rename = "pendingPayments",
+		skip_serializing_if = "Option::is_none",
+		default
+	)]
+	pub pending_payments: Option<usize>,
+}
+
+/// Width of the buckets of a summary breakdown, aligned to UTC.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+	Second,
+	Minute,
+	Hour,
+	Day,
+}
+
+impl Granularity {
+	pub fn duration(self) -> time::Duration {
+		match self {
+			Self::Second => time::Duration::SECOND,
+			Self::Minute => time::Duration::MINUTE,
+			Self::Hour => time::Duration::HOUR,
+			Self::Day => time::Duration::DAY,
+		}
+	}
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct GetPaymentSummaryBreakdownQuery {
+	pub from:        Option<OffsetDateTime>,
+	pub to:          Option<OffsetDateTime>,
+	pub granularity: Granularity,
+	/// Waits for the payments in flight within the window to settle first.
+	pub consistent:  bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PaymentSummaryBucket {
+	#[serde(with = "time::serde::rfc3339")]
+	pub start:          OffsetDateTime,
+	#[serde(rename = "totalRequests")]
+	pub total_requests: usize,
+	#[serde(rename = "totalAmount")]
+	pub total_amount:   f64,
+}
+
+/// Nearest-rank percentiles of the payment amounts.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct AmountPercentiles {
+	pub min: f64,
+	pub p50: f64,
+	pub p90: f64,
+	pub p95: f64,
+	pub p99: f64,
+	pub max: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PaymentSummaryBreakdown {
+	#[serde(rename = "totalRequests")]
+	pub total_requests: usize,
+	#[serde(rename = "totalAmount")]
+	pub total_amount:   f64,
+	/// Only the buckets holding payments, oldest first.
+	pub buckets:        Vec<PaymentSummaryBucket>,
+	/// `None` without payments in the window.
+	pub percentiles:    Option<AmountPercentiles>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PaymentsSummaryBreakdownResponse {
+	pub granularity:      Granularity,
+	pub default:          PaymentSummaryBreakdown,
+	pub fallback:         PaymentSummaryBreakdown,
+	#[serde(
+		rename = "pendingPayments",
+		skip_serializing_if = "Option::is_none",
+		default
+	)]
+	pub pending_payments: Option<usize>,
 }
 
 #[cfg(test)]
@@ -37,14 +301,18 @@ mod tests {
 	#[test]
 	fn test_serialize_payments_summary_response() {
 		let summary = PaymentsSummaryResponse {
-			default:  PaymentSummaryResult {
+			default:          PaymentSummaryResult {
 				total_requests: 43236,
-				total_amount:   415542345.98,
+				total_amount: 415542345.98,
+				..Default::default()
 			},
-			fallback: PaymentSummaryResult {
+			fallback:         PaymentSummaryResult {
 				total_requests: 423545,
-				total_amount:   329347.34,
+				total_amount: 329347.34,
+				..Default::default()
 			},
+			blended_fee_rate: None,
+			pending_payments: None,
 		};
 
 		let serialized = serde_json::to_value(&summary).unwrap();
@@ -62,6 +330,29 @@ mod tests {
 		assert_eq!(serialized, expected);
 	}
 
+	#[test]
+	fn test_serialize_payments_summary_response_with_fees() {
+		let summary = PaymentsSummaryResponse {
+			default:          PaymentSummaryResult {
+				total_requests: 2,
+				total_amount:   100.0,
+				total_fee:      Some(5.0),
+				net_amount:     Some(95.0),
+				fee_rate:       Some(0.05),
+			},
+			fallback:         PaymentSummaryResult::default(),
+			blended_fee_rate: Some(0.05),
+			pending_payments: None,
+		};
+
+		let serialized = serde_json::to_value(&summary).unwrap();
+
+		assert_eq!(serialized["default"]["totalFee"], json!(5.0));
+		assert_eq!(serialized["default"]["netAmount"], json!(95.0));
+		assert_eq!(serialized["default"]["feeRate"], json!(0.05));
+		assert_eq!(serialized["blendedFeeRate"], json!(0.05));
+	}
+
 	#[test]
 	fn test_deserialize_payments_summary_response() {
 		let json = json!({
@@ -78,14 +369,18 @@ mod tests {
 		let deserialized: PaymentsSummaryResponse =
 			serde_json::from_value(json).unwrap();
 		let expected = PaymentsSummaryResponse {
-			default:  PaymentSummaryResult {
+			default:          PaymentSummaryResult {
 				total_requests: 43236,
-				total_amount:   415542345.98,
+				total_amount: 415542345.98,
+				..Default::default()
 			},
-			fallback: PaymentSummaryResult {
+			fallback:         PaymentSummaryResult {
 				total_requests: 423545,
-				total_amount:   329347.34,
+				total_amount: 329347.34,
+				..Default::default()
 			},
+			blended_fee_rate: None,
+			pending_payments: None,
 		};
 
 		assert_eq!(deserialized, expected);
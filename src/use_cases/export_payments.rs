@@ -0,0 +1,195 @@
+use std::error::Error;
+
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+use crate::domain::repository::{PaymentRepository, PaymentsCursor};
+use crate::use_cases::dto::{ExportFormat, ExportPaymentsQuery, PaymentRecord};
+use crate::use_cases::get_payment_summary::SummaryWindowError;
+
+/// Payments read from the repository per round trip, and so held in memory.
+const DEFAULT_PAGE_SIZE: usize = 500;
+const CSV_HEADER: &str =
+	"correlationId,processedBy,amount,receivedAt,submittedAt,processedAt\n";
+
+#[derive(Clone)]
+pub struct ExportPaymentsUseCase<R: PaymentRepository> {
+	payment_repo: R,
+	page_size:    usize,
+}
+
+impl<R: PaymentRepository + Clone> ExportPaymentsUseCase<R> {
+	pub fn new(payment_repo: R) -> Self {
+		Self {
+			payment_repo,
+			page_size: DEFAULT_PAGE_SIZE,
+		}
+	}
+
+	pub fn with_page_size(mut self, page_size: usize) -> Self {
+		self.page_size = page_size.max(1);
+		self
+	}
+
+	pub fn execute(
+		&self,
+		query: ExportPaymentsQuery,
+	) -> Result<PaymentExport<R>, Box<dyn Error + Send>> {
+		if let (Some(from), Some(to)) = (query.from, query.to) &&
+			from > to
+		{
+			return Err(Box::new(SummaryWindowError::Inverted { from, to }));
+		}
+
+		Ok(PaymentExport {
+			payment_repo: self.payment_repo.clone(),
+			query,
+			page_size: self.page_size,
+			cursor: None,
+			started: false,
+			finished: false,
+		})
+	}
+}
+
+/// An export in progress. Each call to `next_chunk` reads one page, so the
+/// window is never loaded into memory at once.
+pub struct PaymentExport<R: PaymentRepository> {
+	payment_repo: R,
+	query:        ExportPaymentsQuery,
+	page_size:    usize,
+	cursor:       Option<PaymentsCursor>,
+	started:      bool,
+	finished:     bool,
+}
+
+impl<R: PaymentRepository> PaymentExport<R> {
+	/// The CSV header first, then the encoded payments of each page. `None`
+	/// once the window is exhausted or after an error.
+	pub async fn next_chunk(
+		&mut self,
+	) -> Result<Option<Vec<u8>>, Box<dyn Error + Send>> {
+		if !self.started {
+			self.started = true;
+			if self.query.format == ExportFormat::Csv {
+				return Ok(Some(CSV_HEADER.as_bytes().to_vec()));
+			}
+		}
+
+		let mut chunk = Vec::new();
+		// Pages whose payments were all purged meanwhile encode to nothing.
+		while chunk.is_empty() && !self.finished {
+			let page = match self
+				.payment_repo
+				.get_processed_payments(
+					self.query.from,
+					self.query.to,
+					self.cursor,
+					self.page_size,
+				)
+				.await
+			{
+				Ok(page) => page,
+				Err(e) => {
+					self.finished = true;
+					return Err(e);
+				}
+			};
+			self.cursor = page.next;
+			self.finished = page.next.is_none();
+
+			for payment in page.payments {
+				encode(&mut chunk, &PaymentRecord::from(payment), self.query.format)
+					.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+			}
+		}
+
+		Ok((!chunk.is_empty()).then_some(chunk))
+	}
+}
+
+fn encode(
+	chunk: &mut Vec<u8>,
+	payment: &PaymentRecord,
+	format: ExportFormat,
+) -> Result<(), serde_json::Error> {
+	match format {
+		// Every field is a UUID, a group name, a number or a timestamp, none
+		// of which need quoting.
+		ExportFormat::Csv => {
+			let timestamp = |timestamp: Option<OffsetDateTime>| {
+				timestamp
+					.and_then(|ts| ts.format(&Rfc3339).ok())
+					.unwrap_or_default()
+			};
+			chunk.extend_from_slice(
+				format!(
+					"{},{},{:.2},{},{},{}\n",
+					payment.correlation_id,
+					payment.processed_by,
+					payment.amount,
+					timestamp(payment.received_at),
+					timestamp(payment.submitted_at),
+					timestamp(payment.processed_at),
+				)
+				.as_bytes(),
+			);
+		}
+		ExportFormat::Ndjson => {
+			serde_json::to_writer(&mut *chunk, payment)?;
+			chunk.push(b'\n');
+		}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use uuid::Uuid;
+
+	use super::*;
+
+	fn exported_payment() -> PaymentRecord {
+		let submitted_at =
+			OffsetDateTime::from_unix_timestamp(1_751_364_000).unwrap();
+		PaymentRecord {
+			correlation_id: Uuid::parse_str("7b3739e4-5be8-4f98-84a7-a13fd5984059")
+				.unwrap(),
+			processed_by:   "default".to_string(),
+			amount:         19.9,
+			received_at:    None,
+			submitted_at:   Some(submitted_at),
+			processed_at:   Some(submitted_at + time::Duration::milliseconds(250)),
+		}
+	}
+
+	#[test]
+	fn test_encode_csv_row() {
+		let mut chunk = Vec::new();
+		encode(&mut chunk, &exported_payment(), ExportFormat::Csv).unwrap();
+
+		assert_eq!(
+			String::from_utf8(chunk).unwrap(),
+			"7b3739e4-5be8-4f98-84a7-a13fd5984059,default,19.90,,2025-07-01T10:00:\
+			 00Z,2025-07-01T10:00:00.25Z\n"
+		);
+	}
+
+	#[test]
+	fn test_encode_ndjson_line() {
+		let mut chunk = Vec::new();
+		encode(&mut chunk, &exported_payment(), ExportFormat::Ndjson).unwrap();
+		encode(&mut chunk, &exported_payment(), ExportFormat::Ndjson).unwrap();
+
+		let lines: Vec<serde_json::Value> = chunk
+			.split(|byte| *byte == b'\n')
+			.filter(|line| !line.is_empty())
+			.map(|line| serde_json::from_slice(line).unwrap())
+			.collect();
+		assert_eq!(lines.len(), 2);
+		assert_eq!(lines[0]["processedBy"], "default");
+		assert_eq!(lines[0]["amount"], 19.9);
+		assert_eq!(lines[0]["receivedAt"], serde_json::Value::Null);
+		assert_eq!(lines[0]["submittedAt"], "2025-07-01T10:00:00Z");
+	}
+}
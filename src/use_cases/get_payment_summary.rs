@@ -1,52 +1,465 @@
-use std::ops::{Add, Sub};
+use std::collections::BTreeMap;
 
-use time::OffsetDateTime;
+use derive_more::derive::{Display, Error};
+use time::{Duration, OffsetDateTime};
+use tokio::time::{Instant, sleep};
 
-use crate::domain::repository::PaymentRepository;
+use crate::domain::fee_schedule::FeeSchedule;
+use crate::domain::repository::{
+	AmountsBreakdown, PaymentRepository, ProcessedFees,
+};
 use crate::use_cases::dto::{
-	GetPaymentSummaryQuery, PaymentSummaryResult, PaymentsSummaryResponse,
+	AmountPercentiles, FeeBasis, GetPaymentSummaryBreakdownQuery,
+	GetPaymentSummaryQuery, PaymentSummaryBreakdown, PaymentSummaryBucket,
+	PaymentSummaryResult, PaymentsSummaryBreakdownResponse, PaymentsSummaryResponse,
 };
 
+/// Buckets a breakdown returns at most per group, a week at minute
+/// granularity.
+const MAX_BREAKDOWN_BUCKETS: usize = 7 * 24 * 60;
+/// First and longest waits between two counts of the payments in flight
+/// while a consistent summary waits for them to settle.
+const SETTLE_MIN_BACKOFF: std::time::Duration = std::time::Duration::from_millis(5);
+const SETTLE_MAX_BACKOFF: std::time::Duration =
+	std::time::Duration::from_millis(100);
+
+/// A summary window the use case refuses to query.
+#[derive(Debug, Display, Error, PartialEq)]
+pub enum SummaryWindowError {
+	#[display("`from` ({from}) is after `to` ({to}).")]
+	Inverted {
+		from: OffsetDateTime,
+		to:   OffsetDateTime,
+	},
+	#[display("The window spans {length}, more than the maximum of {max}.")]
+	TooLong { length: Duration, max: Duration },
+	#[display(
+		"`{bound}` cuts through a bucket of compacted payments, bounds that old \
+		 must be multiples of {bucket}."
+	)]
+	Unaligned {
+		bound:  OffsetDateTime,
+		bucket: Duration,
+	},
+	#[display(
+		"The breakdown spans more than {max} buckets, use a shorter window or a \
+		 coarser granularity."
+	)]
+	TooManyBuckets { max: usize },
+	#[display(
+		"Breakdowns cannot reach before {before}, payments that old may be \
+		 compacted and only count in summaries without `granularity`."
+	)]
+	Compacted { before: OffsetDateTime },
+}
+
+/// Where compacted payments start and how wide their buckets are.
+#[derive(Debug, Clone, Copy)]
+struct Compaction {
+	max_age: Duration,
+	bucket:  Duration,
+}
+
 #[derive(Clone)]
 pub struct GetPaymentSummaryUseCase<R: PaymentRepository> {
-	payment_repo: R,
+	payment_repo:        R,
+	fee_schedule:        FeeSchedule,
+	max_window:          Option<Duration>,
+	compaction:          Option<Compaction>,
+	consistency_timeout: std::time::Duration,
 }
 
 impl<R: PaymentRepository> GetPaymentSummaryUseCase<R> {
 	pub fn new(payment_repo: R) -> Self {
-		Self { payment_repo }
+		Self {
+			payment_repo,
+			fee_schedule: FeeSchedule::default(),
+			max_window: None,
+			compaction: None,
+			consistency_timeout: std::time::Duration::ZERO,
+		}
+	}
+
+	/// Bounds how long consistent summaries wait for in-flight payments, which
+	/// they otherwise only report.
+	pub fn with_consistency_timeout(
+		mut self,
+		consistency_timeout: std::time::Duration,
+	) -> Self {
+		self.consistency_timeout = consistency_timeout;
+		self
+	}
+
+	/// Rejects windows bounded on both sides that span more than
+	/// `max_window`. Windows open on either side are not limited.
+	pub fn with_max_window(mut self, max_window: Duration) -> Self {
+		self.max_window = Some(max_window);
+		self
+	}
+
+	/// Summaries only count the buckets of payments compacted past `max_age`
+	/// that lie entirely within their window, so bounds older than that must
+	/// fall on `bucket` edges. Breakdowns, which need every payment's amount,
+	/// refuse windows starting before `max_age`.
+	pub fn with_compaction(mut self, max_age: Duration, bucket: Duration) -> Self {
+		self.compaction = Some(Compaction { max_age, bucket });
+		self
+	}
+
+	pub fn with_fee_schedule(mut self, fee_schedule: FeeSchedule) -> Self {
+		self.fee_schedule = fee_schedule;
+		self
 	}
 
 	pub async fn execute(
 		&self,
 		query: GetPaymentSummaryQuery,
 	) -> Result<PaymentsSummaryResponse, Box<dyn std::error::Error + Send>> {
-		let from = query
-			.from
-			.unwrap_or(OffsetDateTime::now_utc().sub(time::Duration::days(30)));
-		let to = query
-			.to
-			.unwrap_or(OffsetDateTime::now_utc().add(time::Duration::days(30)));
-
-		let (default_total_requests, default_total_amount) = self
+		let (from, to) = self.window(query.from, query.to)?;
+		let pending_payments = self.settle(query.consistent, from, to).await?;
+
+		// The fees come with the totals, so both cover the same payments.
+		let default = self
 			.payment_repo
-			.get_summary_by_group("default", from, to)
+			.get_summary_with_fees_by_group("default", from, to)
 			.await?;
-
-		let (fallback_total_requests, fallback_total_amount) = self
+		let fallback = self
 			.payment_repo
-			.get_summary_by_group("fallback", from, to)
+			.get_summary_with_fees_by_group("fallback", from, to)
 			.await?;
 
-		Ok(PaymentsSummaryResponse {
-			default:  PaymentSummaryResult {
-				total_requests: default_total_requests,
-				total_amount:   default_total_amount,
+		let mut summary = PaymentsSummaryResponse {
+			default: PaymentSummaryResult {
+				total_requests: default.total_requests,
+				total_amount: default.total_amount,
+				..Default::default()
 			},
 			fallback: PaymentSummaryResult {
-				total_requests: fallback_total_requests,
-				total_amount:   fallback_total_amount,
+				total_requests: fallback.total_requests,
+				total_amount: fallback.total_amount,
+				..Default::default()
 			},
+			blended_fee_rate: None,
+			pending_payments,
+		};
+		if let Some(fee_basis) = query.fees {
+			add_fees(&mut summary, &self.fee_schedule, fee_basis, [
+				default.fees,
+				fallback.fees,
+			]);
+		}
+		Ok(summary)
+	}
+
+	/// Gives the payments in flight within the window the consistency timeout
+	/// to be saved or re-queued when there are any, counting them again with a
+	/// growing backoff until none is left, and returns how many were not.
+	/// Plain summaries do not wait and report `None`.
+	async fn settle(
+		&self,
+		consistent: bool,
+		from: Option<OffsetDateTime>,
+		to: Option<OffsetDateTime>,
+	) -> Result<Option<usize>, Box<dyn std::error::Error + Send>> {
+		if !consistent {
+			return Ok(None);
+		}
+
+		let deadline = Instant::now() + self.consistency_timeout;
+		let mut backoff = SETTLE_MIN_BACKOFF;
+		let mut pending = self.payment_repo.count_in_flight(from, to).await?;
+		while pending > 0 {
+			let remaining = deadline.saturating_duration_since(Instant::now());
+			if remaining.is_zero() {
+				break;
+			}
+			sleep(backoff.min(remaining)).await;
+			backoff = (backoff * 2).min(SETTLE_MAX_BACKOFF);
+			pending = self.payment_repo.count_in_flight(from, to).await?;
+		}
+		Ok(Some(pending))
+	}
+
+	fn window(
+		&self,
+		from: Option<OffsetDateTime>,
+		to: Option<OffsetDateTime>,
+	) -> Result<
+		(Option<OffsetDateTime>, Option<OffsetDateTime>),
+		Box<dyn std::error::Error + Send>,
+	> {
+		if let (Some(from), Some(to)) = (from, to) {
+			if from > to {
+				return Err(Box::new(SummaryWindowError::Inverted { from, to }));
+			}
+			if let Some(max) = self.max_window &&
+				to - from > max
+			{
+				return Err(Box::new(SummaryWindowError::TooLong {
+					length: to - from,
+					max,
+				}));
+			}
+		}
+		if let Some(compaction) = self.compaction {
+			let compacted_before = OffsetDateTime::now_utc() - compaction.max_age;
+			let width = compaction.bucket.whole_nanoseconds().max(1);
+			// `to` is inclusive, so the last nanosecond of a bucket is an edge too.
+			if let Some(bound) = [from, to].into_iter().flatten().find(|bound| {
+				let nanos = bound.unix_timestamp_nanos();
+				*bound < compacted_before &&
+					nanos.rem_euclid(width) != 0 &&
+					(nanos + 1).rem_euclid(width) != 0
+			}) {
+				return Err(Box::new(SummaryWindowError::Unaligned {
+					bound,
+					bucket: compaction.bucket,
+				}));
+			}
+		}
+		Ok((from, to))
+	}
+
+	pub async fn execute_breakdown(
+		&self,
+		query: GetPaymentSummaryBreakdownQuery,
+	) -> Result<PaymentsSummaryBreakdownResponse, Box<dyn std::error::Error + Send>>
+	{
+		let (from, to) = self.window(query.from, query.to)?;
+		if let Some(compaction) = self.compaction {
+			let before = OffsetDateTime::now_utc() - compaction.max_age;
+			if from.is_none_or(|from| from < before) {
+				return Err(Box::new(SummaryWindowError::Compacted { before }));
+			}
+		}
+		let width = query.granularity.duration();
+		if let (Some(from), Some(to)) = (from, to) &&
+			(to - from).whole_nanoseconds() / width.whole_nanoseconds() >=
+				MAX_BREAKDOWN_BUCKETS as i128
+		{
+			return Err(Box::new(SummaryWindowError::TooManyBuckets {
+				max: MAX_BREAKDOWN_BUCKETS,
+			}));
+		}
+		let pending_payments = self.settle(query.consistent, from, to).await?;
+
+		Ok(PaymentsSummaryBreakdownResponse {
+			granularity: query.granularity,
+			default: self.group_breakdown("default", from, to, width).await?,
+			fallback: self.group_breakdown("fallback", from, to, width).await?,
+			pending_payments,
 		})
 	}
+
+	async fn group_breakdown(
+		&self,
+		group: &str,
+		from: Option<OffsetDateTime>,
+		to: Option<OffsetDateTime>,
+		width: Duration,
+	) -> Result<PaymentSummaryBreakdown, Box<dyn std::error::Error + Send>> {
+		match self
+			.payment_repo
+			.get_breakdown_by_group(
+				group,
+				from,
+				to,
+				width.unsigned_abs(),
+				MAX_BREAKDOWN_BUCKETS,
+			)
+			.await?
+		{
+			Some(amounts) => Ok(breakdown(&amounts)),
+			None => Err(Box::new(SummaryWindowError::TooManyBuckets {
+				max: MAX_BREAKDOWN_BUCKETS,
+			})),
+		}
+	}
+}
+
+/// Amounts are summed in cents so bucket totals do not pick up floating
+/// point noise.
+fn breakdown(amounts: &AmountsBreakdown) -> PaymentSummaryBreakdown {
+	PaymentSummaryBreakdown {
+		total_requests: amounts.amounts.values().sum(),
+		total_amount:   from_cents(
+			amounts.buckets.values().map(|(_, cents)| cents).sum(),
+		),
+		buckets:        amounts
+			.buckets
+			.iter()
+			.filter_map(|(start, (total_requests, total_cents))| {
+				Some(PaymentSummaryBucket {
+					start:          OffsetDateTime::from_unix_timestamp_nanos(
+						*start,
+					)
+					.ok()?,
+					total_requests: *total_requests,
+					total_amount:   from_cents(*total_cents),
+				})
+			})
+			.collect(),
+		percentiles:    percentiles(&amounts.amounts),
+	}
+}
+
+/// `amounts` counts the payments per amount in cents.
+fn percentiles(amounts: &BTreeMap<i64, usize>) -> Option<AmountPercentiles> {
+	let total: usize = amounts.values().sum();
+
+	// Nearest rank: the smallest amount with at least `p` of them at or below.
+	let rank = |p: f64| {
+		let rank = ((p * total as f64).ceil() as usize).clamp(1, total);
+		let mut seen = 0;
+		amounts
+			.iter()
+			.find(|(_, count)| {
+				seen += **count;
+				seen >= rank
+			})
+			.map(|(cents, _)| from_cents(*cents))
+			.unwrap_or_default()
+	};
+
+	Some(AmountPercentiles {
+		min: from_cents(*amounts.first_key_value()?.0),
+		p50: rank(0.50),
+		p90: rank(0.90),
+		p95: rank(0.95),
+		p99: rank(0.99),
+		max: from_cents(*amounts.last_key_value()?.0),
+	})
+}
+
+/// `processed_fees` holds the default's and the fallback's, in that order.
+fn add_fees(
+	summary: &mut PaymentsSummaryResponse,
+	fee_schedule: &FeeSchedule,
+	fee_basis: FeeBasis,
+	processed_fees: [ProcessedFees; 2],
+) {
+	let rates = fee_schedule.rates();
+	for ((result, rate), processed_fees) in [
+		(&mut summary.default, rates.default),
+		(&mut summary.fallback, rates.fallback),
+	]
+	.into_iter()
+	.zip(processed_fees)
+	{
+		let total_fee = match fee_basis {
+			FeeBasis::Current => result.total_amount * rate,
+			// Payments saved before rates were stamped fall back to the
+			// current rate.
+			FeeBasis::Processing => {
+				processed_fees.fees + processed_fees.unrated_amount * rate
+			}
+		};
+		let total_fee = from_cents(to_cents(total_fee));
+
+		result.total_fee = Some(total_fee);
+		result.net_amount = Some(from_cents(
+			to_cents(result.total_amount) - to_cents(total_fee),
+		));
+		result.fee_rate = Some(fee_rate(total_fee, result.total_amount));
+	}
+
+	summary.blended_fee_rate = Some(fee_rate(
+		summary.default.total_fee.unwrap_or_default() +
+			summary.fallback.total_fee.unwrap_or_default(),
+		summary.default.total_amount + summary.fallback.total_amount,
+	));
+}
+
+fn fee_rate(fees: f64, amount: f64) -> f64 {
+	if amount > 0.0 { fees / amount } else { 0.0 }
+}
+
+fn to_cents(amount: f64) -> i64 {
+	(amount * 100.0).round() as i64
+}
+
+fn from_cents(cents: i64) -> f64 {
+	cents as f64 / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::use_cases::dto::Granularity;
+
+	/// 2025-07-01T00:00:00Z.
+	const JULY_FIRST: i64 = 1_751_328_000;
+
+	fn at(seconds_since_july_first: i64) -> OffsetDateTime {
+		OffsetDateTime::from_unix_timestamp(JULY_FIRST + seconds_since_july_first)
+			.unwrap()
+	}
+
+	fn breakdown_of(
+		payments: &[(OffsetDateTime, f64)],
+		granularity: Granularity,
+	) -> AmountsBreakdown {
+		let width = granularity.duration().whole_nanoseconds();
+		let mut amounts = AmountsBreakdown::default();
+		for (timestamp, amount) in payments {
+			let timestamp = timestamp.unix_timestamp_nanos();
+			let bucket = amounts
+				.buckets
+				.entry(timestamp - timestamp.rem_euclid(width))
+				.or_default();
+			bucket.0 += 1;
+			bucket.1 += to_cents(*amount);
+			*amounts.amounts.entry(to_cents(*amount)).or_default() += 1;
+		}
+		amounts
+	}
+
+	#[test]
+	fn test_breakdown_groups_payments_into_buckets() {
+		let payments = [(at(36_001), 0.1), (at(36_059), 0.2), (at(36_120), 19.9)];
+
+		let summary = breakdown(&breakdown_of(&payments, Granularity::Minute));
+
+		assert_eq!(summary.total_requests, 3);
+		assert_eq!(summary.total_amount, 20.2);
+		assert_eq!(summary.buckets, vec![
+			PaymentSummaryBucket {
+				start:          at(36_000),
+				total_requests: 2,
+				total_amount:   0.3,
+			},
+			PaymentSummaryBucket {
+				start:          at(36_120),
+				total_requests: 1,
+				total_amount:   19.9,
+			},
+		]);
+
+		let daily = breakdown(&breakdown_of(&payments, Granularity::Day));
+		assert_eq!(daily.buckets.len(), 1);
+		assert_eq!(daily.buckets[0].start, at(0));
+	}
+
+	#[test]
+	fn test_percentiles_use_nearest_rank() {
+		let mut amounts: BTreeMap<i64, usize> =
+			(1..=100).map(|n| (n * 100, 1)).collect();
+
+		assert_eq!(
+			percentiles(&amounts),
+			Some(AmountPercentiles {
+				min: 1.0,
+				p50: 50.0,
+				p90: 90.0,
+				p95: 95.0,
+				p99: 99.0,
+				max: 100.0,
+			})
+		);
+
+		// Repeated amounts are counted once per payment.
+		amounts.insert(100, 101);
+		assert_eq!(percentiles(&amounts).unwrap().p50, 1.0);
+		assert_eq!(percentiles(&BTreeMap::new()), None);
+	}
 }
@@ -1,5 +1,9 @@
 pub mod create_payment;
 pub mod dto;
+pub mod export_payments;
 pub mod get_payment_summary;
+pub mod migrate_payments;
 pub mod process_payment;
 pub mod purge_payments;
+pub mod search_payments;
+pub mod snapshot;
@@ -1,14 +1,22 @@
 use std::error::Error;
 use std::fmt;
+use std::time::Duration;
 
 use circuitbreaker_rs::{BreakerError, CircuitBreaker, DefaultPolicy};
-use log::error;
 use reqwest::Client;
+use serde::Serialize;
 use time::OffsetDateTime;
+use tokio::time::{Instant, interval_at};
+use tracing::{error, warn};
+use uuid::Uuid;
 
 use crate::domain::payment::Payment;
 use crate::domain::repository::PaymentRepository;
 
+/// How often a claim is extended while its processor call is pending, well
+/// within the claim's lifetime.
+const DEFAULT_CLAIM_HEARTBEAT: Duration = Duration::from_secs(10);
+
 #[derive(Debug)]
 pub struct PaymentProcessingError(pub String);
 
@@ -26,10 +34,21 @@ impl From<Box<dyn Error + Send + Sync + 'static>> for PaymentProcessingError {
 	}
 }
 
+/// Body of a processor's `POST /payments`.
+#[derive(Serialize)]
+struct ProcessorPaymentRequest {
+	#[serde(rename = "correlationId")]
+	correlation_id: Uuid,
+	amount:         f64,
+	#[serde(rename = "requestedAt", with = "time::serde::rfc3339")]
+	requested_at:   OffsetDateTime,
+}
+
 #[derive(Clone)]
 pub struct ProcessPaymentUseCase<R: PaymentRepository> {
-	payment_repo: R,
-	http_client:  Client,
+	payment_repo:    R,
+	http_client:     Client,
+	claim_heartbeat: Duration,
 }
 
 impl<R: PaymentRepository> ProcessPaymentUseCase<R> {
@@ -37,9 +56,22 @@ impl<R: PaymentRepository> ProcessPaymentUseCase<R> {
 		Self {
 			payment_repo,
 			http_client,
+			claim_heartbeat: DEFAULT_CLAIM_HEARTBEAT,
 		}
 	}
 
+	/// How often the payment's claim is extended while its processor is yet
+	/// to answer, so slow calls do not let another worker take it.
+	pub fn with_claim_heartbeat(mut self, claim_heartbeat: Duration) -> Self {
+		self.claim_heartbeat = claim_heartbeat;
+		self
+	}
+
+	#[tracing::instrument(
+		name = "call_payment_processor",
+		skip_all,
+		fields(processor = %processed_by)
+	)]
 	pub async fn execute(
 		&self,
 		mut payment: Payment,
@@ -47,38 +79,43 @@ impl<R: PaymentRepository> ProcessPaymentUseCase<R> {
 		processed_by: String,
 		circuit_breaker: &mut CircuitBreaker<DefaultPolicy, PaymentProcessingError>,
 	) -> Result<bool, Box<dyn Error + Send>> {
-		payment.requested_at = Some(OffsetDateTime::now_utc());
+		let submitted_at = OffsetDateTime::now_utc();
+		payment.submitted_at = Some(submitted_at);
+		self.payment_repo
+			.mark_in_flight(&payment, &processed_by)
+			.await?;
 
-		let result: Result<bool, BreakerError<PaymentProcessingError>> =
-			circuit_breaker
-				.call_async(|| async {
-					let response = self
-						.http_client
-						.post(format!("{processor_url}/payments"))
-						.json(&payment)
-						.send()
-						.await
-						.map_err(|e| PaymentProcessingError(e.to_string()))?;
-
-					if response.status().is_success() {
-						Ok(true)
-					} else {
-						error!(
-							"Processor returned non-success status for {}: {}",
-							payment.correlation_id,
-							response.status()
-						);
-
-						if response.status().is_client_error() {
-							return Ok(false);
-						}
-
-						Err(PaymentProcessingError(
-							"Service unavailable".to_string(),
-						))
-					}
+		let call = circuit_breaker.call_async(|| async {
+			let response = self
+				.http_client
+				.post(format!("{processor_url}/payments"))
+				.json(&ProcessorPaymentRequest {
+					correlation_id: payment.correlation_id,
+					amount:         payment.amount,
+					requested_at:   submitted_at,
 				})
-				.await;
+				.send()
+				.await
+				.map_err(|e| PaymentProcessingError(e.to_string()))?;
+
+			if response.status().is_success() {
+				Ok(true)
+			} else {
+				error!(
+					"Processor returned non-success status for {}: {}",
+					payment.correlation_id,
+					response.status()
+				);
+
+				if response.status().is_client_error() {
+					return Ok(false);
+				}
+
+				Err(PaymentProcessingError("Service unavailable".to_string()))
+			}
+		});
+		let result: Result<bool, BreakerError<PaymentProcessingError>> =
+			self.keep_claimed(&payment, call).await;
 
 		match result {
 			Ok(result) => {
@@ -104,4 +141,30 @@ impl<R: PaymentRepository> ProcessPaymentUseCase<R> {
 			}
 		}
 	}
+
+	/// Awaits `call`, extending the payment's claim every heartbeat meanwhile.
+	/// Workers also wait for a processor permit this way, since the wait can
+	/// outlast the claim.
+	pub async fn keep_claimed<T>(
+		&self,
+		payment: &Payment,
+		call: impl Future<Output = T>,
+	) -> T {
+		let payment_id = payment.correlation_id.to_string();
+		let mut heartbeat =
+			interval_at(Instant::now() + self.claim_heartbeat, self.claim_heartbeat);
+		tokio::pin!(call);
+		loop {
+			tokio::select! {
+				result = &mut call => return result,
+				_ = heartbeat.tick() => {
+					match self.payment_repo.extend_claim(&payment_id).await {
+						Ok(true) => {}
+						Ok(false) => warn!("Claim on {payment_id} expired while processing"),
+						Err(e) => error!("Failed to extend the claim on {payment_id}: {e}"),
+					}
+				}
+			}
+		}
+	}
 }
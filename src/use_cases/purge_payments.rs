@@ -1,6 +1,7 @@
 use std::error::Error;
 
-use crate::domain::repository::PaymentRepository;
+use crate::domain::repository::{PaymentRepository, PurgeScope};
+use crate::use_cases::dto::{PurgePaymentsCommand, PurgePaymentsResult};
 
 #[derive(Clone)]
 pub struct PurgePaymentsUseCase<R: PaymentRepository> {
@@ -12,7 +13,26 @@ impl<R: PaymentRepository> PurgePaymentsUseCase<R> {
 		Self { repository }
 	}
 
-	pub async fn execute(&self) -> Result<(), Box<dyn Error + Send>> {
-		self.repository.clear().await
+	pub async fn execute(
+		&self,
+		command: PurgePaymentsCommand,
+	) -> Result<PurgePaymentsResult, Box<dyn Error + Send>> {
+		let mut scope = PurgeScope {
+			group:          command.group,
+			from:           command.from,
+			to:             command.to,
+			preserve_queue: false,
+		};
+		// The queue is indexed by neither processor nor time, so a scoped
+		// purge cannot pick its payments out of it.
+		scope.preserve_queue =
+			command.preserve_queue.unwrap_or(!scope.is_everything());
+
+		let deleted_payments = self.repository.purge(&scope).await?;
+
+		Ok(PurgePaymentsResult {
+			deleted_payments,
+			queue_purged: !scope.preserve_queue,
+		})
 	}
 }
@@ -0,0 +1,204 @@
+use std::error::Error;
+
+use derive_more::derive::{Display, Error};
+
+use crate::domain::repository::{PaymentRepository, PaymentSearch, PaymentsCursor};
+use crate::use_cases::dto::{
+	PaymentRecord, SearchPaymentsQuery, SearchPaymentsResponse,
+};
+use crate::use_cases::get_payment_summary::SummaryWindowError;
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+#[derive(Debug, Display, Error, PartialEq)]
+pub enum SearchPaymentsError {
+	#[display("`cursor` is not the `nextCursor` of a previous page.")]
+	InvalidCursor,
+	#[display("`limit` must be between 1 and {max}.")]
+	InvalidLimit { max: usize },
+	#[display("`minAmount` ({min}) is greater than `maxAmount` ({max}).")]
+	InvertedAmounts { min: f64, max: f64 },
+}
+
+#[derive(Clone)]
+pub struct SearchPaymentsUseCase<R: PaymentRepository> {
+	payment_repo: R,
+}
+
+impl<R: PaymentRepository> SearchPaymentsUseCase<R> {
+	pub fn new(payment_repo: R) -> Self {
+		Self { payment_repo }
+	}
+
+	pub async fn execute(
+		&self,
+		query: SearchPaymentsQuery,
+	) -> Result<SearchPaymentsResponse, Box<dyn Error + Send>> {
+		let (search, cursor, limit) = search_of(query)?;
+
+		let page = self
+			.payment_repo
+			.search_payments(&search, cursor, limit)
+			.await?;
+
+		Ok(SearchPaymentsResponse {
+			payments:    page
+				.payments
+				.into_iter()
+				.map(PaymentRecord::from)
+				.collect(),
+			next_cursor: page.next.map(encode_cursor),
+		})
+	}
+}
+
+fn search_of(
+	query: SearchPaymentsQuery,
+) -> Result<(PaymentSearch, Option<PaymentsCursor>, usize), Box<dyn Error + Send>> {
+	if let (Some(from), Some(to)) = (query.from, query.to) &&
+		from > to
+	{
+		return Err(Box::new(SummaryWindowError::Inverted { from, to }));
+	}
+	if let (Some(min), Some(max)) = (query.min_amount, query.max_amount) &&
+		min > max
+	{
+		return Err(Box::new(SearchPaymentsError::InvertedAmounts { min, max }));
+	}
+	let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+	if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+		return Err(Box::new(SearchPaymentsError::InvalidLimit {
+			max: MAX_PAGE_SIZE,
+		}));
+	}
+	let cursor = query
+		.cursor
+		.as_deref()
+		.map(decode_cursor)
+		.transpose()
+		.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+
+	let search = PaymentSearch {
+		processor:  query.processor,
+		status:     query.status,
+		from:       query.from,
+		to:         query.to,
+		min_amount: query.min_amount,
+		max_amount: query.max_amount,
+		order:      query.order,
+	};
+	Ok((search, cursor, limit))
+}
+
+/// Clients are meant to pass cursors back as they got them, not to build
+/// them.
+fn encode_cursor(cursor: PaymentsCursor) -> String {
+	format!("{}_{}", cursor.position, cursor.skip)
+}
+
+fn decode_cursor(cursor: &str) -> Result<PaymentsCursor, SearchPaymentsError> {
+	let (position, skip) = cursor
+		.split_once('_')
+		.ok_or(SearchPaymentsError::InvalidCursor)?;
+
+	Ok(PaymentsCursor {
+		position: position
+			.parse::<f64>()
+			.ok()
+			.filter(|position| position.is_finite())
+			.ok_or(SearchPaymentsError::InvalidCursor)?,
+		skip:     skip
+			.parse()
+			.map_err(|_| SearchPaymentsError::InvalidCursor)?,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use time::OffsetDateTime;
+
+	use super::*;
+	use crate::domain::payment::PaymentStatus;
+
+	fn search_error(query: SearchPaymentsQuery) -> String {
+		search_of(query).err().unwrap().to_string()
+	}
+
+	#[test]
+	fn test_cursor_round_trips() {
+		let cursor = PaymentsCursor {
+			position: 1_751_364_000_123_456_800.0,
+			skip:     3,
+		};
+
+		assert_eq!(decode_cursor(&encode_cursor(cursor)), Ok(cursor));
+	}
+
+	#[test]
+	fn test_decode_cursor_rejects_made_up_cursors() {
+		for cursor in ["", "42", "x_1", "42_-1", "inf_0"] {
+			assert_eq!(
+				decode_cursor(cursor),
+				Err(SearchPaymentsError::InvalidCursor),
+				"{cursor}"
+			);
+		}
+	}
+
+	#[test]
+	fn test_search_of_applies_defaults() {
+		let (search, cursor, limit) = search_of(SearchPaymentsQuery {
+			processor: Some("fallback".to_string()),
+			..Default::default()
+		})
+		.unwrap();
+
+		assert_eq!(search.processor.as_deref(), Some("fallback"));
+		assert_eq!(search.status, PaymentStatus::Processed);
+		assert_eq!(cursor, None);
+		assert_eq!(limit, DEFAULT_PAGE_SIZE);
+	}
+
+	#[test]
+	fn test_search_of_rejects_invalid_queries() {
+		let now = OffsetDateTime::now_utc();
+
+		assert_eq!(
+			search_error(SearchPaymentsQuery {
+				from: Some(now),
+				to: Some(now - time::Duration::SECOND),
+				..Default::default()
+			}),
+			SummaryWindowError::Inverted {
+				from: now,
+				to:   now - time::Duration::SECOND,
+			}
+			.to_string()
+		);
+		assert_eq!(
+			search_error(SearchPaymentsQuery {
+				min_amount: Some(20.0),
+				max_amount: Some(10.0),
+				..Default::default()
+			}),
+			"`minAmount` (20) is greater than `maxAmount` (10)."
+		);
+		for limit in [0, MAX_PAGE_SIZE + 1] {
+			assert_eq!(
+				search_error(SearchPaymentsQuery {
+					limit: Some(limit),
+					..Default::default()
+				}),
+				"`limit` must be between 1 and 500."
+			);
+		}
+		assert_eq!(
+			search_error(SearchPaymentsQuery {
+				cursor: Some("next".to_string()),
+				..Default::default()
+			}),
+			SearchPaymentsError::InvalidCursor.to_string()
+		);
+	}
+}
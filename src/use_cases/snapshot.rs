@@ -0,0 +1,416 @@
+use std::error::Error;
+
+use derive_more::derive::{Display, Error};
+use time::OffsetDateTime;
+
+use crate::domain::repository::{PaymentRepository, PaymentsCursor, PurgeScope};
+use crate::domain::snapshot::{SNAPSHOT_VERSION, SnapshotRecord, SnapshotStore};
+use crate::use_cases::dto::RestoreSnapshotResult;
+
+/// Payments read per round trip while snapshotting, and records written per
+/// pipeline while restoring.
+const DEFAULT_BATCH_SIZE: usize = 500;
+
+#[derive(Debug, Display, Error, PartialEq)]
+pub enum SnapshotError {
+	#[display("The snapshot does not start with a header.")]
+	MissingHeader,
+	#[display(
+		"The snapshot is version {version}, newer than the supported \
+		 {SNAPSHOT_VERSION}."
+	)]
+	UnsupportedVersion { version: u32 },
+	#[display("Line {line} of the snapshot is invalid: {reason}")]
+	InvalidRecord { line: usize, reason: String },
+	#[display(
+		"{count} payments are being processed; stop the workers before restoring."
+	)]
+	PaymentsInFlight { count: usize },
+}
+
+#[derive(Clone)]
+pub struct SnapshotUseCase<S> {
+	store:      S,
+	batch_size: usize,
+}
+
+impl<S: SnapshotStore + PaymentRepository + Clone> SnapshotUseCase<S> {
+	pub fn new(store: S) -> Self {
+		Self {
+			store,
+			batch_size: DEFAULT_BATCH_SIZE,
+		}
+	}
+
+	pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+		self.batch_size = batch_size.max(1);
+		self
+	}
+
+	pub fn snapshot(&self) -> PaymentSnapshot<S> {
+		PaymentSnapshot {
+			store:      self.store.clone(),
+			batch_size: self.batch_size,
+			stage:      SnapshotStage::Header,
+			cursor:     None,
+			queued:     0,
+		}
+	}
+
+	/// Nothing is deleted until the snapshot's header is read, and only while
+	/// no payment is in flight: the workers must be stopped, or they would
+	/// keep saving into the store being replaced. A restore failing past the
+	/// header leaves the store partially restored.
+	pub fn restore(&self) -> SnapshotRestore<S> {
+		SnapshotRestore {
+			store:      self.store.clone(),
+			batch_size: self.batch_size,
+			pending:    Vec::new(),
+			line:       0,
+			started:    false,
+			batch:      Vec::new(),
+			result:     RestoreSnapshotResult::default(),
+		}
+	}
+}
+
+enum SnapshotStage {
+	Header,
+	Payments,
+	Queue,
+	InFlight,
+	Buckets,
+	Done,
+}
+
+/// A snapshot in progress, as NDJSON `SnapshotRecord` lines. Processed
+/// payments and queued messages are read a page per call to `next_chunk`,
+/// the payments in flight and the buckets at once.
+pub struct PaymentSnapshot<S> {
+	store:      S,
+	batch_size: usize,
+	stage:      SnapshotStage,
+	cursor:     Option<PaymentsCursor>,
+	/// Queued messages read so far.
+	queued:     usize,
+}
+
+impl<S: SnapshotStore> PaymentSnapshot<S> {
+	/// `None` once every record was read or after an error.
+	pub async fn next_chunk(
+		&mut self,
+	) -> Result<Option<Vec<u8>>, Box<dyn Error + Send>> {
+		let records = match self.read_records().await {
+			Ok(Some(records)) => records,
+			Ok(None) => return Ok(None),
+			Err(e) => {
+				self.stage = SnapshotStage::Done;
+				return Err(e);
+			}
+		};
+
+		let mut chunk = Vec::new();
+		for record in records {
+			serde_json::to_writer(&mut chunk, &record)
+				.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+			chunk.push(b'\n');
+		}
+		Ok(Some(chunk))
+	}
+
+	async fn read_records(
+		&mut self,
+	) -> Result<Option<Vec<SnapshotRecord>>, Box<dyn Error + Send>> {
+		let records = match self.stage {
+			SnapshotStage::Header => {
+				self.stage = SnapshotStage::Payments;
+				vec![SnapshotRecord::Header {
+					version:    SNAPSHOT_VERSION,
+					created_at: OffsetDateTime::now_utc(),
+				}]
+			}
+			SnapshotStage::Payments => {
+				let (records, next) = self
+					.store
+					.read_payments(self.cursor, self.batch_size)
+					.await?;
+				self.cursor = next;
+				if next.is_none() {
+					self.stage = SnapshotStage::Queue;
+				}
+				records
+			}
+			SnapshotStage::Queue => {
+				let messages =
+					self.store.read_queue(self.queued, self.batch_size).await?;
+				self.queued += messages.len();
+				if messages.len() < self.batch_size {
+					self.stage = SnapshotStage::InFlight;
+				}
+				messages
+					.into_iter()
+					.map(|message| SnapshotRecord::Queued { message })
+					.collect()
+			}
+			SnapshotStage::InFlight => {
+				self.stage = SnapshotStage::Buckets;
+				self.store
+					.read_in_flight()
+					.await?
+					.into_iter()
+					.map(|payment| SnapshotRecord::InFlight { payment })
+					.collect()
+			}
+			SnapshotStage::Buckets => {
+				self.stage = SnapshotStage::Done;
+				self.store
+					.read_buckets()
+					.await?
+					.into_iter()
+					.map(SnapshotRecord::Bucket)
+					.collect()
+			}
+			SnapshotStage::Done => return Ok(None),
+		};
+		Ok(Some(records))
+	}
+}
+
+/// A restore in progress, fed the snapshot in chunks of any size.
+pub struct SnapshotRestore<S> {
+	store:      S,
+	batch_size: usize,
+	/// The start of a line split across chunks.
+	pending:    Vec<u8>,
+	line:       usize,
+	started:    bool,
+	batch:      Vec<SnapshotRecord>,
+	result:     RestoreSnapshotResult,
+}
+
+impl<S: SnapshotStore + PaymentRepository> SnapshotRestore<S> {
+	pub async fn feed(&mut self, chunk: &[u8]) -> Result<(), Box<dyn Error + Send>> {
+		self.pending.extend_from_slice(chunk);
+
+		let mut consumed = 0;
+		while let Some(end) = self.pending[consumed..]
+			.iter()
+			.position(|byte| *byte == b'\n')
+		{
+			let line = self.pending[consumed..consumed + end].to_vec();
+			consumed += end + 1;
+			self.read_line(&line).await?;
+		}
+		self.pending.drain(..consumed);
+		Ok(())
+	}
+
+	/// Reads a last line left without a newline and writes what is still
+	/// batched.
+	pub async fn finish(
+		mut self,
+	) -> Result<RestoreSnapshotResult, Box<dyn Error + Send>> {
+		let line = std::mem::take(&mut self.pending);
+		self.read_line(&line).await?;
+		if !self.started {
+			return Err(Box::new(SnapshotError::MissingHeader));
+		}
+		self.flush().await?;
+		Ok(self.result)
+	}
+
+	async fn read_line(&mut self, line: &[u8]) -> Result<(), Box<dyn Error + Send>> {
+		self.line += 1;
+		if line.trim_ascii().is_empty() {
+			return Ok(());
+		}
+
+		let record = parse_record(self.line, line, self.started)
+			.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+		if !self.started {
+			let count = self.store.count_in_flight(None, None).await?;
+			if count > 0 {
+				return Err(Box::new(SnapshotError::PaymentsInFlight { count }));
+			}
+			// A snapshot replaces the store's content, queue included.
+			self.store.purge(&PurgeScope::default()).await?;
+			self.started = true;
+			return Ok(());
+		}
+
+		match &record {
+			SnapshotRecord::Payment { .. } => self.result.payments += 1,
+			SnapshotRecord::Queued { .. } | SnapshotRecord::InFlight { .. } => {
+				self.result.queued += 1
+			}
+			SnapshotRecord::Bucket(_) => self.result.buckets += 1,
+			SnapshotRecord::Header { .. } => {}
+		}
+		self.batch.push(record);
+		if self.batch.len() >= self.batch_size {
+			self.flush().await?;
+		}
+		Ok(())
+	}
+
+	async fn flush(&mut self) -> Result<(), Box<dyn Error + Send>> {
+		if self.batch.is_empty() {
+			return Ok(());
+		}
+		self.store.write(std::mem::take(&mut self.batch)).await
+	}
+}
+
+/// Parses line number `line`, which must be a supported header unless the
+/// header was already read, and must not be one otherwise.
+fn parse_record(
+	line: usize,
+	bytes: &[u8],
+	after_header: bool,
+) -> Result<SnapshotRecord, SnapshotError> {
+	let record: SnapshotRecord =
+		serde_json::from_slice(bytes).map_err(|e| SnapshotError::InvalidRecord {
+			line,
+			reason: e.to_string(),
+		})?;
+
+	match (&record, after_header) {
+		(SnapshotRecord::Header { version, .. }, false) => {
+			if *version > SNAPSHOT_VERSION {
+				return Err(SnapshotError::UnsupportedVersion { version: *version });
+			}
+		}
+		(_, false) => return Err(SnapshotError::MissingHeader),
+		(SnapshotRecord::Header { .. }, true) => {
+			return Err(SnapshotError::InvalidRecord {
+				line,
+				reason: "a second header".to_string(),
+			});
+		}
+		(_, true) => {}
+	}
+	Ok(record)
+}
+
+#[cfg(test)]
+mod tests {
+	use uuid::Uuid;
+
+	use super::*;
+	use crate::domain::payment::{Payment, PaymentTimestamp};
+	use crate::domain::queue::Message;
+	use crate::domain::snapshot::PaymentBucket;
+
+	fn payment() -> Payment {
+		Payment {
+			correlation_id: Uuid::new_v4(),
+			amount:         19.9,
+			submitted_at:   Some(
+				OffsetDateTime::from_unix_timestamp(1_751_328_000).unwrap(),
+			),
+			processed_at:   None,
+			processed_by:   Some("default".to_string()),
+			received_at:    None,
+		}
+	}
+
+	fn round_trip(record: &SnapshotRecord) -> SnapshotRecord {
+		let line = serde_json::to_vec(record).unwrap();
+		parse_record(2, &line, true).unwrap()
+	}
+
+	#[test]
+	fn test_records_round_trip() {
+		let payment = payment();
+
+		match round_trip(&SnapshotRecord::Payment {
+			payment:  payment.clone(),
+			fee_rate: Some(0.05),
+		}) {
+			SnapshotRecord::Payment {
+				payment: read,
+				fee_rate,
+			} => {
+				assert_eq!(read.correlation_id, payment.correlation_id);
+				assert_eq!(read.amount, payment.amount);
+				assert_eq!(read.submitted_at, payment.submitted_at);
+				assert_eq!(read.processed_by, payment.processed_by);
+				assert_eq!(fee_rate, Some(0.05));
+			}
+			record => panic!("Read back {record:?}"),
+		}
+
+		let message = Message::with(Uuid::new_v4(), payment.clone());
+		match round_trip(&SnapshotRecord::Queued {
+			message: message.clone(),
+		}) {
+			SnapshotRecord::Queued { message: read } => {
+				assert_eq!(read.id, message.id);
+				assert_eq!(read.body.correlation_id, message.body.correlation_id);
+			}
+			record => panic!("Read back {record:?}"),
+		}
+
+		match round_trip(&SnapshotRecord::InFlight {
+			payment: payment.clone(),
+		}) {
+			SnapshotRecord::InFlight { payment: read } => {
+				assert_eq!(read.correlation_id, payment.correlation_id);
+				assert_eq!(read.processed_by, payment.processed_by);
+			}
+			record => panic!("Read back {record:?}"),
+		}
+
+		let bucket = PaymentBucket {
+			timestamp:      PaymentTimestamp::Processed,
+			group:          "fallback".to_string(),
+			start:          60,
+			end:            119,
+			count:          3,
+			amount:         45.0,
+			fees:           0.0,
+			unrated_amount: 45.0,
+		};
+		match round_trip(&SnapshotRecord::Bucket(bucket.clone())) {
+			SnapshotRecord::Bucket(read) => assert_eq!(read, bucket),
+			record => panic!("Read back {record:?}"),
+		}
+	}
+
+	#[test]
+	fn test_parse_record_requires_a_supported_header_first() {
+		let header = |version: u32| {
+			serde_json::to_vec(&SnapshotRecord::Header {
+				version,
+				created_at: OffsetDateTime::now_utc(),
+			})
+			.unwrap()
+		};
+
+		assert!(parse_record(1, &header(SNAPSHOT_VERSION), false).is_ok());
+		assert_eq!(
+			parse_record(1, &header(SNAPSHOT_VERSION + 1), false).err(),
+			Some(SnapshotError::UnsupportedVersion {
+				version: SNAPSHOT_VERSION + 1,
+			})
+		);
+		assert!(matches!(
+			parse_record(3, &header(SNAPSHOT_VERSION), true),
+			Err(SnapshotError::InvalidRecord { line: 3, .. })
+		));
+
+		let payment = serde_json::to_vec(&SnapshotRecord::Payment {
+			payment:  payment(),
+			fee_rate: None,
+		})
+		.unwrap();
+		assert_eq!(
+			parse_record(1, &payment, false).err(),
+			Some(SnapshotError::MissingHeader)
+		);
+		assert!(matches!(
+			parse_record(7, b"{\"type\":\"payment\"}", true),
+			Err(SnapshotError::InvalidRecord { line: 7, .. })
+		));
+	}
+}
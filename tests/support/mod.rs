@@ -1,5 +1,7 @@
 #![allow(dead_code)]
 
 pub mod payment_processor_container;
+pub mod payment_repository;
+pub mod payments;
 pub mod postgresql_container;
 pub mod redis_container;
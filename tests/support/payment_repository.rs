@@ -0,0 +1,179 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use async_trait::async_trait;
+use rinha_de_backend::domain::payment::Payment;
+use rinha_de_backend::domain::repository::{
+	AmountsBreakdown, GroupSummary, PaymentRepository, PaymentSearch,
+	PaymentsCursor, PaymentsPage, ProcessedFees, PurgeScope,
+};
+use time::OffsetDateTime;
+
+use crate::support::payments::{at, processed_payment};
+
+/// A repository with fixed contents, for tests that do not need Redis. The
+/// default processed 100.00 in 4 payments, 60.00 of it stamped at a 5% rate;
+/// the fallback processed 50.00 in 1 payment with no stamped rate.
+#[derive(Clone, Default)]
+pub struct StubPaymentRepository {
+	/// Payments reported in flight.
+	pub in_flight:        usize,
+	/// Makes every count of the payments in flight find one fewer than the
+	/// last.
+	pub settling:         bool,
+	/// Makes `purge` fail.
+	pub failing_purge:    bool,
+	/// How many times the payments in flight were counted.
+	pub in_flight_counts: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl PaymentRepository for StubPaymentRepository {
+	async fn save(
+		&self,
+		_: Payment,
+	) -> Result<(), Box<dyn std::error::Error + Send>> {
+		Ok(())
+	}
+
+	async fn get_summary_by_group(
+		&self,
+		group: &str,
+		_: Option<OffsetDateTime>,
+		_: Option<OffsetDateTime>,
+	) -> Result<(usize, f64), Box<dyn std::error::Error + Send>> {
+		Ok(if group == "default" {
+			(4, 100.0)
+		} else {
+			(1, 50.0)
+		})
+	}
+
+	async fn get_breakdown_by_group(
+		&self,
+		_: &str,
+		_: Option<OffsetDateTime>,
+		_: Option<OffsetDateTime>,
+		_: std::time::Duration,
+		_: usize,
+	) -> Result<Option<AmountsBreakdown>, Box<dyn std::error::Error + Send>> {
+		Ok(Some(AmountsBreakdown::default()))
+	}
+
+	async fn get_summary_with_fees_by_group(
+		&self,
+		group: &str,
+		from_ts: Option<OffsetDateTime>,
+		to_ts: Option<OffsetDateTime>,
+	) -> Result<GroupSummary, Box<dyn std::error::Error + Send>> {
+		let (total_requests, total_amount) =
+			self.get_summary_by_group(group, from_ts, to_ts).await?;
+		Ok(GroupSummary {
+			total_requests,
+			total_amount,
+			fees: if group == "default" {
+				ProcessedFees {
+					fees:           3.0,
+					unrated_amount: 40.0,
+				}
+			} else {
+				ProcessedFees {
+					fees:           0.0,
+					unrated_amount: 50.0,
+				}
+			},
+		})
+	}
+
+	async fn search_payments(
+		&self,
+		_: &PaymentSearch,
+		_: Option<PaymentsCursor>,
+		_: usize,
+	) -> Result<PaymentsPage, Box<dyn std::error::Error + Send>> {
+		Ok(PaymentsPage::default())
+	}
+
+	async fn mark_in_flight(
+		&self,
+		_: &Payment,
+		_: &str,
+	) -> Result<(), Box<dyn std::error::Error + Send>> {
+		Ok(())
+	}
+
+	async fn count_in_flight(
+		&self,
+		_: Option<OffsetDateTime>,
+		_: Option<OffsetDateTime>,
+	) -> Result<usize, Box<dyn std::error::Error + Send>> {
+		let counted = self.in_flight_counts.fetch_add(1, Ordering::Relaxed);
+		Ok(if self.settling {
+			self.in_flight.saturating_sub(counted)
+		} else {
+			self.in_flight
+		})
+	}
+
+	async fn get_processed_payments(
+		&self,
+		_: Option<OffsetDateTime>,
+		_: Option<OffsetDateTime>,
+		_: Option<PaymentsCursor>,
+		_: usize,
+	) -> Result<PaymentsPage, Box<dyn std::error::Error + Send>> {
+		Ok(PaymentsPage::default())
+	}
+
+	async fn get_payment_summary(
+		&self,
+		group: &str,
+		payment_id: &str,
+	) -> Result<Payment, Box<dyn std::error::Error + Send>> {
+		Ok(Payment {
+			correlation_id: payment_id.parse().unwrap_or_default(),
+			..processed_payment(group, 25.0, at(0))
+		})
+	}
+
+	async fn is_already_processed(
+		&self,
+		_: &str,
+	) -> Result<bool, Box<dyn std::error::Error + Send>> {
+		Ok(false)
+	}
+
+	async fn claim(
+		&self,
+		_: &str,
+	) -> Result<bool, Box<dyn std::error::Error + Send>> {
+		Ok(true)
+	}
+
+	async fn extend_claim(
+		&self,
+		_: &str,
+	) -> Result<bool, Box<dyn std::error::Error + Send>> {
+		Ok(true)
+	}
+
+	async fn compact(
+		&self,
+		_: OffsetDateTime,
+		_: std::time::Duration,
+		_: std::time::Duration,
+		_: usize,
+	) -> Result<usize, Box<dyn std::error::Error + Send>> {
+		Ok(0)
+	}
+
+	async fn purge(
+		&self,
+		_: &PurgeScope,
+	) -> Result<usize, Box<dyn std::error::Error + Send>> {
+		if self.failing_purge {
+			return Err(Box::new(std::io::Error::other("Failed to purge payments")));
+		}
+		Ok(0)
+	}
+}
@@ -0,0 +1,45 @@
+use rinha_de_backend::domain::payment::Payment;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+/// 2025-07-01T00:00:00Z.
+pub const JULY_FIRST: i64 = 1_751_328_000;
+
+pub fn at(seconds_since_july_first: i64) -> OffsetDateTime {
+	OffsetDateTime::from_unix_timestamp(JULY_FIRST + seconds_since_july_first)
+		.unwrap()
+}
+
+/// A payment of `group` received 100ms before `submitted_at` and processed
+/// 100ms after, so each timestamp index sees it at a different time.
+pub fn processed_payment(
+	group: &str,
+	amount: f64,
+	submitted_at: OffsetDateTime,
+) -> Payment {
+	Payment {
+		correlation_id: Uuid::new_v4(),
+		amount,
+		submitted_at: Some(submitted_at),
+		processed_at: Some(submitted_at + Duration::milliseconds(100)),
+		processed_by: Some(group.to_string()),
+		received_at: Some(submitted_at - Duration::milliseconds(100)),
+	}
+}
+
+/// A payment of `group` submitted and processed at once, as saved before
+/// payments recorded when they were received.
+pub fn payment_processed_at(
+	group: &str,
+	amount: f64,
+	processed_at: OffsetDateTime,
+) -> Payment {
+	Payment {
+		correlation_id: Uuid::new_v4(),
+		amount,
+		submitted_at: Some(processed_at),
+		processed_at: Some(processed_at),
+		processed_by: Some(group.to_string()),
+		received_at: None,
+	}
+}
@@ -1,7 +1,7 @@
-use log::info;
 use testcontainers::core::{ContainerPort, Mount, WaitFor};
 use testcontainers::runners::AsyncRunner;
 use testcontainers::{GenericImage, ImageExt};
+use tracing::info;
 use uuid::Uuid;
 
 pub struct PostgresTestContainer {
@@ -1,9 +1,9 @@
-use log::{error, info};
 use redis::AsyncCommands;
 use rinha_de_backend::infrastructure::config::redis::Redis;
 use testcontainers::GenericImage;
 use testcontainers::core::{ContainerPort, WaitFor};
 use testcontainers::runners::AsyncRunner;
+use tracing::{error, info};
 
 pub struct RedisTestContainer {
 	pub client:    redis::Client,
@@ -17,7 +17,7 @@ impl RedisTestContainer {
 
 	pub async fn get_redis(&self) -> Redis {
 		let redis_url =
-			format!("redis://{}", &self.client.get_connection_info().addr);
+			format!("redis://{}", self.client.get_connection_info().addr);
 
 		Redis::new(&redis_url).await.unwrap()
 	}
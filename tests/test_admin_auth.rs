@@ -0,0 +1,124 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use actix_web::{App, test, web};
+use rinha_de_backend::adapters::web::admin_auth::{AdminToken, admin_scope};
+use rinha_de_backend::infrastructure::workers::worker_pool::{
+	WorkerPool, WorkerPoolOptions,
+};
+use serde_json::Value;
+
+fn worker_pool() -> WorkerPool {
+	WorkerPool::new(WorkerPoolOptions {
+		min_workers:        1,
+		max_workers:        1,
+		scale_interval:     Duration::from_secs(1),
+		backlog_per_worker: 100,
+		max_latency:        Duration::from_millis(100),
+	})
+}
+
+async fn call_admin_workers(
+	admin_token: AdminToken,
+	authorization: Option<&str>,
+) -> actix_web::dev::ServiceResponse {
+	let app = test::init_service(
+		App::new()
+			.app_data(web::Data::new(admin_token))
+			.app_data(web::Data::new(worker_pool()))
+			.service(admin_scope()),
+	)
+	.await;
+
+	let mut req = test::TestRequest::get().uri("/admin/workers");
+	if let Some(authorization) = authorization {
+		req = req.insert_header((AUTHORIZATION, authorization));
+	}
+	test::call_service(&app, req.to_request()).await
+}
+
+#[actix_web::test]
+async fn test_admin_scope_accepts_configured_token() {
+	let resp =
+		call_admin_workers(AdminToken::new(Some("s3cr3t")), Some("Bearer s3cr3t"))
+			.await;
+
+	assert_eq!(resp.status(), StatusCode::OK);
+	let body: Value = test::read_body_json(resp).await;
+	assert_eq!(body["max_workers"], 1);
+}
+
+#[actix_web::test]
+async fn test_admin_scope_rejects_missing_token() {
+	let resp = call_admin_workers(AdminToken::new(Some("s3cr3t")), None).await;
+
+	assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+	assert_eq!(resp.headers().get(WWW_AUTHENTICATE).unwrap(), "Bearer");
+}
+
+#[actix_web::test]
+async fn test_admin_scope_rejects_wrong_token() {
+	let resp =
+		call_admin_workers(AdminToken::new(Some("s3cr3t")), Some("Bearer guess"))
+			.await;
+
+	assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn test_admin_scope_is_disabled_without_token() {
+	let resp =
+		call_admin_workers(AdminToken::default(), Some("Bearer anything")).await;
+
+	assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+	let body: Value = test::read_body_json(resp).await;
+	assert_eq!(body["statusCode"], 403);
+}
+
+#[derive(Clone)]
+struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+impl Write for CapturedLogs {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		self.0.lock().unwrap().extend_from_slice(buf);
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
+
+#[actix_web::test]
+async fn test_admin_audit_log_records_the_socket_peer() {
+	let log_output = Arc::new(Mutex::new(Vec::<u8>::new()));
+	let captured_logs = CapturedLogs(log_output.clone());
+	let subscriber = tracing_subscriber::fmt()
+		.with_ansi(false)
+		.with_writer(move || captured_logs.clone())
+		.finish();
+	let _subscriber_guard = tracing::subscriber::set_default(subscriber);
+
+	let app = test::init_service(
+		App::new()
+			.app_data(web::Data::new(AdminToken::new(Some("s3cr3t"))))
+			.app_data(web::Data::new(worker_pool()))
+			.service(admin_scope()),
+	)
+	.await;
+	let req = test::TestRequest::get()
+		.uri("/admin/workers")
+		.peer_addr("10.0.0.7:51234".parse().unwrap())
+		.insert_header(("X-Forwarded-For", "203.0.113.9"))
+		.to_request();
+	let resp = test::call_service(&app, req).await;
+
+	assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+	let logs = String::from_utf8(log_output.lock().unwrap().clone()).unwrap();
+	assert!(logs.contains("Rejected admin request"), "{logs}");
+	assert!(logs.contains("peer=10.0.0.7:51234"), "{logs}");
+	assert!(logs.contains("forwarded_for=203.0.113.9"), "{logs}");
+}
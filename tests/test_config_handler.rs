@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::{App, test, web};
+use rinha_de_backend::adapters::web::handlers::reload_config;
+use rinha_de_backend::infrastructure::config::routing_reloader::RoutingConfigReloader;
+use rinha_de_backend::infrastructure::config::settings::Config;
+use rinha_de_backend::infrastructure::routing::in_memory_payment_router::{
+	InMemoryPaymentRouter, RoutingOptions,
+};
+use rinha_de_backend::infrastructure::workers::worker_pool::{
+	WorkerPool, WorkerPoolOptions,
+};
+use serde_json::{Value, json};
+
+#[actix_web::test]
+async fn test_reload_config_rejects_unloadable_config() {
+	let config: Config = serde_json::from_value(json!({
+		"redis_url": "redis://localhost:6379",
+		"default_payment_processor_url": "http://localhost:8001",
+		"fallback_payment_processor_url": "http://localhost:8002",
+		"server_keepalive": 5,
+		"payment_processor_worker_count": 2,
+		"wal_fsync_policy": "interval",
+		"wal_fsync_interval_ms": 10,
+		"wal_segment_max_bytes": 8_388_608,
+		"ingress_backpressure_mode": "block",
+		"ingress_high_water_mark": 80_000,
+		"ingress_max_wait_ms": 50,
+		"ingress_retry_after_secs": 1,
+		"ingress_batch_size": 128,
+		"ingress_batch_linger_us": 200,
+		"payment_processor_batch_size": 16,
+		"payment_processor_min_concurrency": 1,
+		"payment_processor_max_concurrency": 64,
+		"payment_processor_target_throughput": 500.0,
+		"worker_pool_scale_interval_ms": 1_000,
+		"worker_pool_backlog_per_worker": 1_000,
+		"worker_pool_max_latency_ms": 100,
+		"otlp_service_name": "rinha-de-backend",
+		"health_max_buffer_saturation": 0.9,
+		"server_bind_address": "0.0.0.0:9999",
+		"ingress_buffer_capacity": 100_000,
+		"payments_queue_key": "payments_queue",
+		"processor_health_check_interval_ms": 5_000,
+		"processor_fee_refresh_interval_ms": 60_000,
+		"summary_max_window_ms": 31_622_400_000u64,
+		"summary_consistency_timeout_ms": 500,
+		"retention_bucket_ms": 60_000,
+		"retention_interval_ms": 60_000,
+		"retention_batch_size": 500,
+		"retention_dedup_window_ms": 86_400_000,
+	}))
+	.unwrap();
+	let router = InMemoryPaymentRouter::new(
+		config.get_default_key(),
+		config.get_fallback_key(),
+	);
+	let worker_pool = WorkerPool::new(WorkerPoolOptions {
+		min_workers:        2,
+		max_workers:        2,
+		scale_interval:     Duration::from_secs(1),
+		backlog_per_worker: 100,
+		max_latency:        Duration::from_millis(100),
+	});
+	let reloader =
+		RoutingConfigReloader::new(router.clone(), worker_pool.clone(), &config);
+
+	let app = test::init_service(
+		App::new()
+			.app_data(web::Data::new(reloader))
+			.service(web::scope("/admin").service(reload_config)),
+	)
+	.await;
+
+	// No APP_* variables are set here, so the reloaded configuration misses
+	// the required processor URLs.
+	let req = test::TestRequest::post()
+		.uri("/admin/config/reload")
+		.to_request();
+	let resp = test::call_service(&app, req).await;
+
+	assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+	let body: Value = test::read_body_json(resp).await;
+	assert!(body["error"].as_str().unwrap().contains("missing field"));
+	assert_eq!(router.routing_options(), RoutingOptions::default());
+	assert_eq!(
+		router.default_processor.read().unwrap().key.url,
+		"http://localhost:8001"
+	);
+	assert_eq!(worker_pool.status().max_workers, 2);
+}
@@ -1,12 +1,17 @@
 use std::sync::Arc;
 use std::time::Duration;
 
+use actix_web::http::StatusCode;
+use actix_web::http::header::RETRY_AFTER;
 use actix_web::{App, test, web};
 use rinha_de_backend::adapters::web::handlers::payments;
 use rinha_de_backend::adapters::web::schema::PaymentRequest;
 use rinha_de_backend::domain::payment::Payment;
 use rinha_de_backend::domain::payment_producer::PaymentProducer;
 use rinha_de_backend::domain::queue::Queue;
+use rinha_de_backend::infrastructure::queue::admission_controller::{
+	AdmissionController, AdmissionOptions, BackpressureMode,
+};
 use rinha_de_backend::infrastructure::queue::mpsc_payment_producer::MpscPaymentProducer;
 use rinha_de_backend::infrastructure::queue::redis_payment_queue::PaymentQueue;
 use rinha_de_backend::use_cases::create_payment::CreatePaymentUseCase;
@@ -54,7 +59,8 @@ async fn test_payments_post_returns_success() {
 		tokio::time::timeout(Duration::from_secs(1), payment_receiver.recv())
 			.await
 			.expect("Did not receive payment from MPSC channel")
-			.expect("Channel closed");
+			.expect("Channel closed")
+			.payment;
 
 	assert_eq!(received_payment.correlation_id, payment_req.correlation_id);
 	assert_eq!(received_payment.amount, payment_req.amount);
@@ -105,3 +111,68 @@ async fn test_payments_post_channel_closed() {
 
 	assert!(resp.status().is_server_error());
 }
+
+fn admission_controller(mode: BackpressureMode) -> AdmissionController {
+	AdmissionController::new(AdmissionOptions {
+		mode,
+		high_water_mark: 1,
+		max_wait: Duration::from_millis(20),
+		max_queue_length: None,
+		retry_after: Duration::from_secs(2),
+	})
+}
+
+async fn post_payment_with_full_buffer(
+	mode: BackpressureMode,
+) -> actix_web::dev::ServiceResponse {
+	let (payment_sender, _payment_receiver) = mpsc::channel(1);
+	let mpsc_payment_producer = MpscPaymentProducer::new(payment_sender)
+		.with_admission_controller(admission_controller(mode));
+
+	mpsc_payment_producer
+		.send(Payment {
+			correlation_id: Uuid::new_v4(),
+			amount:         10.0,
+			submitted_at:   None,
+			processed_at:   None,
+			processed_by:   None,
+			received_at:    None,
+		})
+		.await
+		.unwrap();
+
+	let app = test::init_service(
+		App::new()
+			.app_data(web::Data::new(
+				Box::new(mpsc_payment_producer.clone()) as Box<dyn PaymentProducer>
+			))
+			.service(payments),
+	)
+	.await;
+
+	let req = test::TestRequest::post()
+		.uri("/payments")
+		.set_json(PaymentRequest {
+			correlation_id: Uuid::new_v4(),
+			amount:         100.0,
+		})
+		.to_request();
+
+	test::call_service(&app, req).await
+}
+
+#[actix_web::test]
+async fn test_payments_post_fail_fast_returns_service_unavailable() {
+	let resp = post_payment_with_full_buffer(BackpressureMode::FailFast).await;
+
+	assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+	assert_eq!(resp.headers().get(RETRY_AFTER).unwrap(), "2");
+}
+
+#[actix_web::test]
+async fn test_payments_post_bounded_wait_returns_service_unavailable() {
+	let resp = post_payment_with_full_buffer(BackpressureMode::BoundedWait).await;
+
+	assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+	assert_eq!(resp.headers().get(RETRY_AFTER).unwrap(), "2");
+}
@@ -0,0 +1,237 @@
+use rinha_de_backend::domain::fee_schedule::{FeeRates, FeeSchedule};
+use rinha_de_backend::use_cases::dto::{
+	FeeBasis, GetPaymentSummaryBreakdownQuery, GetPaymentSummaryQuery, Granularity,
+};
+use rinha_de_backend::use_cases::get_payment_summary::{
+	GetPaymentSummaryUseCase, SummaryWindowError,
+};
+use time::{Duration, OffsetDateTime};
+
+mod support;
+
+use crate::support::payment_repository::StubPaymentRepository;
+use crate::support::payments::at;
+
+fn use_case() -> GetPaymentSummaryUseCase<StubPaymentRepository> {
+	GetPaymentSummaryUseCase::new(StubPaymentRepository::default())
+		.with_fee_schedule(FeeSchedule::new(FeeRates {
+			default:  0.1,
+			fallback: 0.15,
+		}))
+}
+
+fn query(fees: Option<FeeBasis>) -> GetPaymentSummaryQuery {
+	GetPaymentSummaryQuery {
+		from: None,
+		to: None,
+		fees,
+		consistent: false,
+	}
+}
+
+#[tokio::test]
+async fn test_consistent_execute_reports_pending_payments() {
+	let consistent = GetPaymentSummaryQuery {
+		consistent: true,
+		..query(None)
+	};
+
+	let settled = use_case().execute(consistent.clone()).await.unwrap();
+	assert_eq!(settled.pending_payments, Some(0));
+
+	let timeout = std::time::Duration::from_millis(50);
+	let started_at = tokio::time::Instant::now();
+	let unsettled = GetPaymentSummaryUseCase::new(StubPaymentRepository {
+		in_flight: 3,
+		..Default::default()
+	})
+	.with_consistency_timeout(timeout)
+	.execute(consistent)
+	.await
+	.unwrap();
+	assert!(started_at.elapsed() >= timeout);
+	assert_eq!(unsettled.pending_payments, Some(3));
+	assert_eq!(unsettled.default.total_requests, 4);
+
+	let plain = use_case().execute(query(None)).await.unwrap();
+	assert_eq!(plain.pending_payments, None);
+}
+
+#[tokio::test]
+async fn test_consistent_execute_returns_once_pending_payments_settle() {
+	let timeout = std::time::Duration::from_secs(10);
+	let started_at = tokio::time::Instant::now();
+	let settled = GetPaymentSummaryUseCase::new(StubPaymentRepository {
+		in_flight: 3,
+		settling: true,
+		..Default::default()
+	})
+	.with_consistency_timeout(timeout)
+	.execute(GetPaymentSummaryQuery {
+		consistent: true,
+		..query(None)
+	})
+	.await
+	.unwrap();
+
+	assert_eq!(settled.pending_payments, Some(0));
+	assert!(started_at.elapsed() < std::time::Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn test_execute_rejects_invalid_windows() {
+	let use_case = use_case().with_max_window(Duration::days(31));
+	let window_error = |from, to| {
+		let use_case = use_case.clone();
+		async move {
+			let error = use_case
+				.execute(GetPaymentSummaryQuery {
+					from,
+					to,
+					fees: None,
+					consistent: false,
+				})
+				.await
+				.unwrap_err();
+			error
+				.downcast_ref::<SummaryWindowError>()
+				.map(|e| e.to_string())
+		}
+	};
+
+	assert_eq!(
+		window_error(Some(at(60)), Some(at(0))).await,
+		Some(
+			"`from` (2025-07-01 0:01:00.0 +00:00:00) is after `to` (2025-07-01 \
+			 0:00:00.0 +00:00:00)."
+				.to_string()
+		)
+	);
+	assert!(
+		window_error(Some(at(0)), Some(at(0) + Duration::days(32)))
+			.await
+			.is_some_and(|e| e.starts_with("The window spans"))
+	);
+	// Windows open on either side are not limited.
+	for (from, to) in [
+		(Some(at(0)), Some(at(0))),
+		(None, Some(at(0) + Duration::days(365))),
+		(Some(at(0)), None),
+		(None, None),
+	] {
+		assert!(
+			use_case
+				.execute(GetPaymentSummaryQuery {
+					from,
+					to,
+					fees: None,
+					consistent: false,
+				})
+				.await
+				.is_ok()
+		);
+	}
+}
+
+#[tokio::test]
+async fn test_execute_rejects_bounds_inside_compacted_buckets() {
+	let use_case = use_case().with_compaction(Duration::HOUR, Duration::MINUTE);
+	let summary = |from, to| {
+		use_case.execute(GetPaymentSummaryQuery {
+			from,
+			to,
+			fees: None,
+			consistent: false,
+		})
+	};
+
+	let error = summary(Some(at(30)), None).await.unwrap_err();
+	assert_eq!(
+		error.downcast_ref::<SummaryWindowError>(),
+		Some(&SummaryWindowError::Unaligned {
+			bound:  at(30),
+			bucket: Duration::MINUTE,
+		})
+	);
+	assert!(summary(None, Some(at(90))).await.is_err());
+	for (from, to) in [
+		(Some(at(60)), Some(at(120))),
+		(Some(at(0)), Some(at(120) - Duration::NANOSECOND)),
+		// Recent payments are not compacted yet.
+		(
+			Some(OffsetDateTime::now_utc() - Duration::seconds(90)),
+			None,
+		),
+	] {
+		assert!(summary(from, to).await.is_ok());
+	}
+}
+
+#[tokio::test]
+async fn test_execute_without_fees_keeps_the_plain_summary() {
+	let summary = use_case().execute(query(None)).await.unwrap();
+
+	assert_eq!(summary.default.total_fee, None);
+	assert_eq!(summary.blended_fee_rate, None);
+}
+
+#[tokio::test]
+async fn test_execute_with_current_fees() {
+	let summary = use_case()
+		.execute(query(Some(FeeBasis::Current)))
+		.await
+		.unwrap();
+
+	assert_eq!(summary.default.total_fee, Some(10.0));
+	assert_eq!(summary.default.net_amount, Some(90.0));
+	assert_eq!(summary.fallback.total_fee, Some(7.5));
+	assert_eq!(summary.fallback.fee_rate, Some(0.15));
+	assert_eq!(summary.blended_fee_rate, Some(17.5 / 150.0));
+}
+
+#[tokio::test]
+async fn test_execute_with_processing_fees() {
+	let summary = use_case()
+		.execute(query(Some(FeeBasis::Processing)))
+		.await
+		.unwrap();
+
+	// 3.00 stamped plus 40.00 unrated at the current 10%.
+	assert_eq!(summary.default.total_fee, Some(7.0));
+	assert_eq!(summary.default.net_amount, Some(93.0));
+	assert_eq!(summary.default.fee_rate, Some(0.07));
+	assert_eq!(summary.fallback.total_fee, Some(7.5));
+	assert_eq!(summary.blended_fee_rate, Some(14.5 / 150.0));
+}
+
+#[tokio::test]
+async fn test_breakdown_rejects_windows_with_too_many_buckets() {
+	let use_case = GetPaymentSummaryUseCase::new(StubPaymentRepository::default());
+
+	let error = use_case
+		.execute_breakdown(GetPaymentSummaryBreakdownQuery {
+			from:        Some(at(0)),
+			to:          Some(at(0) + Duration::days(30)),
+			granularity: Granularity::Second,
+			consistent:  false,
+		})
+		.await
+		.unwrap_err();
+
+	assert_eq!(
+		error.downcast_ref::<SummaryWindowError>(),
+		// A week at minute granularity.
+		Some(&SummaryWindowError::TooManyBuckets { max: 7 * 24 * 60 })
+	);
+	assert!(
+		use_case
+			.execute_breakdown(GetPaymentSummaryBreakdownQuery {
+				from:        Some(at(0)),
+				to:          Some(at(0) + Duration::days(30)),
+				granularity: Granularity::Hour,
+				consistent:  false,
+			})
+			.await
+			.is_ok()
+	);
+}
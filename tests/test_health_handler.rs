@@ -0,0 +1,181 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::{App, test, web};
+use rinha_de_backend::adapters::web::handlers::{health_live, health_ready};
+use rinha_de_backend::domain::health_status::HealthStatus;
+use rinha_de_backend::domain::payment::Payment;
+use rinha_de_backend::domain::payment_processor::{
+	PaymentProcessor, PaymentProcessorKey,
+};
+use rinha_de_backend::infrastructure::health::readiness_probe::ReadinessProbe;
+use rinha_de_backend::infrastructure::queue::buffered_payment::BufferedPayment;
+use rinha_de_backend::infrastructure::routing::in_memory_payment_router::InMemoryPaymentRouter;
+use rinha_de_backend::infrastructure::workers::worker_pool::{
+	WorkerPool, WorkerPoolOptions,
+};
+use serde_json::{Value, json};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+mod support;
+
+use crate::support::redis_container::get_test_redis_client;
+
+fn worker_pool(size: usize) -> WorkerPool {
+	let pool = WorkerPool::new(WorkerPoolOptions {
+		min_workers:        1,
+		max_workers:        1,
+		scale_interval:     Duration::from_secs(1),
+		backlog_per_worker: 100,
+		max_latency:        Duration::from_millis(100),
+	});
+	pool.record_size(size, 1);
+	if size > 0 {
+		pool.record_heartbeat();
+	}
+	pool
+}
+
+fn healthy_router() -> InMemoryPaymentRouter {
+	let router = InMemoryPaymentRouter::default();
+	router.update_processor_health(PaymentProcessor {
+		key:               Arc::new(PaymentProcessorKey::new(
+			"default",
+			"http://default".into(),
+		)),
+		health:            HealthStatus::Healthy,
+		min_response_time: 10,
+	});
+	router
+}
+
+#[actix_web::test]
+async fn test_health_live() {
+	let app = test::init_service(App::new().service(health_live)).await;
+
+	let req = test::TestRequest::get().uri("/health/live").to_request();
+	let resp = test::call_service(&app, req).await;
+
+	assert_eq!(resp.status(), StatusCode::OK);
+	let body: Value = test::read_body_json(resp).await;
+	assert_eq!(body, json!({ "status": "up" }));
+}
+
+#[actix_web::test]
+async fn test_health_ready_when_dependencies_are_up() {
+	let redis_container = get_test_redis_client().await;
+	let redis = redis_container.get_redis().await;
+	let (payment_sender, _payment_receiver) = mpsc::channel::<BufferedPayment>(10);
+	let probe = ReadinessProbe::new(
+		Arc::new(redis),
+		payment_sender,
+		worker_pool(1),
+		healthy_router(),
+		0.9,
+	);
+
+	let app = test::init_service(
+		App::new()
+			.app_data(web::Data::new(probe))
+			.service(health_ready),
+	)
+	.await;
+
+	let req = test::TestRequest::get().uri("/health/ready").to_request();
+	let resp = test::call_service(&app, req).await;
+
+	assert_eq!(resp.status(), StatusCode::OK);
+	let body: Value = test::read_body_json(resp).await;
+	assert_eq!(body["status"], "up");
+	assert_eq!(body["checks"]["redis"]["status"], "up");
+	assert_eq!(body["checks"]["ingress_buffer"]["status"], "up");
+	assert_eq!(body["checks"]["workers"]["status"], "up");
+	assert_eq!(body["checks"]["payment_processors"]["status"], "up");
+	assert_eq!(
+		body["checks"]["payment_processors"]["detail"],
+		"routing to default"
+	);
+}
+
+#[actix_web::test]
+async fn test_health_ready_reports_failing_checks() {
+	let redis_container = get_test_redis_client().await;
+	let redis = redis_container.get_redis().await;
+	let (payment_sender, _payment_receiver) = mpsc::channel::<BufferedPayment>(1);
+	payment_sender
+		.send(
+			Payment {
+				correlation_id: Uuid::new_v4(),
+				amount:         10.0,
+				submitted_at:   None,
+				processed_at:   None,
+				processed_by:   None,
+				received_at:    None,
+			}
+			.into(),
+		)
+		.await
+		.unwrap();
+	let probe = ReadinessProbe::new(
+		Arc::new(redis),
+		payment_sender,
+		worker_pool(0),
+		InMemoryPaymentRouter::default(),
+		0.9,
+	);
+
+	let app = test::init_service(
+		App::new()
+			.app_data(web::Data::new(probe))
+			.service(health_ready),
+	)
+	.await;
+
+	let req = test::TestRequest::get().uri("/health/ready").to_request();
+	let resp = test::call_service(&app, req).await;
+
+	assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+	let body: Value = test::read_body_json(resp).await;
+	assert_eq!(body["status"], "down");
+	assert_eq!(body["checks"]["redis"]["status"], "up");
+	assert_eq!(body["checks"]["ingress_buffer"]["status"], "down");
+	assert_eq!(body["checks"]["workers"]["status"], "down");
+	assert_eq!(body["checks"]["payment_processors"]["status"], "down");
+}
+
+#[actix_web::test]
+async fn test_health_ready_reports_workers_that_never_beat() {
+	let redis_container = get_test_redis_client().await;
+	let redis = redis_container.get_redis().await;
+	let (payment_sender, _payment_receiver) = mpsc::channel::<BufferedPayment>(10);
+	// The supervisor counts a worker that is yet to run its loop.
+	let pool = worker_pool(0);
+	pool.record_size(1, 1);
+	let probe = ReadinessProbe::new(
+		Arc::new(redis),
+		payment_sender,
+		pool,
+		healthy_router(),
+		0.9,
+	);
+
+	let app = test::init_service(
+		App::new()
+			.app_data(web::Data::new(probe))
+			.service(health_ready),
+	)
+	.await;
+
+	let req = test::TestRequest::get().uri("/health/ready").to_request();
+	let resp = test::call_service(&app, req).await;
+
+	assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+	let body: Value = test::read_body_json(resp).await;
+	assert_eq!(body["checks"]["workers"]["status"], "down");
+	assert_eq!(
+		body["checks"]["workers"]["detail"],
+		"no payment processing worker has started"
+	);
+}
@@ -1,6 +1,10 @@
 use std::sync::Arc;
 
+use rinha_de_backend::infrastructure::config::redis::PAYMENTS_QUEUE_KEY;
 use rinha_de_backend::infrastructure::config::settings::Config;
+use rinha_de_backend::infrastructure::metrics::registry::MetricsRegistry;
+use rinha_de_backend::infrastructure::queue::admission_controller::BackpressureMode;
+use rinha_de_backend::infrastructure::wal::write_ahead_log::FsyncPolicy;
 use tokio::sync::mpsc;
 
 mod support;
@@ -25,6 +29,66 @@ async fn test_run_bind_error() {
 		server_keepalive: 60,
 		report_url: None,
 		payment_processor_worker_count: 4,
+		wal_dir: None,
+		wal_fsync_policy: FsyncPolicy::Interval,
+		wal_fsync_interval_ms: 10,
+		wal_segment_max_bytes: 8 * 1024 * 1024,
+		ingress_backpressure_mode: BackpressureMode::Block,
+		ingress_high_water_mark: 80_000,
+		ingress_max_wait_ms: 50,
+		ingress_max_queue_length: None,
+		ingress_retry_after_secs: 1,
+		ingress_batch_size: 128,
+		ingress_batch_linger_us: 200,
+		payment_processor_batch_size: 16,
+		payment_processor_min_concurrency: 1,
+		payment_processor_max_concurrency: 64,
+		payment_processor_target_throughput: 500.0,
+		payment_processor_min_workers: None,
+		payment_processor_max_workers: None,
+		worker_pool_scale_interval_ms: 1_000,
+		worker_pool_backlog_per_worker: 1_000,
+		worker_pool_max_latency_ms: 100,
+		log_format: None,
+		otlp_endpoint: None,
+		otlp_service_name: "rinha-de-backend".into(),
+		health_max_buffer_saturation: 0.9,
+		server_bind_address: "0.0.0.0:9999".into(),
+		ingress_buffer_capacity: 100_000,
+		payments_queue_key: PAYMENTS_QUEUE_KEY.into(),
+		processor_health_check_interval_ms: 5_000,
+		routing_max_response_time_ms: None,
+		default_breaker_failure_threshold: None,
+		default_breaker_min_throughput: None,
+		default_breaker_probe_interval: None,
+		default_breaker_cooldown_ms: None,
+		fallback_breaker_failure_threshold: None,
+		fallback_breaker_min_throughput: None,
+		fallback_breaker_probe_interval: None,
+		fallback_breaker_cooldown_ms: None,
+		admin_token: None,
+		redis_key_prefix: None,
+		redis_key_hash_tag: None,
+		redis_mode: None,
+		redis_sentinel_service_name: None,
+		redis_master_username: None,
+		redis_master_password: None,
+		redis_master_db: None,
+		redis_master_tls: None,
+		redis_reconnect_max_delay_ms: None,
+		redis_startup_timeout_ms: None,
+		default_processor_fee_rate: None,
+		fallback_processor_fee_rate: None,
+		processor_admin_token: None,
+		processor_fee_refresh_interval_ms: 60_000,
+		summary_max_window_ms: 366 * 24 * 60 * 60 * 1_000,
+		summary_consistency_timeout_ms: 500,
+		summary_timestamp: None,
+		retention_max_age_ms: None,
+		retention_bucket_ms: 60_000,
+		retention_interval_ms: 60_000,
+		retention_batch_size: 500,
+		retention_dedup_window_ms: 24 * 60 * 60 * 1_000,
 	});
 
 	// Create a dummy MPSC channel for the test
@@ -32,9 +96,15 @@ async fn test_run_bind_error() {
 
 	// Attempt to bind to the same address, which should fail
 	assert!(
-		rinha_de_backend::run(dummy_config, sender, Arc::new(redis))
-			.await
-			.is_err()
+		rinha_de_backend::run(
+			dummy_config,
+			sender,
+			Arc::new(redis),
+			None,
+			MetricsRegistry::new()
+		)
+		.await
+		.is_err()
 	);
 	drop(listener);
 }
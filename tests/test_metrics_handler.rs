@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use actix_web::{App, test, web};
+use rinha_de_backend::adapters::web::handlers::metrics;
+use rinha_de_backend::infrastructure::metrics::ingress_metrics::IngressMetrics;
+use rinha_de_backend::infrastructure::metrics::registry::MetricsRegistry;
+
+#[actix_web::test]
+async fn test_metrics_renders_registered_sources() {
+	let registry = MetricsRegistry::new();
+	let ingress_metrics = IngressMetrics::new();
+	registry.register(Arc::new(ingress_metrics.clone()));
+
+	ingress_metrics.record_batch(3);
+	ingress_metrics.record_failed_batch(2);
+
+	let app = test::init_service(
+		App::new()
+			.app_data(web::Data::new(registry.clone()))
+			.service(metrics),
+	)
+	.await;
+
+	let req = test::TestRequest::get().uri("/metrics").to_request();
+	let resp = test::call_service(&app, req).await;
+
+	assert!(resp.status().is_success());
+
+	let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+	assert!(body.contains("rinha_ingress_batch_size_bucket{le=\"4\"} 1"));
+	assert!(body.contains("rinha_ingress_batch_size_sum 3"));
+	assert!(body.contains("rinha_ingress_failed_payments_total 2"));
+}
@@ -3,8 +3,11 @@ use std::time::Duration;
 
 use rinha_de_backend::domain::payment::Payment;
 use rinha_de_backend::domain::queue::Queue;
+use rinha_de_backend::infrastructure::metrics::ingress_metrics::IngressMetrics;
 use rinha_de_backend::infrastructure::queue::redis_payment_queue::PaymentQueue;
-use rinha_de_backend::infrastructure::workers::mpsc_to_redis_worker::mpsc_to_redis_worker;
+use rinha_de_backend::infrastructure::workers::mpsc_to_redis_worker::{
+	BatchOptions, mpsc_to_redis_worker,
+};
 use rinha_de_backend::use_cases::create_payment::CreatePaymentUseCase;
 use tokio::sync::mpsc;
 use uuid::Uuid;
@@ -22,19 +25,28 @@ async fn test_mpsc_to_redis_worker_happy_path() {
 	let create_payment_use_case = CreatePaymentUseCase::new(payment_queue.clone());
 	let (sender, receiver) = mpsc::channel(1);
 
-	let worker_handle =
-		tokio::spawn(mpsc_to_redis_worker(receiver, create_payment_use_case));
+	let worker_handle = tokio::spawn(mpsc_to_redis_worker(
+		receiver,
+		create_payment_use_case,
+		None,
+		BatchOptions::default(),
+		IngressMetrics::new(),
+	));
 
 	let payment_to_process = Payment {
 		correlation_id: Uuid::new_v4(),
 		amount:         150.75,
-		requested_at:   None,
+		submitted_at:   None,
 		processed_at:   None,
 		processed_by:   None,
+		received_at:    None,
 	};
 
 	// Act
-	sender.send(payment_to_process.clone()).await.unwrap();
+	sender
+		.send(payment_to_process.clone().into())
+		.await
+		.unwrap();
 
 	// Allow time for the worker to process the message
 	tokio::time::sleep(Duration::from_secs(1)).await;
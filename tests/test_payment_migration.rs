@@ -0,0 +1,261 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use rinha_de_backend::adapters::cli::migrate_command::migrate_payments;
+use rinha_de_backend::domain::queue::{Message, Queue};
+use rinha_de_backend::domain::repository::PaymentRepository;
+use rinha_de_backend::domain::snapshot::SnapshotStore;
+use rinha_de_backend::infrastructure::config::redis::RedisKeys;
+use rinha_de_backend::infrastructure::persistence::redis_payment_repository::RedisPaymentRepository;
+use rinha_de_backend::infrastructure::queue::redis_payment_queue::PaymentQueue;
+use rinha_de_backend::use_cases::dto::MigrationCheckpoint;
+use rinha_de_backend::use_cases::migrate_payments::{
+	MigratePaymentsUseCase, MigrationError,
+};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+mod support;
+
+use crate::support::payments::processed_payment;
+use crate::support::redis_container::get_test_redis_client;
+
+fn checkpoint_path() -> std::path::PathBuf {
+	std::env::temp_dir()
+		.join(format!("migration-checkpoint-{}.json", Uuid::new_v4()))
+}
+
+#[tokio::test]
+async fn test_migration_copies_payments_and_moves_the_queue() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let source = RedisPaymentRepository::new(Arc::clone(&redis))
+		.with_keys(RedisKeys::new("old", false));
+	let target = RedisPaymentRepository::new(Arc::clone(&redis))
+		.with_keys(RedisKeys::new("new", true));
+	let source_queue = PaymentQueue::new(Arc::clone(&redis))
+		.with_keys(RedisKeys::new("old", false));
+	let target_queue =
+		PaymentQueue::new(Arc::clone(&redis)).with_keys(RedisKeys::new("new", true));
+
+	let now = OffsetDateTime::now_utc() - time::Duration::MINUTE;
+	for (i, group) in ["default", "fallback", "default", "default", "fallback"]
+		.into_iter()
+		.enumerate()
+	{
+		source
+			.save(processed_payment(
+				group,
+				10.0 + i as f64,
+				now + time::Duration::milliseconds(i as i64),
+			))
+			.await
+			.unwrap();
+	}
+	let queued = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+	for id in queued {
+		source_queue
+			.push(Message::with(id, processed_payment("", 5.0, now)))
+			.await
+			.unwrap();
+	}
+
+	let use_case = MigratePaymentsUseCase::new(source.clone(), target.clone())
+		.with_batch_size(2);
+	let path = checkpoint_path();
+	let (checkpoint, report) =
+		migrate_payments(&use_case, &path, false).await.unwrap();
+
+	assert!(report.matches(), "{report:?}");
+	assert_eq!(checkpoint.payments, 5);
+	assert_eq!(checkpoint.queued, 3);
+	assert!(checkpoint.in_transit.is_empty());
+	for group in ["default", "fallback"] {
+		assert_eq!(
+			target
+				.get_summary_by_group(group, None, None)
+				.await
+				.unwrap(),
+			source
+				.get_summary_by_group(group, None, None)
+				.await
+				.unwrap()
+		);
+	}
+	assert_eq!(source_queue.length().await.unwrap(), 0);
+	for id in queued {
+		assert_eq!(target_queue.pop().await.unwrap().unwrap().id, id);
+	}
+
+	// A later run resumes from the checkpoint and copies what arrived since.
+	let late = processed_payment("fallback", 99.0, OffsetDateTime::now_utc());
+	source.save(late.clone()).await.unwrap();
+	let (_, report) = migrate_payments(&use_case, &path, false).await.unwrap();
+	std::fs::remove_file(&path).unwrap();
+
+	assert!(report.matches(), "{report:?}");
+	assert!(
+		target
+			.is_already_processed(&late.correlation_id.to_string())
+			.await
+			.unwrap()
+	);
+	assert_eq!(
+		target
+			.get_summary_by_group("fallback", None, None)
+			.await
+			.unwrap(),
+		source
+			.get_summary_by_group("fallback", None, None)
+			.await
+			.unwrap()
+	);
+}
+
+#[tokio::test]
+async fn test_migration_resumes_with_the_messages_in_transit() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let source = RedisPaymentRepository::new(Arc::clone(&redis))
+		.with_keys(RedisKeys::new("old", false));
+	let target = RedisPaymentRepository::new(Arc::clone(&redis))
+		.with_keys(RedisKeys::new("new", false));
+	let target_queue = PaymentQueue::new(Arc::clone(&redis))
+		.with_keys(RedisKeys::new("new", false));
+
+	// Taken off the source by a run that stopped before writing them.
+	let message = Message::with(
+		Uuid::new_v4(),
+		processed_payment("", 5.0, OffsetDateTime::now_utc()),
+	);
+	let use_case = MigratePaymentsUseCase::new(source, target);
+	let mut migration = use_case.resume(MigrationCheckpoint {
+		in_transit: vec![message.clone()],
+		..Default::default()
+	});
+
+	assert!(migration.step().await.unwrap());
+	assert!(migration.checkpoint().in_transit.is_empty());
+	assert_eq!(migration.checkpoint().queued, 1);
+	while migration.step().await.unwrap() {}
+
+	assert!(migration.verify().await.unwrap().matches());
+	assert_eq!(target_queue.pop().await.unwrap().unwrap().id, message.id);
+}
+
+#[tokio::test]
+async fn test_migration_recovers_messages_taken_before_a_crash() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let keys = RedisKeys::new("old", false);
+	let source =
+		RedisPaymentRepository::new(Arc::clone(&redis)).with_keys(keys.clone());
+	let target = RedisPaymentRepository::new(Arc::clone(&redis))
+		.with_keys(RedisKeys::new("new", false));
+	let source_queue = PaymentQueue::new(Arc::clone(&redis)).with_keys(keys.clone());
+	let target_queue = PaymentQueue::new(Arc::clone(&redis))
+		.with_keys(RedisKeys::new("new", false));
+	let mut con = redis.connection.as_ref().clone();
+
+	let queued = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+	for id in &queued[..2] {
+		source_queue
+			.push(Message::with(
+				*id,
+				processed_payment("", 5.0, OffsetDateTime::now_utc()),
+			))
+			.await
+			.unwrap();
+	}
+	redis::cmd("LPUSH")
+		.arg(keys.payments_queue())
+		.arg("this is not a valid message")
+		.query_async::<()>(&mut con)
+		.await
+		.unwrap();
+	source_queue
+		.push(Message::with(
+			queued[2],
+			processed_payment("", 5.0, OffsetDateTime::now_utc()),
+		))
+		.await
+		.unwrap();
+
+	// A run that stopped before its checkpoint recorded what it took.
+	let taken = source.take_queue(2).await.unwrap();
+	assert_eq!(taken.len(), 2);
+	assert_eq!(source_queue.length().await.unwrap(), 2);
+
+	let use_case = MigratePaymentsUseCase::new(source, target);
+	let path = checkpoint_path();
+	let (checkpoint, report) =
+		migrate_payments(&use_case, &path, false).await.unwrap();
+	std::fs::remove_file(&path).unwrap();
+
+	assert!(report.matches(), "{report:?}");
+	assert_eq!(checkpoint.queued, 3);
+	for id in queued {
+		assert_eq!(target_queue.pop().await.unwrap().unwrap().id, id);
+	}
+	assert_eq!(target_queue.length().await.unwrap(), 0);
+	let migrating: usize = redis::cmd("LLEN")
+		.arg(keys.migrating_queue())
+		.query_async(&mut con)
+		.await
+		.unwrap();
+	assert_eq!(migrating, 0);
+	let dead_letters: Vec<String> = redis::cmd("LRANGE")
+		.arg(keys.dead_letter_queue())
+		.arg(0)
+		.arg(-1)
+		.query_async(&mut con)
+		.await
+		.unwrap();
+	assert_eq!(dead_letters, vec![
+		"this is not a valid message".to_string()
+	]);
+}
+
+#[tokio::test]
+async fn test_migration_fails_when_the_source_is_compacted_meanwhile() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let source = RedisPaymentRepository::new(Arc::clone(&redis))
+		.with_keys(RedisKeys::new("old", false));
+	let target = RedisPaymentRepository::new(Arc::clone(&redis))
+		.with_keys(RedisKeys::new("new", false));
+
+	let submitted_at = OffsetDateTime::now_utc() - time::Duration::HOUR;
+	for amount in [10.0, 20.0] {
+		source
+			.save(processed_payment("default", amount, submitted_at))
+			.await
+			.unwrap();
+	}
+
+	let use_case =
+		MigratePaymentsUseCase::new(source.clone(), target).with_batch_size(1);
+	let mut migration = use_case.resume(Default::default());
+	assert!(migration.step().await.unwrap());
+	source
+		.compact(
+			OffsetDateTime::now_utc(),
+			Duration::from_secs(60),
+			Duration::from_secs(60),
+			10,
+		)
+		.await
+		.unwrap();
+
+	let error = loop {
+		match migration.step().await {
+			Ok(true) => {}
+			Ok(false) => panic!("The migration ended without noticing"),
+			Err(e) => break e,
+		}
+	};
+	assert_eq!(
+		error.downcast_ref::<MigrationError>(),
+		Some(&MigrationError::CompactedMeanwhile)
+	);
+}
@@ -1,19 +1,35 @@
 use std::sync::Arc;
 
+use async_trait::async_trait;
+use circuitbreaker_rs::{CircuitBreaker, DefaultPolicy};
+use redis::AsyncCommands;
 use reqwest::Client;
 use rinha_de_backend::domain::health_status::HealthStatus;
 use rinha_de_backend::domain::payment::Payment;
 use rinha_de_backend::domain::payment_processor::{
 	PaymentProcessor, PaymentProcessorKey,
 };
+use rinha_de_backend::domain::payment_router::PaymentRouter;
 use rinha_de_backend::domain::queue::{Message, Queue};
 use rinha_de_backend::domain::repository::PaymentRepository;
+use rinha_de_backend::infrastructure::config::redis::RedisKeys;
 use rinha_de_backend::infrastructure::persistence::redis_payment_repository::RedisPaymentRepository;
 use rinha_de_backend::infrastructure::queue::redis_payment_queue::PaymentQueue;
 use rinha_de_backend::infrastructure::routing::in_memory_payment_router::InMemoryPaymentRouter;
+use rinha_de_backend::infrastructure::routing::processor_concurrency_limiter::{
+	ConcurrencyOptions, ProcessorConcurrencyLimiter,
+};
 use rinha_de_backend::infrastructure::workers::payment_processor_worker::payment_processing_worker;
-use rinha_de_backend::use_cases::process_payment::ProcessPaymentUseCase;
+use rinha_de_backend::infrastructure::workers::worker_pool::{
+	WorkerPool, WorkerPoolOptions,
+};
+use rinha_de_backend::use_cases::process_payment::{
+	PaymentProcessingError, ProcessPaymentUseCase,
+};
 use time::OffsetDateTime;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
 use tokio::time::Duration;
 use uuid::Uuid;
 
@@ -22,6 +38,39 @@ mod support;
 use crate::support::payment_processor_container::setup_payment_processors;
 use crate::support::redis_container::get_test_redis_client;
 
+fn concurrency_limiter() -> ProcessorConcurrencyLimiter {
+	ProcessorConcurrencyLimiter::new(ConcurrencyOptions {
+		min_permits:       1,
+		max_permits:       8,
+		target_throughput: 100.0,
+	})
+}
+
+fn worker_pool() -> WorkerPool {
+	WorkerPool::new(WorkerPoolOptions {
+		min_workers:        1,
+		max_workers:        1,
+		scale_interval:     Duration::from_secs(1),
+		backlog_per_worker: 100,
+		max_latency:        Duration::from_secs(1),
+	})
+}
+
+#[derive(Clone)]
+struct PanickingRouter;
+
+#[async_trait]
+impl PaymentRouter for PanickingRouter {
+	async fn get_processor_for_payment(
+		&self,
+	) -> Option<(
+		Arc<PaymentProcessorKey>,
+		CircuitBreaker<DefaultPolicy, PaymentProcessingError>,
+	)> {
+		panic!("router failed")
+	}
+}
+
 #[tokio::test]
 async fn test_payment_processing_worker_default_success() {
 	let redis_container = get_test_redis_client().await;
@@ -63,17 +112,15 @@ async fn test_payment_processing_worker_default_success() {
 	let payment_to_process = Payment {
 		correlation_id: Uuid::new_v4(),
 		amount:         250.0,
-		requested_at:   None,
+		submitted_at:   None,
 		processed_at:   None,
 		processed_by:   None,
+		received_at:    None,
 	};
 
 	// Push payment to queue
 	redis_queue
-		.push(Message {
-			id:   Uuid::new_v4(),
-			body: payment_to_process.clone(),
-		})
+		.push(Message::with(Uuid::new_v4(), payment_to_process.clone()))
 		.await
 		.unwrap();
 
@@ -82,6 +129,10 @@ async fn test_payment_processing_worker_default_success() {
 		payment_repo.clone(),
 		process_payment_use_case.clone(),
 		router.clone(),
+		concurrency_limiter(),
+		worker_pool(),
+		4,
+		watch::channel(false).1,
 	));
 
 	// Give the worker some time to process the payment
@@ -135,7 +186,7 @@ async fn test_payment_processing_worker_fallback_success() {
 		min_response_time: 10,
 	};
 	router.update_processor_health(default_processor);
-	router.default_breaker.force_open(); // Force open to trigger fallback
+	router.default_breaker().force_open(); // Force open to trigger fallback
 
 	let fallback_processor = PaymentProcessor {
 		key:               Arc::clone(&fallback_key),
@@ -147,16 +198,14 @@ async fn test_payment_processing_worker_fallback_success() {
 	let payment_to_process = Payment {
 		correlation_id: Uuid::new_v4(),
 		amount:         300.0,
-		requested_at:   None,
+		submitted_at:   None,
 		processed_at:   None,
 		processed_by:   None,
+		received_at:    None,
 	};
 
 	payment_queue
-		.push(Message {
-			id:   Uuid::new_v4(),
-			body: payment_to_process.clone(),
-		})
+		.push(Message::with(Uuid::new_v4(), payment_to_process.clone()))
 		.await
 		.unwrap();
 
@@ -165,6 +214,10 @@ async fn test_payment_processing_worker_fallback_success() {
 		payment_repo.clone(),
 		process_payment_use_case.clone(),
 		router.clone(),
+		concurrency_limiter(),
+		worker_pool(),
+		4,
+		watch::channel(false).1,
 	));
 
 	// Give the worker some time to process the payment
@@ -227,9 +280,10 @@ async fn test_payment_processing_worker_requeue_message_given_processor_are_down
 	let payment_to_process = Payment {
 		correlation_id: Uuid::new_v4(),
 		amount:         400.0,
-		requested_at:   None,
+		submitted_at:   None,
 		processed_at:   None,
 		processed_by:   None,
+		received_at:    None,
 	};
 
 	// Push payment to queue
@@ -246,6 +300,10 @@ async fn test_payment_processing_worker_requeue_message_given_processor_are_down
 		payment_repo.clone(),
 		process_payment_use_case.clone(),
 		router.clone(),
+		concurrency_limiter(),
+		worker_pool(),
+		4,
+		watch::channel(false).1,
 	));
 
 	// Give the worker some time to attempt processing and re-queue
@@ -308,18 +366,20 @@ async fn test_payment_processing_worker_skip_processed_message() {
 	let payment_to_process = Payment {
 		correlation_id: Uuid::new_v4(),
 		amount:         500.0,
-		requested_at:   None,
+		submitted_at:   None,
 		processed_at:   None,
 		processed_by:   None,
+		received_at:    None,
 	};
 
 	// Pre-process the payment to simulate it being already processed
 	let pre_processed_payment = Payment {
 		correlation_id: payment_to_process.correlation_id,
 		amount:         payment_to_process.amount,
-		requested_at:   Some(OffsetDateTime::now_utc()),
+		submitted_at:   Some(OffsetDateTime::now_utc()),
 		processed_at:   Some(OffsetDateTime::now_utc()),
 		processed_by:   Some("default".to_string()),
+		received_at:    None,
 	};
 	payment_repo.save(pre_processed_payment).await.unwrap();
 
@@ -337,6 +397,10 @@ async fn test_payment_processing_worker_skip_processed_message() {
 		payment_repo.clone(),
 		process_payment_use_case.clone(),
 		router.clone(),
+		concurrency_limiter(),
+		worker_pool(),
+		4,
+		watch::channel(false).1,
 	));
 
 	// Give the worker some time to process
@@ -345,7 +409,7 @@ async fn test_payment_processing_worker_skip_processed_message() {
 	let now = OffsetDateTime::now_utc();
 	let one_day_ago = now.checked_sub(time::Duration::days(1)).unwrap();
 	let (processed_payments, processed_amount) = payment_repo
-		.get_summary_by_group("default", one_day_ago, now)
+		.get_summary_by_group("default", Some(one_day_ago), Some(now))
 		.await
 		.unwrap();
 
@@ -382,6 +446,10 @@ async fn test_payment_processing_worker_redis_failure() {
 		payment_repo,
 		process_payment_use_case,
 		router,
+		concurrency_limiter(),
+		worker_pool(),
+		4,
+		watch::channel(false).1,
 	));
 
 	// Give the worker some time to run
@@ -435,23 +503,21 @@ async fn test_payment_processing_worker_circuit_breaker_open() {
 	router.update_processor_health(fallback_processor);
 
 	// Force the circuit breaker to open
-	router.default_breaker.force_open();
-	router.fallback_breaker.force_open();
+	router.default_breaker().force_open();
+	router.fallback_breaker().force_open();
 
 	let payment_to_process = Payment {
 		correlation_id: Uuid::new_v4(),
 		amount:         600.0,
-		requested_at:   None,
+		submitted_at:   None,
 		processed_at:   None,
 		processed_by:   None,
+		received_at:    None,
 	};
 
 	// Push payment to queue
 	redis_queue
-		.push(Message {
-			id:   Uuid::new_v4(),
-			body: payment_to_process.clone(),
-		})
+		.push(Message::with(Uuid::new_v4(), payment_to_process.clone()))
 		.await
 		.unwrap();
 
@@ -460,6 +526,10 @@ async fn test_payment_processing_worker_circuit_breaker_open() {
 		payment_repo.clone(),
 		process_payment_use_case.clone(),
 		router.clone(),
+		concurrency_limiter(),
+		worker_pool(),
+		4,
+		watch::channel(false).1,
 	));
 
 	// Give the worker some time to attempt processing
@@ -487,3 +557,184 @@ async fn test_payment_processing_worker_circuit_breaker_open() {
 
 	worker_handle.abort();
 }
+
+#[tokio::test]
+async fn test_payment_processing_worker_stops_on_shutdown() {
+	let redis_container = get_test_redis_client().await;
+	let redis = redis_container.get_redis().await;
+	let redis_queue = PaymentQueue::new(Arc::new(redis.clone()));
+	let payment_repo = RedisPaymentRepository::new(Arc::new(redis));
+	let process_payment_use_case =
+		ProcessPaymentUseCase::new(payment_repo.clone(), Client::new());
+	let default_key = Arc::new(PaymentProcessorKey::new("default", "".into()));
+	let fallback_key = Arc::new(PaymentProcessorKey::new("fallback", "".into()));
+	let router = InMemoryPaymentRouter::new(default_key, fallback_key);
+	let (shutdown, shutdown_receiver) = watch::channel(false);
+
+	let worker_handle = tokio::spawn(payment_processing_worker(
+		redis_queue,
+		payment_repo,
+		process_payment_use_case,
+		router,
+		concurrency_limiter(),
+		worker_pool(),
+		4,
+		shutdown_receiver,
+	));
+
+	tokio::time::sleep(Duration::from_millis(500)).await;
+	shutdown.send(true).unwrap();
+
+	tokio::time::timeout(Duration::from_secs(5), worker_handle)
+		.await
+		.expect("worker did not stop after shutdown")
+		.unwrap();
+}
+
+#[tokio::test]
+async fn test_payment_processing_worker_counts_panicked_tasks() {
+	let redis_container = get_test_redis_client().await;
+	let redis = redis_container.get_redis().await;
+	let redis_queue = PaymentQueue::new(Arc::new(redis.clone()));
+	let payment_repo = RedisPaymentRepository::new(Arc::new(redis));
+	let process_payment_use_case =
+		ProcessPaymentUseCase::new(payment_repo.clone(), Client::new());
+	let pool = worker_pool();
+
+	for _ in 0..2 {
+		let payment = Payment {
+			correlation_id: Uuid::new_v4(),
+			amount:         10.0,
+			submitted_at:   None,
+			processed_at:   None,
+			processed_by:   None,
+			received_at:    None,
+		};
+		redis_queue
+			.push(Message::with(Uuid::new_v4(), payment))
+			.await
+			.unwrap();
+	}
+
+	let (shutdown, shutdown_receiver) = watch::channel(false);
+	let worker_handle = tokio::spawn(payment_processing_worker(
+		redis_queue,
+		payment_repo,
+		process_payment_use_case,
+		PanickingRouter,
+		concurrency_limiter(),
+		pool.clone(),
+		4,
+		shutdown_receiver,
+	));
+
+	tokio::time::sleep(Duration::from_secs(2)).await;
+	assert!(!worker_handle.is_finished());
+	shutdown.send(true).unwrap();
+	worker_handle.await.unwrap();
+
+	assert_eq!(pool.status().task_panics, 2);
+}
+
+#[tokio::test]
+async fn test_payment_processing_worker_keeps_the_claim_while_waiting_for_a_permit()
+{
+	let redis_container = get_test_redis_client().await;
+	let redis = redis_container.get_redis().await;
+	let mut con = redis.connection.as_ref().clone();
+	let redis_queue = PaymentQueue::new(Arc::new(redis.clone()));
+	let payment_repo = RedisPaymentRepository::new(Arc::new(redis));
+
+	// A processor that accepts every payment at once.
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let processor_url = format!("http://{}", listener.local_addr().unwrap());
+	tokio::spawn(async move {
+		while let Ok((mut socket, _)) = listener.accept().await {
+			let _ = socket
+				.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+				.await;
+		}
+	});
+	let default_key =
+		Arc::new(PaymentProcessorKey::new("default", processor_url.into()));
+	let fallback_key = Arc::new(PaymentProcessorKey::new(
+		"fallback",
+		"http://127.0.0.1:1".into(),
+	));
+	let router =
+		InMemoryPaymentRouter::new(default_key.clone(), fallback_key.clone());
+	router.update_processor_health(PaymentProcessor {
+		key:               default_key,
+		health:            HealthStatus::Healthy,
+		min_response_time: 0,
+	});
+	router.update_processor_health(PaymentProcessor {
+		key:               fallback_key,
+		health:            HealthStatus::Failing,
+		min_response_time: 0,
+	});
+
+	let process_payment_use_case =
+		ProcessPaymentUseCase::new(payment_repo.clone(), Client::new())
+			.with_claim_heartbeat(Duration::from_millis(50));
+	let limiter = ProcessorConcurrencyLimiter::new(ConcurrencyOptions {
+		min_permits:       1,
+		max_permits:       1,
+		target_throughput: 100.0,
+	});
+	// Every request to the default processor waits for this permit.
+	let held_permit = limiter.acquire("default").await;
+
+	let payment = Payment {
+		correlation_id: Uuid::new_v4(),
+		amount:         10.0,
+		submitted_at:   None,
+		processed_at:   None,
+		processed_by:   None,
+		received_at:    None,
+	};
+	let payment_id = payment.correlation_id.to_string();
+	redis_queue
+		.push(Message::with(Uuid::new_v4(), payment))
+		.await
+		.unwrap();
+
+	let (shutdown, shutdown_receiver) = watch::channel(false);
+	let worker_handle = tokio::spawn(payment_processing_worker(
+		redis_queue,
+		payment_repo.clone(),
+		process_payment_use_case,
+		router,
+		limiter,
+		worker_pool(),
+		4,
+		shutdown_receiver,
+	));
+
+	tokio::time::sleep(Duration::from_millis(800)).await;
+
+	// Left alone, the claim would have lost the time waited for the permit.
+	let claim_ttl: i64 = con
+		.pttl(RedisKeys::default().payment_claim(&payment_id))
+		.await
+		.unwrap();
+	assert!(claim_ttl >= 29_900, "claim ttl was {claim_ttl}");
+	assert!(
+		!payment_repo
+			.is_already_processed(&payment_id)
+			.await
+			.unwrap()
+	);
+
+	drop(held_permit);
+	tokio::time::sleep(Duration::from_millis(500)).await;
+	assert!(
+		payment_repo
+			.is_already_processed(&payment_id)
+			.await
+			.unwrap()
+	);
+
+	shutdown.send(true).unwrap();
+	worker_handle.await.unwrap();
+}
@@ -0,0 +1,291 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use rinha_de_backend::domain::fee_schedule::{FeeRates, FeeSchedule};
+use rinha_de_backend::domain::repository::{
+	PaymentRepository, PaymentSearch, PurgeScope,
+};
+use rinha_de_backend::infrastructure::persistence::redis_payment_repository::RedisPaymentRepository;
+use rinha_de_backend::infrastructure::workers::payment_retention_worker::{
+	RetentionOptions, payment_retention_worker,
+};
+use rinha_de_backend::use_cases::dto::{
+	GetPaymentSummaryBreakdownQuery, GetPaymentSummaryQuery, Granularity,
+};
+use rinha_de_backend::use_cases::get_payment_summary::{
+	GetPaymentSummaryUseCase, SummaryWindowError,
+};
+use time::OffsetDateTime;
+
+mod support;
+
+use crate::support::payments::processed_payment;
+use crate::support::redis_container::get_test_redis_client;
+
+#[tokio::test]
+async fn test_compaction_keeps_summaries_and_drops_details() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let payment_repository = RedisPaymentRepository::new(Arc::clone(&redis))
+		.with_fee_schedule(FeeSchedule::new(FeeRates {
+			default:  0.05,
+			fallback: 0.15,
+		}));
+
+	// Minute-aligned, so buckets line up with the windows below.
+	let now = OffsetDateTime::now_utc();
+	let hour_ago =
+		now.replace_nanosecond(0)
+			.unwrap()
+			.replace_second(0)
+			.unwrap() - time::Duration::HOUR;
+	let old = [
+		processed_payment("default", 10.0, hour_ago + time::Duration::seconds(10)),
+		processed_payment("default", 20.0, hour_ago + time::Duration::seconds(20)),
+		processed_payment("fallback", 30.0, hour_ago + time::Duration::minutes(2)),
+	];
+	let recent = processed_payment("default", 40.0, now);
+	for payment in old.iter().chain([&recent]) {
+		payment_repository.save(payment.clone()).await.unwrap();
+	}
+
+	let summaries = || async {
+		let mut summaries = Vec::new();
+		for window in [
+			(None, None),
+			(Some(hour_ago), Some(hour_ago + time::Duration::minutes(1))),
+			(Some(hour_ago + time::Duration::minutes(1)), None),
+		] {
+			for group in ["default", "fallback"] {
+				summaries.push(
+					payment_repository
+						.get_summary_with_fees_by_group(group, window.0, window.1)
+						.await
+						.unwrap(),
+				);
+			}
+		}
+		summaries
+	};
+	let before_compaction = summaries().await;
+
+	let cutoff = now - time::Duration::minutes(30);
+	let bucket = Duration::from_secs(60);
+	let dedup_window = Duration::from_secs(24 * 60 * 60);
+	assert_eq!(
+		payment_repository
+			.compact(cutoff, bucket, dedup_window, 2)
+			.await
+			.unwrap(),
+		2
+	);
+	assert_eq!(
+		payment_repository
+			.compact(cutoff, bucket, dedup_window, 2)
+			.await
+			.unwrap(),
+		1
+	);
+	assert_eq!(
+		payment_repository
+			.compact(cutoff, bucket, dedup_window, 2)
+			.await
+			.unwrap(),
+		0
+	);
+
+	let after_compaction = summaries().await;
+	assert_eq!(after_compaction.len(), before_compaction.len());
+	for (after, before) in after_compaction.iter().zip(&before_compaction) {
+		assert_eq!(after.total_requests, before.total_requests);
+		assert!(
+			(after.total_amount - before.total_amount).abs() < 1e-9,
+			"{after:?} != {before:?}"
+		);
+		assert!((after.fees.fees - before.fees.fees).abs() < 1e-9);
+	}
+
+	// Their ids outlive the details, so replays are not charged again.
+	for payment in &old {
+		let payment_id = payment.correlation_id.to_string();
+		assert!(
+			payment_repository
+				.is_already_processed(&payment_id)
+				.await
+				.unwrap()
+		);
+		assert!(!payment_repository.claim(&payment_id).await.unwrap());
+	}
+	let page = payment_repository
+		.search_payments(&PaymentSearch::default(), None, 10)
+		.await
+		.unwrap();
+	assert_eq!(page.payments.len(), 1);
+	assert_eq!(page.payments[0].correlation_id, recent.correlation_id);
+
+	// Until they fall out of the dedup window.
+	payment_repository
+		.compact(cutoff, bucket, Duration::ZERO, 2)
+		.await
+		.unwrap();
+	assert!(
+		!payment_repository
+			.is_already_processed(&old[0].correlation_id.to_string())
+			.await
+			.unwrap()
+	);
+
+	// A window cutting through a bucket leaves the bucket out.
+	let (count, _) = payment_repository
+		.get_summary_by_group(
+			"default",
+			Some(hour_ago),
+			Some(hour_ago + time::Duration::seconds(15)),
+		)
+		.await
+		.unwrap();
+	assert_eq!(count, 0);
+
+	payment_repository
+		.purge(&PurgeScope {
+			group: Some("default".to_string()),
+			..Default::default()
+		})
+		.await
+		.unwrap();
+	let (count, _) = payment_repository
+		.get_summary_by_group("default", None, None)
+		.await
+		.unwrap();
+	assert_eq!(count, 0);
+	let (count, amount) = payment_repository
+		.get_summary_by_group("fallback", None, None)
+		.await
+		.unwrap();
+	assert_eq!((count, amount), (1, 30.0));
+}
+
+#[tokio::test]
+async fn test_breakdowns_refuse_windows_reaching_compacted_payments() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let payment_repository = RedisPaymentRepository::new(Arc::clone(&redis));
+
+	let now = OffsetDateTime::now_utc();
+	let old = processed_payment("default", 10.0, now - time::Duration::HOUR);
+	let recent = processed_payment("default", 20.0, now);
+	for payment in [&old, &recent] {
+		payment_repository.save(payment.clone()).await.unwrap();
+	}
+	assert_eq!(
+		payment_repository
+			.compact(
+				now - time::Duration::minutes(30),
+				Duration::from_secs(60),
+				Duration::from_secs(24 * 60 * 60),
+				10,
+			)
+			.await
+			.unwrap(),
+		1
+	);
+
+	let use_case = GetPaymentSummaryUseCase::new(payment_repository)
+		.with_compaction(time::Duration::minutes(30), time::Duration::MINUTE);
+	let breakdown = |from| {
+		use_case.execute_breakdown(GetPaymentSummaryBreakdownQuery {
+			from,
+			to: None,
+			granularity: Granularity::Minute,
+			consistent: false,
+		})
+	};
+
+	for from in [None, Some(now - time::Duration::HOUR)] {
+		let error = breakdown(from).await.unwrap_err();
+		assert!(
+			matches!(
+				error.downcast_ref::<SummaryWindowError>(),
+				Some(SummaryWindowError::Compacted { .. })
+			),
+			"{from:?}: {error}"
+		);
+	}
+	let recent_breakdown = breakdown(Some(now - time::Duration::minutes(10)))
+		.await
+		.unwrap();
+	assert_eq!(recent_breakdown.default.total_requests, 1);
+	assert_eq!(recent_breakdown.default.total_amount, 20.0);
+
+	// Plain summaries still count the compacted payment.
+	let summary = use_case
+		.execute(GetPaymentSummaryQuery {
+			from:       None,
+			to:         None,
+			fees:       None,
+			consistent: false,
+		})
+		.await
+		.unwrap();
+	assert_eq!(summary.default.total_requests, 2);
+}
+
+#[tokio::test]
+async fn test_payment_retention_worker_compacts_old_payments() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let payment_repository = RedisPaymentRepository::new(Arc::clone(&redis));
+
+	let now = OffsetDateTime::now_utc();
+	let old = processed_payment("default", 10.0, now - time::Duration::HOUR);
+	let recent = processed_payment("default", 20.0, now);
+	payment_repository.save(old.clone()).await.unwrap();
+	payment_repository.save(recent.clone()).await.unwrap();
+
+	let worker = tokio::spawn(payment_retention_worker(
+		payment_repository.clone(),
+		RetentionOptions {
+			max_age:      Duration::from_secs(60),
+			bucket:       Duration::from_secs(60),
+			dedup_window: Duration::from_secs(60),
+			interval:     Duration::from_millis(50),
+			batch_size:   10,
+		},
+	));
+	let old_id = old.correlation_id.to_string();
+	for _ in 0..100 {
+		if payment_repository
+			.get_payment_summary("default", &old_id)
+			.await
+			.is_err()
+		{
+			break;
+		}
+		tokio::time::sleep(Duration::from_millis(20)).await;
+	}
+	worker.abort();
+
+	assert!(
+		payment_repository
+			.get_payment_summary("default", &old_id)
+			.await
+			.is_err()
+	);
+	assert!(
+		payment_repository
+			.is_already_processed(&old_id)
+			.await
+			.unwrap()
+	);
+	assert!(
+		payment_repository
+			.is_already_processed(&recent.correlation_id.to_string())
+			.await
+			.unwrap()
+	);
+	let (count, amount) = payment_repository
+		.get_summary_by_group("default", None, None)
+		.await
+		.unwrap();
+	assert_eq!((count, amount), (2, 30.0));
+}
@@ -0,0 +1,139 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use actix_web::{App, test, web};
+use rinha_de_backend::adapters::web::handlers::payments_export;
+use rinha_de_backend::domain::repository::PaymentRepository;
+use rinha_de_backend::infrastructure::persistence::redis_payment_repository::RedisPaymentRepository;
+use rinha_de_backend::use_cases::export_payments::ExportPaymentsUseCase;
+use serde_json::Value;
+use time::{Duration, OffsetDateTime};
+
+mod support;
+
+use crate::support::payments::payment_processed_at;
+use crate::support::redis_container::get_test_redis_client;
+
+#[actix_web::test]
+async fn test_payments_export_pages_through_payments_sharing_a_timestamp() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let payment_repository = RedisPaymentRepository::new(Arc::clone(&redis));
+	let export_payments_use_case =
+		ExportPaymentsUseCase::new(payment_repository.clone()).with_page_size(2);
+
+	let app = test::init_service(
+		App::new()
+			.app_data(web::Data::new(export_payments_use_case))
+			.service(web::scope("/admin").service(payments_export)),
+	)
+	.await;
+
+	// Five payments share a timestamp, so pages end in the middle of the tie.
+	let start = OffsetDateTime::now_utc() - Duration::minutes(5);
+	let mut payments = vec![payment_processed_at("default", 10.0, start)];
+	for _ in 0..5 {
+		payments.push(payment_processed_at(
+			"fallback",
+			10.0,
+			start + Duration::seconds(1),
+		));
+	}
+	payments.push(payment_processed_at(
+		"default",
+		10.0,
+		start + Duration::seconds(2),
+	));
+	for payment in &payments {
+		payment_repository.save(payment.clone()).await.unwrap();
+	}
+
+	let req = test::TestRequest::get()
+		.uri("/admin/payments/export?format=ndjson")
+		.to_request();
+	let resp = test::call_service(&app, req).await;
+	assert!(resp.status().is_success());
+	assert_eq!(
+		resp.headers().get("content-type").unwrap(),
+		"application/x-ndjson"
+	);
+
+	let body = test::read_body(resp).await;
+	let lines: Vec<Value> = body
+		.split(|byte| *byte == b'\n')
+		.filter(|line| !line.is_empty())
+		.map(|line| serde_json::from_slice(line).unwrap())
+		.collect();
+	assert_eq!(lines.len(), payments.len());
+
+	let exported: HashSet<String> = lines
+		.iter()
+		.map(|line| line["correlationId"].as_str().unwrap().to_string())
+		.collect();
+	let saved: HashSet<String> = payments
+		.iter()
+		.map(|payment| payment.correlation_id.to_string())
+		.collect();
+	assert_eq!(exported, saved);
+	assert_eq!(
+		lines.first().unwrap()["correlationId"],
+		payments.first().unwrap().correlation_id.to_string()
+	);
+	assert_eq!(
+		lines.last().unwrap()["correlationId"],
+		payments.last().unwrap().correlation_id.to_string()
+	);
+}
+
+#[actix_web::test]
+async fn test_payments_export_writes_csv_within_the_window() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let payment_repository = RedisPaymentRepository::new(Arc::clone(&redis));
+	let export_payments_use_case =
+		ExportPaymentsUseCase::new(payment_repository.clone());
+
+	let app = test::init_service(
+		App::new()
+			.app_data(web::Data::new(export_payments_use_case))
+			.service(web::scope("/admin").service(payments_export)),
+	)
+	.await;
+
+	let start = OffsetDateTime::from_unix_timestamp(1_751_364_000).unwrap();
+	let inside =
+		payment_processed_at("default", 10.0, start + Duration::seconds(30));
+	let outside =
+		payment_processed_at("fallback", 10.0, start + Duration::minutes(5));
+	payment_repository.save(inside.clone()).await.unwrap();
+	payment_repository.save(outside).await.unwrap();
+
+	let req = test::TestRequest::get()
+		.uri("/admin/payments/export?from=2025-07-01T10:00:00Z&to=1751364060000")
+		.to_request();
+	let resp = test::call_service(&app, req).await;
+	assert!(resp.status().is_success());
+	assert_eq!(
+		resp.headers().get("content-type").unwrap(),
+		"text/csv; charset=utf-8"
+	);
+
+	let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+	assert_eq!(
+		body,
+		format!(
+			"correlationId,processedBy,amount,receivedAt,submittedAt,processedAt\\
+			 n{},default,10.00,,2025-07-01T10:00:30Z,2025-07-01T10:00:30Z\n",
+			inside.correlation_id
+		)
+	);
+
+	let req = test::TestRequest::get()
+		.uri(
+			"/admin/payments/export?from=2025-07-02T00:00:00Z&to=2025-07-01T00:00:\
+			 00Z",
+		)
+		.to_request();
+	let resp = test::call_service(&app, req).await;
+	assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+}
@@ -1,11 +1,15 @@
 use std::sync::Arc;
 
 use actix_web::{App, test, web};
-use async_trait::async_trait;
+use redis::AsyncCommands;
 use rinha_de_backend::adapters::web::handlers::payments_purge;
-use rinha_de_backend::domain::repository::PaymentRepository;
+use rinha_de_backend::domain::repository::{PaymentRepository, PurgeScope};
+use rinha_de_backend::infrastructure::config::redis::{
+	PAYMENTS_QUEUE_KEY, RedisKeys,
+};
 use rinha_de_backend::infrastructure::persistence::redis_payment_repository::RedisPaymentRepository;
 use rinha_de_backend::use_cases::purge_payments::PurgePaymentsUseCase;
+use serde_json::{Value, json};
 use time::OffsetDateTime;
 use uuid::Uuid;
 
@@ -13,6 +17,8 @@ mod support;
 
 use rinha_de_backend::domain::payment::Payment;
 
+use crate::support::payment_repository::StubPaymentRepository;
+use crate::support::payments::payment_processed_at;
 use crate::support::redis_container::get_test_redis_client;
 
 #[actix_web::test]
@@ -26,7 +32,7 @@ async fn test_payments_purge_returns_success() {
 	let app = test::init_service(
 		App::new()
 			.app_data(web::Data::new(purge_payments_use_case.clone()))
-			.service(payments_purge),
+			.service(web::scope("/admin").service(payments_purge)),
 	)
 	.await;
 
@@ -34,16 +40,18 @@ async fn test_payments_purge_returns_success() {
 	let payment1 = Payment {
 		correlation_id: Uuid::new_v4(),
 		amount:         100.0,
-		requested_at:   Some(OffsetDateTime::now_utc()),
+		submitted_at:   Some(OffsetDateTime::now_utc()),
 		processed_at:   Some(OffsetDateTime::now_utc()),
 		processed_by:   Some("group1".to_string()),
+		received_at:    None,
 	};
 	let payment2 = Payment {
 		correlation_id: Uuid::new_v4(),
 		amount:         200.0,
-		requested_at:   Some(OffsetDateTime::now_utc()),
+		submitted_at:   Some(OffsetDateTime::now_utc()),
 		processed_at:   Some(OffsetDateTime::now_utc()),
 		processed_by:   Some("group2".to_string()),
+		received_at:    None,
 	};
 	payment_repository.save(payment1.clone()).await.unwrap();
 	payment_repository.save(payment2.clone()).await.unwrap();
@@ -61,7 +69,7 @@ async fn test_payments_purge_returns_success() {
 	assert!(is_processed2);
 
 	let req = test::TestRequest::post()
-		.uri("/purge-payments")
+		.uri("/admin/purge-payments")
 		.to_request();
 	let resp = test::call_service(&app, req).await;
 
@@ -80,61 +88,252 @@ async fn test_payments_purge_returns_success() {
 	assert!(!is_processed2_after_purge);
 }
 
-#[derive(Clone)]
-struct MockPaymentRepository;
-
-#[async_trait]
-impl PaymentRepository for MockPaymentRepository {
-	async fn save(
-		&self,
-		_: Payment,
-	) -> Result<(), Box<dyn std::error::Error + Send>> {
-		Ok(())
-	}
-
-	async fn get_summary_by_group(
-		&self,
-		_: &str,
-		_: OffsetDateTime,
-		_: OffsetDateTime,
-	) -> Result<(usize, f64), Box<dyn std::error::Error + Send>> {
-		Ok((0, 0.0))
-	}
-
-	async fn get_payment_summary(
-		&self,
-		_: &str,
-		_: &str,
-	) -> Result<Payment, Box<dyn std::error::Error + Send>> {
-		unimplemented!()
-	}
-
-	async fn is_already_processed(
-		&self,
-		_: &str,
-	) -> Result<bool, Box<dyn std::error::Error + Send>> {
-		Ok(false)
-	}
-
-	async fn clear(&self) -> Result<(), Box<dyn std::error::Error + Send>> {
-		Err(Box::new(std::io::Error::other("Failed to clear payments")))
-	}
+#[actix_web::test]
+async fn test_payments_purge_by_group_keeps_other_keys() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let payment_repository = RedisPaymentRepository::new(Arc::clone(&redis));
+	let purge_payments_use_case =
+		PurgePaymentsUseCase::new(payment_repository.clone());
+
+	let now = OffsetDateTime::now_utc();
+	let default_payment = payment_processed_at("default", 10.0, now);
+	let fallback_payment = payment_processed_at("fallback", 10.0, now);
+	payment_repository
+		.save(default_payment.clone())
+		.await
+		.unwrap();
+	payment_repository
+		.save(fallback_payment.clone())
+		.await
+		.unwrap();
+
+	let mut con = redis.connection.as_ref().clone();
+	let _: () = con.set("other_service:key", "value").await.unwrap();
+	let _: () = con.lpush(PAYMENTS_QUEUE_KEY, "pending").await.unwrap();
+
+	let app = test::init_service(
+		App::new()
+			.app_data(web::Data::new(purge_payments_use_case))
+			.service(web::scope("/admin").service(payments_purge)),
+	)
+	.await;
+
+	let req = test::TestRequest::post()
+		.uri("/admin/purge-payments?group=default&preserveQueue=true")
+		.to_request();
+	let resp = test::call_service(&app, req).await;
+
+	assert!(resp.status().is_success());
+	let body: Value = test::read_body_json(resp).await;
+	assert_eq!(body, json!({ "deletedPayments": 1, "queuePurged": false }));
+
+	assert!(
+		!payment_repository
+			.is_already_processed(&default_payment.correlation_id.to_string())
+			.await
+			.unwrap()
+	);
+	assert!(
+		payment_repository
+			.is_already_processed(&fallback_payment.correlation_id.to_string())
+			.await
+			.unwrap()
+	);
+	let other: Option<String> = con.get("other_service:key").await.unwrap();
+	assert_eq!(other.as_deref(), Some("value"));
+	let queue_length: usize = con.llen(PAYMENTS_QUEUE_KEY).await.unwrap();
+	assert_eq!(queue_length, 1);
+}
+
+#[actix_web::test]
+async fn test_scoped_payments_purge_keeps_the_queue_by_default() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let payment_repository = RedisPaymentRepository::new(Arc::clone(&redis));
+
+	let mut con = redis.connection.as_ref().clone();
+	let _: () = con.lpush(PAYMENTS_QUEUE_KEY, "pending").await.unwrap();
+
+	let app = test::init_service(
+		App::new()
+			.app_data(web::Data::new(PurgePaymentsUseCase::new(
+				payment_repository,
+			)))
+			.service(web::scope("/admin").service(payments_purge)),
+	)
+	.await;
+
+	let req = test::TestRequest::post()
+		.uri("/admin/purge-payments?group=fallback")
+		.to_request();
+	let body: Value = test::call_and_read_body_json(&app, req).await;
+
+	assert_eq!(body, json!({ "deletedPayments": 0, "queuePurged": false }));
+	let queue_length: usize = con.llen(PAYMENTS_QUEUE_KEY).await.unwrap();
+	assert_eq!(queue_length, 1);
+}
+
+#[actix_web::test]
+async fn test_payments_purge_drops_the_payments_in_flight() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let payment_repository = RedisPaymentRepository::new(Arc::clone(&redis));
+
+	let payment = Payment {
+		processed_by: None,
+		..payment_processed_at("", 10.0, OffsetDateTime::now_utc())
+	};
+	let payment_id = payment.correlation_id.to_string();
+	assert!(payment_repository.claim(&payment_id).await.unwrap());
+	payment_repository
+		.mark_in_flight(&payment, "default")
+		.await
+		.unwrap();
+
+	payment_repository
+		.purge(&PurgeScope::default())
+		.await
+		.unwrap();
+
+	assert_eq!(
+		payment_repository
+			.count_in_flight(None, None)
+			.await
+			.unwrap(),
+		0
+	);
+	let keys = RedisKeys::default();
+	let mut con = redis.connection.as_ref().clone();
+	let remaining: usize = con
+		.exists(&[
+			keys.payment_claim(&payment_id),
+			keys.in_flight_payment(&payment_id),
+		])
+		.await
+		.unwrap();
+	assert_eq!(remaining, 0);
+	// Submitted again, the payment can be claimed right away.
+	assert!(payment_repository.claim(&payment_id).await.unwrap());
+}
+
+#[actix_web::test]
+async fn test_payments_purge_by_time_window() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let payment_repository = RedisPaymentRepository::new(Arc::clone(&redis));
+
+	let now = OffsetDateTime::now_utc();
+	let old_payment =
+		payment_processed_at("default", 10.0, now - time::Duration::hours(2));
+	let recent_payment = payment_processed_at("fallback", 10.0, now);
+	payment_repository.save(old_payment.clone()).await.unwrap();
+	payment_repository
+		.save(recent_payment.clone())
+		.await
+		.unwrap();
+
+	let mut con = redis.connection.as_ref().clone();
+	let _: () = con.lpush(PAYMENTS_QUEUE_KEY, "pending").await.unwrap();
+
+	let deleted = payment_repository
+		.purge(&PurgeScope {
+			to: Some(now - time::Duration::hours(1)),
+			..PurgeScope::default()
+		})
+		.await
+		.unwrap();
+
+	assert_eq!(deleted, 1);
+	assert!(
+		!payment_repository
+			.is_already_processed(&old_payment.correlation_id.to_string())
+			.await
+			.unwrap()
+	);
+	assert!(
+		payment_repository
+			.is_already_processed(&recent_payment.correlation_id.to_string())
+			.await
+			.unwrap()
+	);
+	let queue_length: usize = con.llen(PAYMENTS_QUEUE_KEY).await.unwrap();
+	assert_eq!(queue_length, 0);
+}
+
+#[actix_web::test]
+async fn test_payments_purge_stays_in_its_namespace() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let staging_repository = RedisPaymentRepository::new(Arc::clone(&redis))
+		.with_keys(RedisKeys::new("staging", true));
+	let production_repository = RedisPaymentRepository::new(Arc::clone(&redis))
+		.with_keys(RedisKeys::new("production", true));
+
+	let now = OffsetDateTime::now_utc();
+	let staging_payment = payment_processed_at("default", 10.0, now);
+	let production_payment = payment_processed_at("default", 10.0, now);
+	staging_repository
+		.save(staging_payment.clone())
+		.await
+		.unwrap();
+	production_repository
+		.save(production_payment.clone())
+		.await
+		.unwrap();
+
+	let deleted = staging_repository
+		.purge(&PurgeScope::default())
+		.await
+		.unwrap();
+
+	assert_eq!(deleted, 1);
+	assert!(
+		production_repository
+			.is_already_processed(&production_payment.correlation_id.to_string())
+			.await
+			.unwrap()
+	);
+	assert_eq!(
+		production_repository
+			.get_summary_by_group(
+				"default",
+				Some(now - time::Duration::minutes(1)),
+				Some(now)
+			)
+			.await
+			.unwrap(),
+		(1, 10.0)
+	);
+	assert_eq!(
+		staging_repository
+			.get_summary_by_group(
+				"default",
+				Some(now - time::Duration::minutes(1)),
+				Some(now)
+			)
+			.await
+			.unwrap(),
+		(0, 0.0)
+	);
 }
 
 #[actix_web::test]
 async fn test_payments_purge_returns_error_on_failure() {
-	let mock_repository = MockPaymentRepository;
-	let purge_payments_use_case = PurgePaymentsUseCase::new(mock_repository);
+	let purge_payments_use_case = PurgePaymentsUseCase::new(StubPaymentRepository {
+		failing_purge: true,
+		..Default::default()
+	});
 
 	let app = test::init_service(
 		App::new()
 			.app_data(web::Data::new(purge_payments_use_case.clone()))
-			.service(payments_purge),
+			.service(web::scope("/admin").service(payments_purge)),
 	)
 	.await;
 
 	let req = test::TestRequest::post()
-		.uri("/purge-payments")
+		.uri("/admin/purge-payments")
 		.to_request();
 	let resp = test::call_service(&app, req).await;
 
@@ -0,0 +1,237 @@
+use std::sync::Arc;
+
+use actix_web::http::StatusCode;
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::{App, test, web};
+use redis::AsyncCommands;
+use rinha_de_backend::adapters::web::admin_auth::AdminToken;
+use rinha_de_backend::adapters::web::handlers::payments_search;
+use rinha_de_backend::domain::payment::{Payment, PaymentTimestamp};
+use rinha_de_backend::domain::repository::PaymentRepository;
+use rinha_de_backend::infrastructure::config::redis::RedisKeys;
+use rinha_de_backend::infrastructure::persistence::redis_payment_repository::RedisPaymentRepository;
+use rinha_de_backend::use_cases::search_payments::SearchPaymentsUseCase;
+use serde_json::Value;
+use time::{Duration, OffsetDateTime};
+
+mod support;
+
+use crate::support::payments::payment_processed_at;
+use crate::support::redis_container::get_test_redis_client;
+
+const ADMIN_TOKEN: &str = "s3cr3t";
+
+fn admin_get() -> test::TestRequest {
+	test::TestRequest::get()
+		.insert_header((AUTHORIZATION, format!("Bearer {ADMIN_TOKEN}")))
+}
+
+fn correlation_ids(page: &Value) -> Vec<String> {
+	page["payments"]
+		.as_array()
+		.unwrap()
+		.iter()
+		.map(|payment| payment["correlationId"].as_str().unwrap().to_string())
+		.collect()
+}
+
+#[actix_web::test]
+async fn test_payments_search_filters_and_pages_with_cursors() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let payment_repository = RedisPaymentRepository::new(Arc::clone(&redis));
+	let search_payments_use_case =
+		SearchPaymentsUseCase::new(payment_repository.clone());
+
+	let app = test::init_service(
+		App::new()
+			.app_data(web::Data::new(search_payments_use_case))
+			.app_data(web::Data::new(AdminToken::new(Some(ADMIN_TOKEN))))
+			.service(payments_search),
+	)
+	.await;
+
+	let start = OffsetDateTime::now_utc() - Duration::minutes(5);
+	let mut expected = Vec::new();
+	for i in 0..5 {
+		// Two share a timestamp, so a page ends in the middle of the tie.
+		let submitted_at = start + Duration::seconds(i.min(3));
+		let matching = payment_processed_at("fallback", 50.0, submitted_at);
+		payment_repository.save(matching.clone()).await.unwrap();
+		expected.push(matching.correlation_id.to_string());
+
+		let too_small = payment_processed_at("fallback", 5.0, submitted_at);
+		let other_processor = payment_processed_at("default", 50.0, submitted_at);
+		payment_repository.save(too_small).await.unwrap();
+		payment_repository.save(other_processor).await.unwrap();
+	}
+
+	let mut found = Vec::new();
+	let mut uri =
+		"/payments?processor=fallback&minAmount=10&order=asc&limit=2".to_string();
+	loop {
+		let req = admin_get().uri(&uri).to_request();
+		let page: Value = test::call_and_read_body_json(&app, req).await;
+		found.extend(correlation_ids(&page));
+		let Some(cursor) = page["nextCursor"].as_str() else {
+			break;
+		};
+		uri = format!(
+			"/payments?processor=fallback&minAmount=10&order=asc&limit=2&\
+			 cursor={cursor}"
+		);
+	}
+	assert_eq!(found[..3], expected[..3]);
+	let mut tied = found[3..].to_vec();
+	tied.sort();
+	let mut expected_tied = expected[3..].to_vec();
+	expected_tied.sort();
+	assert_eq!(tied, expected_tied);
+
+	let req = admin_get()
+		.uri("/payments?processor=fallback&minAmount=10&limit=1")
+		.to_request();
+	let page: Value = test::call_and_read_body_json(&app, req).await;
+	assert!(expected[3..].contains(&correlation_ids(&page)[0]));
+	assert_eq!(page["payments"][0]["processedBy"], "fallback");
+	assert_eq!(page["payments"][0]["amount"], 50.0);
+
+	let req = admin_get()
+		.uri("/payments?cursor=not-a-cursor")
+		.to_request();
+	let resp = test::call_service(&app, req).await;
+	assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn test_payments_search_finds_in_flight_payments() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let payment_repository = RedisPaymentRepository::new(Arc::clone(&redis));
+	let search_payments_use_case =
+		SearchPaymentsUseCase::new(payment_repository.clone());
+
+	let app = test::init_service(
+		App::new()
+			.app_data(web::Data::new(search_payments_use_case))
+			.app_data(web::Data::new(AdminToken::new(Some(ADMIN_TOKEN))))
+			.service(payments_search),
+	)
+	.await;
+
+	let payment = Payment {
+		processed_at: None,
+		processed_by: None,
+		..payment_processed_at("", 25.0, OffsetDateTime::now_utc())
+	};
+	let payment_id = payment.correlation_id.to_string();
+	assert!(payment_repository.claim(&payment_id).await.unwrap());
+	payment_repository
+		.mark_in_flight(&payment, "default")
+		.await
+		.unwrap();
+
+	// The claim is left as it was, held aside from the payment.
+	let claim: String = redis
+		.connection
+		.as_ref()
+		.clone()
+		.get(RedisKeys::default().payment_claim(&payment_id))
+		.await
+		.unwrap();
+	assert_eq!(claim, "1");
+
+	let req = admin_get()
+		.uri("/payments?status=in_flight&processor=default")
+		.to_request();
+	let page: Value = test::call_and_read_body_json(&app, req).await;
+	assert_eq!(correlation_ids(&page), vec![payment_id.clone()]);
+	assert_eq!(page["payments"][0]["amount"], 25.0);
+	assert_eq!(page["payments"][0]["processedAt"], Value::Null);
+	assert_eq!(page["nextCursor"], Value::Null);
+
+	let req = admin_get()
+		.uri("/payments?status=in_flight&processor=fallback")
+		.to_request();
+	let page: Value = test::call_and_read_body_json(&app, req).await;
+	assert!(correlation_ids(&page).is_empty());
+
+	// Saving settles the payment, which then shows up as processed.
+	payment_repository
+		.save(Payment {
+			processed_at: Some(OffsetDateTime::now_utc()),
+			processed_by: Some("default".to_string()),
+			..payment
+		})
+		.await
+		.unwrap();
+	let req = admin_get().uri("/payments?status=in_flight").to_request();
+	let page: Value = test::call_and_read_body_json(&app, req).await;
+	assert!(correlation_ids(&page).is_empty());
+
+	let req = admin_get().uri("/payments?processor=default").to_request();
+	let page: Value = test::call_and_read_body_json(&app, req).await;
+	assert_eq!(correlation_ids(&page), vec![payment_id]);
+}
+
+#[actix_web::test]
+async fn test_payments_search_by_processor_finds_backfilled_payments() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let payment_repository = RedisPaymentRepository::new(Arc::clone(&redis));
+	let search_payments_use_case =
+		SearchPaymentsUseCase::new(payment_repository.clone());
+
+	let app = test::init_service(
+		App::new()
+			.app_data(web::Data::new(search_payments_use_case))
+			.app_data(web::Data::new(AdminToken::new(Some(ADMIN_TOKEN))))
+			.service(payments_search),
+	)
+	.await;
+
+	// Saved before the per-processor indexes existed.
+	let payment = payment_processed_at("fallback", 10.0, OffsetDateTime::now_utc());
+	let payment_id = payment.correlation_id.to_string();
+	payment_repository.save(payment).await.unwrap();
+	let keys = RedisKeys::default();
+	let mut con = redis.connection.as_ref().clone();
+	for timestamp in [
+		PaymentTimestamp::Received,
+		PaymentTimestamp::Submitted,
+		PaymentTimestamp::Processed,
+	] {
+		let _: () = con
+			.zrem(
+				keys.processed_payments_of(timestamp, "fallback"),
+				&payment_id,
+			)
+			.await
+			.unwrap();
+	}
+
+	let search = || admin_get().uri("/payments?processor=fallback").to_request();
+	let page: Value = test::call_and_read_body_json(&app, search()).await;
+	assert!(correlation_ids(&page).is_empty());
+
+	assert_eq!(payment_repository.backfill_indexes(10).await.unwrap(), 1);
+	let page: Value = test::call_and_read_body_json(&app, search()).await;
+	assert_eq!(correlation_ids(&page), vec![payment_id]);
+
+	// Done once.
+	assert_eq!(payment_repository.backfill_indexes(10).await.unwrap(), 0);
+}
+
+#[actix_web::test]
+async fn test_payments_search_requires_the_admin_token() {
+	let app = test::init_service(
+		App::new()
+			.app_data(web::Data::new(AdminToken::new(Some(ADMIN_TOKEN))))
+			.service(payments_search),
+	)
+	.await;
+
+	let req = test::TestRequest::get().uri("/payments").to_request();
+	let resp = test::call_service(&app, req).await;
+	assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
@@ -2,14 +2,21 @@ use std::ops::{Add, Sub};
 use std::sync::Arc;
 use std::time::Duration;
 
+use actix_web::http::StatusCode;
 use actix_web::{App, test, web};
 use futures::future::join_all;
+use redis::AsyncCommands;
 use rinha_de_backend::adapters::web::handlers::payments_summary;
-use rinha_de_backend::domain::payment::Payment;
+use rinha_de_backend::domain::fee_schedule::{FeeRates, FeeSchedule};
+use rinha_de_backend::domain::payment::{Payment, PaymentTimestamp};
 use rinha_de_backend::domain::repository::PaymentRepository;
+use rinha_de_backend::infrastructure::config::redis::RedisKeys;
 use rinha_de_backend::infrastructure::persistence::redis_payment_repository::RedisPaymentRepository;
-use rinha_de_backend::use_cases::dto::PaymentsSummaryResponse;
+use rinha_de_backend::use_cases::dto::{
+	PaymentsSummaryBreakdownResponse, PaymentsSummaryResponse,
+};
 use rinha_de_backend::use_cases::get_payment_summary::GetPaymentSummaryUseCase;
+use serde_json::Value;
 use time::OffsetDateTime;
 use tokio::time::timeout;
 use uuid::Uuid;
@@ -60,9 +67,10 @@ async fn test_get_payments_summary_without_filter_returns_all_data() {
 		.save(Payment {
 			correlation_id: Uuid::new_v4(),
 			amount:         1000.43,
-			requested_at:   Some(now),
+			submitted_at:   Some(now),
 			processed_at:   Some(now),
 			processed_by:   Some("default".to_string()),
+			received_at:    None,
 		})
 		.await
 		.unwrap();
@@ -71,9 +79,10 @@ async fn test_get_payments_summary_without_filter_returns_all_data() {
 		.save(Payment {
 			correlation_id: Uuid::new_v4(),
 			amount:         2000.16,
-			requested_at:   Some(now),
+			submitted_at:   Some(now),
 			processed_at:   Some(now),
 			processed_by:   Some("default".to_string()),
+			received_at:    None,
 		})
 		.await
 		.unwrap();
@@ -82,9 +91,10 @@ async fn test_get_payments_summary_without_filter_returns_all_data() {
 		.save(Payment {
 			correlation_id: Uuid::new_v4(),
 			amount:         500.42,
-			requested_at:   Some(now),
+			submitted_at:   Some(now),
 			processed_at:   Some(now),
 			processed_by:   Some("fallback".to_string()),
+			received_at:    None,
 		})
 		.await
 		.unwrap();
@@ -155,9 +165,10 @@ async fn test_payments_summary_get_with_filter_simple_iso_8601() {
 		.save(Payment {
 			correlation_id: Uuid::new_v4(),
 			amount:         1000.43,
-			requested_at:   Some(now),
+			submitted_at:   Some(now),
 			processed_at:   Some(now),
 			processed_by:   Some("default".to_string()),
+			received_at:    None,
 		})
 		.await
 		.unwrap();
@@ -168,9 +179,10 @@ async fn test_payments_summary_get_with_filter_simple_iso_8601() {
 		.save(Payment {
 			correlation_id: Uuid::new_v4(),
 			amount:         2000.16,
-			requested_at:   Some(one_hour_ago),
+			submitted_at:   Some(one_hour_ago),
 			processed_at:   Some(one_hour_ago),
 			processed_by:   Some("default".to_string()),
+			received_at:    None,
 		})
 		.await
 		.unwrap();
@@ -179,9 +191,10 @@ async fn test_payments_summary_get_with_filter_simple_iso_8601() {
 		.save(Payment {
 			correlation_id: Uuid::new_v4(),
 			amount:         500.42,
-			requested_at:   Some(now),
+			submitted_at:   Some(now),
 			processed_at:   Some(now),
 			processed_by:   Some("fallback".to_string()),
+			received_at:    None,
 		})
 		.await
 		.unwrap();
@@ -228,9 +241,10 @@ async fn test_payments_summary_get_with_extended_iso_8601() {
 		.save(Payment {
 			correlation_id: Uuid::new_v4(),
 			amount:         1000.23,
-			requested_at:   Some(now),
+			submitted_at:   Some(now),
 			processed_at:   Some(now),
 			processed_by:   Some("default".to_string()),
+			received_at:    None,
 		})
 		.await
 		.unwrap();
@@ -241,9 +255,10 @@ async fn test_payments_summary_get_with_extended_iso_8601() {
 		.save(Payment {
 			correlation_id: Uuid::new_v4(),
 			amount:         1000.27,
-			requested_at:   Some(ten_hours_ago),
+			submitted_at:   Some(ten_hours_ago),
 			processed_at:   Some(ten_hours_ago),
 			processed_by:   Some("default".to_string()),
+			received_at:    None,
 		})
 		.await
 		.unwrap();
@@ -303,8 +318,9 @@ async fn test_redis_repository_concurrent_access() {
 					OffsetDateTime::now_utc().add(time::Duration::days(i as i64));
 
 				// Call get_summary_by_group
-				let result_summary =
-					repo.get_summary_by_group("default", from, to).await;
+				let result_summary = repo
+					.get_summary_by_group("default", Some(from), Some(to))
+					.await;
 				assert!(
 					result_summary.is_ok(),
 					"get_summary_by_group failed: {:?}",
@@ -352,9 +368,10 @@ async fn test_payments_summary_decimal_precision() {
 		.save(Payment {
 			correlation_id: Uuid::new_v4(),
 			amount:         1000.12345,
-			requested_at:   Some(now),
+			submitted_at:   Some(now),
 			processed_at:   Some(now),
 			processed_by:   Some("default".to_string()),
+			received_at:    None,
 		})
 		.await
 		.unwrap();
@@ -363,9 +380,10 @@ async fn test_payments_summary_decimal_precision() {
 		.save(Payment {
 			correlation_id: Uuid::new_v4(),
 			amount:         2000.6789,
-			requested_at:   Some(now),
+			submitted_at:   Some(now),
 			processed_at:   Some(now),
 			processed_by:   Some("default".to_string()),
+			received_at:    None,
 		})
 		.await
 		.unwrap();
@@ -374,9 +392,10 @@ async fn test_payments_summary_decimal_precision() {
 		.save(Payment {
 			correlation_id: Uuid::new_v4(),
 			amount:         500.999,
-			requested_at:   Some(now),
+			submitted_at:   Some(now),
 			processed_at:   Some(now),
 			processed_by:   Some("fallback".to_string()),
+			received_at:    None,
 		})
 		.await
 		.unwrap();
@@ -406,3 +425,346 @@ async fn test_payments_summary_decimal_precision() {
 	assert_eq!(summary.fallback.total_requests, 1);
 	assert_eq!(summary.fallback.total_amount, 501.00); // 500.999 rounds to 501.00
 }
+
+#[actix_web::test]
+async fn test_payments_summary_breakdown_by_minute() {
+	let redis_container = get_test_redis_client().await;
+	let redis = redis_container.get_redis().await;
+	let payment_repo = RedisPaymentRepository::new(Arc::new(redis));
+	let get_payment_summary_use_case =
+		GetPaymentSummaryUseCase::new(payment_repo.clone());
+
+	let minute = OffsetDateTime::from_unix_timestamp(1_751_364_000).unwrap();
+	for (offset, amount) in [(1, 10.0), (30, 20.0), (90, 30.0)] {
+		let requested_at = minute.add(time::Duration::seconds(offset));
+		payment_repo
+			.save(Payment {
+				correlation_id: Uuid::new_v4(),
+				amount,
+				submitted_at: Some(requested_at),
+				processed_at: Some(requested_at),
+				processed_by: Some("default".to_string()),
+				received_at: None,
+			})
+			.await
+			.unwrap();
+	}
+
+	let app = test::init_service(
+		App::new()
+			.app_data(web::Data::new(get_payment_summary_use_case))
+			.service(payments_summary),
+	)
+	.await;
+
+	let req = test::TestRequest::get()
+		.uri(
+			"/payments-summary?from=2025-07-01T10:00:00Z&to=2025-07-01T11:00:00Z&\
+			 granularity=minute",
+		)
+		.to_request();
+	let resp = test::call_service(&app, req).await;
+	assert!(resp.status().is_success());
+
+	let breakdown: PaymentsSummaryBreakdownResponse =
+		test::read_body_json(resp).await;
+
+	assert_eq!(breakdown.default.total_requests, 3);
+	assert_eq!(breakdown.default.total_amount, 60.0);
+	assert_eq!(
+		breakdown
+			.default
+			.buckets
+			.iter()
+			.map(|bucket| (bucket.start, bucket.total_requests))
+			.collect::<Vec<_>>(),
+		vec![(minute, 2), (minute.add(time::Duration::MINUTE), 1)]
+	);
+	assert_eq!(breakdown.default.percentiles.unwrap().p50, 20.0);
+	assert_eq!(breakdown.fallback.total_requests, 0);
+	assert!(breakdown.fallback.percentiles.is_none());
+}
+
+#[actix_web::test]
+async fn test_payments_summary_with_fees_at_processing_time() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let fee_schedule = FeeSchedule::new(FeeRates {
+		default:  0.05,
+		fallback: 0.15,
+	});
+	let payment_repo =
+		RedisPaymentRepository::new(redis).with_fee_schedule(fee_schedule.clone());
+	let get_payment_summary_use_case =
+		GetPaymentSummaryUseCase::new(payment_repo.clone())
+			.with_fee_schedule(fee_schedule.clone());
+
+	let requested_at = OffsetDateTime::from_unix_timestamp(1_751_364_000).unwrap();
+	let save = |amount| {
+		payment_repo.save(Payment {
+			correlation_id: Uuid::new_v4(),
+			amount,
+			submitted_at: Some(requested_at),
+			processed_at: Some(requested_at),
+			processed_by: Some("default".to_string()),
+			received_at: None,
+		})
+	};
+	save(100.0).await.unwrap();
+	fee_schedule.set_rate("default", 0.1);
+	save(50.0).await.unwrap();
+
+	let app = test::init_service(
+		App::new()
+			.app_data(web::Data::new(get_payment_summary_use_case))
+			.service(payments_summary),
+	)
+	.await;
+
+	let summary_with = |fees: &str| {
+		test::TestRequest::get()
+			.uri(&format!(
+				"/payments-summary?from=2025-07-01T10:00:00Z&to=2025-07-01T11:00:\
+				 00Z&fees={fees}"
+			))
+			.to_request()
+	};
+
+	let resp = test::call_service(&app, summary_with("processing")).await;
+	assert!(resp.status().is_success());
+	let summary: PaymentsSummaryResponse = test::read_body_json(resp).await;
+	assert_eq!(summary.default.total_fee, Some(10.0));
+	assert_eq!(summary.default.net_amount, Some(140.0));
+	assert_eq!(summary.fallback.total_fee, Some(0.0));
+	assert_eq!(summary.blended_fee_rate, Some(10.0 / 150.0));
+
+	let resp = test::call_service(&app, summary_with("current")).await;
+	let summary: PaymentsSummaryResponse = test::read_body_json(resp).await;
+	assert_eq!(summary.default.total_fee, Some(15.0));
+	assert_eq!(summary.default.fee_rate, Some(0.1));
+}
+
+#[actix_web::test]
+async fn test_payments_summary_rejects_invalid_windows() {
+	let redis_container = get_test_redis_client().await;
+	let redis = redis_container.get_redis().await;
+	let payment_repo = RedisPaymentRepository::new(Arc::new(redis));
+	let get_payment_summary_use_case =
+		GetPaymentSummaryUseCase::new(payment_repo.clone())
+			.with_max_window(time::Duration::days(31));
+
+	let app = test::init_service(
+		App::new()
+			.app_data(web::Data::new(get_payment_summary_use_case))
+			.service(payments_summary),
+	)
+	.await;
+
+	for query in [
+		"from=2025-07-01T11:00:00Z&to=2025-07-01T10:00:00Z",
+		"from=2025-07-01T00:00:00Z&to=2025-09-01T00:00:00Z",
+		"from=yesterday",
+		"granularity=minute&from=1751364000000&to=1751360400000",
+		"granularity=minute&fees=current",
+	] {
+		let req = test::TestRequest::get()
+			.uri(&format!("/payments-summary?{query}"))
+			.to_request();
+		let resp = test::call_service(&app, req).await;
+
+		assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{query}");
+		let body: Value = test::read_body_json(resp).await;
+		assert_eq!(body["statusCode"], 400);
+		assert_eq!(body["message"], "Bad request");
+	}
+
+	// Open windows and epoch milliseconds are accepted, even over payments
+	// older than the maximum window.
+	payment_repo
+		.save(Payment {
+			correlation_id: Uuid::new_v4(),
+			amount:         10.0,
+			submitted_at:   Some(
+				OffsetDateTime::from_unix_timestamp(1_751_364_000).unwrap(),
+			),
+			processed_at:   None,
+			processed_by:   Some("default".to_string()),
+			received_at:    None,
+		})
+		.await
+		.unwrap();
+	for query in [
+		"from=1751364000000",
+		"to=2025-07-01T10:00:00Z",
+		"to=2025-09-01T00:00:00Z",
+		"",
+	] {
+		let req = test::TestRequest::get()
+			.uri(&format!("/payments-summary?{query}"))
+			.to_request();
+		let resp = test::call_service(&app, req).await;
+
+		assert!(resp.status().is_success(), "{query}");
+	}
+}
+
+#[actix_web::test]
+async fn test_payments_summary_filters_on_the_configured_timestamp() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let submitted_repo = RedisPaymentRepository::new(Arc::clone(&redis));
+	let received_repo = RedisPaymentRepository::new(redis)
+		.with_summary_timestamp(PaymentTimestamp::Received);
+
+	// Received at 10:00 but only submitted, after retries, at 11:30.
+	let received_at = OffsetDateTime::from_unix_timestamp(1_751_364_000).unwrap();
+	let submitted_at = received_at.add(time::Duration::minutes(90));
+	submitted_repo
+		.save(Payment {
+			correlation_id: Uuid::new_v4(),
+			amount:         10.0,
+			submitted_at:   Some(submitted_at),
+			processed_at:   Some(submitted_at),
+			processed_by:   Some("default".to_string()),
+			received_at:    Some(received_at),
+		})
+		.await
+		.unwrap();
+
+	let ten_o_clock = (
+		Some(received_at),
+		Some(received_at.add(time::Duration::HOUR)),
+	);
+	assert_eq!(
+		received_repo
+			.get_summary_by_group("default", ten_o_clock.0, ten_o_clock.1)
+			.await
+			.unwrap(),
+		(1, 10.0)
+	);
+	assert_eq!(
+		submitted_repo
+			.get_summary_by_group("default", ten_o_clock.0, ten_o_clock.1)
+			.await
+			.unwrap(),
+		(0, 0.0)
+	);
+}
+
+#[actix_web::test]
+async fn test_payments_without_a_received_at_are_left_out_of_its_index() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let submitted_repo = RedisPaymentRepository::new(Arc::clone(&redis));
+	let received_repo = RedisPaymentRepository::new(Arc::clone(&redis))
+		.with_summary_timestamp(PaymentTimestamp::Received);
+
+	let submitted_at = OffsetDateTime::from_unix_timestamp(1_751_364_000).unwrap();
+	let payment = Payment {
+		correlation_id: Uuid::new_v4(),
+		amount:         10.0,
+		submitted_at:   Some(submitted_at),
+		processed_at:   Some(submitted_at),
+		processed_by:   Some("default".to_string()),
+		received_at:    None,
+	};
+	let payment_id = payment.correlation_id.to_string();
+	submitted_repo.save(payment).await.unwrap();
+
+	let keys = RedisKeys::default();
+	let mut con = redis.connection.as_ref().clone();
+	for key in [
+		keys.processed_payments_by(PaymentTimestamp::Received)
+			.to_string(),
+		keys.processed_payments_of(PaymentTimestamp::Received, "default"),
+	] {
+		let score: Option<f64> = con.zscore(key, &payment_id).await.unwrap();
+		assert_eq!(score, None);
+	}
+	assert_eq!(
+		received_repo
+			.get_summary_by_group("default", None, None)
+			.await
+			.unwrap(),
+		(0, 0.0)
+	);
+	assert_eq!(
+		submitted_repo
+			.get_summary_by_group("default", None, None)
+			.await
+			.unwrap(),
+		(1, 10.0)
+	);
+}
+
+#[actix_web::test]
+async fn test_payments_saved_before_the_timestamp_indexes_are_backfilled() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let submitted_repo = RedisPaymentRepository::new(Arc::clone(&redis));
+	let processed_repo = RedisPaymentRepository::new(Arc::clone(&redis))
+		.with_summary_timestamp(PaymentTimestamp::Processed);
+
+	let submitted_at = OffsetDateTime::from_unix_timestamp(1_751_364_000).unwrap();
+	let processed_at = submitted_at.add(time::Duration::minutes(90));
+	let payment = Payment {
+		correlation_id: Uuid::new_v4(),
+		amount:         10.0,
+		submitted_at:   Some(submitted_at),
+		processed_at:   Some(processed_at),
+		processed_by:   Some("default".to_string()),
+		received_at:    None,
+	};
+	let payment_id = payment.correlation_id.to_string();
+	submitted_repo.save(payment).await.unwrap();
+
+	// Saved by an older version: stamped with `Display` and only indexed by
+	// `submitted_at`.
+	let keys = RedisKeys::default();
+	let mut con = redis.connection.as_ref().clone();
+	let _: () = con
+		.hset(
+			keys.payment("default", &payment_id),
+			"processed_at",
+			processed_at.to_string(),
+		)
+		.await
+		.unwrap();
+	for key in [
+		keys.processed_payments_by(PaymentTimestamp::Processed)
+			.to_string(),
+		keys.processed_payments_of(PaymentTimestamp::Processed, "default"),
+	] {
+		let _: () = con.zrem(key, &payment_id).await.unwrap();
+	}
+
+	let eleven_thirty = (
+		Some(processed_at),
+		Some(processed_at.add(time::Duration::MINUTE)),
+	);
+	assert_eq!(
+		processed_repo
+			.get_summary_by_group("default", eleven_thirty.0, eleven_thirty.1)
+			.await
+			.unwrap(),
+		(0, 0.0)
+	);
+
+	assert_eq!(processed_repo.backfill_indexes(10).await.unwrap(), 1);
+
+	assert_eq!(
+		processed_repo
+			.get_summary_by_group("default", eleven_thirty.0, eleven_thirty.1)
+			.await
+			.unwrap(),
+		(1, 10.0)
+	);
+	assert_eq!(
+		processed_repo
+			.get_payment_summary("default", &payment_id)
+			.await
+			.unwrap()
+			.processed_at,
+		Some(processed_at)
+	);
+}
@@ -2,12 +2,17 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use circuitbreaker_rs::{CircuitBreaker, DefaultPolicy, State};
+use redis::AsyncCommands;
 use reqwest::Client;
 use rinha_de_backend::domain::payment::Payment;
+use rinha_de_backend::domain::repository::PaymentRepository;
+use rinha_de_backend::infrastructure::config::redis::RedisKeys;
 use rinha_de_backend::infrastructure::persistence::redis_payment_repository::RedisPaymentRepository;
 use rinha_de_backend::use_cases::process_payment::{
 	PaymentProcessingError, ProcessPaymentUseCase,
 };
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
 use uuid::Uuid;
 
 mod support;
@@ -32,9 +37,10 @@ async fn test_process_payment_success() {
 	let payment = Payment {
 		correlation_id: Uuid::new_v4(),
 		amount:         100.0,
-		requested_at:   None,
+		submitted_at:   None,
 		processed_at:   None,
 		processed_by:   None,
+		received_at:    None,
 	};
 
 	let mut circuit_breaker: CircuitBreaker<DefaultPolicy, PaymentProcessingError> =
@@ -73,9 +79,10 @@ async fn test_process_payment_duplicate_returns_false() {
 	let payment = Payment {
 		correlation_id: Uuid::new_v4(),
 		amount:         100.0,
-		requested_at:   None,
+		submitted_at:   None,
 		processed_at:   None,
 		processed_by:   None,
+		received_at:    None,
 	};
 
 	let mut circuit_breaker: CircuitBreaker<DefaultPolicy, PaymentProcessingError> =
@@ -129,9 +136,10 @@ async fn test_process_payment_500_returns_false() {
 	let payment = Payment {
 		correlation_id: Uuid::new_v4(),
 		amount:         100.0,
-		requested_at:   None,
+		submitted_at:   None,
 		processed_at:   None,
 		processed_by:   None,
+		received_at:    None,
 	};
 
 	let mut circuit_breaker: CircuitBreaker<DefaultPolicy, PaymentProcessingError> =
@@ -182,9 +190,10 @@ async fn test_process_payment_circuit_breaker_open_returns_false() {
 	let payment = Payment {
 		correlation_id: Uuid::new_v4(),
 		amount:         100.0,
-		requested_at:   None,
+		submitted_at:   None,
 		processed_at:   None,
 		processed_by:   None,
+		received_at:    None,
 	};
 
 	let mut circuit_breaker: CircuitBreaker<DefaultPolicy, PaymentProcessingError> =
@@ -223,9 +232,10 @@ async fn test_process_payment_unreachable_service_opens_circuit_breaker() {
 	let payment = Payment {
 		correlation_id: Uuid::new_v4(),
 		amount:         100.0,
-		requested_at:   None,
+		submitted_at:   None,
 		processed_at:   None,
 		processed_by:   None,
+		received_at:    None,
 	};
 
 	let mut circuit_breaker: CircuitBreaker<DefaultPolicy, PaymentProcessingError> =
@@ -254,3 +264,70 @@ async fn test_process_payment_unreachable_service_opens_circuit_breaker() {
 	// Verify that the circuit breaker is open
 	assert_eq!(circuit_breaker.current_state(), State::Open);
 }
+
+#[tokio::test]
+async fn test_process_payment_extends_the_claim_while_the_processor_is_slow() {
+	let redis_container = get_test_redis_client().await;
+	let redis = redis_container.get_redis().await;
+	let mut con = redis.connection.as_ref().clone();
+	let payment_repo = RedisPaymentRepository::new(Arc::new(redis));
+
+	// A processor that takes a second to accept every payment.
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let processor_url = format!("http://{}", listener.local_addr().unwrap());
+	tokio::spawn(async move {
+		while let Ok((mut socket, _)) = listener.accept().await {
+			tokio::spawn(async move {
+				tokio::time::sleep(Duration::from_secs(1)).await;
+				let _ = socket
+					.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+					.await;
+			});
+		}
+	});
+
+	let process_payment_use_case =
+		ProcessPaymentUseCase::new(payment_repo.clone(), Client::new())
+			.with_claim_heartbeat(Duration::from_millis(50));
+
+	let payment = Payment {
+		correlation_id: Uuid::new_v4(),
+		amount:         100.0,
+		submitted_at:   None,
+		processed_at:   None,
+		processed_by:   None,
+		received_at:    None,
+	};
+	let payment_id = payment.correlation_id.to_string();
+	assert!(payment_repo.claim(&payment_id).await.unwrap());
+
+	let processing = tokio::spawn(async move {
+		let mut circuit_breaker: CircuitBreaker<
+			DefaultPolicy,
+			PaymentProcessingError,
+		> = CircuitBreaker::<DefaultPolicy, PaymentProcessingError>::builder()
+			.failure_threshold(0.5)
+			.cooldown(Duration::from_secs(30))
+			.build();
+		process_payment_use_case
+			.execute(
+				payment,
+				processor_url,
+				"default".to_string(),
+				&mut circuit_breaker,
+			)
+			.await
+	});
+
+	tokio::time::sleep(Duration::from_millis(800)).await;
+
+	// Left alone, both keys would have lost the 800ms already waited.
+	let keys = RedisKeys::default();
+	let claim_ttl: i64 = con.pttl(keys.payment_claim(&payment_id)).await.unwrap();
+	let in_flight_ttl: i64 =
+		con.pttl(keys.in_flight_payment(&payment_id)).await.unwrap();
+	assert!(claim_ttl >= 29_900, "claim ttl was {claim_ttl}");
+	assert!(in_flight_ttl >= 29_900, "in-flight ttl was {in_flight_ttl}");
+
+	assert!(processing.await.unwrap().unwrap());
+}
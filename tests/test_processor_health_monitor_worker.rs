@@ -38,6 +38,7 @@ async fn test_update_processor_health_when_processor_is_reachable() {
 	let worker_handle = tokio::spawn(processor_health_monitor_worker(
 		router.clone(),
 		http_client.clone(),
+		Duration::from_secs(5),
 	));
 
 	wait_for_workflow_to_run().await;
@@ -93,6 +94,7 @@ async fn test_marks_processor_as_failing_when_unreachable() {
 	let worker_handle = tokio::spawn(processor_health_monitor_worker(
 		router.clone(),
 		http_client.clone(),
+		Duration::from_secs(5),
 	));
 
 	wait_for_workflow_to_run().await;
@@ -146,6 +148,7 @@ async fn test_should_not_panic_an_error_occurs() {
 	let worker_handle = tokio::spawn(processor_health_monitor_worker(
 		router.clone(),
 		http_client.clone(),
+		Duration::from_secs(5),
 	));
 
 	wait_for_workflow_to_run().await;
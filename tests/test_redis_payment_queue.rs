@@ -2,8 +2,13 @@ use std::sync::Arc;
 
 use rinha_de_backend::domain::payment::Payment;
 use rinha_de_backend::domain::queue::{Message, Queue};
-use rinha_de_backend::infrastructure::config::redis::PAYMENTS_QUEUE_KEY;
+use rinha_de_backend::domain::repository::PaymentRepository;
+use rinha_de_backend::infrastructure::config::redis::{
+	PAYMENTS_QUEUE_KEY, RedisKeys,
+};
+use rinha_de_backend::infrastructure::persistence::redis_payment_repository::RedisPaymentRepository;
 use rinha_de_backend::infrastructure::queue::redis_payment_queue::PaymentQueue;
+use time::OffsetDateTime;
 use uuid::Uuid;
 
 mod support;
@@ -19,9 +24,10 @@ async fn test_payment_queue_push_and_pop() {
 	let payment = Payment {
 		correlation_id: Uuid::new_v4(),
 		amount:         10000.28,
-		requested_at:   None,
+		submitted_at:   None,
 		processed_at:   None,
 		processed_by:   None,
+		received_at:    None,
 	};
 
 	let message = Message::with(Uuid::new_v4(), payment.clone());
@@ -55,16 +61,18 @@ async fn test_payment_queue_multiple_pushes_and_pops() {
 	let payment1 = Payment {
 		correlation_id: Uuid::new_v4(),
 		amount:         10000.34,
-		requested_at:   None,
+		submitted_at:   None,
 		processed_at:   None,
 		processed_by:   None,
+		received_at:    None,
 	};
 	let payment2 = Payment {
 		correlation_id: Uuid::new_v4(),
 		amount:         20000.28,
-		requested_at:   None,
+		submitted_at:   None,
 		processed_at:   None,
 		processed_by:   None,
+		received_at:    None,
 	};
 
 	let message1 = Message::with(Uuid::new_v4(), payment1.clone());
@@ -103,3 +111,264 @@ async fn test_payment_queue_fault_tolerance() {
 	let popped_message = payment_queue.pop().await;
 	assert!(popped_message.is_err());
 }
+
+#[tokio::test]
+async fn test_payment_queue_length() {
+	let redis_container = get_test_redis_client().await;
+	let redis = redis_container.get_redis().await;
+	let payment_queue = PaymentQueue::new(Arc::new(redis));
+
+	assert_eq!(payment_queue.length().await.unwrap(), 0);
+
+	for amount in [10.0, 20.0] {
+		let payment = Payment {
+			correlation_id: Uuid::new_v4(),
+			amount,
+			submitted_at: None,
+			processed_at: None,
+			processed_by: None,
+			received_at: None,
+		};
+		payment_queue
+			.push(Message::with(payment.correlation_id, payment))
+			.await
+			.unwrap();
+	}
+
+	assert_eq!(payment_queue.length().await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn test_payment_queue_push_batch_keeps_fifo_order() {
+	let redis_container = get_test_redis_client().await;
+	let redis = redis_container.get_redis().await;
+	let payment_queue = PaymentQueue::new(Arc::new(redis));
+
+	let messages: Vec<Message<Payment>> = (1..=3)
+		.map(|i| {
+			let payment = Payment {
+				correlation_id: Uuid::new_v4(),
+				amount:         f64::from(i),
+				submitted_at:   None,
+				processed_at:   None,
+				processed_by:   None,
+				received_at:    None,
+			};
+			Message::with(payment.correlation_id, payment)
+		})
+		.collect();
+
+	payment_queue.push_batch(messages.clone()).await.unwrap();
+
+	assert_eq!(payment_queue.length().await.unwrap(), 3);
+	for message in messages {
+		let popped_message = payment_queue.pop().await.unwrap().unwrap();
+		assert_eq!(popped_message.id, message.id);
+	}
+}
+
+#[tokio::test]
+async fn test_payment_queue_pop_batch() {
+	let redis_container = get_test_redis_client().await;
+	let redis = redis_container.get_redis().await;
+	let payment_queue = PaymentQueue::new(Arc::new(redis));
+
+	let messages: Vec<Message<Payment>> = (1..=3)
+		.map(|i| {
+			let payment = Payment {
+				correlation_id: Uuid::new_v4(),
+				amount:         f64::from(i),
+				submitted_at:   None,
+				processed_at:   None,
+				processed_by:   None,
+				received_at:    None,
+			};
+			Message::with(payment.correlation_id, payment)
+		})
+		.collect();
+
+	payment_queue.push_batch(messages.clone()).await.unwrap();
+
+	let first_batch = payment_queue.pop_batch(2).await.unwrap();
+	let second_batch = payment_queue.pop_batch(5).await.unwrap();
+
+	let popped_ids: Vec<_> = first_batch
+		.iter()
+		.chain(second_batch.iter())
+		.map(|message| message.id)
+		.collect();
+	let pushed_ids: Vec<_> = messages.iter().map(|message| message.id).collect();
+	assert_eq!(first_batch.len(), 2);
+	assert_eq!(popped_ids, pushed_ids);
+	assert!(payment_queue.pop_batch(5).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_payment_queue_pop_batch_dead_letters_unreadable_messages() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let payment_queue = PaymentQueue::new(Arc::clone(&redis));
+	let keys = RedisKeys::default();
+
+	let payment = Payment {
+		correlation_id: Uuid::new_v4(),
+		amount:         10.0,
+		submitted_at:   None,
+		processed_at:   None,
+		processed_by:   None,
+		received_at:    None,
+	};
+	let mut conn = redis.connection.as_ref().clone();
+	let _: () = redis::cmd("LPUSH")
+		.arg(keys.payments_queue())
+		.arg(b"not a message".as_slice())
+		.query_async(&mut conn)
+		.await
+		.unwrap();
+	payment_queue
+		.push(Message::with(payment.correlation_id, payment.clone()))
+		.await
+		.unwrap();
+
+	let popped = payment_queue.pop_batch(5).await.unwrap();
+	assert_eq!(popped.len(), 1);
+	assert_eq!(popped[0].body.correlation_id, payment.correlation_id);
+
+	let dead_letters: Vec<Vec<u8>> = redis::cmd("LRANGE")
+		.arg(keys.dead_letter_queue())
+		.arg(0)
+		.arg(-1)
+		.query_async(&mut conn)
+		.await
+		.unwrap();
+	assert_eq!(dead_letters, vec![b"not a message".to_vec()]);
+}
+
+#[tokio::test]
+async fn test_payment_queues_in_different_namespaces_are_isolated() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let staging_queue = PaymentQueue::new(Arc::clone(&redis))
+		.with_keys(RedisKeys::new("staging", true));
+	let production_queue = PaymentQueue::new(Arc::clone(&redis))
+		.with_keys(RedisKeys::new("production", true));
+
+	let payment = Payment {
+		correlation_id: Uuid::new_v4(),
+		amount:         10.0,
+		submitted_at:   None,
+		processed_at:   None,
+		processed_by:   None,
+		received_at:    None,
+	};
+	staging_queue
+		.push(Message::with(Uuid::new_v4(), payment))
+		.await
+		.unwrap();
+
+	assert_eq!(staging_queue.length().await.unwrap(), 1);
+	assert_eq!(production_queue.length().await.unwrap(), 0);
+
+	let mut conn = redis.connection.as_ref().clone();
+	let staging_length: usize = redis::cmd("LLEN")
+		.arg("{staging}:payments_queue")
+		.query_async(&mut conn)
+		.await
+		.unwrap();
+	assert_eq!(staging_length, 1);
+}
+
+#[tokio::test]
+async fn test_claim_and_requeue_payment() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let payment_queue = PaymentQueue::new(Arc::clone(&redis));
+	let payment_repository = RedisPaymentRepository::new(Arc::clone(&redis));
+
+	let payment = Payment {
+		correlation_id: Uuid::new_v4(),
+		amount:         10.0,
+		submitted_at:   None,
+		processed_at:   None,
+		processed_by:   Some("default".to_string()),
+		received_at:    None,
+	};
+	let payment_id = payment.correlation_id.to_string();
+	let message = Message::with(Uuid::new_v4(), payment.clone());
+
+	assert!(payment_repository.claim(&payment_id).await.unwrap());
+	assert!(!payment_repository.claim(&payment_id).await.unwrap());
+
+	// Re-queueing releases the claim.
+	payment_queue.requeue(message.clone()).await.unwrap();
+	assert_eq!(payment_queue.length().await.unwrap(), 1);
+	assert!(payment_repository.claim(&payment_id).await.unwrap());
+
+	// Saving releases it too, and processed payments are never re-queued or
+	// claimed again.
+	payment_repository.save(payment).await.unwrap();
+	payment_queue.requeue(message).await.unwrap();
+	assert_eq!(payment_queue.length().await.unwrap(), 1);
+	assert!(!payment_repository.claim(&payment_id).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_in_flight_payments_settle_on_save_and_requeue() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let payment_queue = PaymentQueue::new(Arc::clone(&redis));
+	let payment_repository = RedisPaymentRepository::new(Arc::clone(&redis));
+
+	let submitted_at = OffsetDateTime::now_utc();
+	let in_flight = || payment_repository.count_in_flight(None, None);
+	let claimed_in_flight = |processed_by: &str| {
+		let payment = Payment {
+			correlation_id: Uuid::new_v4(),
+			amount:         10.0,
+			submitted_at:   Some(submitted_at),
+			processed_at:   Some(submitted_at),
+			processed_by:   Some(processed_by.to_string()),
+			received_at:    None,
+		};
+		let payment_repository = payment_repository.clone();
+		async move {
+			let payment_id = payment.correlation_id.to_string();
+			assert!(payment_repository.claim(&payment_id).await.unwrap());
+			payment_repository
+				.mark_in_flight(&payment, payment.processed_by.as_deref().unwrap())
+				.await
+				.unwrap();
+			payment
+		}
+	};
+
+	let saved = claimed_in_flight("default").await;
+	let requeued = claimed_in_flight("fallback").await;
+	assert_eq!(in_flight().await.unwrap(), 2);
+	assert_eq!(
+		payment_repository
+			.count_in_flight(Some(submitted_at + time::Duration::SECOND), None)
+			.await
+			.unwrap(),
+		0
+	);
+
+	payment_repository.save(saved).await.unwrap();
+	assert_eq!(in_flight().await.unwrap(), 1);
+
+	payment_queue
+		.requeue(Message::with(Uuid::new_v4(), requeued))
+		.await
+		.unwrap();
+	assert_eq!(in_flight().await.unwrap(), 0);
+
+	// Without a live claim the worker is gone and the entry is dropped.
+	let orphan = claimed_in_flight("default").await;
+	let _: () = redis::AsyncCommands::del(
+		&mut redis.connection.as_ref().clone(),
+		RedisKeys::default().payment_claim(&orphan.correlation_id.to_string()),
+	)
+	.await
+	.unwrap();
+	assert_eq!(in_flight().await.unwrap(), 0);
+}
@@ -0,0 +1,278 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use rinha_de_backend::adapters::cli::snapshot_command::{
+	restore_snapshot, write_snapshot,
+};
+use rinha_de_backend::domain::fee_schedule::{FeeRates, FeeSchedule};
+use rinha_de_backend::domain::payment::Payment;
+use rinha_de_backend::domain::queue::{Message, Queue};
+use rinha_de_backend::domain::repository::PaymentRepository;
+use rinha_de_backend::domain::snapshot::SnapshotRecord;
+use rinha_de_backend::infrastructure::config::redis::RedisKeys;
+use rinha_de_backend::infrastructure::persistence::redis_payment_repository::RedisPaymentRepository;
+use rinha_de_backend::infrastructure::queue::redis_payment_queue::PaymentQueue;
+use rinha_de_backend::use_cases::dto::RestoreSnapshotResult;
+use rinha_de_backend::use_cases::snapshot::{SnapshotError, SnapshotUseCase};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+mod support;
+
+use crate::support::payments::processed_payment;
+use crate::support::redis_container::get_test_redis_client;
+
+#[tokio::test]
+async fn test_snapshot_restores_into_another_namespace() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let source = RedisPaymentRepository::new(Arc::clone(&redis))
+		.with_keys(RedisKeys::new("staging", true))
+		.with_fee_schedule(FeeSchedule::new(FeeRates {
+			default:  0.05,
+			fallback: 0.15,
+		}));
+	let target = RedisPaymentRepository::new(Arc::clone(&redis))
+		.with_keys(RedisKeys::new("production", true));
+	let source_queue = PaymentQueue::new(Arc::clone(&redis))
+		.with_keys(RedisKeys::new("staging", true));
+	let target_queue = PaymentQueue::new(Arc::clone(&redis))
+		.with_keys(RedisKeys::new("production", true));
+
+	let now = OffsetDateTime::now_utc();
+	let payments = [
+		processed_payment("default", 10.0, now - time::Duration::HOUR),
+		processed_payment("default", 20.0, now),
+		processed_payment("fallback", 30.0, now),
+	];
+	for payment in &payments {
+		source.save(payment.clone()).await.unwrap();
+	}
+	source
+		.compact(
+			now - time::Duration::minutes(30),
+			Duration::from_secs(60),
+			Duration::from_secs(60),
+			10,
+		)
+		.await
+		.unwrap();
+	let queued = [Uuid::new_v4(), Uuid::new_v4()];
+	for id in queued {
+		source_queue
+			.push(Message::with(id, Payment {
+				processed_by: None,
+				..processed_payment("", 5.0, now)
+			}))
+			.await
+			.unwrap();
+	}
+	// Left in the target namespace, to be replaced.
+	target
+		.save(processed_payment("default", 99.0, now))
+		.await
+		.unwrap();
+
+	let mut snapshot = Vec::new();
+	write_snapshot(&SnapshotUseCase::new(source.clone()), &mut snapshot)
+		.await
+		.unwrap();
+	let result = restore_snapshot(
+		&SnapshotUseCase::new(target.clone()).with_batch_size(2),
+		&mut snapshot.as_slice(),
+	)
+	.await
+	.unwrap();
+
+	// Every timestamp index of the one compacted payment has its bucket.
+	assert_eq!(result, RestoreSnapshotResult {
+		payments: 2,
+		queued:   2,
+		buckets:  3,
+	});
+	for group in ["default", "fallback"] {
+		assert_eq!(
+			target
+				.get_summary_with_fees_by_group(group, None, None)
+				.await
+				.unwrap(),
+			source
+				.get_summary_with_fees_by_group(group, None, None)
+				.await
+				.unwrap()
+		);
+	}
+	let restored = target
+		.get_payment_summary("fallback", &payments[2].correlation_id.to_string())
+		.await
+		.unwrap();
+	assert_eq!(restored.amount, 30.0);
+	assert_eq!(restored.submitted_at, payments[2].submitted_at);
+	assert_eq!(restored.received_at, payments[2].received_at);
+
+	// Oldest first, as the source would have handed them out.
+	assert_eq!(target_queue.length().await.unwrap(), 2);
+	for id in queued {
+		assert_eq!(target_queue.pop().await.unwrap().unwrap().id, id);
+	}
+
+	// Buckets are written over rather than added to, so restoring the same
+	// snapshot again leaves the summaries alone.
+	restore_snapshot(
+		&SnapshotUseCase::new(target.clone()),
+		&mut snapshot.as_slice(),
+	)
+	.await
+	.unwrap();
+	for group in ["default", "fallback"] {
+		assert_eq!(
+			target
+				.get_summary_by_group(group, None, None)
+				.await
+				.unwrap(),
+			source
+				.get_summary_by_group(group, None, None)
+				.await
+				.unwrap()
+		);
+	}
+}
+
+#[tokio::test]
+async fn test_snapshot_requeues_the_payments_in_flight() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let source = RedisPaymentRepository::new(Arc::clone(&redis))
+		.with_keys(RedisKeys::new("staging", false));
+	let target = RedisPaymentRepository::new(Arc::clone(&redis))
+		.with_keys(RedisKeys::new("production", false));
+	let target_queue = PaymentQueue::new(Arc::clone(&redis))
+		.with_keys(RedisKeys::new("production", false));
+
+	let payment = Payment {
+		processed_by: None,
+		..processed_payment("", 5.0, OffsetDateTime::now_utc())
+	};
+	assert!(
+		source
+			.claim(&payment.correlation_id.to_string())
+			.await
+			.unwrap()
+	);
+	source.mark_in_flight(&payment, "default").await.unwrap();
+
+	let mut snapshot = Vec::new();
+	write_snapshot(&SnapshotUseCase::new(source), &mut snapshot)
+		.await
+		.unwrap();
+	let result = restore_snapshot(
+		&SnapshotUseCase::new(target.clone()),
+		&mut snapshot.as_slice(),
+	)
+	.await
+	.unwrap();
+
+	assert_eq!(result.queued, 1);
+	let message = target_queue.pop().await.unwrap().unwrap();
+	assert_eq!(message.body.correlation_id, payment.correlation_id);
+	assert_eq!(message.body.processed_by, None);
+}
+
+#[tokio::test]
+async fn test_restore_is_refused_while_payments_are_in_flight() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let payment_repository = RedisPaymentRepository::new(Arc::clone(&redis));
+
+	let saved = processed_payment("default", 10.0, OffsetDateTime::now_utc());
+	payment_repository.save(saved.clone()).await.unwrap();
+	let in_flight = processed_payment("", 5.0, OffsetDateTime::now_utc());
+	payment_repository
+		.claim(&in_flight.correlation_id.to_string())
+		.await
+		.unwrap();
+	payment_repository
+		.mark_in_flight(&in_flight, "default")
+		.await
+		.unwrap();
+
+	let mut snapshot = Vec::new();
+	write_snapshot(
+		&SnapshotUseCase::new(payment_repository.clone()),
+		&mut snapshot,
+	)
+	.await
+	.unwrap();
+	let error = restore_snapshot(
+		&SnapshotUseCase::new(payment_repository.clone()),
+		&mut snapshot.as_slice(),
+	)
+	.await
+	.unwrap_err();
+
+	assert_eq!(
+		error.downcast_ref::<SnapshotError>(),
+		Some(&SnapshotError::PaymentsInFlight { count: 1 })
+	);
+	assert!(
+		payment_repository
+			.is_already_processed(&saved.correlation_id.to_string())
+			.await
+			.unwrap()
+	);
+}
+
+#[tokio::test]
+async fn test_snapshot_fails_on_an_unreadable_queued_message() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let payment_repository = RedisPaymentRepository::new(Arc::clone(&redis));
+
+	redis::cmd("LPUSH")
+		.arg(RedisKeys::default().payments_queue())
+		.arg("this is not a valid message")
+		.query_async::<()>(&mut redis.connection.as_ref().clone())
+		.await
+		.unwrap();
+
+	let mut snapshot = Vec::new();
+	assert!(
+		write_snapshot(&SnapshotUseCase::new(payment_repository), &mut snapshot)
+			.await
+			.is_err()
+	);
+}
+
+#[tokio::test]
+async fn test_restore_without_a_header_leaves_the_store_alone() {
+	let redis_container = get_test_redis_client().await;
+	let redis = Arc::new(redis_container.get_redis().await);
+	let payment_repository = RedisPaymentRepository::new(Arc::clone(&redis));
+
+	let payment = processed_payment("default", 10.0, OffsetDateTime::now_utc());
+	payment_repository.save(payment.clone()).await.unwrap();
+
+	let mut line = serde_json::to_vec(&SnapshotRecord::Payment {
+		payment:  processed_payment("default", 20.0, OffsetDateTime::now_utc()),
+		fee_rate: None,
+	})
+	.unwrap();
+	line.push(b'\n');
+	let error = restore_snapshot(
+		&SnapshotUseCase::new(payment_repository.clone()),
+		&mut line.as_slice(),
+	)
+	.await
+	.unwrap_err();
+
+	assert_eq!(
+		error.downcast_ref::<SnapshotError>(),
+		Some(&SnapshotError::MissingHeader)
+	);
+	assert!(
+		payment_repository
+			.is_already_processed(&payment.correlation_id.to_string())
+			.await
+			.unwrap()
+	);
+}
@@ -0,0 +1,145 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, mpsc as std_mpsc};
+use std::time::Duration;
+
+use actix_web::{App, HttpResponse, HttpServer, test, web};
+use rinha_de_backend::adapters::web::handlers::payments;
+use rinha_de_backend::adapters::web::schema::PaymentRequest;
+use rinha_de_backend::domain::payment_producer::PaymentProducer;
+use rinha_de_backend::infrastructure::queue::mpsc_payment_producer::MpscPaymentProducer;
+use rinha_de_backend::infrastructure::telemetry::propagation::follow_trace_context;
+use rinha_de_backend::infrastructure::telemetry::subscriber::{
+	LogFormat, TelemetryOptions, init_telemetry,
+};
+use serde_json::Value;
+use tokio::sync::mpsc;
+use tracing::info_span;
+use uuid::Uuid;
+
+type ExportedTraces = Arc<Mutex<Vec<Value>>>;
+
+/// Stands in for an OTLP/HTTP collector, recording every JSON export request.
+/// It runs on its own thread because flushing the exporter blocks the caller.
+fn start_collector() -> (SocketAddr, ExportedTraces) {
+	let exported_traces = ExportedTraces::default();
+	let (address_sender, address_receiver) = std_mpsc::channel();
+
+	let collector_traces = Arc::clone(&exported_traces);
+	std::thread::spawn(move || {
+		actix_web::rt::System::new().block_on(async move {
+			let server = HttpServer::new(move || {
+				let traces = Arc::clone(&collector_traces);
+				App::new().route(
+					"/v1/traces",
+					web::post().to(move |body: web::Bytes| {
+						let traces = Arc::clone(&traces);
+						async move {
+							traces
+								.lock()
+								.unwrap()
+								.push(serde_json::from_slice(&body).unwrap());
+							HttpResponse::Ok()
+								.content_type("application/json")
+								.body("{}")
+						}
+					}),
+				)
+			})
+			.workers(1)
+			.bind(("127.0.0.1", 0))
+			.unwrap();
+
+			address_sender.send(server.addrs()[0]).unwrap();
+			server.run().await.unwrap();
+		});
+	});
+
+	(address_receiver.recv().unwrap(), exported_traces)
+}
+
+fn exported_spans(exported_traces: &ExportedTraces) -> Vec<Value> {
+	exported_traces
+		.lock()
+		.unwrap()
+		.iter()
+		.flat_map(|export| export["resourceSpans"].as_array().unwrap().clone())
+		.flat_map(|resource_spans| {
+			resource_spans["scopeSpans"].as_array().unwrap().clone()
+		})
+		.flat_map(|scope_spans| scope_spans["spans"].as_array().unwrap().clone())
+		.collect()
+}
+
+fn span_named<'a>(spans: &'a [Value], name: &str) -> &'a Value {
+	spans
+		.iter()
+		.find(|span| span["name"] == name)
+		.unwrap_or_else(|| panic!("span '{name}' was not exported"))
+}
+
+#[actix_web::test]
+async fn test_correlation_id_trace_reaches_collector() {
+	let (collector_address, exported_traces) = start_collector();
+	let telemetry_guard = init_telemetry(&TelemetryOptions {
+		log_format:    LogFormat::Json,
+		otlp_endpoint: Some(format!("http://{collector_address}")),
+		service_name:  "rinha-de-backend-test".to_string(),
+	})
+	.unwrap();
+
+	let (payment_sender, mut payment_receiver) = mpsc::channel(1);
+	let app = test::init_service(
+		App::new()
+			.app_data(web::Data::new(Box::new(MpscPaymentProducer::new(
+				payment_sender,
+			)) as Box<dyn PaymentProducer>))
+			.service(payments),
+	)
+	.await;
+
+	let correlation_id = Uuid::new_v4();
+	let req = test::TestRequest::post()
+		.uri("/payments")
+		.set_json(PaymentRequest {
+			correlation_id,
+			amount: 19.9,
+		})
+		.to_request();
+	assert!(test::call_service(&app, req).await.status().is_success());
+
+	// Continue the trace the way the processing worker does after the payment
+	// went through the buffer and the queue.
+	let buffered_payment =
+		tokio::time::timeout(Duration::from_secs(1), payment_receiver.recv())
+			.await
+			.unwrap()
+			.unwrap();
+	assert!(buffered_payment.trace_context.0.contains_key("traceparent"));
+
+	let process_span = info_span!(
+		"process_payment",
+		correlation_id = %buffered_payment.payment.correlation_id
+	);
+	follow_trace_context(&process_span, &buffered_payment.trace_context);
+	drop(process_span);
+
+	drop(telemetry_guard);
+
+	let spans = exported_spans(&exported_traces);
+	let create_span = span_named(&spans, "create_payment");
+	let process_span = span_named(&spans, "process_payment");
+
+	assert_eq!(process_span["traceId"], create_span["traceId"]);
+	assert_eq!(process_span["parentSpanId"], create_span["spanId"]);
+	assert!(
+		create_span["attributes"]
+			.as_array()
+			.unwrap()
+			.iter()
+			.any(|attribute| {
+				attribute["key"] == "correlation_id" &&
+					attribute["value"]["stringValue"] ==
+						correlation_id.to_string()
+			})
+	);
+}
@@ -0,0 +1,45 @@
+use std::time::Duration;
+
+use actix_web::{App, test, web};
+use rinha_de_backend::adapters::web::handlers::worker_pool_status;
+use rinha_de_backend::infrastructure::workers::worker_pool::{
+	WorkerPool, WorkerPoolOptions,
+};
+use serde_json::{Value, json};
+
+#[actix_web::test]
+async fn test_worker_pool_status_reports_pool_size() {
+	let pool = WorkerPool::new(WorkerPoolOptions {
+		min_workers:        2,
+		max_workers:        6,
+		scale_interval:     Duration::from_secs(1),
+		backlog_per_worker: 100,
+		max_latency:        Duration::from_millis(100),
+	});
+	pool.record_size(3, 4);
+
+	let app = test::init_service(
+		App::new()
+			.app_data(web::Data::new(pool.clone()))
+			.service(web::scope("/admin").service(worker_pool_status)),
+	)
+	.await;
+
+	let req = test::TestRequest::get().uri("/admin/workers").to_request();
+	let resp = test::call_service(&app, req).await;
+
+	assert!(resp.status().is_success());
+
+	let body: Value = test::read_body_json(resp).await;
+	assert_eq!(
+		body,
+		json!({
+			"size": 3,
+			"desired_size": 4,
+			"min_workers": 2,
+			"max_workers": 6,
+			"restarts": 0,
+			"task_panics": 0,
+		})
+	);
+}